@@ -5,11 +5,19 @@
 
 use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
 use mosaicterm::models::{CommandBlock, OutputLine};
+#[cfg(unix)]
+use mosaicterm::pty::{PtyEventBus, PtyHandle, PtyManager};
 use mosaicterm::terminal::ansi_parser::AnsiParser;
 use mosaicterm::terminal::output::{OutputChunk, OutputProcessor};
 use mosaicterm::terminal::StreamType;
+use mosaicterm::ui::history_layout::{HistoryLayout, RowMetrics};
+#[cfg(unix)]
+use std::collections::HashMap;
 use std::hint::black_box;
 use std::path::PathBuf;
+use std::time::Duration;
+#[cfg(unix)]
+use std::time::Instant;
 
 // ============================================================================
 // ANSI Parsing Benchmarks
@@ -277,6 +285,216 @@ fn bench_throughput_parse_ansi(c: &mut Criterion) {
     });
 }
 
+// ============================================================================
+// History Layout Benchmarks
+// ============================================================================
+
+/// Blocks and output lines per block of the laid-out history: 1M rows
+const HISTORY_BLOCKS: usize = 100;
+const HISTORY_LINES_PER_BLOCK: usize = 10_000;
+
+fn history_blocks() -> Vec<CommandBlock> {
+    (0..HISTORY_BLOCKS)
+        .map(|b| {
+            let mut block = CommandBlock::new(format!("command{}", b), PathBuf::from("/tmp"));
+            for i in 0..HISTORY_LINES_PER_BLOCK {
+                // Every tenth line wraps
+                let text = if i % 10 == 0 {
+                    "x".repeat(200)
+                } else {
+                    format!("output line {}", i)
+                };
+                block.add_output_line(OutputLine::with_line_number(text, i));
+            }
+            block.mark_completed(Duration::ZERO);
+            block
+        })
+        .collect()
+}
+
+fn history_layout(blocks: &[CommandBlock]) -> HistoryLayout {
+    let mut layout = HistoryLayout::new();
+    layout.sync(blocks, RowMetrics::default());
+    layout
+}
+
+fn bench_history_sync(c: &mut Criterion) {
+    let blocks = history_blocks();
+    c.bench_function("history/sync_1m_rows", |b| {
+        b.iter_batched(
+            HistoryLayout::new,
+            |mut layout| {
+                layout.sync(black_box(&blocks), RowMetrics::default());
+                layout
+            },
+            BatchSize::LargeInput,
+        );
+    });
+}
+
+fn bench_history_append(c: &mut Criterion) {
+    let mut blocks = history_blocks();
+    let mut layout = history_layout(&blocks);
+    let last = blocks.len() - 1;
+    c.bench_function("history/append_line_1m_rows", |b| {
+        b.iter(|| {
+            blocks[last].add_output_line(OutputLine::new("streamed line"));
+            layout.sync(black_box(&blocks), RowMetrics::default());
+        });
+    });
+}
+
+fn bench_history_scroll_to_offset(c: &mut Criterion) {
+    let layout = history_layout(&history_blocks());
+    let offset = layout.total_height() * 0.37;
+    c.bench_function("history/scroll_to_offset_1m_rows", |b| {
+        b.iter(|| {
+            let anchor = layout.anchor_at(black_box(offset));
+            anchor.and_then(|anchor| layout.offset_of(&anchor))
+        });
+    });
+}
+
+fn bench_history_visible_range(c: &mut Criterion) {
+    let layout = history_layout(&history_blocks());
+    let top = layout.total_height() * 0.37;
+    c.bench_function("history/visible_range_1m_rows", |b| {
+        b.iter(|| layout.visible(black_box(top), black_box(top + 800.0)));
+    });
+}
+
+// ============================================================================
+// Idle Benchmarks
+// ============================================================================
+//
+// These report the CPU time the whole process (PTY reader threads, async
+// runtime and a headless egui loop standing in for eframe's) uses while a
+// PTY sits idle for `IDLE_WINDOW`. The event-driven loop asks for frames
+// the way the app does, through `mosaicterm::ui::repaint::schedule`, and
+// should stay near zero. CPU time is read with `getrusage`, so they only
+// run on Unix.
+
+/// Wall-clock span of one idle iteration
+#[cfg(unix)]
+const IDLE_WINDOW: Duration = Duration::from_millis(50);
+
+/// Idle windows actually waited per sample; longer runs are extrapolated so
+/// near-zero readings do not make criterion wait for hours
+#[cfg(unix)]
+const IDLE_WINDOWS_MEASURED: u64 = 4;
+
+/// Frame interval the UI used to poll PTY output at
+#[cfg(unix)]
+const FRAME_INTERVAL: Duration = Duration::from_millis(16);
+
+/// CPU time, user and system, this process has used so far
+#[cfg(unix)]
+fn process_cpu_time() -> Duration {
+    use nix::sys::resource::{getrusage, UsageWho};
+    use nix::sys::time::TimeValLike;
+
+    let usage = getrusage(UsageWho::RUSAGE_SELF).expect("getrusage");
+    let micros = usage.user_time().num_microseconds() + usage.system_time().num_microseconds();
+    Duration::from_micros(micros.max(0) as u64)
+}
+
+/// CPU time used over `iters` idle windows spent in `wait`
+#[cfg(unix)]
+fn idle_cpu_time(iters: u64, mut wait: impl FnMut(Duration)) -> Duration {
+    let windows = iters.clamp(1, IDLE_WINDOWS_MEASURED);
+    let start = process_cpu_time();
+    for _ in 0..windows {
+        wait(IDLE_WINDOW);
+    }
+    let used = process_cpu_time().saturating_sub(start);
+    used.mul_f64(iters as f64 / windows as f64)
+}
+
+/// A PTY running `cat`, which waits for input and prints nothing
+#[cfg(unix)]
+fn idle_pty(runtime: &tokio::runtime::Runtime, manager: &PtyManager) -> PtyHandle {
+    runtime
+        .block_on(manager.create_pty("cat", &[], &HashMap::new(), None))
+        .expect("spawn cat")
+}
+
+/// Run a headless egui loop for `window` the way eframe does: draw a frame,
+/// then sleep until the frame's requested repaint is due or `wake` fires
+#[cfg(unix)]
+fn run_ui_loop(
+    ctx: &eframe::egui::Context,
+    window: Duration,
+    wake: &std::sync::mpsc::Receiver<()>,
+    mut frame: impl FnMut(&eframe::egui::Context),
+) {
+    let end = Instant::now() + window;
+    loop {
+        let output = ctx.run_ui(eframe::egui::RawInput::default(), |ui| frame(ui.ctx()));
+        let delay = output
+            .viewport_output
+            .get(&eframe::egui::ViewportId::ROOT)
+            .map_or(Duration::MAX, |viewport| viewport.repaint_delay);
+        let now = Instant::now();
+        if now >= end {
+            break;
+        }
+        let _ = wake.recv_timeout(delay.min(end - now));
+    }
+}
+
+#[cfg(unix)]
+fn bench_idle_event_driven(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().expect("tokio runtime");
+    let bus = PtyEventBus::default();
+    let manager = PtyManager::with_event_bus(bus.clone());
+    let handle = idle_pty(&runtime, &manager);
+    // Like the app's `Repainter`: PTY activity wakes the UI loop
+    let (wake_tx, wake_rx) = std::sync::mpsc::channel();
+    runtime.spawn(async move {
+        bus.notify_activity(move || {
+            let _ = wake_tx.send(());
+        })
+        .await
+    });
+    let ctx = eframe::egui::Context::default();
+
+    c.bench_function("idle/event_driven_cpu", |b| {
+        b.iter_custom(|iters| {
+            idle_cpu_time(iters, |window| {
+                run_ui_loop(&ctx, window, &wake_rx, |ctx| {
+                    let data = runtime.block_on(manager.try_read_output_now(&handle));
+                    let pending = data.is_ok_and(|d| !d.is_empty());
+                    mosaicterm::ui::repaint::schedule(ctx, pending, []);
+                })
+            })
+        });
+    });
+
+    let _ = runtime.block_on(manager.terminate_pty(&handle));
+}
+
+#[cfg(unix)]
+fn bench_idle_per_frame_polling(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().expect("tokio runtime");
+    let manager = PtyManager::new();
+    let handle = idle_pty(&runtime, &manager);
+    let (_wake_tx, wake_rx) = std::sync::mpsc::channel();
+    let ctx = eframe::egui::Context::default();
+
+    c.bench_function("idle/per_frame_polling_cpu", |b| {
+        b.iter_custom(|iters| {
+            idle_cpu_time(iters, |window| {
+                run_ui_loop(&ctx, window, &wake_rx, |ctx| {
+                    black_box(runtime.block_on(manager.try_read_output_now(&handle)).ok());
+                    ctx.request_repaint_after(FRAME_INTERVAL);
+                })
+            })
+        });
+    });
+
+    let _ = runtime.block_on(manager.terminate_pty(&handle));
+}
+
 // ============================================================================
 // Criterion Groups
 // ============================================================================
@@ -312,9 +530,32 @@ criterion_group!(
     bench_throughput_parse_ansi,
 );
 
+criterion_group! {
+    name = history_benchmarks;
+    config = Criterion::default().sample_size(10);
+    targets =
+        bench_history_sync,
+        bench_history_append,
+        bench_history_scroll_to_offset,
+        bench_history_visible_range
+}
+
+#[cfg(unix)]
+criterion_group! {
+    name = idle_benchmarks;
+    config = Criterion::default().sample_size(10);
+    targets = bench_idle_event_driven, bench_idle_per_frame_polling
+}
+
+/// Idle CPU time is only measured on Unix
+#[cfg(not(unix))]
+fn idle_benchmarks() {}
+
 criterion_main!(
     ansi_benchmarks,
     output_benchmarks,
     block_benchmarks,
-    throughput_benchmarks
+    throughput_benchmarks,
+    history_benchmarks,
+    idle_benchmarks
 );
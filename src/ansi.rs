@@ -3,6 +3,7 @@
 //! This module handles parsing and processing of ANSI escape sequences
 //! for proper display of colored and formatted terminal output.
 
+use crate::terminal::grid::{CellStyle, Color as GridColor, Palette, Underline};
 use crate::Result;
 use regex::Regex;
 
@@ -16,18 +17,14 @@ pub struct AnsiParser {
 
 #[derive(Debug, Clone, Default)]
 struct ParserState {
-    /// Current foreground color
-    fg_color: Option<Color>,
-    /// Current background color
-    bg_color: Option<Color>,
-    /// Current text attributes
-    attributes: Vec<TextAttribute>,
+    /// Current graphic rendition, interpreted by the shared terminal grid rules
+    style: CellStyle,
 }
 
 impl AnsiParser {
     /// Create a new ANSI parser
     pub fn new() -> Self {
-        let escape_regex = Regex::new(r"\x1b\[[0-9;]*[a-zA-Z]").unwrap();
+        let escape_regex = Regex::new(r"\x1b\[[0-9;:]*[a-zA-Z]").unwrap();
         Self {
             escape_regex,
             state: ParserState::default(),
@@ -47,12 +44,7 @@ impl AnsiParser {
             if mat.start() > last_end {
                 let text_content = &text[last_end..mat.start()];
                 if !text_content.is_empty() {
-                    segments.push(AnsiSegment {
-                        text: text_content.to_string(),
-                        foreground_color: self.state.fg_color.clone(),
-                        background_color: self.state.bg_color.clone(),
-                        attributes: self.state.attributes.clone(),
-                    });
+                    segments.push(self.segment(text_content));
                 }
             }
 
@@ -67,12 +59,7 @@ impl AnsiParser {
         if last_end < text.len() {
             let remaining_text = &text[last_end..];
             if !remaining_text.is_empty() {
-                segments.push(AnsiSegment {
-                    text: remaining_text.to_string(),
-                    foreground_color: self.state.fg_color.clone(),
-                    background_color: self.state.bg_color.clone(),
-                    attributes: self.state.attributes.clone(),
-                });
+                segments.push(self.segment(remaining_text));
             }
         }
 
@@ -89,66 +76,61 @@ impl AnsiParser {
         Ok(segments)
     }
 
-    /// Process a single escape sequence
+    /// Process a single escape sequence; only SGR sequences affect formatting
     fn process_escape_sequence(&mut self, seq: &str) {
-        // Remove the ESC[ prefix and the final character
-        let seq = seq.trim_start_matches('\x1b').trim_start_matches('[');
-        let seq = seq.trim_end_matches(char::is_alphabetic);
+        self.state.style.apply_sgr_sequence(seq);
+    }
 
-        if seq.is_empty() {
-            return;
+    /// Build a segment carrying the current formatting
+    fn segment(&self, text: &str) -> AnsiSegment {
+        let style = &self.state.style;
+        let mut attributes = Vec::new();
+        if style.bold {
+            attributes.push(TextAttribute::Bold);
         }
-
-        // Split by semicolon to get individual codes
-        let codes: Vec<u32> = seq.split(';').filter_map(|s| s.parse().ok()).collect();
-
-        for &code in &codes {
-            match code {
-                0 => self.reset_formatting(),
-                1 => self.add_attribute(TextAttribute::Bold),
-                3 => self.add_attribute(TextAttribute::Italic),
-                4 => self.add_attribute(TextAttribute::Underline),
-                22 => self.remove_attribute(&TextAttribute::Bold),
-                23 => self.remove_attribute(&TextAttribute::Italic),
-                24 => self.remove_attribute(&TextAttribute::Underline),
-                30..=37 => self.set_foreground_color(Self::ansi_color_to_rgb(code - 30)),
-                40..=47 => self.set_background_color(Self::ansi_color_to_rgb(code - 40)),
-                90..=97 => self.set_foreground_color(Self::ansi_bright_color_to_rgb(code - 90)),
-                100..=107 => self.set_background_color(Self::ansi_bright_color_to_rgb(code - 100)),
-                _ => {
-                    // Ignore unknown codes
-                }
-            }
+        if style.italic {
+            attributes.push(TextAttribute::Italic);
         }
-    }
-
-    /// Reset all formatting to default
-    fn reset_formatting(&mut self) {
-        self.state.fg_color = None;
-        self.state.bg_color = None;
-        self.state.attributes.clear();
-    }
-
-    /// Add a text attribute
-    fn add_attribute(&mut self, attr: TextAttribute) {
-        if !self.state.attributes.contains(&attr) {
-            self.state.attributes.push(attr);
+        if style.underline != Underline::None {
+            attributes.push(TextAttribute::Underline);
+        }
+        if style.strikethrough {
+            attributes.push(TextAttribute::Strikethrough);
+        }
+        if style.dim {
+            attributes.push(TextAttribute::Dim);
         }
-    }
-
-    /// Remove a text attribute
-    fn remove_attribute(&mut self, attr: &TextAttribute) {
-        self.state.attributes.retain(|a| a != attr);
-    }
 
-    /// Set foreground color
-    fn set_foreground_color(&mut self, color: Color) {
-        self.state.fg_color = Some(color);
+        AnsiSegment {
+            text: text.to_string(),
+            foreground_color: Self::grid_color_to_rgb(style.fg),
+            background_color: Self::grid_color_to_rgb(style.bg),
+            attributes,
+        }
     }
 
-    /// Set background color
-    fn set_background_color(&mut self, color: Color) {
-        self.state.bg_color = Some(color);
+    /// Convert a grid color to RGB, keeping this parser's 16-color table
+    fn grid_color_to_rgb(color: GridColor) -> Option<Color> {
+        match color {
+            GridColor::Default => None,
+            GridColor::Indexed(idx @ 0..=7) => Some(Self::ansi_color_to_rgb(idx as u32)),
+            GridColor::Indexed(idx @ 8..=15) => {
+                Some(Self::ansi_bright_color_to_rgb(idx as u32 - 8))
+            }
+            GridColor::Indexed(idx) => {
+                let rgb = Palette::default().indexed(idx);
+                Some(Color {
+                    r: rgb.r,
+                    g: rgb.g,
+                    b: rgb.b,
+                })
+            }
+            GridColor::Rgb(rgb) => Some(Color {
+                r: rgb.r,
+                g: rgb.g,
+                b: rgb.b,
+            }),
+        }
     }
 
     /// Convert ANSI color code to RGB
@@ -308,4 +290,20 @@ mod tests {
         let bright_red = AnsiParser::ansi_bright_color_to_rgb(1);
         assert_eq!(bright_red, Color { r: 255, g: 0, b: 0 });
     }
+
+    #[test]
+    fn test_extended_colors() {
+        let mut parser = AnsiParser::new();
+        let result = parser
+            .parse("\x1b[38;5;196mA\x1b[38;2;1;2;3mB\x1b[2J")
+            .unwrap();
+
+        assert_eq!(result.len(), 2);
+        assert_eq!(
+            result[0].foreground_color,
+            Some(Color { r: 255, g: 0, b: 0 })
+        );
+        assert_eq!(result[1].foreground_color, Some(Color { r: 1, g: 2, b: 3 }));
+        assert!(result[1].attributes.is_empty());
+    }
 }
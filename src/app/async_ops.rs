@@ -31,7 +31,7 @@
 //!
 //! The async loop is spawned once at app startup and runs for the lifetime of
 //! the application. The UI thread sends requests via `async_tx` and polls for
-//! results via `async_rx`.
+//! results via `async_rx` in the frame the loop wakes it for.
 
 use mosaicterm::config::RuntimeConfig;
 use mosaicterm::error::Result;
@@ -43,6 +43,7 @@ use std::sync::Arc;
 use tokio::sync::mpsc;
 use tracing::{debug, error, info, warn};
 
+use super::repaint::Repainter;
 use super::{AsyncRequest, AsyncResult};
 
 /// Run the async operation processing loop
@@ -51,13 +52,15 @@ use super::{AsyncRequest, AsyncResult};
 /// It handles terminal initialization, command execution, and PTY lifecycle events.
 ///
 /// `runtime_config` is the same config the UI loaded so the async side uses
-/// identical shell type, paths, and theme settings.
+/// identical shell type, paths, and theme settings. `repainter` wakes the UI
+/// after each request so it picks up the result.
 pub async fn async_operation_loop(
     request_rx: &mut mpsc::UnboundedReceiver<AsyncRequest>,
     result_tx: mpsc::UnboundedSender<AsyncResult>,
     pty_manager: Arc<PtyManager>,
     terminal_factory: TerminalFactory,
     runtime_config: RuntimeConfig,
+    repainter: Repainter,
 ) {
     info!("Starting async operation loop");
 
@@ -137,6 +140,7 @@ pub async fn async_operation_loop(
                 }
             }
         }
+        repainter.request();
     }
 
     info!("Async operation loop ended");
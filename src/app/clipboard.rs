@@ -0,0 +1,151 @@
+//! Program Clipboard Access
+//!
+//! Carries out the OSC 52 requests that the overlay grid and the block-mode
+//! query scanner collect, according to `[terminal.clipboard]`: writes reach
+//! the system clipboard unless denied, reads (off by default) are answered
+//! with its contents, and `ask` holds a request until the user decides.
+
+use arboard::Clipboard;
+use eframe::egui;
+use mosaicterm::config::ClipboardPolicy;
+use mosaicterm::security_audit;
+use mosaicterm::terminal::clipboard::{read_reply, ClipboardRequest};
+use tracing::{debug, warn};
+
+use super::{send_to_app, MosaicTermApp};
+
+/// Characters of the text shown when asking about a write
+const PREVIEW_CHARS: usize = 80;
+
+impl MosaicTermApp {
+    /// Act on the clipboard requests made since the last frame, and ask the
+    /// user about one waiting for an answer
+    pub(super) fn handle_clipboard_requests(&mut self, ctx: &egui::Context) {
+        let overlay = self.tui_overlay.take_clipboard_requests();
+        let block = self.queries.take_clipboard_requests();
+        let requests = overlay
+            .into_iter()
+            .map(|request| (request, "overlay"))
+            .chain(block.into_iter().map(|request| (request, "block")));
+        for (request, source) in requests {
+            let policy = self
+                .runtime_config
+                .config()
+                .terminal
+                .clipboard
+                .policy_for(&request);
+            match policy {
+                ClipboardPolicy::Ask => {
+                    // A newer request supersedes one still waiting
+                    if let Some((previous, source)) =
+                        self.pending_clipboard.replace((request, source))
+                    {
+                        self.answer_clipboard_request(previous, source, false);
+                    }
+                }
+                policy => {
+                    self.answer_clipboard_request(request, source, policy == ClipboardPolicy::Allow)
+                }
+            }
+        }
+
+        self.render_clipboard_prompt(ctx);
+    }
+
+    /// Carry out `request`, or refuse it
+    fn answer_clipboard_request(&mut self, request: ClipboardRequest, source: &str, allowed: bool) {
+        match request {
+            ClipboardRequest::Write(text) => {
+                if !allowed {
+                    debug!("Refused clipboard write of {} bytes", text.len());
+                    return;
+                }
+                if let Err(e) = Clipboard::new().and_then(|mut c| c.set_text(text)) {
+                    warn!("Failed to set clipboard: {}", e);
+                }
+            }
+            ClipboardRequest::Read { targets, bell } => {
+                security_audit::log_clipboard_read(allowed, source);
+                // A refused read still gets an (empty) reply, so the program
+                // does not sit waiting for one
+                let text = if allowed {
+                    Clipboard::new()
+                        .and_then(|mut c| c.get_text())
+                        .unwrap_or_default()
+                } else {
+                    String::new()
+                };
+                if let Some(handle) = self.terminal.as_ref().and_then(|t| t.pty_handle()) {
+                    send_to_app(
+                        &self.pty_manager,
+                        handle,
+                        &read_reply(&targets, &text, bell),
+                    );
+                }
+            }
+        }
+    }
+
+    /// Ask whether to allow the pending request
+    fn render_clipboard_prompt(&mut self, ctx: &egui::Context) {
+        let Some((request, _)) = &self.pending_clipboard else {
+            return;
+        };
+        let (message, preview) = match request {
+            ClipboardRequest::Write(text) => {
+                let mut preview: String = text.chars().take(PREVIEW_CHARS).collect();
+                if text.chars().count() > PREVIEW_CHARS {
+                    preview.push_str("...");
+                }
+                (
+                    format!(
+                        "A program wants to copy {} bytes to the clipboard:",
+                        text.len()
+                    ),
+                    Some(preview),
+                )
+            }
+            ClipboardRequest::Read { .. } => (
+                "A program wants to read the clipboard.\nIt will see whatever you copied last."
+                    .to_string(),
+                None,
+            ),
+        };
+
+        let mut answer = None;
+        egui::Window::new("Clipboard Access")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .show(ctx, |ui| {
+                ui.label(
+                    egui::RichText::new(message)
+                        .font(egui::FontId::monospace(12.0))
+                        .color(self.ui_colors.foreground),
+                );
+                if let Some(preview) = preview {
+                    ui.add_space(4.0);
+                    ui.label(
+                        egui::RichText::new(preview)
+                            .font(egui::FontId::monospace(11.0))
+                            .color(self.ui_colors.status_bar.text),
+                    );
+                }
+                ui.add_space(8.0);
+                ui.horizontal(|ui| {
+                    if ui.button("Allow").clicked() {
+                        answer = Some(true);
+                    }
+                    if ui.button("Deny").clicked() {
+                        answer = Some(false);
+                    }
+                });
+            });
+
+        if let Some(allowed) = answer {
+            if let Some((request, source)) = self.pending_clipboard.take() {
+                self.answer_clipboard_request(request, source, allowed);
+            }
+        }
+    }
+}
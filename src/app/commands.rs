@@ -21,10 +21,11 @@ pub fn get_command_name(command: &str) -> String {
     }
 }
 
-/// Check if a command is a TUI app that should open in fullscreen overlay
+/// Check if a command is a known TUI app that should open in fullscreen overlay
 ///
-/// TUI apps are interactive terminal applications that need full screen control
-/// (vim, htop, top, less, etc.)
+/// This is only a hint that opens the overlay before the app starts. Any other
+/// command moves to the overlay once it enters the alternate screen or puts the
+/// terminal into raw mode.
 pub fn is_tui_command(command: &str, config: &Config) -> bool {
     let cmd_name = get_command_name(command);
     config
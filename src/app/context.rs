@@ -33,7 +33,10 @@ pub fn detect_git_context(terminal: Option<&Terminal>) -> Option<String> {
 
 /// Detect detailed git status for prompt rendering
 pub fn detect_git_status(terminal: Option<&Terminal>) -> Option<GitPromptStatus> {
-    let working_dir = terminal?.get_working_directory();
+    // A directory on another machine can't be looked up here
+    let working_dir = terminal
+        .filter(|t| t.remote_host().is_none())?
+        .get_working_directory();
 
     let repo = git2::Repository::discover(working_dir).ok()?;
 
@@ -0,0 +1,116 @@
+//! Inline Images in Blocks
+//!
+//! Draws the images programs put in block output (kitty graphics, iTerm2,
+//! Sixel) at the size they asked for, or as a one-line note in blocks whose
+//! images are hidden, and keeps the memory all images take within
+//! `terminal.images.max_total_bytes`: those in blocks, those kitty clients
+//! stored for later placement, and those in the TUI overlay.
+
+use eframe::egui;
+use mosaicterm::terminal::Placement;
+use tracing::debug;
+
+use super::MosaicTermApp;
+
+impl MosaicTermApp {
+    /// Memory taken by every image held: in blocks, stored by kitty
+    /// clients, and in the TUI overlay's screens and scrollback
+    fn image_bytes(&self) -> usize {
+        let blocks: usize = self
+            .state_manager
+            .get_command_history()
+            .iter()
+            .map(|block| block.image_bytes())
+            .sum();
+        let stored = self
+            .terminal
+            .as_ref()
+            .map_or(0, |terminal| terminal.stored_image_bytes());
+        blocks + stored + self.tui_overlay.image_bytes()
+    }
+
+    /// Drop images until all of them fit the budget: first those stored for
+    /// later placement, which aren't shown, then the oldest blocks', then
+    /// those in the overlay's scrollback
+    pub(super) fn enforce_image_budget(&mut self) {
+        let budget = self.runtime_config.config().terminal.images.max_total_bytes;
+        let mut total = self.image_bytes();
+        if total <= budget {
+            return;
+        }
+
+        if let Some(terminal) = self.terminal.as_mut() {
+            terminal.drop_stored_images(total - budget);
+        }
+        total = self.image_bytes();
+        if total > budget {
+            self.tui_overlay.drop_stored_images(total - budget);
+            total = self.image_bytes();
+        }
+
+        if let Some(history) = self.state_manager.command_history_mut() {
+            for block in history.iter_mut() {
+                if total <= budget {
+                    break;
+                }
+                let freed = block.drop_images();
+                if freed > 0 {
+                    debug!(
+                        "Dropped {} bytes of images from '{}' to stay within {} bytes",
+                        freed, block.command, budget
+                    );
+                    total -= freed;
+                }
+            }
+        }
+
+        if total > budget {
+            let freed = self.tui_overlay.drop_scrollback_images(total - budget);
+            debug!(
+                "Dropped {} bytes of images from the overlay's scrollback to stay within {} bytes",
+                freed, budget
+            );
+        }
+    }
+
+    /// Draw an image line of a block's output, or a note standing in for it
+    pub(super) fn render_output_image_static(
+        ui: &mut egui::Ui,
+        placement: &Placement,
+        hidden: bool,
+        colors: &mosaicterm::ui::UiColors,
+    ) {
+        let image = &placement.image;
+        let mono_font = egui::FontId::monospace(12.0);
+        if hidden {
+            ui.label(
+                egui::RichText::new(format!("[{}x{} image]", image.width, image.height))
+                    .font(mono_font)
+                    .color(colors.blocks.timestamp),
+            );
+            return;
+        }
+
+        // Sizes in cells and pixels are physical, like the image's own
+        let (char_width, line_height) = ui.fonts_mut(|fonts| {
+            (
+                fonts.glyph_width(&mono_font, 'M'),
+                fonts.row_height(&mono_font),
+            )
+        });
+        let pixels_per_point = ui.ctx().pixels_per_point();
+        let (width, height) = placement.display_size(
+            (
+                char_width * pixels_per_point,
+                line_height * pixels_per_point,
+            ),
+            ui.available_width() * pixels_per_point,
+        );
+        let size = egui::vec2(width, height) / pixels_per_point;
+        let texture = mosaicterm::ui::images::texture(ui.ctx(), image);
+        ui.add(
+            egui::Image::new(egui::load::SizedTexture::new(texture.id(), size))
+                .fit_to_exact_size(size),
+        );
+    }
+}
@@ -18,11 +18,16 @@
 //!
 //! - `mod.rs` - Core application struct, eframe::App impl, UI rendering, PTY polling
 //! - `async_ops.rs` - Background async task loop for terminal init, direct execution
+//! - `clipboard.rs` - Clipboard requests from programs (OSC 52) and the policy for them
 //! - `commands.rs` - Command detection and classification (TUI, cd, interactive, exit)
 //! - `context.rs` - Environment context detection (venv, conda, nvm) and git info
+//! - `images.rs` - Inline images in block output and the memory budget for them
 //! - `input.rs` - Keyboard shortcuts and input handling
 //! - `prompt.rs` - Prompt building with contexts and SSH support
+//! - `repaint.rs` - Repaints on PTY events, finished background work and due timers
+//! - `resize.rs` - PTY size tracking for the block view and panes
 //! - `ssh.rs` - SSH session detection, remote prompt parsing, session lifecycle
+//! - `title.rs` - Titles set by programs (OSC 0/1/2), pane labels and the window title
 //!
 //! ### Main Components
 //!
@@ -50,20 +55,26 @@
 //!
 //! ## Performance Considerations
 //!
-//! - **Conditional Repaints:** Only repaints when needed (running command, pending output)
+//! - **Event-Driven Repaints:** Repaints when PTY output arrives or a timer is due, never while idle
 //! - **Output Batching:** Processes multiple lines at once to reduce UI updates
 //! - **Size Limits:** Enforces max lines per command (10K) and chars per line (10K)
 //! - **Async I/O:** Background task handles terminal init and direct command execution
 
 // Submodules
 mod async_ops;
+mod clipboard;
 mod commands;
 mod context;
+mod images;
 mod input;
 #[allow(dead_code)]
 pub mod pane_tree;
 mod prompt;
+mod repaint;
+mod resize;
+mod scrollback;
 mod ssh;
+mod title;
 
 use arboard::Clipboard;
 use eframe::egui;
@@ -75,20 +86,23 @@ use mosaicterm::error::Result;
 use mosaicterm::execution::DirectExecutor;
 use mosaicterm::models::{CommandBlock, ExecutionStatus};
 use mosaicterm::models::{ShellType as ModelShellType, TerminalSession};
-use mosaicterm::pty::PtyManager;
+use mosaicterm::pty::{PtyEventBus, PtyHandle, PtyManager};
 use mosaicterm::state_manager::StateManager;
-use mosaicterm::terminal::{Terminal, TerminalFactory};
+use mosaicterm::terminal::{Palette, Terminal, TerminalFactory};
 use mosaicterm::ui::{
-    CommandBlocks, CompletionPopup, InputPrompt, MetricsPanel, ScrollableHistory,
+    CommandBlocks, CompletionPopup, HistoryLayout, InputPrompt, MetricsPanel, ScrollableHistory,
 };
 use std::sync::Arc;
 use tokio::sync::mpsc;
 use tracing::{debug, error, info, warn};
 
-// Output size limits to prevent memory leaks
-const MAX_OUTPUT_LINES_PER_COMMAND: usize = 10_000;
+// Longest output line kept; how many lines are kept is the scrollback budget
 const MAX_LINE_LENGTH: usize = 10_000;
 
+// Horizontal margins of a command block in the history
+const BLOCK_INNER_MARGIN: f32 = 12.0;
+const BLOCK_OUTER_MARGIN: f32 = 4.0;
+
 // Atomic flags for native macOS menu bar actions
 #[cfg(target_os = "macos")]
 static NATIVE_MENU_ABOUT: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
@@ -96,6 +110,19 @@ static NATIVE_MENU_ABOUT: std::sync::atomic::AtomicBool = std::sync::atomic::Ato
 static NATIVE_MENU_DEV: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
 #[cfg(target_os = "macos")]
 static NATIVE_MENU_PERF: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+/// Context woken when a native menu item is clicked, so the flags are seen
+/// without waiting for input
+#[cfg(target_os = "macos")]
+static NATIVE_MENU_CTX: std::sync::OnceLock<egui::Context> = std::sync::OnceLock::new();
+
+/// Raise a native menu flag and wake the UI to handle it
+#[cfg(target_os = "macos")]
+fn native_menu_clicked(flag: &std::sync::atomic::AtomicBool) {
+    flag.store(true, std::sync::atomic::Ordering::Relaxed);
+    if let Some(ctx) = NATIVE_MENU_CTX.get() {
+        ctx.request_repaint();
+    }
+}
 
 /// Async operation request sent from UI to background task
 #[derive(Debug, Clone)]
@@ -149,6 +176,8 @@ pub struct MosaicTermApp {
     terminal: Option<Terminal>,
     /// Multi-pane tree (Phase 3)
     pane_tree: Option<pane_tree::PaneTree>,
+    /// Debounced PTY size of the primary terminal, measured from the history area
+    pty_size: mosaicterm::terminal::ResizeDebouncer,
     /// PTY manager for process management (with per-terminal locking)
     pty_manager: Arc<PtyManager>,
     /// Terminal factory for creating terminals
@@ -157,6 +186,12 @@ pub struct MosaicTermApp {
     command_blocks: CommandBlocks,
     input_prompt: InputPrompt,
     scrollable_history: ScrollableHistory,
+    /// Row heights of the command history, so only visible rows are laid out
+    history_layout: HistoryLayout,
+    /// Scroll offset of the command history in the last frame
+    history_offset: f32,
+    /// Directory output past the scrollback budget is spilled to
+    spill_dir: Option<std::path::PathBuf>,
     completion_popup: CompletionPopup,
     metrics_panel: MetricsPanel,
     /// Runtime configuration
@@ -198,8 +233,20 @@ pub struct MosaicTermApp {
     async_tx: mpsc::UnboundedSender<AsyncRequest>,
     /// Channel for receiving async results from background to UI
     async_rx: mpsc::UnboundedReceiver<AsyncResult>,
+    /// Wakes the UI when PTY output arrives or background work finishes
+    repainter: repaint::Repainter,
     /// Fullscreen TUI overlay for interactive apps
     tui_overlay: mosaicterm::ui::TuiOverlay,
+    /// Watches block output for alternate-screen entry to hand off to the overlay
+    alt_screen: mosaicterm::terminal::AltScreenDetector,
+    /// Picks terminal queries out of block output so they can be answered
+    queries: mosaicterm::terminal::QueryScanner,
+    /// Clipboard request waiting for the user's answer, with where it came from
+    pending_clipboard: Option<(mosaicterm::terminal::ClipboardRequest, &'static str)>,
+    /// Window title last sent to the viewport
+    window_title: String,
+    /// Theme colours for the terminal grid and colour query replies
+    terminal_palette: Palette,
     /// SSH prompt overlay for interactive authentication
     ssh_prompt_overlay: mosaicterm::ui::SshPromptOverlay,
     /// Buffer for accumulating output to detect SSH prompts
@@ -410,6 +457,17 @@ fn walk_font_dir_inner(
     Ok(())
 }
 
+/// Write bytes the terminal generates itself (query replies, focus events)
+/// to the application on the PTY
+fn send_to_app(pty_manager: &PtyManager, handle: &PtyHandle, bytes: &[u8]) {
+    if bytes.is_empty() {
+        return;
+    }
+    if let Err(e) = executor::block_on(pty_manager.send_input(handle, bytes)) {
+        warn!("Failed to write terminal reply to application: {}", e);
+    }
+}
+
 /// Send a system notification (macOS: osascript, Linux: notify-send)
 fn send_system_notification(title: &str, message: &str) {
     let title = title.to_string();
@@ -421,6 +479,46 @@ fn send_system_notification(title: &str, message: &str) {
     });
 }
 
+/// Most links listed in a block's context menu
+const MAX_MENU_LINKS: usize = 8;
+
+/// Distinct OSC 8 link targets in a block's output, in order of appearance
+fn block_links(block: &CommandBlock) -> Vec<String> {
+    let mut links: Vec<String> = Vec::new();
+    for link in block.output.iter().flat_map(|line| &line.hyperlinks) {
+        if !links.contains(&link.uri) {
+            links.push(link.uri.clone());
+            if links.len() == MAX_MENU_LINKS {
+                break;
+            }
+        }
+    }
+    links
+}
+
+/// Notify that a long-running command finished while the window was unfocused
+fn notify_command_finished(command: &str, exit_code: i32, elapsed_ms: u128) {
+    let cmd_short = if command.chars().count() > 40 {
+        format!("{}...", command.chars().take(40).collect::<String>())
+    } else {
+        command.to_string()
+    };
+    let status_str = if exit_code == 0 {
+        "completed"
+    } else {
+        "failed"
+    };
+    send_system_notification(
+        "MosaicTerm",
+        &format!(
+            "Command {} ({}s): {}",
+            status_str,
+            elapsed_ms / 1000,
+            cmd_short
+        ),
+    );
+}
+
 fn send_system_notification_sync(title: &str, message: &str) {
     #[cfg(target_os = "macos")]
     {
@@ -530,10 +628,11 @@ fn find_app_icon_path() -> Option<String> {
 }
 
 /// Set up the native macOS menu bar with About and Dev menu items.
-/// This adds items to the existing native app menu that macOS provides.
+/// This adds items to the existing native app menu that macOS provides;
+/// clicking them wakes `ctx`.
 #[cfg(target_os = "macos")]
 #[allow(deprecated)]
-pub fn setup_native_menu_bar() {
+pub fn setup_native_menu_bar(ctx: &egui::Context) {
     #[allow(unused_imports)]
     use cocoa::appkit::{NSApp, NSMenu, NSMenuItem};
     use cocoa::base::{nil, selector};
@@ -543,6 +642,8 @@ pub fn setup_native_menu_bar() {
     #[allow(unused_imports)]
     use objc::{msg_send, sel, sel_impl};
 
+    let _ = NATIVE_MENU_CTX.set(ctx.clone());
+
     unsafe {
         let _pool = NSAutoreleasePool::new(nil);
         let app = NSApp();
@@ -554,13 +655,13 @@ pub fn setup_native_menu_bar() {
             let mut decl = ClassDecl::new(handler_class_name, superclass).unwrap();
 
             extern "C" fn about_action(_this: &Object, _cmd: Sel, _sender: *mut Object) {
-                NATIVE_MENU_ABOUT.store(true, std::sync::atomic::Ordering::Relaxed);
+                native_menu_clicked(&NATIVE_MENU_ABOUT);
             }
             extern "C" fn dev_action(_this: &Object, _cmd: Sel, _sender: *mut Object) {
-                NATIVE_MENU_DEV.store(true, std::sync::atomic::Ordering::Relaxed);
+                native_menu_clicked(&NATIVE_MENU_DEV);
             }
             extern "C" fn perf_action(_this: &Object, _cmd: Sel, _sender: *mut Object) {
-                NATIVE_MENU_PERF.store(true, std::sync::atomic::Ordering::Relaxed);
+                native_menu_clicked(&NATIVE_MENU_PERF);
             }
 
             decl.add_method(
@@ -717,8 +818,11 @@ impl MosaicTermApp {
     pub fn new() -> Self {
         info!("Initializing MosaicTerm application");
 
-        // Create PTY manager (with per-terminal locking for better concurrency)
-        let pty_manager = Arc::new(PtyManager::new());
+        // Create PTY manager (with per-terminal locking for better concurrency),
+        // announcing output on an event bus so the UI can sleep until it arrives
+        let pty_events = PtyEventBus::default();
+        let pty_manager = Arc::new(PtyManager::with_event_bus(pty_events.clone()));
+        let repainter = repaint::Repainter::default();
 
         // Create terminal factory
         let terminal_factory = TerminalFactory::new(pty_manager.clone());
@@ -782,6 +886,7 @@ impl MosaicTermApp {
         let pty_manager_clone = pty_manager.clone();
         let terminal_factory_clone = terminal_factory.clone();
         let runtime_config_clone = runtime_config.clone();
+        let repainter_clone = repainter.clone();
 
         // Spawn background task to handle async operations
         runtime.spawn(async move {
@@ -791,10 +896,14 @@ impl MosaicTermApp {
                 pty_manager_clone,
                 terminal_factory_clone,
                 runtime_config_clone,
+                repainter_clone,
             )
             .await;
         });
 
+        // Repaint when any PTY has output or exits
+        runtime.spawn(repainter.clone().watch(pty_events));
+
         // Initialize StateManager and add demo commands directly
         let mut state_manager = StateManager::new();
         Self::add_demo_commands(&mut state_manager);
@@ -834,6 +943,13 @@ impl MosaicTermApp {
             None
         };
 
+        let terminal_palette = runtime_config
+            .theme_manager()
+            .create_terminal_palette()
+            .unwrap_or_default();
+        let mut tui_overlay = mosaicterm::ui::TuiOverlay::new();
+        tui_overlay.set_palette(terminal_palette.clone());
+
         let pending_restore_sessions = tmux_manager
             .as_ref()
             .map(|mgr| mgr.list_mosaicterm_sessions())
@@ -844,11 +960,15 @@ impl MosaicTermApp {
             state_manager,
             terminal: None,
             pane_tree: None,
+            pty_size: mosaicterm::terminal::ResizeDebouncer::default(),
             pty_manager,
             terminal_factory,
             command_blocks,
             input_prompt,
             scrollable_history,
+            history_layout: HistoryLayout::new(),
+            history_offset: 0.0,
+            spill_dir: scrollback::spill_dir(),
             completion_popup,
             metrics_panel,
             runtime_config,
@@ -873,7 +993,13 @@ impl MosaicTermApp {
             runtime,
             async_tx: request_tx,
             async_rx: result_rx,
-            tui_overlay: mosaicterm::ui::TuiOverlay::new(),
+            repainter,
+            tui_overlay,
+            alt_screen: mosaicterm::terminal::AltScreenDetector::new(),
+            queries: mosaicterm::terminal::QueryScanner::new(),
+            pending_clipboard: None,
+            window_title: String::new(),
+            terminal_palette,
             ssh_prompt_overlay: mosaicterm::ui::SshPromptOverlay::new(),
             ssh_prompt_buffer: String::new(),
             ssh_session_active: false,
@@ -895,6 +1021,21 @@ impl MosaicTermApp {
                 .with_style(runtime_config.config().prompt.style.clone())
                 .with_custom_segments(runtime_config.config().prompt.segments.clone());
         app.ui_colors = mosaicterm::ui::UiColors::from_theme(&runtime_config.config().ui.theme);
+        app.tui_overlay
+            .set_scrollback_limit(runtime_config.config().tui_apps.scrollback_lines);
+        app.terminal_palette = runtime_config
+            .theme_manager()
+            .create_terminal_palette()
+            .unwrap_or_default();
+        app.tui_overlay.set_palette(app.terminal_palette.clone());
+        let image_limits = runtime_config.config().terminal.images.limits();
+        app.tui_overlay.set_image_limits(image_limits);
+        app.queries.set_images_enabled(image_limits.enabled);
+        app.tui_overlay
+            .set_cursor_style(mosaicterm::terminal::CursorStyle {
+                shape: runtime_config.config().ui.cursor_style,
+                blinking: runtime_config.config().ui.cursor_blink,
+            });
         app.runtime_config = runtime_config;
         app
     }
@@ -1054,8 +1195,11 @@ impl MosaicTermApp {
         let session = TerminalSession::with_environment(shell_type, working_dir, environment);
 
         // Create and initialize terminal (shell is spawned here)
-        let terminal = self.terminal_factory.create_and_initialize(session).await?;
+        let mut terminal = self.terminal_factory.create_and_initialize(session).await?;
+        terminal.set_image_limits(self.runtime_config.config().terminal.images.limits());
         self.terminal = Some(terminal);
+        // New shell starts at the default size; resend the measured one
+        self.pty_size.reset();
 
         // Update state manager
         self.state_manager.set_terminal_ready(true);
@@ -1231,6 +1375,8 @@ impl MosaicTermApp {
 
         // Record command execution time for timeout detection
         self.state_manager.set_last_command_time();
+        self.alt_screen.reset();
+        self.queries.reset();
 
         // UI will be updated automatically on the next frame
 
@@ -1266,6 +1412,8 @@ impl MosaicTermApp {
 
     /// Update the prompt display based on current working directory
     fn update_prompt(&mut self) {
+        self.prompt_formatter
+            .set_title(self.terminal.as_ref().and_then(|t| t.title().title()));
         self.prompt_segments = prompt::build_prompt_segments(
             self.terminal.as_ref(),
             &self.state_manager,
@@ -1314,9 +1462,12 @@ impl MosaicTermApp {
 
     /// Sync shell state (CWD, environment) directly from the OS after a command completes.
     fn sync_shell_state(&mut self) {
+        // Shells that report their directory with OSC 7 are followed as the
+        // reports arrive; only the others are probed
         let shell_pid = self
             .terminal
             .as_ref()
+            .filter(|t| !t.reports_cwd())
             .and_then(|t| t.pty_handle())
             .and_then(|h| h.pid);
 
@@ -1357,6 +1508,33 @@ impl MosaicTermApp {
         self.update_prompt();
     }
 
+    /// Follow a working directory the shell reported with OSC 7. Remote
+    /// directories keep their host so the prompt can label them.
+    fn apply_reported_cwd(&mut self, cwd: mosaicterm::terminal::ReportedCwd) {
+        let Some(terminal) = &mut self.terminal else {
+            return;
+        };
+        let remote_host = cwd.remote_host().map(str::to_string);
+        if terminal.get_working_directory() == cwd.path
+            && terminal.remote_host() == remote_host.as_deref()
+        {
+            return;
+        }
+        info!(
+            "CWD reported by shell: {:?} (host {:?})",
+            cwd.path, cwd.host
+        );
+        terminal.set_working_directory(cwd.path.clone());
+        terminal.set_remote_host(remote_host.clone());
+        self.state_manager.change_directory(cwd.path.clone());
+
+        if self.tool_availability.zoxide && remote_host.is_none() {
+            Self::zoxide_add_background(&cwd.path);
+        }
+        self.update_contexts();
+        self.update_prompt();
+    }
+
     fn zoxide_query(query: &str) -> Option<String> {
         let output = std::process::Command::new("zoxide")
             .args(["query", "--", query])
@@ -1385,7 +1563,7 @@ impl MosaicTermApp {
         });
     }
 
-    /// Check if a command is a TUI app that should open in fullscreen overlay
+    /// Check if a command is known to be a TUI app, so the overlay opens up front
     fn is_tui_command(&self, command: &str) -> bool {
         commands::is_tui_command(command, self.runtime_config.config())
     }
@@ -1427,6 +1605,8 @@ impl MosaicTermApp {
                 }
 
                 self.tui_overlay.start(command.clone(), handle_id);
+                self.alt_screen.reset();
+                self.queries.reset();
                 self.set_status_message(Some(format!("Running TUI app: {}", command)));
 
                 info!("TUI overlay started for command: {}", command);
@@ -1436,6 +1616,108 @@ impl MosaicTermApp {
         Ok(())
     }
 
+    /// Move the running block-mode command into the fullscreen overlay.
+    ///
+    /// Called when the command turns out to be a TUI app mid-run (it entered
+    /// the alternate screen or put the tty into raw mode). `screen_output` is
+    /// the output from the switch onwards and seeds the overlay's screen.
+    fn promote_to_tui_overlay(&mut self, screen_output: &[u8]) {
+        let Some(handle_id) = self
+            .terminal
+            .as_ref()
+            .and_then(|t| t.pty_handle())
+            .map(|h| h.id.clone())
+        else {
+            return;
+        };
+
+        let Some(command) = self
+            .state_manager
+            .command_history_mut()
+            .and_then(|history| history.last_mut())
+            .filter(|block| block.status == mosaicterm::models::ExecutionStatus::Running)
+            .map(|block| {
+                block.mark_tui_mode();
+                block.command.clone()
+            })
+        else {
+            return;
+        };
+
+        info!(
+            "Command switched to fullscreen, opening overlay: {}",
+            command
+        );
+        self.tui_overlay.start(command.clone(), handle_id);
+        if !screen_output.is_empty() {
+            self.tui_overlay.add_raw_output(screen_output);
+            self.tui_overlay.note_alt_screen_enter();
+            if let Some(handle) = self.terminal.as_ref().and_then(|t| t.pty_handle()) {
+                let replies = self.tui_overlay.take_responses();
+                send_to_app(&self.pty_manager, handle, &replies);
+            }
+        }
+        self.state_manager.clear_last_command_time();
+        self.set_status_message(Some(format!("Running TUI app: {}", command)));
+    }
+
+    /// Whether the live terminal has focus: the window is focused and, once
+    /// split, the active pane is the one the live terminal serves (panes
+    /// holding a terminal of their own are in the background)
+    fn terminal_has_focus(&self) -> bool {
+        self.window_has_focus
+            && self.pane_tree.as_ref().is_none_or(|tree| {
+                tree.active_pane()
+                    .is_some_and(|pane| pane.terminal.is_none())
+            })
+    }
+
+    /// Send focus events to the apps that enabled focus reporting: the one
+    /// in the TUI overlay or else the command running in block mode, and
+    /// those running in panes holding a terminal of their own, each focused
+    /// while its pane is the active one
+    fn report_focus(&mut self) {
+        let focused = self.terminal_has_focus();
+        let report = if self.tui_overlay.is_active() {
+            self.tui_overlay.set_focused(focused)
+        } else {
+            let block_running = self
+                .state_manager
+                .get_command_history()
+                .last()
+                .is_some_and(|b| b.status == mosaicterm::models::ExecutionStatus::Running);
+            self.terminal.as_mut().and_then(|terminal| {
+                // Focus reporting ends with the command that turned it on
+                if !block_running {
+                    terminal.reset_modes();
+                }
+                terminal.set_focused(focused)
+            })
+        };
+        if let Some(report) = report {
+            if let Some(handle) = self.terminal.as_ref().and_then(|t| t.pty_handle()) {
+                send_to_app(&self.pty_manager, handle, &report);
+            }
+        }
+        let Some(tree) = &mut self.pane_tree else {
+            return;
+        };
+        let active = tree.active_id().to_string();
+        let window_has_focus = self.window_has_focus;
+        let pty_manager = &self.pty_manager;
+        tree.for_each_pane_mut(|pane| {
+            let focused = window_has_focus && pane.id == active;
+            let Some(terminal) = pane.terminal.as_mut() else {
+                return;
+            };
+            if let Some(report) = terminal.set_focused(focused) {
+                if let Some(handle) = terminal.pty_handle() {
+                    send_to_app(pty_manager, handle, &report);
+                }
+            }
+        });
+    }
+
     /// Check if a command is interactive (TUI-based) and may not work well in block mode
     fn is_interactive_command(&self, command: &str) -> bool {
         commands::is_interactive_command(command)
@@ -1594,6 +1876,9 @@ impl MosaicTermApp {
             Error::OutputBufferFull { .. } => {
                 "Output buffer full. Command output was truncated.".to_string()
             }
+            Error::HyperlinkOpenFailed { reason, .. } => {
+                format!("Could not open link: {}.", reason)
+            }
             Error::Toml(e) => {
                 format!("Configuration file error: {}. Using default settings.", e)
             }
@@ -1962,10 +2247,14 @@ impl MosaicTermApp {
                                     .map(|line| line.text.clone())
                                     .collect::<Vec<_>>(),
                                 block.working_directory.clone(),
+                                block_links(block),
+                                block.has_images().then_some(block.images_hidden),
                             )
                         })
                 };
-                if let Some((command, status, output_lines, working_dir)) = block_data {
+                if let Some((command, status, output_lines, working_dir, links, images_hidden)) =
+                    block_data
+                {
                     // Create context menu
                     let mut menu_open = true;
                     egui::Window::new("Context Menu")
@@ -2008,7 +2297,7 @@ impl MosaicTermApp {
 
                             // Copy output
                             if ui.button("📄 Copy Output").clicked() {
-                                let output_text = output_lines.join("\n");
+                                let output_text = self.copied_output(block_id, &output_lines);
                                 if let Ok(mut clipboard) = Clipboard::new() {
                                     let _ = clipboard.set_text(&output_text);
                                 }
@@ -2017,13 +2306,52 @@ impl MosaicTermApp {
 
                             // Copy both
                             if ui.button("📋📄 Copy Both").clicked() {
-                                let output_text = output_lines.join("\n");
+                                let output_text = self.copied_output(block_id, &output_lines);
                                 let both_text = format!("{}\n{}", command, output_text);
                                 if let Ok(mut clipboard) = Clipboard::new() {
                                     let _ = clipboard.set_text(&both_text);
                                 }
                                 menu_open = false;
                             }
+
+                            // Hide or show inline images (only if the output has some)
+                            if let Some(hidden) = images_hidden {
+                                let label = if hidden {
+                                    "🖼 Show Images"
+                                } else {
+                                    "🖼 Hide Images"
+                                };
+                                if ui.button(label).clicked() {
+                                    if let Some(block) = self
+                                        .state_manager
+                                        .command_history_mut()
+                                        .and_then(|h| h.iter_mut().find(|b| &b.id == block_id))
+                                    {
+                                        block.images_hidden = !hidden;
+                                    }
+                                    menu_open = false;
+                                }
+                            }
+
+                            // Links the output carries (OSC 8)
+                            if !links.is_empty() {
+                                ui.separator();
+                            }
+                            for uri in &links {
+                                let label = if uri.chars().count() > 40 {
+                                    format!("🔗 {}...", uri.chars().take(40).collect::<String>())
+                                } else {
+                                    format!("🔗 {}", uri)
+                                };
+                                if ui.button(label).on_hover_text(uri).clicked() {
+                                    if let Err(e) = mosaicterm::terminal::hyperlink::open(uri) {
+                                        warn!("{}", e);
+                                        let user_msg = self.user_friendly_error(&e);
+                                        self.set_status_message(Some(user_msg));
+                                    }
+                                    menu_open = false;
+                                }
+                            }
                         });
 
                     // Close menu if clicked outside or if an action was taken
@@ -2038,9 +2366,13 @@ impl MosaicTermApp {
                     if ctx.input(|i| i.pointer.any_click()) {
                         if let Some(mouse_pos) = ctx.input(|i| i.pointer.hover_pos()) {
                             // Use a generous rect that covers the menu including padding/shadow.
-                            // The menu has 5-6 buttons with separators; 220x250 is a safe upper bound.
+                            // The menu has 5-6 buttons with separators; 220x250 is a safe upper bound,
+                            // plus a row per link and one for the image toggle.
+                            let height = 250.0
+                                + 30.0 * links.len() as f32
+                                + if images_hidden.is_some() { 30.0 } else { 0.0 };
                             let menu_rect =
-                                egui::Rect::from_min_size(menu_pos, egui::vec2(220.0, 250.0));
+                                egui::Rect::from_min_size(menu_pos, egui::vec2(360.0, height));
                             if !menu_rect.contains(mouse_pos) {
                                 self.command_blocks
                                     .interaction_state_mut()
@@ -2064,6 +2396,14 @@ impl eframe::App for MosaicTermApp {
         // Track window focus state for background notifications
         self.window_has_focus = ctx.input(|i| i.focused);
 
+        // Apps that enabled focus reporting (mode 1004) hear when the window
+        // or their pane gains or loses focus
+        self.report_focus();
+
+        // Free the textures of images no longer drawn, even on frames that
+        // draw none
+        mosaicterm::ui::images::release_idle(ctx);
+
         // Handle keyboard shortcut for performance metrics (Ctrl+Shift+P)
         if ctx.input(|i| i.modifiers.ctrl && i.modifiers.shift && i.key_pressed(egui::Key::P)) {
             self.metrics_panel.toggle();
@@ -2158,16 +2498,14 @@ impl eframe::App for MosaicTermApp {
             ctx.request_repaint(); // Keep animating
         }
 
-        // Update window title with application state
-        self.update_window_title(frame);
-
         // Load custom fonts and set up native menu bar on first frame
         if !self.fonts_loaded {
             self.load_fonts(ctx);
             self.fonts_loaded = true;
+            self.repainter.attach(ctx);
 
             #[cfg(target_os = "macos")]
-            setup_native_menu_bar();
+            setup_native_menu_bar(ctx);
         }
 
         // Set up visual style
@@ -2328,14 +2666,10 @@ impl eframe::App for MosaicTermApp {
 
             // Apply pending PTY resize if the overlay detected a size change
             if let Some((rows, cols)) = self.tui_overlay.take_pending_resize() {
-                if let Some(terminal) = &self.terminal {
-                    if let Some(pty_handle) = terminal.pty_handle() {
-                        let pty_manager = &*self.pty_manager;
-                        if let Err(e) = executor::block_on(async {
-                            pty_manager.resize_pty(pty_handle, rows, cols).await
-                        }) {
-                            warn!("Failed to resize PTY for TUI overlay: {}", e);
-                        }
+                if let Some(terminal) = &mut self.terminal {
+                    let size = mosaicterm::terminal::GridSize::new(rows, cols);
+                    if let Err(e) = executor::block_on(terminal.resize_pty(size)) {
+                        warn!("Failed to resize PTY for TUI overlay: {}", e);
                     }
                 }
             }
@@ -2375,6 +2709,8 @@ impl eframe::App for MosaicTermApp {
                 }
 
                 self.tui_overlay.stop();
+                // The overlay resized the shared PTY; restore the block view size
+                self.pty_size.reset();
             }
         } else {
             // Main layout with scrollable history and pinned input
@@ -2405,12 +2741,16 @@ impl eframe::App for MosaicTermApp {
                             egui::Vec2::new(ui.available_width(), history_height),
                             egui::Layout::top_down(egui::Align::LEFT),
                             |ui| {
+                                let history_rect = ui.available_rect_before_wrap();
+                                self.observe_history_area(ui, history_rect);
                                 self.render_command_history_area(ui);
                             },
                         );
                     });
                 });
 
+            self.apply_pending_pty_resizes();
+
             // Render context menu if active (only in normal mode)
             self.render_context_menu(ctx);
         }
@@ -2484,33 +2824,17 @@ impl eframe::App for MosaicTermApp {
         // Handle async operations
         self.handle_async_operations(ctx);
 
+        // Clipboard requests from programs (OSC 52)
+        self.handle_clipboard_requests(ctx);
+
+        // Titles from programs (OSC 0/1/2) and the window title
+        self.handle_title_changes(ctx);
+
         // Poll for async operation results (non-blocking)
         self.poll_async_results();
 
-        // Only repaint when needed to save CPU
-        // Repaint if: command is running, has pending output, user input changed, or overlays active
-        let needs_repaint = self.state_manager.last_command_time().is_some()
-            || (self
-                .terminal
-                .as_ref()
-                .map(|t| t.has_pending_output())
-                .unwrap_or(false))
-            || self.completion_popup.is_visible()
-            || self.tui_overlay.is_active()
-            || self.ssh_prompt_overlay.is_active();
-
-        if needs_repaint {
-            // Repaint immediately for active operations
-            ctx.request_repaint();
-
-            // For TUI overlay or SSH prompt, request very fast repaints (16ms = ~60fps) for smooth updates
-            if self.tui_overlay.is_active() || self.ssh_prompt_overlay.is_active() {
-                ctx.request_repaint_after(std::time::Duration::from_millis(16));
-            }
-        } else {
-            // Check again in 100ms for idle state (efficient polling)
-            ctx.request_repaint_after(std::time::Duration::from_millis(100));
-        }
+        // Sleep until output arrives or a timer is due
+        self.schedule_repaint(ctx);
     }
 
     fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
@@ -2520,62 +2844,6 @@ impl eframe::App for MosaicTermApp {
 }
 
 impl MosaicTermApp {
-    /// Update window title with application state
-    fn update_window_title(&self, _frame: &mut eframe::Frame) {
-        // Build dynamic title based on terminal state
-        let title = if self.state_manager.app_state().terminal_ready {
-            let stats = self.state_manager.statistics();
-            let cmd_count = stats.total_commands;
-
-            // Show command count and current directory if available
-            if let Some(session) = self.state_manager.active_session() {
-                let dir_name = session
-                    .working_directory
-                    .file_name()
-                    .and_then(|n| n.to_str())
-                    .unwrap_or("~");
-                format!("MosaicTerm - {} [{} cmds]", dir_name, cmd_count)
-            } else {
-                format!("MosaicTerm - Ready [{} cmds]", cmd_count)
-            }
-        } else {
-            "MosaicTerm - Initializing...".to_string()
-        };
-
-        // Note: eframe 0.24 doesn't have viewport() method on frame.info()
-        // This infrastructure is ready for when the API becomes available
-
-        // Alternative: Try using native window handle if available
-        // This is a workaround that may work on some platforms
-        #[cfg(not(target_arch = "wasm32"))]
-        {
-            use std::sync::atomic::{AtomicBool, Ordering};
-            static TITLE_UPDATE_ATTEMPTED: AtomicBool = AtomicBool::new(false);
-
-            // Only attempt once per second to avoid overhead
-            if !TITLE_UPDATE_ATTEMPTED.swap(true, Ordering::Relaxed) {
-                // Store title in a static for potential future use
-                static CURRENT_TITLE: std::sync::Mutex<Option<String>> =
-                    std::sync::Mutex::new(None);
-
-                if let Ok(mut current) = CURRENT_TITLE.lock() {
-                    *current = Some(title.clone());
-                }
-
-                // Reset the flag after a delay
-                std::thread::spawn(move || {
-                    std::thread::sleep(std::time::Duration::from_secs(1));
-                    TITLE_UPDATE_ATTEMPTED.store(false, Ordering::Relaxed);
-                });
-            }
-        }
-
-        // Note: As of eframe 0.24, there's no direct API to update window title at runtime.
-        // The title is set once at window creation via ViewportBuilder.
-        // This implementation prepares the infrastructure for when eframe adds support.
-        let _ = title; // Use the variable to avoid unused warning
-    }
-
     /// Load a system font matching the configured font_family into egui.
     /// Falls back to egui's built-in monospace if the font isn't found.
     fn load_fonts(&mut self, ctx: &egui::Context) {
@@ -2730,6 +2998,21 @@ impl MosaicTermApp {
                 let old_input = self.input_prompt.current_input().to_string();
                 let mut current_input = old_input.clone();
 
+                // A multi-line paste becomes one editable buffer: normalise the
+                // pasted text and switch the editor to multi-line so egui keeps
+                // the line breaks instead of flattening them to spaces.
+                let pasted_lines = ui.input_mut(|i| {
+                    let mut multiline = false;
+                    for event in &mut i.events {
+                        if let egui::Event::Paste(text) = event {
+                            *text = mosaicterm::ui::input::utils::normalize_paste(text);
+                            multiline |= text.contains('\n');
+                        }
+                    }
+                    multiline
+                });
+                let multiline = pasted_lines || self.input_prompt.is_multiline();
+
                 let tab_pressed = ui.input(|i| i.key_pressed(egui::Key::Tab));
                 let escape_pressed = ui.input(|i| i.key_pressed(egui::Key::Escape));
                 let up_pressed = ui.input(|i| i.key_pressed(egui::Key::ArrowUp));
@@ -2752,8 +3035,19 @@ impl MosaicTermApp {
                         vis.widgets.noninteractive.bg_stroke = egui::Stroke::NONE;
                         vis.selection.stroke = egui::Stroke::NONE;
 
-                        ui.add(
+                        // Enter submits the whole buffer; Shift+Enter adds a line
+                        let editor = if multiline {
+                            egui::TextEdit::multiline(&mut current_input)
+                                .desired_rows(1)
+                                .return_key(egui::KeyboardShortcut::new(
+                                    egui::Modifiers::SHIFT,
+                                    egui::Key::Enter,
+                                ))
+                        } else {
                             egui::TextEdit::singleline(&mut current_input)
+                        };
+                        ui.add(
+                            editor
                                 .font(egui::FontId::monospace(font_size))
                                 .desired_width(f32::INFINITY)
                                 .frame(egui::Frame::NONE)
@@ -2890,6 +3184,8 @@ impl MosaicTermApp {
                             }
                         } else if tab_pressed {
                             self.handle_tab_completion(&current_input, input_rect);
+                        } else if (up_pressed || down_pressed) && multiline {
+                            // Arrows move between lines of a multi-line buffer
                         } else if up_pressed {
                             self.input_prompt.navigate_history_previous();
                             self.ghost_completion = None;
@@ -2901,7 +3197,7 @@ impl MosaicTermApp {
                         }
 
                         if input_changed {
-                            if current_input.is_empty() {
+                            if current_input.is_empty() || multiline {
                                 self.ghost_completion = None;
                             } else {
                                 self.ghost_completion =
@@ -2911,7 +3207,10 @@ impl MosaicTermApp {
                     }
 
                     // Handle Enter key to submit command
-                    if input_response.has_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                    let enter_pressed = ui.input(|i| {
+                        i.key_pressed(egui::Key::Enter) && !(multiline && i.modifiers.shift)
+                    });
+                    if input_response.has_focus() && enter_pressed {
                         // Check if completion popup is visible
                         if self.completion_popup.is_visible() {
                             // Select completion
@@ -3328,90 +3627,162 @@ impl MosaicTermApp {
                                     .color(colors.status_bar.text),
                             );
                         }
-                        if let Some(tree) = &self.pane_tree {
-                            let count = tree.pane_count();
-                            if count > 1 {
-                                ui.separator();
-                                ui.label(
-                                    egui::RichText::new(format!("pane {}", tree.active_id()))
-                                        .font(egui::FontId::monospace(11.0))
-                                        .color(colors.accent),
-                                );
-                            }
+                        if let Some(label) = self.active_pane_label() {
+                            ui.separator();
+                            ui.label(
+                                egui::RichText::new(label)
+                                    .font(egui::FontId::monospace(11.0))
+                                    .color(colors.accent),
+                            );
                         }
                     });
                 });
             });
 
-            // Scrollable command history - commands from newest to oldest (bottom to top)
-            egui::ScrollArea::vertical()
+            // Scrollable command history - commands from newest to oldest (bottom to top).
+            // Only the rows inside the viewport are laid out; the history
+            // layout knows where every row is
+            let command_history = self.state_manager.get_command_history();
+            let metrics = Self::history_row_metrics(ui);
+            self.history_layout.sync(command_history, metrics);
+
+            let mut scroll_area = egui::ScrollArea::vertical()
                 .auto_shrink([false; 2])
                 .stick_to_bottom(true)
                 .scroll_source(
                     egui::scroll_area::ScrollSource::SCROLL_BAR
                         | egui::scroll_area::ScrollSource::MOUSE_WHEEL,
-                )
-                .show(ui, |ui| {
-                    ui.vertical(|ui| {
-                        // Commands appear in execution order: oldest at top, newest at bottom
-                        let command_history = self.state_manager.get_command_history();
-                        for (i, block) in command_history.iter().enumerate() {
-                            if let Some((block_id, pos)) =
-                                Self::render_single_command_block_static(ui, block, i, &colors)
-                            {
-                                // Right-click detected, show context menu
-                                self.command_blocks
-                                    .interaction_state_mut()
-                                    .context_menu_block = Some(block_id);
-                                self.command_blocks.interaction_state_mut().context_menu_pos =
-                                    Some(pos);
-                            }
-                        }
+                );
+            // Keep the row being read in place while output lands above it
+            if let Some(offset) = self.history_layout.anchored_offset() {
+                if (offset - self.history_offset).abs() > 0.5 {
+                    scroll_area = scroll_area.vertical_scroll_offset(offset);
+                }
+            }
 
-                        if command_history.is_empty() {
-                            ui.add_space(40.0);
-                            ui.vertical_centered(|ui| {
-                                ui.label(
-                                    egui::RichText::new("MosaicTerm")
-                                        .font(egui::FontId::proportional(28.0))
-                                        .color(colors.accent)
-                                        .strong(),
-                                );
-                                ui.add_space(6.0);
-                                ui.label(
-                                    egui::RichText::new("Type a command below to get started")
-                                        .font(egui::FontId::proportional(14.0))
-                                        .color(colors.status_bar.text),
-                                );
-                                ui.add_space(20.0);
-                                let hint_color = egui::Color32::from_rgb(100, 100, 120);
-                                for hint in &[
-                                    "Tab         Auto-complete commands and paths",
-                                    "Up/Down     Navigate command history",
-                                    "Ctrl+R      Search history",
-                                    "Ctrl+L      Clear screen",
-                                    "Ctrl+Q      Quit",
-                                ] {
-                                    ui.label(
-                                        egui::RichText::new(*hint)
-                                            .font(egui::FontId::monospace(12.0))
-                                            .color(hint_color),
-                                    );
-                                }
-                            });
+            let layout = &self.history_layout;
+            let mut context_menu = None;
+            let mut paging = scrollback::Paging::default();
+            let output = scroll_area.show_viewport(ui, |ui, viewport| {
+                ui.set_min_height(layout.total_height());
+                let origin = ui.min_rect().min;
+                // Commands appear in execution order: oldest at top, newest at bottom
+                let visible_blocks = layout.visible(viewport.min.y, viewport.max.y);
+                for visible in &visible_blocks {
+                    let block = &command_history[visible.index];
+                    if let Some(menu) = Self::render_visible_block_static(
+                        ui, layout, visible, block, origin, &colors,
+                    ) {
+                        context_menu = Some(menu);
+                    }
+
+                    // Read the previous page back once the view scrolls past
+                    // the top of the oldest row in memory, or onto a block
+                    // that has none left
+                    let scrolled_to_top = visible.rows.start == 0
+                        && layout
+                            .row_top(visible.index, 0)
+                            .is_some_and(|top| top <= viewport.min.y);
+                    if block.scrollback.has_spilled()
+                        && (scrolled_to_top || layout.row_count(visible.index) == 0)
+                    {
+                        paging.page_in.push(visible.index);
+                    }
+                }
+                paging.page_out = command_history
+                    .iter()
+                    .enumerate()
+                    .filter(|(index, block)| {
+                        block.scrollback.loaded_lines() > 0
+                            && !visible_blocks.iter().any(|v| v.index == *index)
+                    })
+                    .map(|(index, _)| index)
+                    .collect();
+
+                if command_history.is_empty() {
+                    ui.add_space(40.0);
+                    ui.vertical_centered(|ui| {
+                        ui.label(
+                            egui::RichText::new("MosaicTerm")
+                                .font(egui::FontId::proportional(28.0))
+                                .color(colors.accent)
+                                .strong(),
+                        );
+                        ui.add_space(6.0);
+                        ui.label(
+                            egui::RichText::new("Type a command below to get started")
+                                .font(egui::FontId::proportional(14.0))
+                                .color(colors.status_bar.text),
+                        );
+                        ui.add_space(20.0);
+                        let hint_color = egui::Color32::from_rgb(100, 100, 120);
+                        for hint in &[
+                            "Tab         Auto-complete commands and paths",
+                            "Up/Down     Navigate command history",
+                            "Ctrl+R      Search history",
+                            "Ctrl+L      Clear screen",
+                            "Ctrl+Q      Quit",
+                        ] {
+                            ui.label(
+                                egui::RichText::new(*hint)
+                                    .font(egui::FontId::monospace(12.0))
+                                    .color(hint_color),
+                            );
                         }
                     });
-                });
+                }
+            });
+
+            // Remember where the view is so the next frame can hold it there
+            self.history_offset = output.state.offset.y;
+            self.history_layout
+                .set_viewport(output.state.offset.y, output.inner_rect.height());
+            if self.apply_paging(paging) {
+                ui.ctx().request_repaint();
+            }
+            if let Some((block_id, pos)) = context_menu {
+                // Right-click detected, show context menu
+                self.command_blocks
+                    .interaction_state_mut()
+                    .context_menu_block = Some(block_id);
+                self.command_blocks.interaction_state_mut().context_menu_pos = Some(pos);
+            }
         });
     }
 
-    /// Render a single command block (static version to avoid borrow checker issues)
-    fn render_single_command_block_static(
+    /// Sizes the history area's rows are laid out with
+    fn history_row_metrics(ui: &egui::Ui) -> mosaicterm::ui::history_layout::RowMetrics {
+        let output_font = egui::FontId::monospace(12.0);
+        let command_font = egui::FontId::monospace(12.5);
+        let (char_width, line_height, command_height) = ui.fonts_mut(|fonts| {
+            (
+                fonts.glyph_width(&output_font, 'M'),
+                fonts.row_height(&output_font),
+                fonts.row_height(&command_font),
+            )
+        });
+        mosaicterm::ui::history_layout::RowMetrics {
+            line_height,
+            char_width,
+            header_height: command_height.max(ui.spacing().interact_size.y),
+            wrap_width: (ui.available_width() - 2.0 * (BLOCK_INNER_MARGIN + BLOCK_OUTER_MARGIN))
+                .max(char_width),
+            pixels_per_point: ui.ctx().pixels_per_point(),
+        }
+    }
+
+    /// Render the part of a command block inside the viewport, at the place
+    /// the history layout gives it below `origin`
+    fn render_visible_block_static(
         ui: &mut egui::Ui,
+        layout: &HistoryLayout,
+        visible: &mosaicterm::ui::history_layout::VisibleBlock,
         block: &CommandBlock,
-        _index: usize,
+        origin: egui::Pos2,
         colors: &mosaicterm::ui::UiColors,
     ) -> Option<(String, egui::Pos2)> {
+        use mosaicterm::ui::history_layout::{BLOCK_PADDING, COMPACT_BLOCK_PADDING};
+
         let accent_color = match block.status {
             ExecutionStatus::Running => colors.blocks.status_running,
             ExecutionStatus::Failed => colors.blocks.status_failed,
@@ -3421,7 +3792,21 @@ impl MosaicTermApp {
             ExecutionStatus::TuiMode => colors.blocks.status_tui,
         };
 
-        let is_compact = block.output.is_empty();
+        let metrics = layout.metrics();
+        let rows = layout.row_count(visible.index);
+        let padding = if rows == 0 {
+            COMPACT_BLOCK_PADDING
+        } else {
+            BLOCK_PADDING
+        };
+        let rect = egui::Rect::from_min_size(
+            origin + egui::vec2(BLOCK_OUTER_MARGIN, visible.top),
+            egui::vec2(
+                metrics.wrap_width + 2.0 * BLOCK_INNER_MARGIN,
+                visible.height,
+            ),
+        );
+        let left = rect.left() + BLOCK_INNER_MARGIN;
 
         // Base block: very subtle background lift from main bg
         let block_bg = egui::Color32::from_rgb(
@@ -3429,97 +3814,121 @@ impl MosaicTermApp {
             colors.background.g().saturating_add(4),
             colors.background.b().saturating_add(5),
         );
-
-        let block_frame = egui::Frame::new()
-            .fill(block_bg)
-            .inner_margin(if is_compact {
-                egui::Margin::symmetric(12, 4)
-            } else {
-                egui::Margin::symmetric(12, 6)
-            })
-            .outer_margin(egui::Margin {
-                left: 4,
-                right: 4,
-                top: 1,
-                bottom: 1,
-            })
-            .corner_radius(egui::CornerRadius::same(4));
-
         // Reserve a shape slot BEFORE the block content for hover background.
         // This ensures the hover fill is drawn BEHIND the text.
         let hover_bg_idx = ui.painter().add(egui::Shape::Noop);
+        ui.painter()
+            .rect_filled(rect, egui::CornerRadius::same(4), block_bg);
 
-        let frame_response = block_frame.show(ui, |ui| {
-            ui.vertical(|ui| {
-                ui.horizontal(|ui| {
+        let header_rect = egui::Rect::from_min_size(
+            egui::pos2(left, rect.top() + padding),
+            egui::vec2(metrics.wrap_width, metrics.header_height),
+        );
+        ui.scope_builder(egui::UiBuilder::new().max_rect(header_rect), |ui| {
+            ui.horizontal(|ui| {
+                ui.label(
+                    egui::RichText::new(&block.command)
+                        .font(egui::FontId::monospace(12.5))
+                        .color(colors.blocks.command_text)
+                        .strong(),
+                );
+                // Title the command set while it ran, unless it just
+                // repeats the command
+                if let Some(title) = block.title.as_deref().filter(|t| *t != block.command) {
                     ui.label(
-                        egui::RichText::new(&block.command)
-                            .font(egui::FontId::monospace(12.5))
-                            .color(colors.blocks.command_text)
-                            .strong(),
+                        egui::RichText::new(title)
+                            .font(egui::FontId::monospace(10.0))
+                            .color(colors.blocks.timestamp),
                     );
+                }
+                let spilled = block.scrollback.spilled_lines();
+                if spilled > 0 {
+                    ui.label(
+                        egui::RichText::new(format!("{} earlier lines on disk", spilled))
+                            .font(egui::FontId::monospace(10.0))
+                            .color(colors.blocks.timestamp),
+                    );
+                }
 
-                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
-                        ui.label(
-                            egui::RichText::new(block.timestamp.format("%H:%M:%S").to_string())
-                                .font(egui::FontId::monospace(10.0))
-                                .color(colors.blocks.timestamp),
-                        );
-
-                        let (status_text, status_color) = match block.status {
-                            ExecutionStatus::Running => ("running", colors.blocks.status_running),
-                            ExecutionStatus::Completed => ("ok", colors.blocks.status_completed),
-                            ExecutionStatus::Failed => ("fail", colors.blocks.status_failed),
-                            ExecutionStatus::Cancelled => {
-                                ("cancel", colors.blocks.status_cancelled)
-                            }
-                            ExecutionStatus::Pending => ("...", colors.blocks.status_pending),
-                            ExecutionStatus::TuiMode => ("tui", colors.blocks.status_tui),
-                        };
-
-                        ui.label(
-                            egui::RichText::new(status_text)
-                                .font(egui::FontId::monospace(10.0))
-                                .color(status_color),
-                        );
-                    });
-                });
-
-                if !block.output.is_empty() {
-                    ui.add_space(3.0);
-
-                    let mono_font = egui::FontId::monospace(12.0);
-                    let output_color = colors.blocks.output_text;
-                    let (plain_text, layout_job) = mosaicterm::ui::text::build_output_layout_job(
-                        &block.output,
-                        mono_font.clone(),
-                        output_color,
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    ui.label(
+                        egui::RichText::new(block.timestamp.format("%H:%M:%S").to_string())
+                            .font(egui::FontId::monospace(10.0))
+                            .color(colors.blocks.timestamp),
                     );
 
-                    let job_for_layouter = layout_job;
-                    let mut layouter =
-                        move |ui: &egui::Ui, _buf: &dyn egui::TextBuffer, wrap_width: f32| {
-                            let mut j = job_for_layouter.clone();
-                            j.wrap.max_width = wrap_width;
-                            ui.fonts_mut(|f| f.layout_job(j))
-                        };
-                    let mut text_ref: &str = &plain_text;
-                    ui.add(
-                        egui::TextEdit::multiline(&mut text_ref)
-                            .id_source(format!("output_{}", block.id))
-                            .font(mono_font)
-                            .desired_width(f32::INFINITY)
-                            .frame(egui::Frame::NONE)
-                            .layouter(&mut layouter),
+                    let (status_text, status_color) = match block.status {
+                        ExecutionStatus::Running => ("running", colors.blocks.status_running),
+                        ExecutionStatus::Completed => ("ok", colors.blocks.status_completed),
+                        ExecutionStatus::Failed => ("fail", colors.blocks.status_failed),
+                        ExecutionStatus::Cancelled => ("cancel", colors.blocks.status_cancelled),
+                        ExecutionStatus::Pending => ("...", colors.blocks.status_pending),
+                        ExecutionStatus::TuiMode => ("tui", colors.blocks.status_tui),
+                    };
+
+                    ui.label(
+                        egui::RichText::new(status_text)
+                            .font(egui::FontId::monospace(10.0))
+                            .color(status_color),
                     );
-                }
+                });
             });
         });
 
-        // Hover effect: fill the pre-reserved shape slot so the background draws BEHIND text
-        if frame_response.response.hovered() {
-            let rect = frame_response.response.rect;
+        // Output rows: settled lines, split by images, then the output still
+        // being rewritten while the command runs
+        let settled = block.output.len();
+        let run_rect = |start: usize, end: usize| {
+            let top = origin.y + layout.row_top(visible.index, start)?;
+            let bottom = origin.y + layout.row_top(visible.index, end)?;
+            Some(egui::Rect::from_min_max(
+                egui::pos2(left, top),
+                egui::pos2(left + metrics.wrap_width, bottom),
+            ))
+        };
+        let text_end = visible.rows.end.min(settled);
+        let mut start = visible.rows.start;
+        for i in visible.rows.start..text_end {
+            let Some(placement) = &block.output[i].image else {
+                continue;
+            };
+            if let Some(run) = run_rect(start, i).filter(|_| start < i) {
+                ui.scope_builder(egui::UiBuilder::new().max_rect(run), |ui| {
+                    Self::render_output_text_static(ui, block, start..i, colors);
+                });
+            }
+            if let Some(run) = run_rect(i, i + 1) {
+                ui.scope_builder(egui::UiBuilder::new().max_rect(run), |ui| {
+                    Self::render_output_image_static(ui, placement, block.images_hidden, colors);
+                });
+            }
+            start = i + 1;
+        }
+        if let Some(run) = run_rect(start, text_end).filter(|_| start < text_end) {
+            ui.scope_builder(egui::UiBuilder::new().max_rect(run), |ui| {
+                Self::render_output_text_static(ui, block, start..text_end, colors)
+            });
+        }
+        let live_start = visible.rows.start.max(settled);
+        let live_rect = run_rect(live_start, visible.rows.end)
+            .filter(|_| live_start < visible.rows.end && block.status == ExecutionStatus::Running);
+        if let Some(run) = live_rect {
+            let live = &block.live_output
+                [live_start - settled..(visible.rows.end - settled).min(block.live_output.len())];
+            ui.scope_builder(egui::UiBuilder::new().max_rect(run), |ui| {
+                Self::render_output_lines_static(
+                    ui,
+                    format!("output_{}_live", block.id),
+                    live,
+                    colors,
+                )
+            });
+        }
+
+        let hovered = ui.rect_contains_pointer(rect);
 
+        // Hover effect: fill the pre-reserved shape slot so the background draws BEHIND text
+        if hovered {
             let hover_bg = egui::Color32::from_rgb(
                 colors.background.r().saturating_add(14),
                 colors.background.g().saturating_add(14),
@@ -3559,7 +3968,7 @@ impl MosaicTermApp {
         }
 
         // Check if mouse is over this block and right-click was pressed
-        if frame_response.response.hovered() && ui.input(|i| i.pointer.secondary_clicked()) {
+        if hovered && ui.input(|i| i.pointer.secondary_clicked()) {
             if let Some(pos) = ui.input(|i| i.pointer.hover_pos()) {
                 return Some((block.id.clone(), pos));
             }
@@ -3568,6 +3977,105 @@ impl MosaicTermApp {
         None
     }
 
+    /// Render a run of a block's output lines as selectable text
+    fn render_output_text_static(
+        ui: &mut egui::Ui,
+        block: &CommandBlock,
+        lines: std::ops::Range<usize>,
+        colors: &mosaicterm::ui::UiColors,
+    ) {
+        // Keyed on where the run starts in the block, not in the viewport,
+        // so a selection survives scrolling
+        let run_start = block.output[..lines.start]
+            .iter()
+            .rposition(|line| line.image.is_some())
+            .map_or(0, |image| image + 1);
+        let id = if run_start == 0 {
+            format!("output_{}", block.id)
+        } else {
+            format!("output_{}_{}", block.id, run_start)
+        };
+        Self::render_output_lines_static(ui, id, &block.output[lines], colors);
+    }
+
+    /// Render output lines as selectable text, with their links
+    fn render_output_lines_static(
+        ui: &mut egui::Ui,
+        id: String,
+        lines: &[mosaicterm::models::OutputLine],
+        colors: &mosaicterm::ui::UiColors,
+    ) {
+        let mono_font = egui::FontId::monospace(12.0);
+        let output_color = colors.blocks.output_text;
+        let time = ui.input(|i| i.time);
+        let (plain_text, layout_job, decorations) = mosaicterm::ui::text::build_output_layout_job(
+            lines,
+            mono_font.clone(),
+            output_color,
+            mosaicterm::ui::text::text_blink_on(time),
+        );
+        if decorations.has_blink() {
+            let interval = mosaicterm::ui::text::TEXT_BLINK_INTERVAL;
+            ui.ctx()
+                .request_repaint_after(std::time::Duration::from_secs_f64(
+                    interval - time % interval,
+                ));
+        }
+
+        let job_for_layouter = layout_job;
+        let mut layouter = move |ui: &egui::Ui, _buf: &dyn egui::TextBuffer, wrap_width: f32| {
+            let mut j = job_for_layouter.clone();
+            // Wrap at any cell, like a terminal, so rows match the history layout
+            j.wrap.max_width = wrap_width;
+            j.wrap.break_anywhere = true;
+            ui.fonts_mut(|f| {
+                let j = mosaicterm::ui::text::align_wide_chars(j, f);
+                f.layout_job(j)
+            })
+        };
+        let mut text_ref: &str = &plain_text;
+        let output = egui::TextEdit::multiline(&mut text_ref)
+            .id_source(id)
+            .font(mono_font)
+            .desired_width(f32::INFINITY)
+            .frame(egui::Frame::NONE)
+            .layouter(&mut layouter)
+            .show(ui);
+        decorations.paint(ui.painter(), &output.galley, output.galley_pos);
+
+        // OSC 8 links: underlined on hover, opened with Ctrl/Cmd+Click
+        let hovered_link = output
+            .response
+            .hover_pos()
+            .and_then(|pos| decorations.link_at(&output.galley, output.galley_pos, pos));
+        if let Some((range, uri)) = hovered_link {
+            decorations.paint_link(
+                ui.painter(),
+                &output.galley,
+                output.galley_pos,
+                range,
+                output_color,
+            );
+            let modifier = if cfg!(target_os = "macos") {
+                "Cmd"
+            } else {
+                "Ctrl"
+            };
+            if ui.input(|i| i.modifiers.command) {
+                ui.ctx().set_cursor_icon(egui::CursorIcon::PointingHand);
+            }
+            if output.response.clicked() && ui.input(|i| i.modifiers.command) {
+                if let Err(e) = mosaicterm::terminal::hyperlink::open(uri) {
+                    warn!("{}", e);
+                }
+            }
+            output
+                .response
+                .response
+                .on_hover_text(format!("{}\n{}+Click to open", uri, modifier));
+        }
+    }
+
     /// Poll for async operation results (non-blocking)
     fn poll_async_results(&mut self) {
         // Try to receive all pending results without blocking
@@ -3768,15 +4276,20 @@ impl MosaicTermApp {
                 return Some(1);
             }
             // segfault
-            if lower.contains("segmentation fault") || lower.contains("killed") {
+            if lower.contains("segmentation fault") {
                 return Some(139);
             }
+            // bash's report for a job ended by SIGKILL, not any output that
+            // happens to mention the word
+            if lower == "killed" {
+                return Some(137);
+            }
         }
         None
     }
 
     /// Handle async operations (called from update) - SIMPLIFIED VERSION
-    fn handle_async_operations(&mut self, _ctx: &egui::Context) {
+    fn handle_async_operations(&mut self, ctx: &egui::Context) {
         let bg_notification_threshold_ms: u128 = 10_000;
         // SIMPLIFIED: Poll PTY output and add to current command (no complex prompt detection)
         let mut should_update_contexts = false;
@@ -3784,6 +4297,31 @@ impl MosaicTermApp {
         let mut ssh_session_ended = false; // Track if SSH session ended
         let mut ssh_session_should_activate = false; // Track if SSH session should be activated
         let mut new_remote_prompt: Option<String> = None; // Track new remote prompt
+        let mut reported_cwd = None; // Latest OSC 7 directory from the shell
+        let mut images_added = false; // Whether output brought inline images
+        let mut output_added = false; // Whether output brought lines
+
+        // A running command that turns off canonical mode (fzf, a pager without
+        // the alternate screen) wants raw keys; SSH is handled in block mode
+        if !self.tui_overlay.is_active() && self.ssh_session_command.is_none() {
+            let block_running = self
+                .state_manager
+                .get_command_history()
+                .last()
+                .is_some_and(|b| b.status == mosaicterm::models::ExecutionStatus::Running);
+            let is_raw = block_running
+                && self
+                    .terminal
+                    .as_ref()
+                    .and_then(|t| t.pty_handle())
+                    .is_some_and(|handle| {
+                        executor::block_on(self.pty_manager.is_foreground_raw(handle))
+                            .unwrap_or(false)
+                    });
+            if is_raw {
+                self.promote_to_tui_overlay(&[]);
+            }
+        }
 
         if let Some(_terminal) = &mut self.terminal {
             if let Some(handle) = _terminal.pty_handle() {
@@ -3794,17 +4332,20 @@ impl MosaicTermApp {
                     executor::block_on(async { pty_manager.try_read_output_now(handle).await })
                 {
                     if !data.is_empty() {
+                        // One chunk is read per frame; come back for the rest
+                        ctx.request_repaint();
+
                         // If TUI overlay is active, send RAW output there (don't process it!)
                         if self.tui_overlay.is_active() {
                             debug!("Routing {} bytes to TUI overlay", data.len());
 
                             // Send raw bytes directly to overlay - TUI apps need ANSI codes!
                             self.tui_overlay.add_raw_output(&data);
+                            let replies = self.tui_overlay.take_responses();
+                            send_to_app(pty_manager, handle, &replies);
 
                             // Track alternate screen enter (TUI app starting)
-                            let has_alt_screen_enter = data.windows(8).any(|w| w == b"\x1b[?1049h")
-                                || data.windows(6).any(|w| w == b"\x1b[?47h");
-                            if has_alt_screen_enter {
+                            if self.alt_screen.scan(&data).is_some() {
                                 self.tui_overlay.note_alt_screen_enter();
                             }
 
@@ -3841,9 +4382,66 @@ impl MosaicTermApp {
                                 }
                             }
 
+                            // The app may have drawn or stored images
+                            self.enforce_image_budget();
                             return; // Don't process output for command blocks
                         }
 
+                        // A running command that switches to the alternate screen is a
+                        // TUI app whatever its name: keep what it printed before the
+                        // switch in its block and hand the rest to the overlay
+                        let block_running = self
+                            .state_manager
+                            .get_command_history()
+                            .last()
+                            .is_some_and(|b| {
+                                b.status == mosaicterm::models::ExecutionStatus::Running
+                            });
+                        if block_running {
+                            if let Some(offset) = self.alt_screen.scan(&data) {
+                                let (before, screen) = data.split_at(offset);
+                                let mut lines =
+                                    futures::executor::block_on(_terminal.process_output(
+                                        before,
+                                        mosaicterm::terminal::StreamType::Stdout,
+                                    ))
+                                    .unwrap_or_default();
+                                lines.extend(_terminal.flush_output());
+                                lines.retain(|line| {
+                                    !line.text.trim().is_empty() || line.image.is_some()
+                                });
+                                if let Some(block) = self
+                                    .state_manager
+                                    .command_history_mut()
+                                    .and_then(|history| history.last_mut())
+                                {
+                                    block.add_output_lines(lines);
+                                }
+                                self.promote_to_tui_overlay(screen);
+                                return;
+                            }
+                        }
+
+                        // Answer terminal queries (fish's cursor probe, vim's background
+                        // check) from the block view: the cursor is taken to sit after
+                        // the partial line on the bottom row
+                        let queries = self.queries.scan(&data);
+                        if !queries.is_empty() {
+                            let row = self
+                                .pty_size
+                                .applied()
+                                .map_or(24, |size| usize::from(size.rows));
+                            let col = _terminal.cursor_col();
+                            let replies: Vec<u8> = queries
+                                .iter()
+                                .flat_map(|query| {
+                                    query
+                                        .reply(&self.terminal_palette, (row.saturating_sub(1), col))
+                                })
+                                .collect();
+                            send_to_app(pty_manager, handle, &replies);
+                        }
+
                         // Check for SSH prompts that need user interaction
                         let data_str = String::from_utf8_lossy(&data);
 
@@ -3920,7 +4518,7 @@ impl MosaicTermApp {
                             // --- All output goes through the OutputProcessor ---
                             // This ensures OSC / CSI sequences are properly
                             // stripped before any text reaches the UI.
-                            let ready_lines = futures::executor::block_on(async {
+                            let mut ready_lines = futures::executor::block_on(async {
                                 _terminal
                                     .process_output(&data, mosaicterm::terminal::StreamType::Stdout)
                                     .await
@@ -3933,8 +4531,53 @@ impl MosaicTermApp {
                             // Peek at the partial (un-newlined) text the
                             // processor is accumulating — used for prompt
                             // detection below.
-                            let partial_line_text: Option<String> =
-                                _terminal.peek_partial_line().map(|s| s.to_owned());
+                            let partial_line_text = _terminal.peek_partial_line();
+
+                            // A shell that marks its prompts (OSC 133) says where
+                            // each command ends and how it exited, so the prompt
+                            // and error-text heuristics below stand down. Remote
+                            // shells in an SSH session don't send the marks.
+                            let prompt_marks = _terminal.take_prompt_marks();
+                            reported_cwd = _terminal.take_reported_cwd();
+                            let marked = _terminal.has_prompt_marks() && !self.ssh_session_active;
+
+                            // ---- Prompt-based completion detection ----
+                            // Use the partial line from the OutputProcessor
+                            // (already has OSC stripped) for prompt detection.
+                            let prompt_detected = if let Some(ref partial) = partial_line_text {
+                                let pt = partial.trim();
+                                if pt.is_empty() {
+                                    false
+                                } else {
+                                    let classic = pt.ends_with("$ ")
+                                        || pt.ends_with("$")
+                                        || pt.ends_with("% ")
+                                        || pt.ends_with("%")
+                                        || pt.ends_with("> ")
+                                        || pt.ends_with(">")
+                                        || pt.ends_with("# ")
+                                        || pt.ends_with("#")
+                                        || (pt.contains("@")
+                                            && (pt.contains("$")
+                                                || pt.contains("%")
+                                                || pt.contains("#")));
+                                    let ohmyzsh = pt.contains("@")
+                                        && pt.len() < 120
+                                        && (pt.trim_end().ends_with("~")
+                                            || pt.trim_end().ends_with("/"));
+                                    classic || ohmyzsh
+                                }
+                            } else {
+                                false
+                            };
+
+                            // Output a program could still rewrite is final once
+                            // the shell is back at its prompt; shells that mark
+                            // their prompts settle it with the marks
+                            if prompt_detected && !marked {
+                                ready_lines.extend(_terminal.settle_output());
+                            }
+                            let live_lines = _terminal.live_output_lines();
 
                             debug!(
                                 "Got {} lines from terminal, partial: {:?}",
@@ -3988,25 +4631,32 @@ impl MosaicTermApp {
                                             && line_text.len() < 120
                                             && (line_text.trim_end().ends_with("~")
                                                 || line_text.trim_end().ends_with("/"));
-                                        let looks_like_prompt = classic_prompt || ohmyzsh_prompt;
+                                        let looks_like_prompt =
+                                            !marked && (classic_prompt || ohmyzsh_prompt);
 
                                         // Detect user@host pattern (prompt from zsh/Oh My Zsh themes)
                                         let has_user_at_host =
                                             line_text.contains("@") && line_text.len() < 200;
                                         // Prompt line with command echo:
                                         //   " user@host  ~  eza -l"
-                                        let is_prompt_command_echo = has_user_at_host
+                                        let is_prompt_command_echo = !marked
+                                            && has_user_at_host
                                             && !command_text.is_empty()
                                             && line_text.ends_with(&command_text);
 
+                                        // Command echo and `^C`; marked output holds
+                                        // neither, so lines like these are real output
+                                        let is_echo = !marked
+                                            && (line_text == command_text
+                                                || (is_first_few
+                                                    && !line_text.is_empty()
+                                                    && command_text.starts_with(line_text)
+                                                    && line_text.len() <= 3)
+                                                || line_text.contains("^C"));
+
                                         // Filter prompts in BOTH local and SSH modes
-                                        let should_skip = line_text == command_text
-                                            || (is_first_few
-                                                && !line_text.is_empty()
-                                                && command_text.starts_with(line_text)
-                                                && line_text.len() <= 3)
-                                            || line_text.contains("^C")
-                                            || line_text.is_empty()
+                                        let should_skip = is_echo
+                                            || (line_text.is_empty() && line.image.is_none())
                                             || looks_like_prompt
                                             || is_prompt_command_echo;
 
@@ -4052,12 +4702,16 @@ impl MosaicTermApp {
                                     if !lines_to_add.is_empty() {
                                         last_block.add_output_lines(lines_to_add.clone());
                                         lines_count = lines_to_add.len();
+                                        output_added = true;
+                                        images_added |=
+                                            lines_to_add.iter().any(|line| line.image.is_some());
 
                                         // Eagerly detect shell errors in the new output.
                                         // If the output contains "command not found" or similar,
                                         // mark the block as failed right now.
-                                        if last_block.status
-                                            == mosaicterm::models::ExecutionStatus::Running
+                                        if !marked
+                                            && last_block.status
+                                                == mosaicterm::models::ExecutionStatus::Running
                                         {
                                             if let Some(err_code) =
                                                 Self::detect_error_in_output(&lines_to_add)
@@ -4076,55 +4730,8 @@ impl MosaicTermApp {
                                         }
                                     }
 
-                                    if last_block.output.len() > MAX_OUTPUT_LINES_PER_COMMAND {
-                                        let to_remove = last_block.output.len()
-                                            - MAX_OUTPUT_LINES_PER_COMMAND
-                                            + 1000;
-                                        last_block.output.drain(0..to_remove);
-                                        let truncation_notice =
-                                            mosaicterm::models::OutputLine::new(format!(
-                                                "... [truncated {} lines due to size limit] ...",
-                                                to_remove
-                                            ));
-                                        last_block.output.insert(0, truncation_notice);
-                                        warn!(
-                                            "Truncated {} lines from command output (limit: {})",
-                                            to_remove, MAX_OUTPUT_LINES_PER_COMMAND
-                                        );
-                                    }
-
-                                    // ---- Prompt-based completion detection ----
-                                    // Use the partial line from the OutputProcessor
-                                    // (already has OSC stripped) for prompt detection.
-                                    let prompt_detected =
-                                        if let Some(ref partial) = partial_line_text {
-                                            let pt = partial.trim();
-                                            if pt.is_empty() {
-                                                false
-                                            } else {
-                                                let classic = pt.ends_with("$ ")
-                                                    || pt.ends_with("$")
-                                                    || pt.ends_with("% ")
-                                                    || pt.ends_with("%")
-                                                    || pt.ends_with("> ")
-                                                    || pt.ends_with(">")
-                                                    || pt.ends_with("# ")
-                                                    || pt.ends_with("#")
-                                                    || (pt.contains("@")
-                                                        && (pt.contains("$")
-                                                            || pt.contains("%")
-                                                            || pt.contains("#")));
-                                                let ohmyzsh = pt.contains("@")
-                                                    && pt.len() < 120
-                                                    && (pt.trim_end().ends_with("~")
-                                                        || pt.trim_end().ends_with("/"));
-                                                classic || ohmyzsh
-                                            }
-                                        } else {
-                                            false
-                                        };
-
                                     if prompt_detected
+                                        && !marked
                                         && last_block.status
                                             == mosaicterm::models::ExecutionStatus::Running
                                     {
@@ -4151,6 +4758,46 @@ impl MosaicTermApp {
                                         );
                                     }
 
+                                    // ---- Prompt-mark completion: exact exit status ----
+                                    for mark in &prompt_marks {
+                                        let mosaicterm::terminal::PromptMark::CommandFinished {
+                                            exit_code,
+                                        } = *mark
+                                        else {
+                                            continue;
+                                        };
+                                        if last_block.status
+                                            != mosaicterm::models::ExecutionStatus::Running
+                                        {
+                                            continue;
+                                        }
+                                        let elapsed = last_command_time
+                                            .map(|s| s.elapsed())
+                                            .unwrap_or_default();
+                                        let exit_code = exit_code
+                                            .or_else(|| {
+                                                mosaicterm::pty::shell_state::read_exit_code(
+                                                    std::process::id(),
+                                                )
+                                            })
+                                            .unwrap_or(0);
+                                        last_block.mark_completed_with_code(elapsed, exit_code);
+                                        should_clear_command_time = true;
+                                        debug!(
+                                            "Command completed (prompt mark, exit {}): {}",
+                                            exit_code, last_block.command
+                                        );
+                                        if !self.window_has_focus
+                                            && elapsed.as_millis() >= bg_notification_threshold_ms
+                                        {
+                                            notify_command_finished(
+                                                &last_block.command,
+                                                exit_code,
+                                                elapsed.as_millis(),
+                                            );
+                                        }
+                                    }
+
                                     // Also check completed lines for prompt-based completion
                                     if !should_clear_command_time {
                                         if let Some(start_time) = last_command_time {
@@ -4161,7 +4808,9 @@ impl MosaicTermApp {
                                                 last_block.output.clone();
                                             let command_clone = last_block.command.clone();
 
-                                            let is_complete = if !output_lines_clone.is_empty() {
+                                            let is_complete = if !marked
+                                                && !output_lines_clone.is_empty()
+                                            {
                                                 let recent_lines = if output_lines_clone.len() > 3 {
                                                     &output_lines_clone
                                                         [output_lines_clone.len() - 3..]
@@ -4285,6 +4934,21 @@ impl MosaicTermApp {
                                 }
                             }
 
+                            // Lines still being rewritten show under a running command
+                            if let Some(block) = self
+                                .state_manager
+                                .command_history_mut()
+                                .and_then(|history| history.last_mut())
+                            {
+                                block.live_output = if block.status
+                                    == mosaicterm::models::ExecutionStatus::Running
+                                {
+                                    live_lines
+                                } else {
+                                    Vec::new()
+                                };
+                            }
+
                             if lines_count > 0 {
                                 self.state_manager.statistics_mut().total_output_lines +=
                                     lines_count;
@@ -4300,6 +4964,17 @@ impl MosaicTermApp {
             }
         }
 
+        if let Some(cwd) = reported_cwd {
+            self.apply_reported_cwd(cwd);
+        }
+
+        if images_added {
+            self.enforce_image_budget();
+        }
+        if output_added {
+            self.enforce_scrollback_budget();
+        }
+
         // Set timeout kill status message after all borrows are released
         if let Some(msg) = timeout_kill_status_message {
             self.set_status_message(Some(msg));
@@ -4330,8 +5005,10 @@ impl MosaicTermApp {
         // for a shell prompt even when no new data has arrived.  This catches
         // fast-failing commands (typos, command-not-found) where the error +
         // prompt arrive in one burst and no subsequent data triggers the
-        // in-data-handler check.
-        if !should_update_contexts && !self.ssh_session_active {
+        // in-data-handler check.  Shells that mark their prompts are finished
+        // from the marks instead, here and in the process-tree check below.
+        let prompt_marked = self.terminal.as_ref().is_some_and(|t| t.has_prompt_marks());
+        if !should_update_contexts && !self.ssh_session_active && !prompt_marked {
             if let Some(terminal) = &self.terminal {
                 if let Some(partial) = terminal.peek_partial_line() {
                     let pt = partial.trim();
@@ -4395,7 +5072,7 @@ impl MosaicTermApp {
 
         // Process-tree-based command completion: poll whether the shell
         // still has child processes.  When children disappear the command is done.
-        if !should_update_contexts && !self.ssh_session_active {
+        if !should_update_contexts && !self.ssh_session_active && !prompt_marked {
             if let Some(terminal) = &self.terminal {
                 if let Some(handle) = terminal.pty_handle() {
                     if let Some(shell_pid) = handle.pid {
@@ -4446,24 +5123,10 @@ impl MosaicTermApp {
                                         if !self.window_has_focus
                                             && elapsed_ms >= bg_notification_threshold_ms
                                         {
-                                            let cmd_short = if last_block.command.len() > 40 {
-                                                format!("{}...", &last_block.command[..40])
-                                            } else {
-                                                last_block.command.clone()
-                                            };
-                                            let status_str = if exit_code == 0 {
-                                                "completed"
-                                            } else {
-                                                "failed"
-                                            };
-                                            send_system_notification(
-                                                "MosaicTerm",
-                                                &format!(
-                                                    "Command {} ({}s): {}",
-                                                    status_str,
-                                                    elapsed_ms / 1000,
-                                                    cmd_short
-                                                ),
+                                            notify_command_finished(
+                                                &last_block.command,
+                                                exit_code,
+                                                elapsed_ms,
                                             );
                                         }
 
@@ -4516,4 +5179,20 @@ mod tests {
         app.set_status_message(None);
         assert!(app.state_manager.app_state().status_message.is_none());
     }
+
+    #[test]
+    fn test_block_links_are_distinct() {
+        use mosaicterm::models::output_line::{Hyperlink, OutputLine};
+
+        let mut block = CommandBlock::new("ls --hyperlink".to_string(), "/".into());
+        for (text, uri) in [("a", "file:///a"), ("b", "file:///b"), ("a", "file:///a")] {
+            let mut line = OutputLine::new(text);
+            line.hyperlinks.push(Hyperlink {
+                range: 0..1,
+                uri: uri.to_string(),
+            });
+            block.add_output_line(line);
+        }
+        assert_eq!(block_links(&block), ["file:///a", "file:///b"]);
+    }
 }
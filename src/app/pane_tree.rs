@@ -1,5 +1,5 @@
 use mosaicterm::models::command_block::CommandBlock;
-use mosaicterm::terminal::Terminal;
+use mosaicterm::terminal::{ResizeDebouncer, Terminal};
 use mosaicterm::ui::input::InputPrompt;
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -22,6 +22,8 @@ pub struct Pane {
     pub command_history: Vec<CommandBlock>,
     pub input_state: InputPrompt,
     pub scroll_offset: f32,
+    /// Debounced PTY size measured from this pane's share of the window
+    pub pty_size: ResizeDebouncer,
 }
 
 impl Pane {
@@ -32,6 +34,7 @@ impl Pane {
             command_history: Vec::new(),
             input_state: InputPrompt::new(),
             scroll_offset: 0.0,
+            pty_size: ResizeDebouncer::default(),
         }
     }
 }
@@ -44,6 +47,9 @@ pub enum PaneNode {
     },
 }
 
+/// Width of the divider drawn between split panes
+const DIVIDER_WIDTH: f32 = 2.0;
+
 pub struct PaneTree {
     root: PaneNode,
     active_id: String,
@@ -161,6 +167,13 @@ impl PaneTree {
         Self::visit_leaves_mut(&mut self.root, &mut f);
     }
 
+    /// Screen rect of every pane when the tree fills `rect`
+    pub fn layout(&self, rect: eframe::egui::Rect) -> Vec<(String, eframe::egui::Rect)> {
+        let mut rects = Vec::new();
+        Self::layout_node(&self.root, rect, &mut rects);
+        rects
+    }
+
     /// Render the pane tree into the UI, calling `render_fn` for each pane leaf
     pub fn render<F>(&self, ui: &mut eframe::egui::Ui, render_fn: &mut F)
     where
@@ -190,57 +203,90 @@ impl PaneTree {
                 render_fn(&mut child_ui, pane, is_active);
             }
             PaneNode::Branch { axis, children } => {
-                let total_flex: f32 = children.iter().map(|(f, _)| *f).sum();
-                if total_flex <= 0.0 {
-                    return;
-                }
-                let divider_width = 2.0;
-                let total_divider_space = divider_width * (children.len() as f32 - 1.0).max(0.0);
-
-                let available = match axis {
-                    SplitAxis::Horizontal => rect.width() - total_divider_space,
-                    SplitAxis::Vertical => rect.height() - total_divider_space,
-                };
-
-                let mut offset = 0.0;
-                for (i, (flex, child)) in children.iter().enumerate() {
-                    let size = available * (flex / total_flex);
-                    let child_rect = match axis {
-                        SplitAxis::Horizontal => eframe::egui::Rect::from_min_size(
-                            eframe::egui::pos2(rect.left() + offset, rect.top()),
-                            eframe::egui::vec2(size, rect.height()),
-                        ),
-                        SplitAxis::Vertical => eframe::egui::Rect::from_min_size(
-                            eframe::egui::pos2(rect.left(), rect.top() + offset),
-                            eframe::egui::vec2(rect.width(), size),
-                        ),
-                    };
+                for ((child_rect, divider_rect), (_, child)) in
+                    Self::split_rects(*axis, children, rect)
+                        .into_iter()
+                        .zip(children)
+                {
                     Self::render_node(ui, child, child_rect, active_id, render_fn);
-                    offset += size;
-
-                    if i < children.len() - 1 {
-                        let divider_rect = match axis {
-                            SplitAxis::Horizontal => eframe::egui::Rect::from_min_size(
-                                eframe::egui::pos2(rect.left() + offset, rect.top()),
-                                eframe::egui::vec2(divider_width, rect.height()),
-                            ),
-                            SplitAxis::Vertical => eframe::egui::Rect::from_min_size(
-                                eframe::egui::pos2(rect.left(), rect.top() + offset),
-                                eframe::egui::vec2(rect.width(), divider_width),
-                            ),
-                        };
+                    if let Some(divider_rect) = divider_rect {
                         ui.painter().rect_filled(
                             divider_rect,
                             eframe::egui::CornerRadius::ZERO,
                             eframe::egui::Color32::from_rgb(60, 60, 80),
                         );
-                        offset += divider_width;
                     }
                 }
             }
         }
     }
 
+    fn layout_node(
+        node: &PaneNode,
+        rect: eframe::egui::Rect,
+        rects: &mut Vec<(String, eframe::egui::Rect)>,
+    ) {
+        match node {
+            PaneNode::Leaf(pane) => rects.push((pane.id.clone(), rect)),
+            PaneNode::Branch { axis, children } => {
+                for ((child_rect, _), (_, child)) in Self::split_rects(*axis, children, rect)
+                    .into_iter()
+                    .zip(children)
+                {
+                    Self::layout_node(child, child_rect, rects);
+                }
+            }
+        }
+    }
+
+    /// Divide `rect` among a branch's children by flex weight, returning each
+    /// child's rect and the divider that follows it (none after the last)
+    fn split_rects(
+        axis: SplitAxis,
+        children: &[(f32, PaneNode)],
+        rect: eframe::egui::Rect,
+    ) -> Vec<(eframe::egui::Rect, Option<eframe::egui::Rect>)> {
+        let total_flex: f32 = children.iter().map(|(f, _)| *f).sum();
+        if total_flex <= 0.0 {
+            return Vec::new();
+        }
+        let total_divider_space = DIVIDER_WIDTH * (children.len() as f32 - 1.0).max(0.0);
+
+        let available = match axis {
+            SplitAxis::Horizontal => rect.width() - total_divider_space,
+            SplitAxis::Vertical => rect.height() - total_divider_space,
+        };
+
+        let span = |offset: f32, size: f32| match axis {
+            SplitAxis::Horizontal => eframe::egui::Rect::from_min_size(
+                eframe::egui::pos2(rect.left() + offset, rect.top()),
+                eframe::egui::vec2(size, rect.height()),
+            ),
+            SplitAxis::Vertical => eframe::egui::Rect::from_min_size(
+                eframe::egui::pos2(rect.left(), rect.top() + offset),
+                eframe::egui::vec2(rect.width(), size),
+            ),
+        };
+
+        let mut rects = Vec::with_capacity(children.len());
+        let mut offset = 0.0;
+        for (i, (flex, _)) in children.iter().enumerate() {
+            let size = available * (flex / total_flex);
+            let child_rect = span(offset, size);
+            offset += size;
+
+            let divider_rect = if i < children.len() - 1 {
+                let divider = span(offset, DIVIDER_WIDTH);
+                offset += DIVIDER_WIDTH;
+                Some(divider)
+            } else {
+                None
+            };
+            rects.push((child_rect, divider_rect));
+        }
+        rects
+    }
+
     fn find_in_node<'a>(node: &'a PaneNode, id: &str) -> Option<&'a Pane> {
         match node {
             PaneNode::Leaf(pane) => {
@@ -415,6 +461,22 @@ mod tests {
         assert!(!tree.close("root"));
     }
 
+    #[test]
+    fn test_layout_splits_rect() {
+        use eframe::egui::{pos2, Rect};
+
+        let mut tree = PaneTree::new(make_pane("root"));
+        let new_id = tree.split("root", SplitAxis::Horizontal, None).unwrap();
+        let layout = tree.layout(Rect::from_min_max(pos2(0.0, 0.0), pos2(202.0, 100.0)));
+
+        assert_eq!(layout.len(), 2);
+        assert_eq!(layout[0].0, "root");
+        assert_eq!(layout[0].1.width(), 100.0);
+        assert_eq!(layout[1].0, new_id);
+        assert_eq!(layout[1].1.left(), 102.0);
+        assert_eq!(layout[1].1.height(), 100.0);
+    }
+
     #[test]
     fn test_navigate() {
         let mut tree = PaneTree::new(make_pane("root"));
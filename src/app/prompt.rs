@@ -75,7 +75,7 @@ pub fn build_prompt_segments(
             })
             .unwrap_or_default();
 
-        match prompt_formatter.style() {
+        let mut prompt = match prompt_formatter.style() {
             PromptStyle::Classic | PromptStyle::Minimal => {
                 let base_prompt = prompt_formatter.render(working_dir);
                 let mut left_context = String::new();
@@ -115,7 +115,22 @@ pub fn build_prompt_segments(
 
                 segments
             }
+        };
+
+        // The shell reported a directory on another machine (OSC 7)
+        if let Some(host) = terminal.remote_host() {
+            prompt.insert(
+                0,
+                PromptSegment {
+                    text: format!("[{}] ", host),
+                    fg: egui::Color32::from_rgb(180, 180, 200),
+                    bg: None,
+                    bold: false,
+                    separator: None,
+                },
+            );
         }
+        prompt
     } else {
         vec![PromptSegment {
             text: "$ ".to_string(),
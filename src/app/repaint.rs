@@ -0,0 +1,64 @@
+//! Repaint Scheduling
+//!
+//! The UI only draws when something changed. PTY output and process exits
+//! are announced on the PTY event bus and finished background operations
+//! wake the UI through [`Repainter`]; egui repaints for input on its own.
+//! Everything else is a timer: while a command runs its completion and
+//! timeout are checked every [`RUNNING_COMMAND_POLL`], and size changes are
+//! sent once they settle. An idle terminal draws nothing.
+
+use eframe::egui;
+use mosaicterm::pty::PtyEventBus;
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+
+use super::MosaicTermApp;
+
+/// How often a running command is checked for completion and timeout
+const RUNNING_COMMAND_POLL: Duration = Duration::from_millis(200);
+
+/// Wakes the UI from background threads once it has drawn its first frame
+#[derive(Clone, Default)]
+pub(super) struct Repainter(Arc<OnceLock<egui::Context>>);
+
+impl Repainter {
+    /// Remember the context to wake; later calls keep the first
+    pub fn attach(&self, ctx: &egui::Context) {
+        let _ = self.0.set(ctx.clone());
+    }
+
+    /// Ask for a frame as soon as possible
+    pub fn request(&self) {
+        if let Some(ctx) = self.0.get() {
+            ctx.request_repaint();
+        }
+    }
+
+    /// Repaint whenever a PTY on `bus` has output or exits
+    pub async fn watch(self, bus: PtyEventBus) {
+        bus.notify_activity(|| self.request()).await;
+    }
+}
+
+impl MosaicTermApp {
+    /// Ask for the next frame: at once while output is still coming in,
+    /// otherwise when the earliest timer is due, and not at all when idle
+    pub(super) fn schedule_repaint(&self, ctx: &egui::Context) {
+        let pending_output = self
+            .terminal
+            .as_ref()
+            .is_some_and(|t| t.has_pending_output());
+
+        let now = Instant::now();
+        let mut due = Vec::new();
+        if self.state_manager.last_command_time().is_some() {
+            due.push(RUNNING_COMMAND_POLL);
+        }
+        due.extend(self.pty_size.due_in(now));
+        due.extend(self.tui_overlay.resize_due_in());
+        if let Some(tree) = &self.pane_tree {
+            tree.for_each_pane(|pane| due.extend(pane.pty_size.due_in(now)));
+        }
+        mosaicterm::ui::repaint::schedule(ctx, pending_output, due);
+    }
+}
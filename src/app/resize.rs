@@ -0,0 +1,77 @@
+//! PTY size tracking for the block view and panes
+//!
+//! Every frame the history area (and each pane's share of it) is measured in
+//! cells of the block output font, so commands like `ls` or `git log` format
+//! for the visible width. Settled changes are pushed to the PTYs. The TUI
+//! overlay measures its own area and is applied in `ui()` through
+//! `TuiOverlay::take_pending_resize`.
+
+use eframe::egui;
+use futures::executor;
+use mosaicterm::terminal::{GridSize, Terminal};
+use std::time::Instant;
+use tracing::{debug, warn};
+
+use super::MosaicTermApp;
+
+/// Font size used for command output in blocks
+const BLOCK_OUTPUT_FONT_SIZE: f32 = 12.0;
+
+/// Horizontal space taken by a block's inner and outer margins plus the scrollbar
+const BLOCK_HORIZONTAL_CHROME: f32 = 2.0 * (12.0 + 4.0) + 12.0;
+
+impl MosaicTermApp {
+    /// Measure the history area and record the size for the primary terminal and panes
+    pub(super) fn observe_history_area(&mut self, ui: &egui::Ui, rect: egui::Rect) {
+        let font = egui::FontId::monospace(BLOCK_OUTPUT_FONT_SIZE);
+        let (cell_width, cell_height) =
+            ui.fonts_mut(|fonts| (fonts.glyph_width(&font, 'M'), fonts.row_height(&font)));
+        let cells = |area: egui::Rect| {
+            GridSize::from_pixels(
+                area.width() - BLOCK_HORIZONTAL_CHROME,
+                area.height(),
+                cell_width,
+                cell_height,
+            )
+        };
+
+        let now = Instant::now();
+        self.pty_size.observe(cells(rect), now);
+
+        if let Some(tree) = &mut self.pane_tree {
+            for (id, pane_rect) in tree.layout(rect) {
+                if let Some(pane) = tree.find_pane_mut(&id) {
+                    pane.pty_size.observe(cells(pane_rect), now);
+                }
+            }
+        }
+    }
+
+    /// Send settled size changes to the PTYs of the primary terminal and panes
+    pub(super) fn apply_pending_pty_resizes(&mut self) {
+        let now = Instant::now();
+
+        if let Some(terminal) = self.terminal.as_mut().filter(|t| t.is_initialized()) {
+            if let Some(size) = self.pty_size.poll(now) {
+                resize_terminal(terminal, size);
+            }
+        }
+
+        if let Some(tree) = &mut self.pane_tree {
+            tree.for_each_pane_mut(|pane| {
+                if let Some(terminal) = pane.terminal.as_mut().filter(|t| t.is_initialized()) {
+                    if let Some(size) = pane.pty_size.poll(now) {
+                        resize_terminal(terminal, size);
+                    }
+                }
+            });
+        }
+    }
+}
+
+fn resize_terminal(terminal: &mut Terminal, size: GridSize) {
+    match executor::block_on(terminal.resize_pty(size)) {
+        Ok(()) => debug!("Resized PTY to {}x{}", size.cols, size.rows),
+        Err(e) => warn!("Failed to resize PTY: {}", e),
+    }
+}
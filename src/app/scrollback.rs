@@ -0,0 +1,92 @@
+//! Bounded Scrollback
+//!
+//! Keeps the output lines the history holds in memory within
+//! `ui.scrollback_lines` per block and `terminal.scrollback_buffer` in all,
+//! spilling the oldest to a temp file in the cache directory, and reads
+//! spilled pages back as the view scrolls to them.
+
+use mosaicterm::models::scrollback::{self, ScrollbackBudget};
+use std::path::PathBuf;
+use tracing::warn;
+
+use super::MosaicTermApp;
+
+/// Directory spilled output goes to, under the platform's cache directory
+pub(super) fn spill_dir() -> Option<PathBuf> {
+    match mosaicterm::platform::Platform::paths().cache_dir() {
+        Ok(dir) => Some(dir.join("mosaicterm").join("scrollback")),
+        Err(e) => {
+            warn!("No cache directory for spilled output: {}", e);
+            None
+        }
+    }
+}
+
+/// Blocks to read pages back into and to drop read-back pages from
+#[derive(Debug, Default)]
+pub(super) struct Paging {
+    /// Blocks whose oldest row in memory came into view
+    pub page_in: Vec<usize>,
+    /// Blocks holding read-back pages that left the view
+    pub page_out: Vec<usize>,
+}
+
+impl MosaicTermApp {
+    /// Spill the oldest output until the history fits its budget
+    pub(super) fn enforce_scrollback_budget(&mut self) {
+        let budget = ScrollbackBudget::from_config(self.runtime_config.config());
+        let Some(history) = self.state_manager.command_history_mut() else {
+            return;
+        };
+        if let Err(e) = scrollback::enforce_budget(history, budget, self.spill_dir.as_deref()) {
+            warn!("{}", e);
+            let user_msg = self.user_friendly_error(&e);
+            self.set_status_message(Some(user_msg));
+        }
+    }
+
+    /// Output of block `block_id` to copy: `resident`, the lines in memory,
+    /// after any spilled to disk
+    pub(super) fn copied_output(&self, block_id: &str, resident: &[String]) -> String {
+        let block = self
+            .state_manager
+            .get_command_history()
+            .iter()
+            .find(|block| block.id == block_id && block.scrollback.has_spilled());
+        match block.map(|block| block.get_full_plain_output()) {
+            Some(Ok(text)) => text,
+            Some(Err(e)) => {
+                warn!("Failed to read spilled output: {}", e);
+                resident.join("\n")
+            }
+            None => resident.join("\n"),
+        }
+    }
+
+    /// Read back or drop spilled pages as the history view asked; returns
+    /// whether any were read back
+    pub(super) fn apply_paging(&mut self, paging: Paging) -> bool {
+        let Some(history) = self.state_manager.command_history_mut() else {
+            return false;
+        };
+        for index in paging.page_out {
+            if let Some(block) = history.get_mut(index) {
+                block.page_out_output();
+            }
+        }
+        let mut paged_in = false;
+        for index in paging.page_in {
+            let Some(block) = history.get_mut(index) else {
+                continue;
+            };
+            match block.page_in_output() {
+                Ok(count) => paged_in |= count > 0,
+                Err(e) => warn!(
+                    "Failed to read spilled output of '{}': {}",
+                    block.command, e
+                ),
+            }
+        }
+        paged_in
+    }
+}
@@ -0,0 +1,110 @@
+//! Window and Pane Titles
+//!
+//! Applies the titles the shell and its programs set (OSC 0/1/2) to the
+//! terminal that received them and to the block running at the time, and
+//! keeps the window title in step, letting a program's title replace
+//! MosaicTerm's own when `ui.program_titles` allows.
+
+use eframe::egui;
+use mosaicterm::models::command_block::ExecutionStatus;
+use mosaicterm::terminal::Terminal;
+
+use super::MosaicTermApp;
+
+/// Characters of a title shown in the pane label
+const PANE_LABEL_CHARS: usize = 32;
+
+impl MosaicTermApp {
+    /// Apply the titles set since the last frame, in block output and in the
+    /// TUI overlay, and refresh what shows them
+    pub(super) fn handle_title_changes(&mut self, ctx: &egui::Context) {
+        let overlay = self.tui_overlay.take_title_changes();
+        let title = self.terminal.as_mut().and_then(|terminal| {
+            let changed = terminal.update_title() | terminal.apply_title_changes(overlay);
+            changed.then(|| terminal.title().title().map(str::to_string))
+        });
+        if let Some(title) = title {
+            if let Some(block) = self
+                .state_manager
+                .command_history_mut()
+                .and_then(|history| history.last_mut())
+                .filter(|block| {
+                    matches!(
+                        block.status,
+                        ExecutionStatus::Running | ExecutionStatus::TuiMode
+                    )
+                })
+            {
+                block.title = title;
+            }
+            self.update_prompt();
+        }
+
+        self.update_window_title(ctx);
+    }
+
+    /// Terminal serving the active pane: the live terminal, unless the
+    /// active pane holds one of its own
+    fn active_terminal(&self) -> Option<&Terminal> {
+        self.pane_tree
+            .as_ref()
+            .and_then(|tree| tree.active_pane())
+            .and_then(|pane| pane.terminal.as_ref())
+            .or(self.terminal.as_ref())
+    }
+
+    /// Label for the active pane in the status bar, with its program's icon
+    /// name or title when one is set
+    pub(super) fn active_pane_label(&self) -> Option<String> {
+        let tree = self
+            .pane_tree
+            .as_ref()
+            .filter(|tree| tree.pane_count() > 1)?;
+        let label = match self.active_terminal().and_then(|t| t.title().icon()) {
+            Some(icon) => {
+                let mut short: String = icon.chars().take(PANE_LABEL_CHARS).collect();
+                if icon.chars().count() > PANE_LABEL_CHARS {
+                    short.push('…');
+                }
+                format!("pane {}: {}", tree.active_id(), short)
+            }
+            None => format!("pane {}", tree.active_id()),
+        };
+        Some(label)
+    }
+
+    /// Set the window title from the active pane's program, or else from
+    /// MosaicTerm's own state, when it differs from the one shown
+    fn update_window_title(&mut self, ctx: &egui::Context) {
+        let program_title = self
+            .active_terminal()
+            .and_then(|t| t.title().title())
+            .filter(|_| self.runtime_config.config().ui.program_titles);
+
+        let title = if let Some(title) = program_title {
+            title.to_string()
+        } else if self.state_manager.app_state().terminal_ready {
+            let stats = self.state_manager.statistics();
+            let cmd_count = stats.total_commands;
+
+            // Show command count and current directory if available
+            if let Some(session) = self.state_manager.active_session() {
+                let dir_name = session
+                    .working_directory
+                    .file_name()
+                    .and_then(|n| n.to_str())
+                    .unwrap_or("~");
+                format!("MosaicTerm - {} [{} cmds]", dir_name, cmd_count)
+            } else {
+                format!("MosaicTerm - Ready [{} cmds]", cmd_count)
+            }
+        } else {
+            "MosaicTerm - Initializing...".to_string()
+        };
+
+        if title != self.window_title {
+            ctx.send_viewport_cmd(egui::ViewportCommand::Title(title.clone()));
+            self.window_title = title;
+        }
+    }
+}
@@ -14,6 +14,7 @@ pub mod watcher;
 use crate::config::shell::ShellManager;
 use crate::config::theme::ThemeManager;
 use crate::models::config::{PromptConfig, SessionConfig};
+use crate::terminal::{ClipboardRequest, ImageLimits};
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
 
@@ -59,7 +60,7 @@ pub struct UiConfig {
     /// Font size in points
     pub font_size: u32,
 
-    /// Maximum number of lines to keep in history
+    /// Output lines a command block keeps in memory; older ones spill to disk
     pub scrollback_lines: usize,
 
     /// UI theme name (for preset selection)
@@ -80,6 +81,15 @@ pub struct UiConfig {
 
     /// Word wrap mode
     pub word_wrap: bool,
+
+    /// Cursor shape in the TUI overlay until an app picks one
+    pub cursor_style: crate::terminal::CursorShape,
+
+    /// Whether the TUI overlay cursor blinks until an app says otherwise
+    pub cursor_blink: bool,
+
+    /// Whether titles set by programs (OSC 0/1/2) replace the window title
+    pub program_titles: bool,
 }
 
 impl Default for UiConfig {
@@ -94,6 +104,9 @@ impl Default for UiConfig {
             animation_duration_ms: 200,
             show_line_numbers: false,
             word_wrap: true,
+            cursor_style: crate::terminal::CursorShape::default(),
+            cursor_blink: false,
+            program_titles: true,
         }
     }
 }
@@ -120,7 +133,8 @@ pub struct TerminalConfig {
     /// Enable mouse support
     pub mouse_support: bool,
 
-    /// Scrollback buffer size
+    /// Output lines all command blocks keep in memory together; older ones
+    /// spill to disk
     pub scrollback_buffer: usize,
 
     /// Bell style
@@ -134,6 +148,14 @@ pub struct TerminalConfig {
     /// Command execution timeout settings
     #[serde(default)]
     pub timeout: TimeoutConfig,
+
+    /// What programs may do with the clipboard (OSC 52)
+    #[serde(default)]
+    pub clipboard: ClipboardConfig,
+
+    /// Inline images drawn by programs (kitty graphics, iTerm2, Sixel)
+    #[serde(default)]
+    pub images: ImageConfig,
 }
 
 impl Default for TerminalConfig {
@@ -149,6 +171,97 @@ impl Default for TerminalConfig {
             bell_style: BellStyle::Sound,
             prompt_format: "$USER@$HOSTNAME:$PWD$ ".to_string(),
             timeout: TimeoutConfig::default(),
+            clipboard: ClipboardConfig::default(),
+            images: ImageConfig::default(),
+        }
+    }
+}
+
+/// How to treat a program's request to use the clipboard
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ClipboardPolicy {
+    /// Carry it out
+    Allow,
+    /// Ignore it
+    Deny,
+    /// Ask the user each time
+    Ask,
+}
+
+/// Clipboard access for programs through OSC 52
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClipboardConfig {
+    /// Setting the clipboard (copying from vim, tmux or neovim, also over SSH)
+    /// Default: allow
+    pub write: ClipboardPolicy,
+
+    /// Reading the clipboard, which hands the program whatever was copied last
+    /// Default: deny
+    pub read: ClipboardPolicy,
+
+    /// Largest text a program may put on the clipboard, in bytes
+    /// Default: 1 MiB
+    pub max_bytes: usize,
+}
+
+impl Default for ClipboardConfig {
+    fn default() -> Self {
+        Self {
+            write: ClipboardPolicy::Allow,
+            read: ClipboardPolicy::Deny,
+            max_bytes: 1024 * 1024,
+        }
+    }
+}
+
+impl ClipboardConfig {
+    /// How to treat `request`; writes over the size limit are denied
+    pub fn policy_for(&self, request: &ClipboardRequest) -> ClipboardPolicy {
+        match request {
+            ClipboardRequest::Write(text) if text.len() > self.max_bytes => ClipboardPolicy::Deny,
+            ClipboardRequest::Write(_) => self.write,
+            ClipboardRequest::Read { .. } => self.read,
+        }
+    }
+}
+
+/// Inline images in block output and the TUI overlay
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ImageConfig {
+    /// Decode and draw images; when off their escape sequences are dropped
+    /// Default: true
+    pub enabled: bool,
+
+    /// Largest image kept, in bytes of decoded pixels (4 per pixel)
+    /// Default: 32 MiB
+    pub max_image_bytes: usize,
+
+    /// Memory all images may take together: in blocks, stored by kitty
+    /// clients, and in the TUI overlay. Stored images go first, then the
+    /// oldest blocks', then those in the overlay's scrollback
+    /// Default: 256 MiB
+    pub max_total_bytes: usize,
+}
+
+impl Default for ImageConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_image_bytes: ImageLimits::default().max_image_bytes,
+            max_total_bytes: 256 * 1024 * 1024,
+        }
+    }
+}
+
+impl ImageConfig {
+    /// Limits for the image decoders
+    pub fn limits(&self) -> ImageLimits {
+        ImageLimits {
+            enabled: self.enabled,
+            max_image_bytes: self.max_image_bytes,
         }
     }
 }
@@ -296,8 +409,11 @@ pub enum BellStyle {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
 pub struct TuiAppConfig {
-    /// List of commands that should open in fullscreen mode
+    /// Commands that open in fullscreen mode straight away; others switch to it
+    /// when they enter the alternate screen or raw mode
     pub fullscreen_commands: Vec<String>,
+    /// Lines of primary-screen scrollback kept by the fullscreen overlay
+    pub scrollback_lines: usize,
 }
 
 impl Default for TuiAppConfig {
@@ -338,6 +454,7 @@ impl Default for TuiAppConfig {
                 "mutt".to_string(),
                 "ncmpcpp".to_string(),
             ],
+            scrollback_lines: crate::terminal::grid::DEFAULT_SCROLLBACK_LIMIT,
         }
     }
 }
@@ -610,6 +727,9 @@ pub mod utils {
             },
             show_line_numbers: overlay.show_line_numbers,
             word_wrap: overlay.word_wrap,
+            cursor_style: overlay.cursor_style,
+            cursor_blink: overlay.cursor_blink,
+            program_titles: overlay.program_titles,
         }
     }
 
@@ -645,6 +765,8 @@ pub mod utils {
                 overlay.prompt_format
             },
             timeout: overlay.timeout,
+            clipboard: overlay.clipboard,
+            images: overlay.images,
         }
     }
 
@@ -693,6 +815,11 @@ pub mod utils {
                 }
                 merged
             },
+            scrollback_lines: if overlay.scrollback_lines == 0 {
+                base.scrollback_lines
+            } else {
+                overlay.scrollback_lines
+            },
         }
     }
 }
@@ -764,6 +891,28 @@ mod tests {
         assert!(!config.terminal.shell_path.as_os_str().is_empty());
     }
 
+    #[test]
+    fn test_clipboard_policy() {
+        let mut clipboard = ClipboardConfig::default();
+        let read = ClipboardRequest::Read {
+            targets: "c".to_string(),
+            bell: true,
+        };
+        let write = ClipboardRequest::Write("x".repeat(16));
+        // Reads are off unless enabled
+        assert_eq!(clipboard.policy_for(&read), ClipboardPolicy::Deny);
+        assert_eq!(clipboard.policy_for(&write), ClipboardPolicy::Allow);
+
+        clipboard.read = ClipboardPolicy::Ask;
+        clipboard.max_bytes = 8;
+        assert_eq!(clipboard.policy_for(&read), ClipboardPolicy::Ask);
+        assert_eq!(clipboard.policy_for(&write), ClipboardPolicy::Deny);
+
+        let parsed: ClipboardConfig = toml::from_str("read = \"ask\"").unwrap();
+        assert_eq!(parsed.read, ClipboardPolicy::Ask);
+        assert_eq!(parsed.write, ClipboardPolicy::Allow);
+    }
+
     #[test]
     fn test_tui_apps_config_default() {
         let config = Config::default();
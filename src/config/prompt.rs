@@ -96,6 +96,8 @@ pub struct PromptFormatter {
     format: String,
     style: PromptStyle,
     custom_segments: Vec<PromptSegmentConfig>,
+    /// Title the shell or a program last set, for `$TITLE`
+    title: Option<String>,
 }
 
 impl PromptFormatter {
@@ -104,6 +106,7 @@ impl PromptFormatter {
             format,
             style: PromptStyle::default(),
             custom_segments: Vec::new(),
+            title: None,
         }
     }
 
@@ -560,6 +563,8 @@ impl PromptFormatter {
             .unwrap_or("");
         result = result.replace("$KUBE", kube);
 
+        result = result.replace("$TITLE", self.title.as_deref().unwrap_or(""));
+
         // Restore escaped
         result = result.replace("\x00USER\x00", "$USER");
         result = result.replace("\x00HOSTNAME\x00", "$HOSTNAME");
@@ -577,6 +582,11 @@ impl PromptFormatter {
     pub fn format(&self) -> &str {
         &self.format
     }
+
+    /// Set the title substituted for `$TITLE` (OSC 0/1/2)
+    pub fn set_title(&mut self, title: Option<&str>) {
+        self.title = title.map(str::to_string);
+    }
 }
 
 impl Default for PromptFormatter {
@@ -690,6 +700,14 @@ mod tests {
         assert_eq!(formatter.format(), "new");
     }
 
+    #[test]
+    fn test_title_substitution() {
+        let mut formatter = PromptFormatter::new("[$TITLE]$ ".to_string());
+        assert_eq!(formatter.render(Path::new("/tmp")), "[]$ ");
+        formatter.set_title(Some("user@host:~"));
+        assert_eq!(formatter.render(Path::new("/tmp")), "[user@host:~]$ ");
+    }
+
     #[test]
     fn test_default_format() {
         let formatter = PromptFormatter::default();
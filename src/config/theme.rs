@@ -886,6 +886,40 @@ impl ThemeManager {
         })
     }
 
+    /// Create the palette for the terminal grid, which is also what colour
+    /// queries (OSC 4/10/11) report to programs
+    pub fn create_terminal_palette(&self) -> Result<crate::terminal::Palette> {
+        let theme = self.current_theme()?;
+        let rgb = |color: &Color| {
+            let channel = |v: f32| (v.clamp(0.0, 1.0) * 255.0).round() as u8;
+            crate::terminal::grid::Rgb::new(channel(color.r), channel(color.g), channel(color.b))
+        };
+        let ansi = &theme.colors.ansi_colors;
+
+        Ok(crate::terminal::Palette {
+            foreground: rgb(&theme.colors.text.primary),
+            background: rgb(&theme.colors.background.primary),
+            ansi: [
+                rgb(&ansi.black),
+                rgb(&ansi.red),
+                rgb(&ansi.green),
+                rgb(&ansi.yellow),
+                rgb(&ansi.blue),
+                rgb(&ansi.magenta),
+                rgb(&ansi.cyan),
+                rgb(&ansi.white),
+                rgb(&ansi.bright_black),
+                rgb(&ansi.bright_red),
+                rgb(&ansi.bright_green),
+                rgb(&ansi.bright_yellow),
+                rgb(&ansi.bright_blue),
+                rgb(&ansi.bright_magenta),
+                rgb(&ansi.bright_cyan),
+                rgb(&ansi.bright_white),
+            ],
+        })
+    }
+
     /// Get theme colors for UI components
     pub fn get_component_colors(&self, component: &str) -> Result<ComponentColors> {
         let theme = self.current_theme()?;
@@ -1156,6 +1190,21 @@ mod tests {
         assert!(manager.set_theme("nonexistent").is_err());
     }
 
+    #[test]
+    fn test_terminal_palette_follows_theme() {
+        use crate::terminal::grid::Rgb;
+
+        let mut manager = ThemeManager::new();
+        let dark = manager.create_terminal_palette().unwrap();
+        assert_eq!(dark.background, Rgb::new(24, 24, 37));
+        assert_eq!(dark.foreground, Rgb::new(229, 229, 229));
+        assert_eq!(dark.ansi[0], Rgb::new(0, 0, 0));
+
+        manager.set_theme("default-light").unwrap();
+        let light = manager.create_terminal_palette().unwrap();
+        assert_ne!(light.background, dark.background);
+    }
+
     #[test]
     fn test_custom_theme() {
         let mut manager = ThemeManager::new();
@@ -122,6 +122,9 @@ pub enum Error {
     /// Output buffer full
     OutputBufferFull { command: String, size: usize },
 
+    /// A hyperlink could not be opened
+    HyperlinkOpenFailed { uri: String, reason: String },
+
     // === I/O and serialization errors (kept for compatibility) ===
     /// I/O errors
     Io(std::io::Error),
@@ -280,6 +283,9 @@ impl fmt::Display for Error {
                     command, size
                 )
             }
+            Error::HyperlinkOpenFailed { uri, reason } => {
+                write!(f, "Failed to open link '{}': {}", uri, reason)
+            }
 
             // I/O and serialization errors
             Error::Io(err) => write!(f, "I/O error: {}", err),
@@ -591,6 +597,10 @@ mod tests {
                 command: "cmd".to_string(),
                 size: 1000,
             },
+            Error::HyperlinkOpenFailed {
+                uri: "file://host/tmp".to_string(),
+                reason: "reason".to_string(),
+            },
         ];
 
         for err in errors {
@@ -150,6 +150,7 @@ impl HistoryManager {
             .collect();
 
         // Sort by score (descending) and recency (later entries are better)
+        #[allow(clippy::unnecessary_sort_by)]
         results.sort_by(|a, b| b.0.cmp(&a.0));
 
         // Remove duplicates while preserving order
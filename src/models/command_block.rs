@@ -20,10 +20,12 @@
 
 use chrono::{DateTime, Local};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 use uuid::Uuid;
 
+use crate::error::{Error, Result};
+use crate::models::scrollback::Scrollback;
 use crate::models::OutputLine;
 
 /// Execution status of a command
@@ -70,6 +72,28 @@ pub struct CommandBlock {
 
     /// Exit code from the command (None if still running)
     pub exit_code: Option<i32>,
+
+    /// Window title set while the command ran (OSC 0/1/2), if any
+    #[serde(default)]
+    pub title: Option<String>,
+
+    /// Show inline images as one-line notes instead of drawing them
+    #[serde(default)]
+    pub images_hidden: bool,
+
+    /// Output the command may still rewrite (progress bars), shown after
+    /// `output` while it runs; it moves to `output` as it settles
+    #[serde(skip)]
+    pub live_output: Vec<OutputLine>,
+
+    /// Bumped whenever lines leave `output` other than by appending, so
+    /// views that index it know to start over
+    #[serde(skip)]
+    pub output_generation: u64,
+
+    /// Older output lines spilled to disk
+    #[serde(skip)]
+    pub scrollback: Scrollback,
 }
 
 impl CommandBlock {
@@ -84,6 +108,11 @@ impl CommandBlock {
             working_directory,
             execution_time: None,
             exit_code: None,
+            title: None,
+            images_hidden: false,
+            live_output: Vec::new(),
+            output_generation: 0,
+            scrollback: Scrollback::default(),
         }
     }
 
@@ -131,6 +160,76 @@ impl CommandBlock {
         self.output.push(line);
     }
 
+    /// Drop the first `count` output lines, putting `notice` in their place
+    pub fn drop_output_front(&mut self, count: usize, notice: Option<OutputLine>) {
+        self.scrollback.page_out(&mut self.output);
+        let count = count.min(self.output.len());
+        let noticed = usize::from(notice.is_some());
+        self.output.splice(0..count, notice);
+        self.scrollback.note_dropped(count.saturating_sub(noticed));
+        self.output_generation += 1;
+    }
+
+    /// Output lines held against the scrollback budget: those in memory,
+    /// less pages read back from disk
+    pub fn resident_line_count(&self) -> usize {
+        self.output.len() - self.scrollback.loaded_lines()
+    }
+
+    /// Move the first `count` output lines to the spill file in `dir`.
+    ///
+    /// Lines that can't be spilled, for want of a directory or a failed
+    /// write, are dropped with a note in their place, and
+    /// `Error::OutputBufferFull` says how much went.
+    pub fn spill_output_front(&mut self, count: usize, dir: Option<&Path>) -> Result<()> {
+        self.scrollback.page_out(&mut self.output);
+        let count = count.min(self.output.len());
+        if count == 0 {
+            return Ok(());
+        }
+        self.output_generation += 1;
+        let before = self.output.len();
+        let spilled = match dir {
+            Some(dir) => self.scrollback.spill(dir, &mut self.output, count),
+            None => Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                "no cache directory",
+            )),
+        };
+        let left = count - (before - self.output.len());
+        let Err(e) = spilled else {
+            return Ok(());
+        };
+
+        let size = self.output[..left].iter().map(|line| line.text.len()).sum();
+        let notice = OutputLine::new(format!(
+            "... [{} lines dropped, could not spill them to disk: {}] ...",
+            left, e
+        ));
+        self.drop_output_front(left, Some(notice));
+        Err(Error::OutputBufferFull {
+            command: self.command.clone(),
+            size,
+        })
+    }
+
+    /// Read the newest spilled page back to the front of the output;
+    /// returns how many lines it brought
+    pub fn page_in_output(&mut self) -> Result<usize> {
+        let count = self.scrollback.page_in(&mut self.output)?;
+        if count > 0 {
+            self.output_generation += 1;
+        }
+        Ok(count)
+    }
+
+    /// Drop the pages read back from disk from the output again
+    pub fn page_out_output(&mut self) {
+        if self.scrollback.page_out(&mut self.output) > 0 {
+            self.output_generation += 1;
+        }
+    }
+
     /// Add multiple output lines to the block
     pub fn add_output_lines(&mut self, lines: Vec<OutputLine>) {
         self.output.extend(lines);
@@ -156,6 +255,37 @@ impl CommandBlock {
         matches!(self.status, ExecutionStatus::Failed)
     }
 
+    /// Whether any output line is an inline image
+    pub fn has_images(&self) -> bool {
+        self.output.iter().any(|line| line.image.is_some())
+    }
+
+    /// Memory taken by the block's inline images
+    pub fn image_bytes(&self) -> usize {
+        self.output
+            .iter()
+            .filter_map(|line| line.image.as_ref())
+            .map(|placement| placement.image.byte_len())
+            .sum()
+    }
+
+    /// Replace the block's images with a note saying they were dropped;
+    /// returns the memory freed
+    pub fn drop_images(&mut self) -> usize {
+        let mut freed = 0;
+        for line in &mut self.output {
+            if let Some(placement) = line.image.take() {
+                let image = &placement.image;
+                freed += image.byte_len();
+                line.text = format!(
+                    "[{}x{} image dropped to save memory]",
+                    image.width, image.height
+                );
+            }
+        }
+        freed
+    }
+
     /// Get the plain text output (without ANSI codes)
     pub fn get_plain_output(&self) -> String {
         self.output
@@ -165,6 +295,17 @@ impl CommandBlock {
             .join("\n")
     }
 
+    /// Plain text output, reading spilled lines back from disk
+    pub fn get_full_plain_output(&self) -> Result<String> {
+        let mut text = self.scrollback.spilled_text()?;
+        text.extend(
+            self.output
+                .iter()
+                .map(|line| line.get_plain_text().to_owned()),
+        );
+        Ok(text.join("\n"))
+    }
+
     /// Get the formatted output with ANSI codes
     pub fn get_formatted_output(&self) -> String {
         self.output
@@ -202,6 +343,23 @@ mod tests {
         assert!(!block.id.is_empty()); // UUID should be generated
     }
 
+    #[test]
+    fn test_drop_output_front() {
+        let mut block = CommandBlock::new("test".to_string(), PathBuf::from("/tmp"));
+        for i in 0..5 {
+            block.add_output_line(OutputLine::new(i.to_string()));
+        }
+
+        block.drop_output_front(3, Some(OutputLine::new("notice")));
+        let text: Vec<&str> = block.output.iter().map(|l| l.text.as_str()).collect();
+        assert_eq!(text, ["notice", "3", "4"]);
+        assert_eq!(block.output_generation, 1);
+
+        block.drop_output_front(10, None);
+        assert!(block.output.is_empty());
+        assert_eq!(block.output_generation, 2);
+    }
+
     #[test]
     fn test_command_block_state_transitions() {
         let mut block = CommandBlock::new("test".to_string(), PathBuf::from("/tmp"));
@@ -241,4 +399,23 @@ mod tests {
         assert_eq!(block.output_line_count(), 2);
         assert_eq!(block.get_plain_output(), "line 1\nline 2");
     }
+
+    #[test]
+    fn test_drop_images() {
+        use crate::terminal::images::{CursorAfter, InlineImage, Placement};
+
+        let mut block = CommandBlock::new("viu cat.png".to_string(), PathBuf::from("/tmp"));
+        block.add_output_line(OutputLine::new("before"));
+        let image = InlineImage::new(2, 3, vec![0; 24]);
+        block.add_output_line(OutputLine::with_image(
+            Placement::new(image, CursorAfter::AfterImage),
+            1,
+        ));
+        assert!(block.has_images());
+        assert_eq!(block.image_bytes(), 24);
+
+        assert_eq!(block.drop_images(), 24);
+        assert!(!block.has_images());
+        assert_eq!(block.output[1].text, "[2x3 image dropped to save memory]");
+    }
 }
@@ -42,7 +42,7 @@ pub struct UiConfig {
     /// Font size in points
     pub font_size: u32,
 
-    /// Maximum number of lines to keep in history
+    /// Output lines a command block keeps in memory; older ones spill to disk
     pub scrollback_lines: usize,
 
     /// UI theme
@@ -108,7 +108,7 @@ pub struct TerminalConfig {
     /// Whether to inherit parent environment
     pub inherit_env: bool,
 
-    /// Terminal scrollback buffer size
+    /// Output lines all command blocks keep in memory together
     pub scrollback_buffer: usize,
 
     /// Whether to enable mouse support
@@ -8,6 +8,7 @@ pub mod command_block;
 pub mod config;
 pub mod output_line;
 pub mod pty_process;
+pub mod scrollback;
 pub mod shell_type;
 pub mod terminal_session;
 
@@ -16,5 +17,6 @@ pub use command_block::{CommandBlock, ExecutionStatus};
 pub use config::Config;
 pub use output_line::OutputLine;
 pub use pty_process::PtyProcess;
+pub use scrollback::{Scrollback, ScrollbackBudget};
 pub use shell_type::ShellType;
 pub use terminal_session::{SessionState, SessionStatistics, TerminalSession};
@@ -9,10 +9,12 @@
 //! ANSI codes are parsed lazily using `OnceCell` for better performance.
 //! The raw text is stored and only parsed when rendering requires it.
 
+use crate::terminal::images::Placement;
 use chrono::{DateTime, Utc};
 use once_cell::sync::OnceCell;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
+use std::ops::Range;
 use std::sync::Arc;
 
 /// ANSI escape sequence representation
@@ -55,6 +57,15 @@ impl AnsiCode {
     }
 }
 
+/// Text marked as a link with OSC 8
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Hyperlink {
+    /// Byte range of the linked text in the line
+    pub range: Range<usize>,
+    /// Link target
+    pub uri: String,
+}
+
 /// Lazily parsed ANSI content
 #[derive(Debug, Clone)]
 struct ParsedContent {
@@ -79,11 +90,17 @@ pub struct OutputLine {
     /// Legacy field for backward compatibility
     pub ansi_codes: Vec<AnsiCode>,
 
+    /// Linked text, in order
+    pub hyperlinks: Vec<Hyperlink>,
+
     /// Position in the output (line number)
     pub line_number: usize,
 
     /// When this line was received
     pub timestamp: DateTime<Utc>,
+
+    /// Image a program drew here, in place of text; not saved with the line
+    pub image: Option<Placement>,
 }
 
 // Custom Serialize implementation to handle OnceCell
@@ -93,9 +110,10 @@ impl Serialize for OutputLine {
         S: serde::Serializer,
     {
         use serde::ser::SerializeStruct;
-        let mut state = serializer.serialize_struct("OutputLine", 4)?;
+        let mut state = serializer.serialize_struct("OutputLine", 5)?;
         state.serialize_field("text", &self.text)?;
         state.serialize_field("ansi_codes", &self.ansi_codes)?;
+        state.serialize_field("hyperlinks", &self.hyperlinks)?;
         state.serialize_field("line_number", &self.line_number)?;
         state.serialize_field("timestamp", &self.timestamp)?;
         state.end()
@@ -112,6 +130,8 @@ impl<'de> Deserialize<'de> for OutputLine {
         struct OutputLineHelper {
             text: String,
             ansi_codes: Vec<AnsiCode>,
+            #[serde(default)]
+            hyperlinks: Vec<Hyperlink>,
             line_number: usize,
             timestamp: DateTime<Utc>,
         }
@@ -121,8 +141,10 @@ impl<'de> Deserialize<'de> for OutputLine {
             text: helper.text,
             parsed: Arc::new(OnceCell::new()),
             ansi_codes: helper.ansi_codes,
+            hyperlinks: helper.hyperlinks,
             line_number: helper.line_number,
             timestamp: helper.timestamp,
+            image: None,
         })
     }
 }
@@ -134,8 +156,10 @@ impl OutputLine {
             text: text.into(),
             parsed: Arc::new(OnceCell::new()),
             ansi_codes: Vec::new(),
+            hyperlinks: Vec::new(),
             line_number: 0,
             timestamp: Utc::now(),
+            image: None,
         }
     }
 
@@ -145,8 +169,10 @@ impl OutputLine {
             text: text.into(),
             parsed: Arc::new(OnceCell::new()),
             ansi_codes: Vec::new(),
+            hyperlinks: Vec::new(),
             line_number,
             timestamp: Utc::now(),
+            image: None,
         }
     }
 
@@ -156,8 +182,18 @@ impl OutputLine {
             text,
             parsed: Arc::new(OnceCell::new()),
             ansi_codes,
+            hyperlinks: Vec::new(),
             line_number,
             timestamp: Utc::now(),
+            image: None,
+        }
+    }
+
+    /// Create a line showing an image
+    pub fn with_image(image: Placement, line_number: usize) -> Self {
+        Self {
+            image: Some(image),
+            ..Self::with_line_number(String::new(), line_number)
         }
     }
 
@@ -386,4 +422,22 @@ mod tests {
         let cloned = original.clone();
         assert_eq!(cloned.get_plain_text(), "Red");
     }
+
+    #[test]
+    fn test_hyperlinks_serialization() {
+        let mut line = OutputLine::new("see docs");
+        line.hyperlinks.push(Hyperlink {
+            range: 4..8,
+            uri: "https://example.com".to_string(),
+        });
+        let json = serde_json::to_string(&line).unwrap();
+        let restored: OutputLine = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.hyperlinks, line.hyperlinks);
+
+        // Lines saved before links were kept have none
+        let old =
+            r#"{"text":"x","ansi_codes":[],"line_number":0,"timestamp":"2024-01-01T00:00:00Z"}"#;
+        let restored: OutputLine = serde_json::from_str(old).unwrap();
+        assert!(restored.hyperlinks.is_empty());
+    }
 }
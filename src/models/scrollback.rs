@@ -0,0 +1,513 @@
+//! Scrollback Spill
+//!
+//! Output lines a command block no longer keeps in memory. Past its line
+//! budget a block compresses its oldest lines a page at a time and appends
+//! them to a temp file in the cache directory. Pages are read back when the
+//! view scrolls to them, and the file is overwritten with zeros and removed
+//! once the last block holding it is dropped.
+//!
+//! Spilled lines keep their text, colors and links; inline images are not
+//! saved with a line, so they come back as text.
+
+use crate::error::{Error, Result};
+use crate::models::{CommandBlock, OutputLine};
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tracing::warn;
+
+/// Lines spilled together and read back together
+pub const PAGE_LINES: usize = 1000;
+
+/// How many output lines are kept in memory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScrollbackBudget {
+    /// Lines one block keeps
+    pub block_lines: usize,
+    /// Lines all blocks of the history keep together
+    pub total_lines: usize,
+}
+
+impl ScrollbackBudget {
+    /// Budget set by `ui.scrollback_lines` and `terminal.scrollback_buffer`
+    pub fn from_config(config: &crate::config::Config) -> Self {
+        Self {
+            block_lines: config.ui.scrollback_lines.max(1),
+            total_lines: config.terminal.scrollback_buffer.max(1),
+        }
+    }
+}
+
+/// Where a page sits in the spill file
+#[derive(Debug, Clone, Copy)]
+struct Page {
+    offset: u64,
+    len: u64,
+    lines: usize,
+}
+
+/// Temp file pages are written to, wiped when dropped
+#[derive(Debug)]
+struct SpillFile {
+    path: PathBuf,
+    file: Mutex<File>,
+}
+
+impl SpillFile {
+    fn create(dir: &Path) -> std::io::Result<Self> {
+        std::fs::create_dir_all(dir)?;
+        let path = dir.join(format!("spill-{}", uuid::Uuid::new_v4()));
+        let mut options = OpenOptions::new();
+        options.read(true).write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let file = options.open(&path)?;
+        Ok(Self {
+            path,
+            file: Mutex::new(file),
+        })
+    }
+
+    /// Append `bytes`, returning where they start
+    fn append(&self, bytes: &[u8]) -> std::io::Result<u64> {
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        let offset = file.seek(SeekFrom::End(0))?;
+        file.write_all(bytes)?;
+        Ok(offset)
+    }
+
+    fn read(&self, offset: u64, len: u64) -> std::io::Result<Vec<u8>> {
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        file.seek(SeekFrom::Start(offset))?;
+        let mut bytes = vec![0; len as usize];
+        file.read_exact(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    /// Overwrite the whole file with zeros and flush it to disk
+    fn wipe(file: &mut File) -> std::io::Result<()> {
+        let len = file.metadata()?.len();
+        file.seek(SeekFrom::Start(0))?;
+        let zeros = [0u8; 64 * 1024];
+        let mut left = len;
+        while left > 0 {
+            let n = left.min(zeros.len() as u64) as usize;
+            file.write_all(&zeros[..n])?;
+            left -= n as u64;
+        }
+        file.sync_all()
+    }
+}
+
+impl Drop for SpillFile {
+    fn drop(&mut self) {
+        let file = self.file.get_mut().unwrap_or_else(|e| e.into_inner());
+        if let Err(e) = Self::wipe(file) {
+            warn!(
+                "Failed to wipe spilled output {}: {}",
+                self.path.display(),
+                e
+            );
+        }
+        if let Err(e) = std::fs::remove_file(&self.path) {
+            warn!(
+                "Failed to remove spilled output {}: {}",
+                self.path.display(),
+                e
+            );
+        }
+    }
+}
+
+/// Output lines of a block that were spilled to disk.
+///
+/// Pages are in output order; the last `loaded` of them have been read back
+/// and sit at the front of the block's output.
+#[derive(Debug, Clone, Default)]
+pub struct Scrollback {
+    file: Option<Arc<SpillFile>>,
+    pages: Vec<Page>,
+    loaded: usize,
+    /// Index of the first line in the block's output among all it printed
+    first_line: usize,
+}
+
+impl Scrollback {
+    /// Lines on disk and not read back
+    pub fn spilled_lines(&self) -> usize {
+        self.pages[..self.pages.len() - self.loaded]
+            .iter()
+            .map(|page| page.lines)
+            .sum()
+    }
+
+    /// Lines at the front of the output that were read back from disk
+    pub fn loaded_lines(&self) -> usize {
+        self.pages[self.pages.len() - self.loaded..]
+            .iter()
+            .map(|page| page.lines)
+            .sum()
+    }
+
+    /// Lines printed before the first one in the block's output
+    pub fn first_line(&self) -> usize {
+        self.first_line
+    }
+
+    /// Whether pages are on disk and not read back
+    pub fn has_spilled(&self) -> bool {
+        self.loaded < self.pages.len()
+    }
+
+    /// Path of the spill file, if anything was spilled
+    pub fn path(&self) -> Option<&Path> {
+        self.file.as_deref().map(|file| file.path.as_path())
+    }
+
+    /// Note that `count` lines were dropped from the front for good
+    pub(crate) fn note_dropped(&mut self, count: usize) {
+        self.first_line += count;
+    }
+
+    /// Compress `lines` and append them to the spill file as a page
+    fn write_page(&mut self, dir: &Path, lines: &[OutputLine]) -> std::io::Result<()> {
+        let file = match &self.file {
+            Some(file) => file.clone(),
+            None => {
+                let file = Arc::new(SpillFile::create(dir)?);
+                self.file = Some(file.clone());
+                file
+            }
+        };
+        let mut encoder = DeflateEncoder::new(Vec::new(), flate2::Compression::fast());
+        serde_json::to_writer(&mut encoder, lines)?;
+        let bytes = encoder.finish()?;
+        let offset = file.append(&bytes)?;
+        self.pages.push(Page {
+            offset,
+            len: bytes.len() as u64,
+            lines: lines.len(),
+        });
+        self.first_line += lines.len();
+        Ok(())
+    }
+
+    /// Read page `index` back from disk
+    fn read_page(&self, index: usize) -> Result<Vec<OutputLine>> {
+        let page = self.pages[index];
+        let file = self.file.as_ref().ok_or_else(|| {
+            Error::Io(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                "no spill file",
+            ))
+        })?;
+        let bytes = file.read(page.offset, page.len)?;
+        let lines: Vec<OutputLine> = serde_json::from_reader(DeflateDecoder::new(&bytes[..]))?;
+        Ok(lines)
+    }
+
+    /// Spill the first `count` lines of `output` (which must not hold loaded
+    /// pages), a page at a time. Lines that could not be written are left in
+    /// `output`; returns how many were spilled.
+    pub(crate) fn spill(
+        &mut self,
+        dir: &Path,
+        output: &mut Vec<OutputLine>,
+        count: usize,
+    ) -> std::io::Result<usize> {
+        debug_assert_eq!(self.loaded, 0);
+        let count = count.min(output.len());
+        let mut spilled = 0;
+        let mut result = Ok(());
+        for page in output[..count].chunks(PAGE_LINES) {
+            if let Err(e) = self.write_page(dir, page) {
+                result = Err(e);
+                break;
+            }
+            spilled += page.len();
+        }
+        output.drain(..spilled);
+        result.map(|_| spilled)
+    }
+
+    /// Read the newest page still on disk back to the front of `output`;
+    /// returns how many lines it brought
+    pub(crate) fn page_in(&mut self, output: &mut Vec<OutputLine>) -> Result<usize> {
+        if !self.has_spilled() {
+            return Ok(0);
+        }
+        let index = self.pages.len() - self.loaded - 1;
+        let lines = self.read_page(index)?;
+        let count = lines.len();
+        output.splice(0..0, lines);
+        self.loaded += 1;
+        self.first_line -= count;
+        Ok(count)
+    }
+
+    /// Drop the pages read back from the front of `output`; they are still
+    /// on disk. Returns how many lines went.
+    pub(crate) fn page_out(&mut self, output: &mut Vec<OutputLine>) -> usize {
+        let count = self.loaded_lines().min(output.len());
+        output.drain(..count);
+        self.loaded = 0;
+        self.first_line += count;
+        count
+    }
+
+    /// Plain text of the lines on disk and not read back, one per line
+    pub(crate) fn spilled_text(&self) -> Result<Vec<String>> {
+        let mut text = Vec::with_capacity(self.spilled_lines());
+        for index in 0..self.pages.len() - self.loaded {
+            let lines = self.read_page(index)?;
+            text.extend(lines.iter().map(|line| line.get_plain_text().to_owned()));
+        }
+        Ok(text)
+    }
+}
+
+/// Lines to spill to get `over` lines under `budget`: whole steps of up to
+/// a page, so a block streaming past its budget spills in pages rather
+/// than a few lines every batch
+fn spill_count(over: usize, budget: usize) -> usize {
+    over.next_multiple_of(PAGE_LINES.min(budget / 2).max(1))
+}
+
+/// Spill the oldest output of `blocks` to `dir` until each block is within
+/// `budget.block_lines` and all of them within `budget.total_lines`.
+///
+/// The global budget takes from the oldest blocks first, and from blocks
+/// whose spilled pages are being read last. Returns the first error; lines
+/// that couldn't be spilled were dropped.
+pub fn enforce_budget(
+    blocks: &mut [CommandBlock],
+    budget: ScrollbackBudget,
+    dir: Option<&Path>,
+) -> Result<()> {
+    let mut result = Ok(());
+    let mut spill = |block: &mut CommandBlock, count: usize| {
+        if let Err(e) = block.spill_output_front(count, dir) {
+            if result.is_ok() {
+                result = Err(e);
+            }
+        }
+    };
+
+    for block in blocks.iter_mut() {
+        let over = block
+            .resident_line_count()
+            .saturating_sub(budget.block_lines);
+        if over > 0 {
+            spill(block, spill_count(over, budget.block_lines));
+        }
+    }
+
+    let mut total: usize = blocks.iter().map(CommandBlock::resident_line_count).sum();
+    for reading in [false, true] {
+        for block in blocks.iter_mut() {
+            if total <= budget.total_lines {
+                break;
+            }
+            if (block.scrollback.loaded_lines() > 0) != reading {
+                continue;
+            }
+            let resident = block.resident_line_count();
+            let count = spill_count(total - budget.total_lines, budget.total_lines);
+            spill(block, count.min(resident));
+            total -= resident - block.resident_line_count();
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use tempfile::tempdir;
+
+    fn lines(range: std::ops::Range<usize>) -> Vec<OutputLine> {
+        range
+            .map(|i| OutputLine::with_line_number(format!("line {}", i), i))
+            .collect()
+    }
+
+    #[test]
+    fn test_spill_and_page_in_round_trip() {
+        let dir = tempdir().unwrap();
+        let mut scrollback = Scrollback::default();
+        let mut output = lines(0..2500);
+
+        assert_eq!(
+            scrollback.spill(dir.path(), &mut output, 2200).unwrap(),
+            2200
+        );
+        assert_eq!(output.len(), 300);
+        assert_eq!(output[0].text, "line 2200");
+        assert_eq!(scrollback.spilled_lines(), 2200);
+        assert_eq!(scrollback.first_line(), 2200);
+
+        // The newest page comes back first
+        assert_eq!(scrollback.page_in(&mut output).unwrap(), 200);
+        assert_eq!(output[0].text, "line 2000");
+        assert_eq!(output[0].line_number, 2000);
+        assert_eq!(scrollback.page_in(&mut output).unwrap(), 1000);
+        assert_eq!(output[0].text, "line 1000");
+        assert_eq!(scrollback.first_line(), 1000);
+        assert_eq!(scrollback.loaded_lines(), 1200);
+
+        assert_eq!(scrollback.page_out(&mut output), 1200);
+        assert_eq!(output[0].text, "line 2200");
+        assert_eq!(scrollback.first_line(), 2200);
+    }
+
+    #[test]
+    fn test_spilled_text_reads_pages() {
+        let dir = tempdir().unwrap();
+        let mut scrollback = Scrollback::default();
+        let mut output = lines(0..1500);
+        scrollback.spill(dir.path(), &mut output, 1500).unwrap();
+
+        let text = scrollback.spilled_text().unwrap();
+        assert_eq!(text.len(), 1500);
+        assert_eq!(text[1234], "line 1234");
+    }
+
+    #[test]
+    fn test_spill_fails_without_directory() {
+        let dir = tempdir().unwrap();
+        let missing = dir.path().join("file");
+        std::fs::write(&missing, b"not a directory").unwrap();
+        let mut scrollback = Scrollback::default();
+        let mut output = lines(0..10);
+
+        assert!(scrollback.spill(&missing, &mut output, 5).is_err());
+        assert_eq!(output.len(), 10);
+        assert_eq!(scrollback.spilled_lines(), 0);
+    }
+
+    fn block(count: usize) -> CommandBlock {
+        let mut block = CommandBlock::new("cmd".to_string(), PathBuf::from("/tmp"));
+        block.add_output_lines(lines(0..count));
+        block
+    }
+
+    #[test]
+    fn test_block_budget_spills_whole_pages() {
+        let dir = tempdir().unwrap();
+        let budget = ScrollbackBudget {
+            block_lines: 5000,
+            total_lines: 100_000,
+        };
+        let mut blocks = vec![block(4000), block(5001)];
+        enforce_budget(&mut blocks, budget, Some(dir.path())).unwrap();
+
+        assert_eq!(blocks[0].output.len(), 4000);
+        assert_eq!(blocks[1].output.len(), 4001);
+        assert_eq!(blocks[1].scrollback.spilled_lines(), 1000);
+        assert_eq!(blocks[1].output[0].text, "line 1000");
+        assert!(blocks[1].scrollback.path().unwrap().starts_with(dir.path()));
+    }
+
+    #[test]
+    fn test_total_budget_spills_oldest_first() {
+        let dir = tempdir().unwrap();
+        let budget = ScrollbackBudget {
+            block_lines: 10_000,
+            total_lines: 6000,
+        };
+        let mut blocks = vec![block(3000), block(3000), block(3000)];
+        enforce_budget(&mut blocks, budget, Some(dir.path())).unwrap();
+
+        assert_eq!(blocks[0].resident_line_count(), 0);
+        assert_eq!(blocks[1].resident_line_count(), 3000);
+        assert_eq!(blocks[2].resident_line_count(), 3000);
+        assert_eq!(blocks[0].scrollback.spilled_lines(), 3000);
+    }
+
+    #[test]
+    fn test_pages_being_read_are_spilled_last() {
+        let dir = tempdir().unwrap();
+        let budget = ScrollbackBudget {
+            block_lines: 2000,
+            total_lines: 100_000,
+        };
+        let mut blocks = vec![block(3000), block(3000)];
+        enforce_budget(&mut blocks, budget, Some(dir.path())).unwrap();
+        assert_eq!(blocks[0].page_in_output().unwrap(), 1000);
+
+        // Read-back pages don't count against the budget
+        let budget = ScrollbackBudget {
+            block_lines: 2000,
+            total_lines: 3000,
+        };
+        enforce_budget(&mut blocks, budget, Some(dir.path())).unwrap();
+        assert_eq!(blocks[0].scrollback.loaded_lines(), 1000);
+        assert_eq!(blocks[1].resident_line_count(), 1000);
+    }
+
+    #[test]
+    fn test_unspillable_lines_are_dropped() {
+        let budget = ScrollbackBudget {
+            block_lines: 1000,
+            total_lines: 100_000,
+        };
+        let mut blocks = vec![block(1500)];
+        let result = enforce_budget(&mut blocks, budget, None);
+
+        assert!(matches!(
+            result,
+            Err(Error::OutputBufferFull { size, .. }) if size > 0
+        ));
+        assert_eq!(blocks[0].output.len(), 1001);
+        assert!(blocks[0].output[0].text.contains("500 lines dropped"));
+        assert_eq!(blocks[0].output[1].text, "line 500");
+    }
+
+    #[test]
+    fn test_block_pages_in_and_out() {
+        let dir = tempdir().unwrap();
+        let mut block = block(2500);
+        block.spill_output_front(2000, Some(dir.path())).unwrap();
+        let generation = block.output_generation;
+
+        assert_eq!(block.get_full_plain_output().unwrap().lines().count(), 2500);
+
+        assert_eq!(block.page_in_output().unwrap(), 1000);
+        assert_eq!(block.output[0].text, "line 1000");
+        assert_eq!(block.scrollback.first_line(), 1000);
+        assert!(block.output_generation > generation);
+
+        block.page_out_output();
+        assert_eq!(block.output[0].text, "line 2000");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_spill_file_is_wiped_on_drop() {
+        let dir = tempdir().unwrap();
+        let mut scrollback = Scrollback::default();
+        let mut output = lines(0..100);
+        scrollback.spill(dir.path(), &mut output, 100).unwrap();
+        let path = scrollback.path().unwrap().to_path_buf();
+
+        // A second link shows what the file held after it was removed
+        let link = dir.path().join("link");
+        std::fs::hard_link(&path, &link).unwrap();
+        let clone = scrollback.clone();
+        drop(scrollback);
+        assert!(path.exists(), "a clone still holds the file");
+
+        drop(clone);
+        assert!(!path.exists());
+        let bytes = std::fs::read(&link).unwrap();
+        assert!(!bytes.is_empty());
+        assert!(bytes.iter().all(|&b| b == 0));
+    }
+}
@@ -16,6 +16,12 @@ pub enum PtyEvent {
         /// Raw output bytes
         data: Vec<u8>,
     },
+    /// Output arrived and can be read from the PTY's streams; carries no
+    /// data, so announcing it costs nothing however large the output is
+    OutputReady {
+        /// The PTY handle ID
+        handle_id: String,
+    },
     /// PTY process has exited
     ProcessExited {
         /// The PTY handle ID
@@ -62,14 +68,17 @@ impl Drop for PtyEventSubscription {
 
 impl PtyEventSubscription {
     /// Receive the next event, waiting if necessary
+    ///
+    /// Events missed while lagging behind are skipped; `None` means the bus
+    /// is gone.
     pub async fn recv(&mut self) -> Option<PtyEvent> {
-        match self.receiver.recv().await {
-            Ok(event) => Some(event),
-            Err(broadcast::error::RecvError::Closed) => None,
-            Err(broadcast::error::RecvError::Lagged(count)) => {
-                tracing::warn!("PTY event subscriber lagged by {} events", count);
-                // Try to receive the next available event
-                self.receiver.recv().await.ok()
+        loop {
+            match self.receiver.recv().await {
+                Ok(event) => return Some(event),
+                Err(broadcast::error::RecvError::Closed) => return None,
+                Err(broadcast::error::RecvError::Lagged(count)) => {
+                    tracing::warn!("PTY event subscriber lagged by {} events", count);
+                }
             }
         }
     }
@@ -130,6 +139,29 @@ impl PtyEventBus {
     pub async fn subscriber_count(&self) -> usize {
         *self.active_subscribers.read().await
     }
+
+    /// Call `wake` each time a PTY has output or its process exits, for as
+    /// long as the bus lives.
+    ///
+    /// Meant for consumers that read output from the streams themselves and
+    /// only need to know when to look: events a slow subscriber misses are
+    /// skipped, while the streams keep every byte.
+    pub async fn notify_activity(&self, wake: impl Fn()) {
+        let mut subscription = self.subscribe().await;
+        loop {
+            match subscription.receiver.recv().await {
+                Ok(
+                    PtyEvent::Output { .. }
+                    | PtyEvent::OutputReady { .. }
+                    | PtyEvent::ProcessExited { .. },
+                ) => wake(),
+                Ok(_) => {}
+                // Some of the missed events were likely output
+                Err(broadcast::error::RecvError::Lagged(_)) => wake(),
+                Err(broadcast::error::RecvError::Closed) => return,
+            }
+        }
+    }
 }
 
 impl Default for PtyEventBus {
@@ -208,6 +240,37 @@ impl PtyOutputWatcher {
         WatchHandle { stop_tx }
     }
 
+    /// Pass PTY output from `upstream` on to `downstream`, announcing each
+    /// chunk as it arrives and the process exit once `upstream` closes.
+    ///
+    /// Runs on its own thread, like the PTY reader feeding it, so it can be
+    /// started with or without a tokio runtime. Stops quietly when
+    /// `downstream` is dropped because the PTY was terminated.
+    pub fn start_forwarding(
+        &self,
+        handle_id: String,
+        mut upstream: mpsc::Receiver<Vec<u8>>,
+        downstream: mpsc::Sender<Vec<u8>>,
+    ) {
+        let event_bus = self.event_bus.clone();
+        std::thread::spawn(move || {
+            while let Some(data) = upstream.blocking_recv() {
+                // Hand the data on first so it is there when subscribers look
+                if downstream.blocking_send(data).is_err() {
+                    return;
+                }
+                event_bus.publish(PtyEvent::OutputReady {
+                    handle_id: handle_id.clone(),
+                });
+            }
+            tracing::debug!("PTY output closed for handle: {}", handle_id);
+            event_bus.publish(PtyEvent::ProcessExited {
+                handle_id,
+                exit_code: None,
+            });
+        });
+    }
+
     /// Get a reference to the event bus
     pub fn event_bus(&self) -> &PtyEventBus {
         &self.event_bus
@@ -332,4 +395,119 @@ mod tests {
         // Stop watching
         handle.stop().await;
     }
+
+    #[tokio::test]
+    async fn test_forwarding_publishes_after_delivery() {
+        let event_bus = PtyEventBus::new(16);
+        let watcher = PtyOutputWatcher::new(event_bus.clone());
+        let mut sub = event_bus.subscribe().await;
+
+        let (pty_tx, pty_rx) = mpsc::channel(16);
+        let (tx, mut rx) = mpsc::channel(16);
+        watcher.start_forwarding("test-4".to_string(), pty_rx, tx);
+
+        pty_tx.send(b"chunk".to_vec()).await.unwrap();
+        match sub.recv().await {
+            Some(PtyEvent::OutputReady { handle_id }) => assert_eq!(handle_id, "test-4"),
+            other => panic!("Expected OutputReady event, got {:?}", other),
+        }
+        assert_eq!(rx.try_recv().unwrap(), b"chunk");
+
+        drop(pty_tx);
+        assert!(matches!(
+            sub.recv().await,
+            Some(PtyEvent::ProcessExited { .. })
+        ));
+        assert!(rx.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_notify_activity_wakes_on_output_and_exit() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let bus = PtyEventBus::new(16);
+        let wakes = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&wakes);
+        let listener = bus.clone();
+        tokio::spawn(async move {
+            listener
+                .notify_activity(move || {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                })
+                .await;
+        });
+        while bus.subscriber_count().await == 0 {
+            tokio::task::yield_now().await;
+        }
+
+        for event in [
+            PtyEvent::Created {
+                handle_id: "test-5".to_string(),
+                pid: None,
+            },
+            PtyEvent::OutputReady {
+                handle_id: "test-5".to_string(),
+            },
+            PtyEvent::ProcessExited {
+                handle_id: "test-5".to_string(),
+                exit_code: None,
+            },
+        ] {
+            bus.publish(event);
+        }
+        tokio::time::sleep(tokio::time::Duration::from_millis(20)).await;
+        assert_eq!(wakes.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_recv_skips_repeated_lag() {
+        let bus = PtyEventBus::new(2);
+        let mut sub = bus.subscribe().await;
+        for _ in 0..3 {
+            for i in 0..5 {
+                bus.publish(PtyEvent::Terminated {
+                    handle_id: i.to_string(),
+                });
+            }
+            assert!(sub.recv().await.is_some());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_notify_activity_survives_lag() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let bus = PtyEventBus::new(2);
+        let wakes = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&wakes);
+        let listener = bus.clone();
+        tokio::spawn(async move {
+            listener
+                .notify_activity(move || {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                })
+                .await;
+        });
+        while bus.subscriber_count().await == 0 {
+            tokio::task::yield_now().await;
+        }
+
+        // Twice as many events as the bus holds, before the listener runs
+        for _ in 0..2 {
+            for _ in 0..4 {
+                bus.publish(PtyEvent::OutputReady {
+                    handle_id: "test-6".to_string(),
+                });
+            }
+            tokio::time::sleep(tokio::time::Duration::from_millis(20)).await;
+        }
+        let after_lag = wakes.load(Ordering::SeqCst);
+        assert!(after_lag > 0);
+
+        bus.publish(PtyEvent::OutputReady {
+            handle_id: "test-6".to_string(),
+        });
+        tokio::time::sleep(tokio::time::Duration::from_millis(20)).await;
+        assert_eq!(wakes.load(Ordering::SeqCst), after_lag + 1);
+    }
 }
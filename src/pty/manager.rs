@@ -7,7 +7,8 @@
 //! ## Event-Driven Mode
 //!
 //! The manager can optionally publish events to a `PtyEventBus` for
-//! event-driven output handling. This eliminates the need for polling.
+//! event-driven output handling. This eliminates the need for polling:
+//! output is announced as it arrives from the PTY, and reads then drain it.
 //!
 //! ```ignore
 //! use mosaicterm::pty::{PtyManager, PtyEventBus};
@@ -28,7 +29,7 @@ use std::sync::{Arc, Mutex};
 use tokio::sync::RwLock;
 use uuid::Uuid;
 
-use super::events::{PtyEvent, PtyEventBus};
+use super::events::{PtyEvent, PtyEventBus, PtyOutputWatcher};
 use super::process::{spawn_pty_process, validate_command};
 use super::streams::PtyStreams;
 use crate::error::{Error, Result};
@@ -141,13 +142,18 @@ impl PtyManager {
         // Create PTY handle
         let mut handle = PtyHandle::new();
 
-        let (process, streams, master) =
+        let (process, mut streams, master) =
             spawn_pty_process(command, args, env, working_directory).await?;
 
         if let Some(pid) = process.pid {
             handle.pid = Some(pid);
         }
 
+        // Announce output as it arrives rather than when it is read
+        if let Some(bus) = &self.event_bus {
+            streams.watch_output(&PtyOutputWatcher::new(bus.clone()), handle.id.clone());
+        }
+
         let entry = Arc::new(RwLock::new(PtyEntry {
             process,
             streams,
@@ -279,30 +285,65 @@ impl PtyManager {
         }
     }
 
+    /// Whether a foreground job has switched the terminal out of canonical mode.
+    ///
+    /// Only jobs other than the shell itself count: line editors such as
+    /// readline turn off `ICANON` while the shell waits at its prompt.
+    pub async fn is_foreground_raw(&self, handle: &PtyHandle) -> Result<bool> {
+        let terminals = self.terminals.read().await;
+        let Some(entry_lock) = terminals.get(&handle.id) else {
+            return Err(Error::PtyHandleNotFound {
+                handle_id: handle.id.to_string(),
+            });
+        };
+        let entry = entry_lock.read().await;
+        let master = entry.master.lock().map_err(|e| Error::PtyCreationFailed {
+            command: "termios".to_string(),
+            reason: format!("mutex poisoned: {}", e),
+        })?;
+
+        #[cfg(unix)]
+        {
+            use nix::sys::termios::{tcgetattr, LocalFlags};
+            use std::os::fd::BorrowedFd;
+
+            let shell_pid = entry.process.pid.or(handle.pid);
+            match master.process_group_leader() {
+                Some(leader) if Some(leader as u32) != shell_pid => {}
+                _ => return Ok(false),
+            }
+            let Some(fd) = master.as_raw_fd() else {
+                return Ok(false);
+            };
+            // SAFETY: the descriptor belongs to `master`, which stays locked
+            // (and therefore open) for the duration of the call
+            let fd = unsafe { BorrowedFd::borrow_raw(fd) };
+            Ok(tcgetattr(fd)
+                .map(|termios| !termios.local_flags.contains(LocalFlags::ICANON))
+                .unwrap_or(false))
+        }
+
+        #[cfg(not(unix))]
+        {
+            let _ = master;
+            Ok(false)
+        }
+    }
+
     /// Read output from a PTY process, with a timeout in milliseconds
     /// This operation only locks the specific terminal, not all terminals
     ///
-    /// Note: In event-driven mode, prefer subscribing to the event bus instead
-    /// of polling with this method.
+    /// Note: In event-driven mode, subscribe to the event bus to learn when
+    /// there is output to read instead of polling with this method.
     pub async fn read_output(&self, handle: &PtyHandle, timeout_ms: u64) -> Result<Vec<u8>> {
         let terminals = self.terminals.read().await;
         if let Some(entry_lock) = terminals.get(&handle.id) {
             let mut entry = entry_lock.write().await;
-            let data = if timeout_ms == 0 {
-                entry.streams.read().await?
+            if timeout_ms == 0 {
+                entry.streams.read().await
             } else {
-                entry.streams.read_with_timeout(timeout_ms).await?
-            };
-
-            // Publish output event if we have data and event bus is configured
-            if !data.is_empty() {
-                self.publish_event(PtyEvent::Output {
-                    handle_id: handle.id.clone(),
-                    data: data.clone(),
-                });
+                entry.streams.read_with_timeout(timeout_ms).await
             }
-
-            Ok(data)
         } else {
             Err(Error::PtyStreamsNotFound {
                 handle_id: handle.id.to_string(),
@@ -313,23 +354,13 @@ impl PtyManager {
     /// Try to read output immediately without waiting
     /// This operation only locks the specific terminal, not all terminals
     ///
-    /// Note: In event-driven mode, prefer subscribing to the event bus instead
-    /// of polling with this method.
+    /// Note: In event-driven mode, subscribe to the event bus to learn when
+    /// there is output to read instead of polling with this method.
     pub async fn try_read_output_now(&self, handle: &PtyHandle) -> Result<Vec<u8>> {
         let terminals = self.terminals.read().await;
         if let Some(entry_lock) = terminals.get(&handle.id) {
             let mut entry = entry_lock.write().await;
-            let data = entry.streams.try_read_now()?;
-
-            // Publish output event if we have data and event bus is configured
-            if !data.is_empty() {
-                self.publish_event(PtyEvent::Output {
-                    handle_id: handle.id.clone(),
-                    data: data.clone(),
-                });
-            }
-
-            Ok(data)
+            entry.streams.try_read_now()
         } else {
             Err(Error::PtyStreamsNotFound {
                 handle_id: handle.id.to_string(),
@@ -447,4 +478,33 @@ mod tests {
         // can operate concurrently without blocking each other
         assert_eq!(manager.active_count().await, 0);
     }
+
+    #[tokio::test]
+    async fn test_event_driven_output_is_announced_before_read() {
+        let bus = PtyEventBus::new(64);
+        let manager = PtyManager::with_event_bus(bus.clone());
+        let mut sub = bus.subscribe().await;
+
+        let handle = manager
+            .create_pty("echo", &["announced".to_string()], &HashMap::new(), None)
+            .await
+            .expect("spawn echo");
+
+        let mut read = Vec::new();
+        loop {
+            let event = tokio::time::timeout(std::time::Duration::from_secs(5), sub.recv())
+                .await
+                .expect("event before timeout");
+            match event {
+                Some(PtyEvent::OutputReady { handle_id }) => {
+                    assert_eq!(handle_id, handle.id);
+                    read.extend(manager.try_read_output_now(&handle).await.unwrap());
+                }
+                Some(PtyEvent::ProcessExited { .. }) => break,
+                Some(_) => {}
+                None => panic!("event bus closed"),
+            }
+        }
+        assert!(String::from_utf8_lossy(&read).contains("announced"));
+    }
 }
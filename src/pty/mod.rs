@@ -22,7 +22,7 @@
 //! // Handle events
 //! while let Some(event) = subscription.recv().await {
 //!     match event {
-//!         PtyEvent::Output { handle_id, data } => { /* process output */ }
+//!         PtyEvent::OutputReady { handle_id } => { /* read the PTY's output */ }
 //!         PtyEvent::ProcessExited { handle_id, exit_code } => { /* handle exit */ }
 //!         _ => {}
 //!     }
@@ -19,7 +19,9 @@
 //! [`create_zdotdir`] / [`create_bash_rcfile`] write temporary startup files
 //! that install a `precmd` / `PROMPT_COMMAND` hook.  Because the hook is sourced
 //! during shell startup (not typed into the PTY), it produces **zero** visible
-//! output.
+//! output.  The hook and prompts emit FinalTerm / OSC 133 marks (see
+//! [`crate::terminal::semantic`]) so each command's output and exit status
+//! can be read straight from the stream.
 
 use std::path::PathBuf;
 
@@ -297,7 +299,24 @@ const ENV_VARS: &[&str] = &[
     "TF_WORKSPACE",
 ];
 
-fn hook_body(state_path: &str) -> String {
+/// zsh prompts: empty apart from the input-start mark, wrapped in `%{ %}` so
+/// it takes no width
+const ZSH_PROMPT: &str = r"PS1=$'%{\e]133;B\a%}' PS2='' PS3='' PS4=''";
+
+/// bash prompts: empty apart from the input-start mark, wrapped in `\[ \]`
+/// so it takes no width
+const BASH_PROMPT: &str = r"PS1='\[\e]133;B\a\]' PS2='' PS3='' PS4=''";
+
+/// Output-start mark, printed by bash (4.4+) through `PS0` once a command line
+/// has been read
+const BASH_OUTPUT_MARK: &str = r"\e]133;C\a";
+
+/// The precmd hook: records the exit status and environment in the state
+/// file, reports the working directory (`OSC 7`, with `%` percent-encoded so
+/// the path reads back unchanged), the finished command
+/// (`OSC 133 ; D`) and the start of the next prompt (`OSC 133 ; A`), then
+/// installs `prompt`, which carries the input-start mark.
+fn hook_body(state_path: &str, prompt: &str) -> String {
     let env_writes: String = ENV_VARS
         .iter()
         .map(|v| format!("  echo \"{}=${{{}}}\"", v, v))
@@ -307,13 +326,16 @@ fn hook_body(state_path: &str) -> String {
     format!(
         r#"__mosaicterm_precmd() {{
   local __ec=$?
-  PS1='' PS2='' PS3='' PS4=''
+  {prompt}
   {{
     echo "EXIT:$__ec"
 {env_writes}
   }} > "{state_path}" 2>/dev/null
+  printf '\033]7;file://%s%s\007\033]133;D;%s\007\033]133;A\007' \
+    "${{HOSTNAME:-$HOST}}" "${{PWD//\%/%25}}" "$__ec"
   return $__ec
 }}"#,
+        prompt = prompt,
         env_writes = env_writes,
         state_path = state_path,
     )
@@ -333,7 +355,7 @@ pub fn create_zdotdir(shell_pid: u32) -> Option<PathBuf> {
     }
 
     let state_path = state_file_path(shell_pid).display().to_string();
-    let body = hook_body(&state_path);
+    let body = hook_body(&state_path, ZSH_PROMPT);
 
     let user_home = dirs::home_dir()
         .unwrap_or_else(|| PathBuf::from("/"))
@@ -359,7 +381,9 @@ pub fn create_zdotdir(shell_pid: u32) -> Option<PathBuf> {
 
 # Install MosaicTerm precmd hook (after user RC so it runs first in the chain)
 {body}
+__mosaicterm_preexec() {{ printf '\033]133;C\007'; }}
 precmd_functions=(__mosaicterm_precmd ${{precmd_functions[@]}})
+preexec_functions+=(__mosaicterm_preexec)
 
 # Restore ZDOTDIR so nested shells and tools see the real home
 ZDOTDIR="{home}"
@@ -384,7 +408,7 @@ ZDOTDIR="{home}"
 /// `--rcfile <path>` instead of the default `~/.bashrc`.
 pub fn create_bash_rcfile(shell_pid: u32) -> Option<PathBuf> {
     let state_path = state_file_path(shell_pid).display().to_string();
-    let body = hook_body(&state_path);
+    let body = hook_body(&state_path, BASH_PROMPT);
 
     let user_home = dirs::home_dir()
         .unwrap_or_else(|| PathBuf::from("/"))
@@ -399,10 +423,13 @@ pub fn create_bash_rcfile(shell_pid: u32) -> Option<PathBuf> {
 {body}
 PROMPT_COMMAND="__mosaicterm_precmd${{PROMPT_COMMAND:+;$PROMPT_COMMAND}}"
 
-PS1='' PS2='' PS3='' PS4=''
+PS0='{output_mark}'
+{prompt}
 "#,
         home = user_home,
         body = body,
+        output_mark = BASH_OUTPUT_MARK,
+        prompt = BASH_PROMPT,
     );
 
     let path = std::env::temp_dir().join(format!("mosaicterm_bashrc_{}", shell_pid));
@@ -461,7 +488,9 @@ mod tests {
         assert!(contents.contains("EXIT:$__ec"));
         assert!(contents.contains("VIRTUAL_ENV"));
         assert!(contents.contains("source"));
-        assert!(contents.contains("PS1=''"));
+        assert!(contents.contains("PS1=$'%{\\e]133;B\\a%}'"));
+        assert!(contents.contains("133;D;%s"));
+        assert!(contents.contains("preexec_functions+=(__mosaicterm_preexec)"));
         cleanup_shell_files(pid);
         assert!(!dir.exists());
     }
@@ -475,10 +504,39 @@ mod tests {
         assert!(contents.contains("__mosaicterm_precmd"));
         assert!(contents.contains("PROMPT_COMMAND"));
         assert!(contents.contains(".bashrc"));
+        assert!(contents.contains("133;D;%s"));
+        assert!(contents.contains("]7;file://%s%s"));
+        assert!(contents.contains("PS0='\\e]133;C\\a'"));
         cleanup_shell_files(pid);
         assert!(!path.exists());
     }
 
+    #[cfg(unix)]
+    #[test]
+    fn hook_reports_cwd_that_decodes_to_pwd() {
+        let dir = tempfile::tempdir().unwrap();
+        let cwd = dir.path().join("100%25 done");
+        std::fs::create_dir(&cwd).unwrap();
+        let script = format!(
+            "{}\ncd \"$1\" && __mosaicterm_precmd",
+            hook_body("/dev/null", "")
+        );
+        let Ok(output) = std::process::Command::new("bash")
+            .args(["-c", &script, "bash"])
+            .arg(&cwd)
+            .output()
+        else {
+            return;
+        };
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        let body = stdout
+            .strip_prefix("\x1b]")
+            .and_then(|rest| rest.split('\x07').next())
+            .unwrap();
+        let reported = crate::terminal::semantic::ReportedCwd::from_osc(body).unwrap();
+        assert_eq!(reported.path, cwd);
+    }
+
     #[test]
     fn read_exit_code_from_state_file() {
         let pid = 99999;
@@ -3,6 +3,7 @@
 //! Provides async-friendly interfaces for PTY I/O by bridging blocking
 //! PTY master reads/writes to async code using channels.
 
+use super::events::PtyOutputWatcher;
 use crate::error::{Error, Result};
 use std::sync::mpsc::Sender as StdSender;
 use tokio::sync::mpsc::Receiver;
@@ -65,6 +66,14 @@ impl PtyStreams {
         }
     }
 
+    /// Route output through `watcher`, which announces it on its event bus
+    /// as it arrives; reads are unchanged
+    pub fn watch_output(&mut self, watcher: &PtyOutputWatcher, handle_id: String) {
+        let (tx, rx) = tokio::sync::mpsc::channel(self.output_rx.max_capacity());
+        let upstream = std::mem::replace(&mut self.output_rx, rx);
+        watcher.start_forwarding(handle_id, upstream, tx);
+    }
+
     /// Drain all pending output from the channel (discard it)
     /// Used when switching contexts (e.g., ending SSH session) to avoid stale output
     pub fn drain_output(&mut self) -> usize {
@@ -16,6 +16,7 @@
 //! - Authentication prompt display (type only, no input)
 //! - File permission changes
 //! - Configuration loads/errors
+//! - Clipboard reads by programs (OSC 52), allowed or not
 
 use tracing::{info, warn};
 
@@ -42,6 +43,8 @@ pub enum SecurityEvent {
     ConfigError,
     /// Suspicious activity detected
     SuspiciousActivity,
+    /// A program asked to read the clipboard
+    ClipboardRead,
 }
 
 impl SecurityEvent {
@@ -56,6 +59,7 @@ impl SecurityEvent {
             SecurityEvent::ConfigLoaded => "Configuration loaded successfully",
             SecurityEvent::ConfigError => "Configuration loading error",
             SecurityEvent::SuspiciousActivity => "Suspicious activity detected",
+            SecurityEvent::ClipboardRead => "Clipboard read requested by program",
         }
     }
 
@@ -153,6 +157,19 @@ pub fn log_config_event(is_error: bool, details: Option<&str>) {
     log_security_event(event, details);
 }
 
+/// Log a program's clipboard read and what became of it (never the contents)
+pub fn log_clipboard_read(allowed: bool, source: &str) {
+    let decision = if allowed { "allowed" } else { "denied" };
+    log_security_event(
+        SecurityEvent::ClipboardRead,
+        Some(&format!(
+            "decision={} source={}",
+            decision,
+            sanitize_metadata(source)
+        )),
+    );
+}
+
 /// Sanitize hostname to prevent log injection
 fn sanitize_hostname(host: &str) -> String {
     // Remove any characters that could be used for log injection
@@ -220,6 +237,8 @@ mod tests {
         log_history_access("read");
         log_config_event(false, Some("test"));
         log_config_event(true, None);
+        log_clipboard_read(false, "overlay");
+        log_clipboard_read(true, "block\noutput");
     }
 
     #[test]
@@ -572,6 +572,13 @@ impl StateManager {
         }
     }
 
+    /// Change the working directory of the active session
+    pub fn change_directory(&mut self, new_dir: PathBuf) {
+        if let Some(session) = self.active_session_mut() {
+            session.change_directory(new_dir);
+        }
+    }
+
     // Statistics methods
 
     /// Get statistics
@@ -810,6 +817,16 @@ mod tests {
             assert_eq!(prev, Some(PathBuf::from("/tmp")));
             assert_eq!(session.working_directory, PathBuf::from("/tmp"));
         }
+
+        manager.change_directory(PathBuf::from("/var"));
+        assert_eq!(
+            manager.active_session().unwrap().working_directory,
+            PathBuf::from("/var")
+        );
+        assert_eq!(
+            manager.get_previous_directory(),
+            Some(PathBuf::from("/tmp"))
+        );
     }
 
     #[test]
@@ -5,6 +5,7 @@
 
 use crate::error::Result;
 use crate::models::output_line::AnsiCode;
+use crate::terminal::grid::CellStyle;
 use regex::Regex;
 
 /// ANSI escape sequence parser
@@ -28,7 +29,7 @@ impl AnsiParser {
     /// Create a new ANSI parser
     pub fn new() -> Self {
         Self {
-            ansi_regex: Regex::new(r"\x1b\[([0-9;]*)[mG]").unwrap(),
+            ansi_regex: Regex::new(r"\x1b\[([0-9;:]*)[mG]").unwrap(),
             state: ParserState::Normal,
             buffer: String::new(),
         }
@@ -77,68 +78,26 @@ impl AnsiParser {
             return None;
         }
 
-        let content = &sequence[2..sequence.len() - 1]; // Remove \x1b[ and final character
-        let parts: Vec<&str> = content.split(';').collect();
-
         match sequence.chars().last() {
-            Some('m') => self.parse_sgr_sequence(&parts),
-            Some('G') => self.parse_cursor_sequence(&parts),
+            Some('m') => self.parse_sgr_sequence(sequence),
+            Some('G') => {
+                let content = &sequence[2..sequence.len() - 1];
+                let parts: Vec<&str> = content.split(';').collect();
+                self.parse_cursor_sequence(&parts)
+            }
             _ => None,
         }
     }
 
     /// Parse Select Graphic Rendition (SGR) sequences (colors, styles)
-    fn parse_sgr_sequence(&self, parts: &[&str]) -> Option<AnsiCode> {
-        let mut attributes = Vec::new();
-
-        for part in parts {
-            if let Ok(code) = part.parse::<u8>() {
-                match code {
-                    0 => attributes.push(AnsiAttribute::Reset),
-                    1 => attributes.push(AnsiAttribute::Bold),
-                    2 => attributes.push(AnsiAttribute::Dim),
-                    3 => attributes.push(AnsiAttribute::Italic),
-                    4 => attributes.push(AnsiAttribute::Underline),
-                    5 | 6 => attributes.push(AnsiAttribute::Blink),
-                    7 => attributes.push(AnsiAttribute::Reverse),
-                    8 => attributes.push(AnsiAttribute::Hidden),
-                    9 => attributes.push(AnsiAttribute::Strikethrough),
-                    21 => attributes.push(AnsiAttribute::DoubleUnderline),
-                    22 => attributes.push(AnsiAttribute::NormalIntensity),
-                    23 => attributes.push(AnsiAttribute::NotItalic),
-                    24 => attributes.push(AnsiAttribute::NotUnderlined),
-                    25 => attributes.push(AnsiAttribute::NotBlinking),
-                    27 => attributes.push(AnsiAttribute::NotReversed),
-                    28 => attributes.push(AnsiAttribute::NotHidden),
-                    29 => attributes.push(AnsiAttribute::NotStrikethrough),
-                    30..=37 => {
-                        let color = AnsiColor::from_ansi_code(code - 30)?;
-                        attributes.push(AnsiAttribute::ForegroundColor(color));
-                    }
-                    40..=47 => {
-                        let color = AnsiColor::from_ansi_code(code - 40)?;
-                        attributes.push(AnsiAttribute::BackgroundColor(color));
-                    }
-                    90..=97 => {
-                        let color = AnsiColor::from_bright_ansi_code(code - 90)?;
-                        attributes.push(AnsiAttribute::ForegroundColor(color));
-                    }
-                    100..=107 => {
-                        let color = AnsiColor::from_bright_ansi_code(code - 100)?;
-                        attributes.push(AnsiAttribute::BackgroundColor(color));
-                    }
-                    _ => {} // Unknown code, ignore
-                }
-            }
-        }
-
-        if attributes.is_empty() {
-            None
-        } else {
-            // Convert attributes to ANSI escape sequence string
-            let code_str = Self::attributes_to_ansi_string(&attributes);
-            Some(AnsiCode::new(&code_str))
-        }
+    ///
+    /// The sequence is kept verbatim so that 256-color and truecolor parameters
+    /// survive until the renderer resolves them with [`CellStyle`].
+    fn parse_sgr_sequence(&self, sequence: &str) -> Option<AnsiCode> {
+        let mut style = CellStyle::default();
+        style
+            .apply_sgr_sequence(sequence)
+            .then(|| AnsiCode::new(sequence))
     }
 
     /// Parse cursor positioning sequences
@@ -152,76 +111,6 @@ impl AnsiParser {
         None
     }
 
-    /// Convert ANSI attributes to escape sequence string
-    fn attributes_to_ansi_string(attributes: &[AnsiAttribute]) -> String {
-        if attributes.is_empty() {
-            return String::new();
-        }
-
-        let mut codes = Vec::new();
-
-        for attr in attributes {
-            let code = match attr {
-                AnsiAttribute::Reset => "0",
-                AnsiAttribute::Bold => "1",
-                AnsiAttribute::Dim => "2",
-                AnsiAttribute::Italic => "3",
-                AnsiAttribute::Underline => "4",
-                AnsiAttribute::Blink => "5",
-                AnsiAttribute::Reverse => "7",
-                AnsiAttribute::Hidden => "8",
-                AnsiAttribute::Strikethrough => "9",
-                AnsiAttribute::DoubleUnderline => "21",
-                AnsiAttribute::NormalIntensity => "22",
-                AnsiAttribute::NotItalic => "23",
-                AnsiAttribute::NotUnderlined => "24",
-                AnsiAttribute::NotBlinking => "25",
-                AnsiAttribute::NotReversed => "27",
-                AnsiAttribute::NotHidden => "28",
-                AnsiAttribute::NotStrikethrough => "29",
-                AnsiAttribute::ForegroundColor(color) => match color {
-                    AnsiColor::Black => "30",
-                    AnsiColor::Red => "31",
-                    AnsiColor::Green => "32",
-                    AnsiColor::Yellow => "33",
-                    AnsiColor::Blue => "34",
-                    AnsiColor::Magenta => "35",
-                    AnsiColor::Cyan => "36",
-                    AnsiColor::White => "37",
-                    AnsiColor::BrightBlack => "90",
-                    AnsiColor::BrightRed => "91",
-                    AnsiColor::BrightGreen => "92",
-                    AnsiColor::BrightYellow => "93",
-                    AnsiColor::BrightBlue => "94",
-                    AnsiColor::BrightMagenta => "95",
-                    AnsiColor::BrightCyan => "96",
-                    AnsiColor::BrightWhite => "97",
-                },
-                AnsiAttribute::BackgroundColor(color) => match color {
-                    AnsiColor::Black => "40",
-                    AnsiColor::Red => "41",
-                    AnsiColor::Green => "42",
-                    AnsiColor::Yellow => "43",
-                    AnsiColor::Blue => "44",
-                    AnsiColor::Magenta => "45",
-                    AnsiColor::Cyan => "46",
-                    AnsiColor::White => "47",
-                    AnsiColor::BrightBlack => "100",
-                    AnsiColor::BrightRed => "101",
-                    AnsiColor::BrightGreen => "102",
-                    AnsiColor::BrightYellow => "103",
-                    AnsiColor::BrightBlue => "104",
-                    AnsiColor::BrightMagenta => "105",
-                    AnsiColor::BrightCyan => "106",
-                    AnsiColor::BrightWhite => "107",
-                },
-            };
-            codes.push(code);
-        }
-
-        format!("\x1b[{}m", codes.join(";"))
-    }
-
     /// Reset parser state
     pub fn reset(&mut self) {
         self.state = ParserState::Normal;
@@ -317,6 +206,15 @@ impl AnsiColor {
         }
     }
 
+    /// Create color from a palette index (0-15)
+    pub fn from_index(idx: u8) -> Option<Self> {
+        match idx {
+            0..=7 => Self::from_ansi_code(idx),
+            8..=15 => Self::from_bright_ansi_code(idx - 8),
+            _ => None,
+        }
+    }
+
     /// Create color from bright ANSI code (0-7)
     pub fn from_bright_ansi_code(code: u8) -> Option<Self> {
         match code {
@@ -434,6 +332,28 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parse_extended_colors_kept_verbatim() {
+        let mut parser = AnsiParser::new();
+        let result = parser
+            .parse("\x1b[38;5;196mA\x1b[48;2;1;2;3mB\x1b[38:2::4:5:6mC")
+            .unwrap();
+
+        assert_eq!(result.clean_text, "ABC");
+        let codes: Vec<&str> = result.ansi_codes.iter().map(|c| c.code.as_str()).collect();
+        assert_eq!(
+            codes,
+            vec!["\x1b[38;5;196m", "\x1b[48;2;1;2;3m", "\x1b[38:2::4:5:6m"]
+        );
+    }
+
+    #[test]
+    fn test_ansi_color_from_index() {
+        assert_eq!(AnsiColor::from_index(1), Some(AnsiColor::Red));
+        assert_eq!(AnsiColor::from_index(9), Some(AnsiColor::BrightRed));
+        assert_eq!(AnsiColor::from_index(16), None);
+    }
+
     #[test]
     fn test_parsed_text_position_mapping() {
         let mut parsed = ParsedText::new("Test text".to_string());
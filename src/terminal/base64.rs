@@ -0,0 +1,97 @@
+//! Base64 (RFC 4648) for escape sequence payloads
+//!
+//! OSC 52 clipboard contents travel base64-encoded in both directions, and
+//! kitty and iTerm2 inline images arrive that way too.
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Value of each byte in [`ALPHABET`], [`INVALID`] for the rest; images run
+/// to megabytes, so decoding looks values up rather than searching
+const VALUES: [u8; 256] = {
+    let mut values = [INVALID; 256];
+    let mut i = 0;
+    while i < ALPHABET.len() {
+        values[ALPHABET[i] as usize] = i as u8;
+        i += 1;
+    }
+    values
+};
+const INVALID: u8 = 0xff;
+
+/// Encode `data` with padding
+pub fn encode(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let group = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(group >> (18 - 6 * i)) as usize & 0x3f] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// Decode `text`; padding is optional. `None` if it is not base64.
+pub fn decode(text: &[u8]) -> Option<Vec<u8>> {
+    let text = match text.iter().position(|&b| b == b'=') {
+        Some(pad) if text[pad..].iter().all(|&b| b == b'=') && text.len().is_multiple_of(4) => {
+            &text[..pad]
+        }
+        Some(_) => return None,
+        None => text,
+    };
+    if text.len() % 4 == 1 {
+        return None;
+    }
+
+    let mut out = Vec::with_capacity(text.len() * 3 / 4);
+    for chunk in text.chunks(4) {
+        let mut group = 0u32;
+        for (i, &byte) in chunk.iter().enumerate() {
+            let value = VALUES[byte as usize];
+            if value == INVALID {
+                return None;
+            }
+            group |= (value as u32) << (18 - 6 * i);
+        }
+        let bytes = group.to_be_bytes();
+        out.extend_from_slice(&bytes[1..chunk.len()]);
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        for text in ["", "f", "fo", "foo", "foob", "fooba", "foobar", "héllo ✓"] {
+            assert_eq!(
+                decode(encode(text.as_bytes()).as_bytes()).unwrap(),
+                text.as_bytes()
+            );
+        }
+        assert_eq!(encode(b"foobar"), "Zm9vYmFy");
+        assert_eq!(encode(b"fo"), "Zm8=");
+    }
+
+    #[test]
+    fn test_decode_padding_and_errors() {
+        assert_eq!(decode(b"Zm8").unwrap(), b"fo");
+        assert_eq!(decode(b"Zm8=").unwrap(), b"fo");
+        assert_eq!(decode(b"Zg==").unwrap(), b"f");
+        assert!(decode(b"Zm8=x").is_none());
+        assert!(decode(b"Z").is_none());
+        assert!(decode(b"?").is_none());
+        assert!(decode(b"Zm 8").is_none());
+    }
+}
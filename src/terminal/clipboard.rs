@@ -0,0 +1,83 @@
+//! Clipboard Requests (OSC 52)
+//!
+//! Programs set the clipboard with `OSC 52 ; targets ; base64 ST` and ask for
+//! its contents with `OSC 52 ; targets ; ? ST`. This works over SSH and inside
+//! tmux, where the program has no other way to reach the local clipboard.
+//! The grid and [`crate::terminal::QueryScanner`] collect the requests; the
+//! application decides what to do with them according to the configured
+//! policy, as reads expose whatever the user last copied.
+
+use crate::terminal::base64;
+
+/// A program's request to use the clipboard
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ClipboardRequest {
+    /// Replace the clipboard contents; empty text clears it
+    Write(String),
+    /// Send the clipboard contents back. `targets` is echoed in the reply and
+    /// `bell` replies with BEL instead of ST, as the request was terminated.
+    Read { targets: String, bell: bool },
+}
+
+impl ClipboardRequest {
+    /// Recognise a request in an OSC sequence
+    pub fn from_osc(params: &[&[u8]], bell: bool) -> Option<Self> {
+        let [b"52", targets, data] = params else {
+            return None;
+        };
+        if *data == b"?" {
+            return Some(Self::Read {
+                targets: String::from_utf8_lossy(targets).into_owned(),
+                bell,
+            });
+        }
+        // Anything that isn't base64 clears the clipboard, as in xterm
+        let text = base64::decode(data).unwrap_or_default();
+        Some(Self::Write(String::from_utf8_lossy(&text).into_owned()))
+    }
+}
+
+/// Reply to a read with the clipboard's `text`
+pub fn read_reply(targets: &str, text: &str, bell: bool) -> Vec<u8> {
+    let terminator = if bell { "\x07" } else { "\x1b\\" };
+    format!(
+        "\x1b]52;{};{}{}",
+        targets,
+        base64::encode(text.as_bytes()),
+        terminator
+    )
+    .into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_write() {
+        assert_eq!(
+            ClipboardRequest::from_osc(&[b"52", b"c", b"aGVsbG8="], true),
+            Some(ClipboardRequest::Write("hello".to_string()))
+        );
+        assert_eq!(
+            ClipboardRequest::from_osc(&[b"52", b"", b"!"], true),
+            Some(ClipboardRequest::Write(String::new()))
+        );
+        assert_eq!(ClipboardRequest::from_osc(&[b"52", b"c"], true), None);
+        assert_eq!(ClipboardRequest::from_osc(&[b"8", b"", b"x"], true), None);
+    }
+
+    #[test]
+    fn test_parse_read_and_reply() {
+        let request = ClipboardRequest::from_osc(&[b"52", b"c", b"?"], false).unwrap();
+        assert_eq!(
+            request,
+            ClipboardRequest::Read {
+                targets: "c".to_string(),
+                bell: false
+            }
+        );
+        assert_eq!(read_reply("c", "hi", false), b"\x1b]52;c;aGk=\x1b\\");
+        assert_eq!(read_reply("pc", "", true), b"\x1b]52;pc;\x07");
+    }
+}
@@ -0,0 +1,124 @@
+//! Fullscreen Application Detection
+//!
+//! Watches block-mode output for a switch to the alternate screen (DECSET
+//! 47/1047/1049). Any command that does this — `git log` through a pager,
+//! `fzf`, an editor started from a script — draws a fullscreen UI and belongs
+//! in the TUI overlay, whatever its name.
+
+use vte::{Params, Perform};
+
+/// Scans PTY output for alternate-screen entry
+pub struct AltScreenDetector {
+    parser: vte::Parser,
+    scanner: Scanner,
+}
+
+/// Records alternate-screen entries seen by the parser
+#[derive(Default)]
+struct Scanner {
+    entered: bool,
+}
+
+impl Perform for Scanner {
+    fn csi_dispatch(&mut self, params: &Params, intermediates: &[u8], ignore: bool, action: char) {
+        if ignore || action != 'h' || intermediates != [b'?'] {
+            return;
+        }
+        if params
+            .iter()
+            .any(|group| matches!(group.first(), Some(47 | 1047 | 1049)))
+        {
+            self.entered = true;
+        }
+    }
+}
+
+impl Default for AltScreenDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AltScreenDetector {
+    /// Create a detector in the ground state
+    pub fn new() -> Self {
+        Self {
+            parser: vte::Parser::new(),
+            scanner: Scanner::default(),
+        }
+    }
+
+    /// Feed a chunk of output.
+    ///
+    /// Returns the offset of the escape sequence that entered the alternate
+    /// screen, so the bytes before it can stay in the block and the rest can
+    /// go to the overlay. A sequence split across chunks reports offset 0.
+    pub fn scan(&mut self, bytes: &[u8]) -> Option<usize> {
+        // ESC starts a new sequence and aborts any pending one, so a sequence
+        // completed between two ESCs started at the first of them
+        let mut start = 0;
+        while start < bytes.len() {
+            let end = bytes[start + 1..]
+                .iter()
+                .position(|&b| b == 0x1b)
+                .map_or(bytes.len(), |pos| start + 1 + pos);
+            self.parser.advance(&mut self.scanner, &bytes[start..end]);
+            if std::mem::take(&mut self.scanner.entered) {
+                self.reset();
+                return Some(start);
+            }
+            start = end;
+        }
+        None
+    }
+
+    /// Forget any partial sequence (e.g. when a new command starts)
+    pub fn reset(&mut self) {
+        self.parser = vte::Parser::new();
+        self.scanner = Scanner::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detects_alt_screen_modes() {
+        for seq in ["\x1b[?1049h", "\x1b[?47h", "\x1b[?1047h", "\x1b[?1;1049h"] {
+            let mut detector = AltScreenDetector::new();
+            assert_eq!(detector.scan(seq.as_bytes()), Some(0), "{:?}", seq);
+        }
+    }
+
+    #[test]
+    fn test_ignores_other_sequences() {
+        let mut detector = AltScreenDetector::new();
+        assert_eq!(
+            detector.scan(b"\x1b[?1049l\x1b[?25h\x1b[1049h\x1b[31mtext"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_reports_offset() {
+        let mut detector = AltScreenDetector::new();
+        let data = b"commit abc\r\n\x1b[1m\x1b[?1049h\x1b[H";
+        assert_eq!(detector.scan(data), Some(16));
+    }
+
+    #[test]
+    fn test_sequence_split_across_chunks() {
+        let mut detector = AltScreenDetector::new();
+        assert_eq!(detector.scan(b"before\x1b[?10"), None);
+        assert_eq!(detector.scan(b"49hafter"), Some(0));
+    }
+
+    #[test]
+    fn test_reset_discards_partial_sequence() {
+        let mut detector = AltScreenDetector::new();
+        detector.scan(b"\x1b[?10");
+        detector.reset();
+        assert_eq!(detector.scan(b"49h"), None);
+    }
+}
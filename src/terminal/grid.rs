@@ -0,0 +1,2323 @@
+//! Virtual Terminal Grid
+//!
+//! A VT100/xterm screen model driven by the `vte` state machine. This is the
+//! single place where escape sequences are interpreted: the TUI overlay renders
+//! a [`TerminalGrid`] directly, block output resolves its SGR codes through
+//! [`CellStyle::apply_sgr_sequence`], and the conformance suite under
+//! `tests/conformance` compares [`TerminalGrid::dump`] against expected screens.
+
+use crate::terminal::clipboard::ClipboardRequest;
+use crate::terminal::images::{
+    ApcPiece, ApcSplitter, CursorAfter, ImageDecoder, ImageLimits, InlineImage, Placement,
+};
+use crate::terminal::keyboard::{KeyboardModes, KittyKeyboard};
+use crate::terminal::mouse::MouseProtocol;
+use crate::terminal::queries::Query;
+use crate::terminal::title::TitleChange;
+use crate::terminal::width;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashSet, VecDeque};
+use std::sync::{Arc, Weak};
+use vte::{Params, Perform};
+
+/// Width of a default tab stop
+const TAB_WIDTH: usize = 8;
+
+/// Scrollback lines kept by a new grid
+pub const DEFAULT_SCROLLBACK_LIMIT: usize = 10_000;
+
+/// Cell size, in pixels, assumed for images until the grid is drawn
+const DEFAULT_CELL_PIXELS: (f32, f32) = (8.0, 16.0);
+
+/// A 24-bit colour
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Rgb {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Rgb {
+    /// Create a colour from its components
+    pub const fn new(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b }
+    }
+}
+
+/// Cell colour as selected by SGR, resolved against a [`Palette`] when drawn
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Color {
+    /// The terminal's default foreground or background
+    #[default]
+    Default,
+    /// An entry of the 256-colour palette (0-15 are the ANSI colours)
+    Indexed(u8),
+    /// A direct 24-bit colour
+    Rgb(Rgb),
+}
+
+/// Colours used to resolve [`Color`] values into RGB
+#[derive(Clone, Debug, PartialEq)]
+pub struct Palette {
+    /// Default foreground
+    pub foreground: Rgb,
+    /// Default background
+    pub background: Rgb,
+    /// The 16 ANSI colours (normal 0-7, bright 8-15)
+    pub ansi: [Rgb; 16],
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self {
+            foreground: Rgb::new(204, 204, 204),
+            background: Rgb::new(30, 30, 46),
+            ansi: [
+                Rgb::new(0, 0, 0),
+                Rgb::new(205, 0, 0),
+                Rgb::new(0, 205, 0),
+                Rgb::new(205, 205, 0),
+                Rgb::new(0, 0, 238),
+                Rgb::new(205, 0, 205),
+                Rgb::new(0, 205, 205),
+                Rgb::new(229, 229, 229),
+                Rgb::new(127, 127, 127),
+                Rgb::new(255, 0, 0),
+                Rgb::new(0, 255, 0),
+                Rgb::new(255, 255, 0),
+                Rgb::new(92, 92, 255),
+                Rgb::new(255, 0, 255),
+                Rgb::new(0, 255, 255),
+                Rgb::new(255, 255, 255),
+            ],
+        }
+    }
+}
+
+impl Palette {
+    /// Look up an entry of the xterm 256-colour palette
+    pub fn indexed(&self, idx: u8) -> Rgb {
+        match idx {
+            0..=15 => self.ansi[idx as usize],
+            16..=231 => {
+                let idx = idx - 16;
+                let level = |v: u8| if v == 0 { 0 } else { 55 + 40 * v };
+                Rgb::new(level(idx / 36), level((idx / 6) % 6), level(idx % 6))
+            }
+            232..=255 => {
+                let gray = 8 + 10 * (idx - 232);
+                Rgb::new(gray, gray, gray)
+            }
+        }
+    }
+
+    /// Resolve a foreground colour
+    pub fn resolve_fg(&self, color: Color) -> Rgb {
+        match color {
+            Color::Default => self.foreground,
+            Color::Indexed(idx) => self.indexed(idx),
+            Color::Rgb(rgb) => rgb,
+        }
+    }
+
+    /// Resolve a background colour
+    pub fn resolve_bg(&self, color: Color) -> Rgb {
+        match color {
+            Color::Default => self.background,
+            Color::Indexed(idx) => self.indexed(idx),
+            Color::Rgb(rgb) => rgb,
+        }
+    }
+
+    /// Resolve the (foreground, background) pair of a style, honouring reverse video
+    pub fn resolve_style(&self, style: &CellStyle) -> (Rgb, Rgb) {
+        let fg = self.resolve_fg(style.fg);
+        let bg = self.resolve_bg(style.bg);
+        if style.reverse {
+            (bg, fg)
+        } else {
+            (fg, bg)
+        }
+    }
+}
+
+/// Underline style, from SGR 4 and its `4:x` sub-parameter forms
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Underline {
+    #[default]
+    None,
+    Single,
+    Double,
+    Curly,
+    Dotted,
+    Dashed,
+}
+
+/// Graphic rendition of a cell
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct CellStyle {
+    pub fg: Color,
+    pub bg: Color,
+    pub bold: bool,
+    pub dim: bool,
+    pub italic: bool,
+    pub underline: Underline,
+    /// SGR 58; `Color::Default` draws the underline in the text colour
+    pub underline_color: Color,
+    pub blink: bool,
+    pub reverse: bool,
+    pub hidden: bool,
+    pub strikethrough: bool,
+}
+
+impl CellStyle {
+    /// Apply SGR parameters, one slice per parameter with any colon sub-parameters
+    pub fn apply_sgr(&mut self, params: &[&[u16]]) {
+        if params.is_empty() {
+            *self = Self::default();
+            return;
+        }
+
+        let mut i = 0;
+        while i < params.len() {
+            let code = params[i].first().copied().unwrap_or(0);
+            match code {
+                0 => *self = Self::default(),
+                1 => self.bold = true,
+                2 => self.dim = true,
+                3 => self.italic = true,
+                4 => {
+                    self.underline = match params[i].get(1) {
+                        None | Some(1) => Underline::Single,
+                        Some(2) => Underline::Double,
+                        Some(3) => Underline::Curly,
+                        Some(4) => Underline::Dotted,
+                        Some(5) => Underline::Dashed,
+                        Some(_) => Underline::None,
+                    }
+                }
+                5 | 6 => self.blink = true,
+                7 => self.reverse = true,
+                8 => self.hidden = true,
+                9 => self.strikethrough = true,
+                21 => self.underline = Underline::Double,
+                22 => {
+                    self.bold = false;
+                    self.dim = false;
+                }
+                23 => self.italic = false,
+                24 => self.underline = Underline::None,
+                25 => self.blink = false,
+                27 => self.reverse = false,
+                28 => self.hidden = false,
+                29 => self.strikethrough = false,
+                30..=37 => self.fg = Color::Indexed((code - 30) as u8),
+                38 => {
+                    if let Some(color) = extended_color(params, &mut i) {
+                        self.fg = color;
+                    }
+                }
+                39 => self.fg = Color::Default,
+                40..=47 => self.bg = Color::Indexed((code - 40) as u8),
+                48 => {
+                    if let Some(color) = extended_color(params, &mut i) {
+                        self.bg = color;
+                    }
+                }
+                49 => self.bg = Color::Default,
+                90..=97 => self.fg = Color::Indexed((code - 90 + 8) as u8),
+                58 => {
+                    if let Some(color) = extended_color(params, &mut i) {
+                        self.underline_color = color;
+                    }
+                }
+                59 => self.underline_color = Color::Default,
+                100..=107 => self.bg = Color::Indexed((code - 100 + 8) as u8),
+                _ => {}
+            }
+            i += 1;
+        }
+    }
+
+    /// Apply a complete SGR escape sequence such as `"\x1b[1;38;5;196m"`.
+    ///
+    /// Returns `false` (leaving the style untouched) if `seq` is not an SGR sequence.
+    pub fn apply_sgr_sequence(&mut self, seq: &str) -> bool {
+        let Some(body) = seq.strip_prefix("\x1b[").and_then(|s| s.strip_suffix('m')) else {
+            return false;
+        };
+        if !body
+            .bytes()
+            .all(|b| b.is_ascii_digit() || b == b';' || b == b':')
+        {
+            return false;
+        }
+
+        let groups: Vec<Vec<u16>> = if body.is_empty() {
+            Vec::new()
+        } else {
+            body.split(';')
+                .map(|group| {
+                    group
+                        .split(':')
+                        .map(|n| n.parse::<u16>().unwrap_or(0))
+                        .collect()
+                })
+                .collect()
+        };
+        let slices: Vec<&[u16]> = groups.iter().map(Vec::as_slice).collect();
+        self.apply_sgr(&slices);
+        true
+    }
+
+    /// SGR sequence that selects this style from any other, starting with a
+    /// reset
+    pub fn sgr(&self) -> String {
+        let mut params = vec!["0".to_string()];
+        let flags = [
+            (self.bold, "1"),
+            (self.dim, "2"),
+            (self.italic, "3"),
+            (self.blink, "5"),
+            (self.reverse, "7"),
+            (self.hidden, "8"),
+            (self.strikethrough, "9"),
+        ];
+        params.extend(
+            flags
+                .iter()
+                .filter(|(on, _)| *on)
+                .map(|(_, p)| p.to_string()),
+        );
+        match self.underline {
+            Underline::None => {}
+            Underline::Single => params.push("4".to_string()),
+            Underline::Double => params.push("4:2".to_string()),
+            Underline::Curly => params.push("4:3".to_string()),
+            Underline::Dotted => params.push("4:4".to_string()),
+            Underline::Dashed => params.push("4:5".to_string()),
+        }
+        let color = |color: Color, base: u8, bright: u8, extended: u8| match color {
+            Color::Default => None,
+            Color::Indexed(n @ 0..=7) => Some((base + n).to_string()),
+            Color::Indexed(n @ 8..=15) => Some((bright + n - 8).to_string()),
+            Color::Indexed(n) => Some(format!("{};5;{}", extended, n)),
+            Color::Rgb(rgb) => Some(format!("{};2;{};{};{}", extended, rgb.r, rgb.g, rgb.b)),
+        };
+        params.extend(color(self.fg, 30, 90, 38));
+        params.extend(color(self.bg, 40, 100, 48));
+        // Underline colours have no short forms
+        params.extend(match self.underline_color {
+            Color::Default => None,
+            Color::Indexed(n) => Some(format!("58;5;{}", n)),
+            Color::Rgb(rgb) => Some(format!("58;2;{};{};{}", rgb.r, rgb.g, rgb.b)),
+        });
+        format!("\x1b[{}m", params.join(";"))
+    }
+}
+
+/// Parse an extended colour (`38;5;n`, `38;2;r;g;b` or their colon forms).
+/// Advances `i` past any parameters consumed in the semicolon form.
+fn extended_color(params: &[&[u16]], i: &mut usize) -> Option<Color> {
+    let group = params[*i];
+    if group.len() > 1 {
+        return match group[1] {
+            5 => group.get(2).map(|&n| Color::Indexed(n as u8)),
+            2 => {
+                // `38:2:<colorspace>:r:g:b` or the common `38:2:r:g:b`
+                let rgb = if group.len() >= 6 {
+                    &group[3..6]
+                } else if group.len() == 5 {
+                    &group[2..5]
+                } else {
+                    return None;
+                };
+                Some(Color::Rgb(Rgb::new(
+                    rgb[0] as u8,
+                    rgb[1] as u8,
+                    rgb[2] as u8,
+                )))
+            }
+            _ => None,
+        };
+    }
+
+    let arg = |offset: usize| params.get(*i + offset).and_then(|g| g.first().copied());
+    match arg(1)? {
+        5 => {
+            let idx = arg(2)?;
+            *i += 2;
+            Some(Color::Indexed(idx as u8))
+        }
+        2 => {
+            let (r, g, b) = (arg(2)?, arg(3)?, arg(4)?);
+            *i += 4;
+            Some(Color::Rgb(Rgb::new(r as u8, g as u8, b as u8)))
+        }
+        _ => None,
+    }
+}
+
+/// A single character cell
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cell {
+    pub ch: char,
+    /// Combining marks and joined characters completing `ch`'s grapheme cluster
+    pub extra: Option<Box<str>>,
+    pub style: CellStyle,
+    /// Columns taken: 2 for a wide character, 0 for the spacer cell after one
+    pub width: u8,
+    /// Target of the OSC 8 link the character was printed in
+    pub link: Option<Arc<str>>,
+    /// Part of an inline image drawn over the cell
+    pub image: Option<Box<ImageTile>>,
+}
+
+/// The part of an inline image covering one cell
+#[derive(Clone, Debug)]
+pub struct ImageTile {
+    pub image: Arc<InlineImage>,
+    /// Row and column of this cell within the image
+    pub row: u16,
+    pub col: u16,
+    /// Size of the whole image, in cells; the last row and column may be
+    /// covered in part
+    pub extent: (f32, f32),
+}
+
+impl ImageTile {
+    /// The covered part of the cell, as `[right, bottom]` fractions of it,
+    /// and the texture coordinates of the image drawn there, as
+    /// `[left, top, right, bottom]`
+    pub fn crop(&self) -> ([f32; 2], [f32; 4]) {
+        let (col, row) = (self.col as f32, self.row as f32);
+        let right = (col + 1.0).min(self.extent.0);
+        let bottom = (row + 1.0).min(self.extent.1);
+        (
+            [right - col, bottom - row],
+            [
+                col / self.extent.0,
+                row / self.extent.1,
+                right / self.extent.0,
+                bottom / self.extent.1,
+            ],
+        )
+    }
+}
+
+impl PartialEq for ImageTile {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.image, &other.image)
+            && (self.row, self.col) == (other.row, other.col)
+            && self.extent.0.to_bits() == other.extent.0.to_bits()
+            && self.extent.1.to_bits() == other.extent.1.to_bits()
+    }
+}
+
+impl Eq for ImageTile {}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Self {
+            ch: ' ',
+            extra: None,
+            style: CellStyle::default(),
+            width: 1,
+            link: None,
+            image: None,
+        }
+    }
+}
+
+impl Cell {
+    fn new(ch: char, style: CellStyle, width: u8) -> Self {
+        Self {
+            ch,
+            extra: None,
+            style,
+            width,
+            link: None,
+            image: None,
+        }
+    }
+
+    /// Whether this cell is the right half of a wide character
+    pub fn is_spacer(&self) -> bool {
+        self.width == 0
+    }
+
+    /// Append the cell's grapheme cluster to `out` (nothing for a spacer)
+    pub fn push_text(&self, out: &mut String) {
+        if self.is_spacer() {
+            return;
+        }
+        out.push(self.ch);
+        if let Some(extra) = &self.extra {
+            out.push_str(extra);
+        }
+    }
+
+    fn cluster(&self) -> String {
+        let mut text = String::new();
+        self.push_text(&mut text);
+        text
+    }
+}
+
+/// Character set designated into G0 or G1 by `ESC (` / `ESC )`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum Charset {
+    #[default]
+    Ascii,
+    /// `ESC ( A`: `#` becomes a pound sign
+    Uk,
+    /// `ESC ( 0`: lowercase letters become line-drawing glyphs
+    DecSpecialGraphics,
+}
+
+impl Charset {
+    fn map(self, c: char) -> char {
+        match self {
+            Charset::Ascii => c,
+            Charset::Uk => {
+                if c == '#' {
+                    '£'
+                } else {
+                    c
+                }
+            }
+            Charset::DecSpecialGraphics => match c {
+                '_' => ' ',
+                '`' => '◆',
+                'a' => '▒',
+                'b' => '␉',
+                'c' => '␌',
+                'd' => '␍',
+                'e' => '␊',
+                'f' => '°',
+                'g' => '±',
+                'h' => '␤',
+                'i' => '␋',
+                'j' => '┘',
+                'k' => '┐',
+                'l' => '┌',
+                'm' => '└',
+                'n' => '┼',
+                'o' => '⎺',
+                'p' => '⎻',
+                'q' => '─',
+                'r' => '⎼',
+                's' => '⎽',
+                't' => '├',
+                'u' => '┤',
+                'v' => '┴',
+                'w' => '┬',
+                'x' => '│',
+                'y' => '≤',
+                'z' => '≥',
+                '{' => 'π',
+                '|' => '≠',
+                '}' => '£',
+                '~' => '·',
+                _ => c,
+            },
+        }
+    }
+
+    fn from_final(byte: u8) -> Option<Self> {
+        match byte {
+            b'B' => Some(Charset::Ascii),
+            b'A' => Some(Charset::Uk),
+            b'0' => Some(Charset::DecSpecialGraphics),
+            _ => None,
+        }
+    }
+}
+
+/// Cursor shape, as selected by DECSCUSR (`CSI n SP q`)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CursorShape {
+    #[default]
+    Block,
+    Underline,
+    Bar,
+}
+
+/// How the cursor is drawn
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CursorStyle {
+    pub shape: CursorShape,
+    pub blinking: bool,
+}
+
+/// Cursor state saved by DECSC / `CSI s` / mode 1049
+#[derive(Clone, Copy, Debug)]
+struct SavedCursor {
+    row: usize,
+    col: usize,
+    style: CellStyle,
+    charsets: [Charset; 2],
+    shift_out: bool,
+}
+
+/// Screen state mutated by the parser
+struct Screen {
+    rows: usize,
+    cols: usize,
+    lines: Vec<Vec<Cell>>,
+    /// Primary screen contents stashed while the alternate screen is shown
+    saved_primary: Option<Vec<Vec<Cell>>>,
+    /// Lines scrolled off the top of the primary screen, oldest first
+    scrollback: VecDeque<Vec<Cell>>,
+    scrollback_limit: usize,
+    cursor_row: usize,
+    cursor_col: usize,
+    /// Set after printing into the last column; the next printable wraps first
+    wrap_pending: bool,
+    /// DECTCEM (mode 25): whether the cursor is shown
+    cursor_visible: bool,
+    /// Shape chosen by DECSCUSR; `None` uses the configured default
+    cursor_shape: Option<CursorShape>,
+    /// Blinking chosen by DECSCUSR or mode 12; `None` uses the configured default
+    cursor_blinking: Option<bool>,
+    /// Cursor visibility, shape and blinking of the primary screen, kept
+    /// while the alternate screen is shown
+    saved_cursor_look: (bool, Option<CursorShape>, Option<bool>),
+    style: CellStyle,
+    /// Target of the OSC 8 link being printed, if any
+    link: Option<Arc<str>>,
+    scroll_top: usize,
+    scroll_bottom: usize,
+    saved_cursor: Option<SavedCursor>,
+    auto_wrap: bool,
+    keyboard: KeyboardModes,
+    /// Kitty keyboard flag stacks of the primary and alternate screens
+    kitty_keyboard: [KittyKeyboard; 2],
+    mouse: MouseProtocol,
+    /// Mode 1004: report focus changes as `CSI I` / `CSI O`
+    focus_reporting: bool,
+    /// Mode 2004: wrap pastes in `CSI 200~` / `CSI 201~`
+    bracketed_paste: bool,
+    /// Mode 2026: the application is part-way through an update that should
+    /// be shown all at once
+    synchronized_output: bool,
+    /// Character sets designated into G0 and G1
+    charsets: [Charset; 2],
+    /// Whether SO has shifted G1 in (until SI shifts G0 back)
+    shift_out: bool,
+    /// Colours reported to colour queries
+    palette: Palette,
+    /// Replies to queries, waiting to be written to the application
+    responses: Vec<u8>,
+    /// OSC 52 requests, waiting for the application's clipboard policy
+    clipboard_requests: Vec<ClipboardRequest>,
+    /// OSC 0/1/2 titles, waiting to be applied to the terminal
+    title_changes: Vec<TitleChange>,
+    /// Decoder for kitty, iTerm2 and Sixel images
+    images: ImageDecoder,
+    /// Images laid over cells, which live on while any cell shows them
+    placed: Vec<Weak<InlineImage>>,
+    /// Size of a cell in pixels, for sizing images
+    cell_pixels: (f32, f32),
+}
+
+impl Screen {
+    fn new(rows: usize, cols: usize) -> Self {
+        let rows = rows.max(1);
+        let cols = cols.max(1);
+        Self {
+            rows,
+            cols,
+            lines: vec![vec![Cell::default(); cols]; rows],
+            saved_primary: None,
+            scrollback: VecDeque::new(),
+            scrollback_limit: DEFAULT_SCROLLBACK_LIMIT,
+            cursor_row: 0,
+            cursor_col: 0,
+            wrap_pending: false,
+            cursor_visible: true,
+            cursor_shape: None,
+            cursor_blinking: None,
+            saved_cursor_look: (true, None, None),
+            style: CellStyle::default(),
+            link: None,
+            scroll_top: 0,
+            scroll_bottom: rows - 1,
+            saved_cursor: None,
+            auto_wrap: true,
+            keyboard: KeyboardModes::default(),
+            kitty_keyboard: Default::default(),
+            mouse: MouseProtocol::default(),
+            focus_reporting: false,
+            bracketed_paste: false,
+            synchronized_output: false,
+            charsets: [Charset::Ascii; 2],
+            shift_out: false,
+            palette: Palette::default(),
+            responses: Vec::new(),
+            clipboard_requests: Vec::new(),
+            title_changes: Vec::new(),
+            images: ImageDecoder::default(),
+            placed: Vec::new(),
+            cell_pixels: DEFAULT_CELL_PIXELS,
+        }
+    }
+
+    fn resize(&mut self, rows: usize, cols: usize) {
+        let rows = rows.max(1);
+        let cols = cols.max(1);
+        if rows == self.rows && cols == self.cols {
+            return;
+        }
+        resize_lines(&mut self.lines, rows, cols);
+        if let Some(primary) = &mut self.saved_primary {
+            resize_lines(primary, rows, cols);
+        }
+        if cols != self.cols {
+            for line in &mut self.scrollback {
+                line.resize(cols, Cell::default());
+            }
+        }
+        self.rows = rows;
+        self.cols = cols;
+        self.scroll_top = 0;
+        self.scroll_bottom = rows - 1;
+        self.cursor_row = self.cursor_row.min(rows - 1);
+        self.cursor_col = self.cursor_col.min(cols - 1);
+        self.wrap_pending = false;
+    }
+
+    /// Blank cell carrying the current background (xterm's background colour erase)
+    fn blank(&self) -> Cell {
+        Cell::new(
+            ' ',
+            CellStyle {
+                bg: self.style.bg,
+                ..CellStyle::default()
+            },
+            1,
+        )
+    }
+
+    fn put_char(&mut self, ch: char) {
+        let char_width = width::char_width(ch);
+        if self.join_previous(ch) {
+            return;
+        }
+        if char_width == 0 {
+            // A combining mark with nothing to combine with
+            return;
+        }
+
+        if self.wrap_pending {
+            self.wrap_pending = false;
+            if self.auto_wrap {
+                self.cursor_col = 0;
+                self.linefeed();
+            }
+        }
+        let wide = char_width == 2 && self.cols > 1;
+        if wide && self.cursor_col + 1 >= self.cols {
+            // A wide character never straddles the margin
+            if self.auto_wrap {
+                self.clear_wide_at(self.cursor_row, self.cursor_col);
+                self.lines[self.cursor_row][self.cursor_col] = self.blank();
+                self.cursor_col = 0;
+                self.linefeed();
+            } else {
+                self.cursor_col = self.cols - 2;
+            }
+        }
+
+        let (row, col) = (self.cursor_row, self.cursor_col);
+        self.clear_wide_at(row, col);
+        if wide {
+            self.clear_wide_at(row, col + 1);
+            self.lines[row][col] = Cell::new(ch, self.style, 2);
+            self.lines[row][col + 1] = Cell::new(' ', self.style, 0);
+            self.lines[row][col + 1].link = self.link.clone();
+        } else {
+            self.lines[row][col] = Cell::new(ch, self.style, 1);
+        }
+        self.lines[row][col].link = self.link.clone();
+
+        let advance = if wide { 2 } else { 1 };
+        if col + advance < self.cols {
+            self.cursor_col = col + advance;
+        } else {
+            self.cursor_col = self.cols - 1;
+            if self.auto_wrap {
+                self.wrap_pending = true;
+            }
+        }
+    }
+
+    /// Lay an image over the cells from the cursor on, scrolling as needed,
+    /// and move the cursor as the image's protocol does
+    fn place_image(&mut self, placement: Placement) {
+        let (cell_width, cell_height) = self.cell_pixels;
+        let available = (self.cols - self.cursor_col) as f32 * cell_width;
+        let (width, height) = placement.display_size(self.cell_pixels, available);
+        let extent = (width / cell_width, height / cell_height);
+        let cols = (extent.0.ceil() as usize).clamp(1, self.cols - self.cursor_col);
+        let rows = (extent.1.ceil() as usize).max(1);
+
+        self.placed.retain(|image| image.strong_count() > 0);
+        self.placed.push(Arc::downgrade(&placement.image));
+
+        let start_col = self.cursor_col;
+        self.wrap_pending = false;
+        for row in 0..rows {
+            if row > 0 {
+                self.linefeed();
+            }
+            for col in 0..cols {
+                let (line, at) = (self.cursor_row, start_col + col);
+                self.clear_wide_at(line, at);
+                let mut cell = self.blank();
+                cell.image = Some(Box::new(ImageTile {
+                    image: placement.image.clone(),
+                    row: row as u16,
+                    col: col as u16,
+                    extent,
+                }));
+                self.lines[line][at] = cell;
+            }
+        }
+
+        match placement.cursor {
+            CursorAfter::Stay => {
+                self.cursor_row = self.cursor_row.saturating_sub(rows - 1);
+                self.cursor_col = start_col;
+            }
+            CursorAfter::AfterImage => {
+                if start_col + cols < self.cols {
+                    self.cursor_col = start_col + cols;
+                } else {
+                    self.cursor_col = self.cols - 1;
+                    self.wrap_pending = self.auto_wrap;
+                }
+            }
+            CursorAfter::BelowImage => {
+                self.linefeed();
+                self.cursor_col = start_col;
+            }
+        }
+    }
+
+    /// Handle the body of an APC string: kitty graphics
+    fn apc(&mut self, body: &[u8]) {
+        if let Some(placement) = self.images.kitty(body) {
+            self.place_image(placement);
+        }
+        let replies = self.images.take_responses();
+        self.responses.extend_from_slice(&replies);
+    }
+
+    /// Cell holding the last printed grapheme cluster, if the cursor is right after it
+    fn previous_cell(&self) -> Option<(usize, usize)> {
+        let row = self.cursor_row;
+        let mut col = if self.wrap_pending {
+            self.cursor_col
+        } else {
+            self.cursor_col.checked_sub(1)?
+        };
+        if self.lines[row][col].is_spacer() {
+            col = col.checked_sub(1)?;
+        }
+        Some((row, col))
+    }
+
+    /// Add `ch` to the previous cluster when it continues it (combining marks,
+    /// emoji modifiers and ZWJ sequences), widening the cell if needed
+    fn join_previous(&mut self, ch: char) -> bool {
+        let Some((row, col)) = self.previous_cell() else {
+            return false;
+        };
+        let cluster = self.lines[row][col].cluster();
+        if !width::extends_cluster(&cluster, ch) {
+            return false;
+        }
+
+        let cell = &mut self.lines[row][col];
+        let mut extra = cell.extra.take().map(String::from).unwrap_or_default();
+        extra.push(ch);
+        cell.extra = Some(extra.into_boxed_str());
+
+        // e.g. a text-style symbol followed by VS16 becomes a two-column emoji
+        let grown = cell.width == 1
+            && width::cluster_width(&cell.cluster()) == 2
+            && !self.wrap_pending
+            && col + 1 < self.cols;
+        if grown {
+            let style = cell.style;
+            cell.width = 2;
+            self.clear_wide_at(row, col + 1);
+            self.lines[row][col + 1] = Cell::new(' ', style, 0);
+            if col + 2 < self.cols {
+                self.cursor_col = col + 2;
+            } else {
+                self.cursor_col = self.cols - 1;
+                self.wrap_pending = self.auto_wrap;
+            }
+        }
+        true
+    }
+
+    /// Blank the other half of a wide character overlapping (row, col), so
+    /// overwriting either half never leaves half a glyph behind
+    fn clear_wide_at(&mut self, row: usize, col: usize) {
+        let blank = self.blank();
+        let line = &mut self.lines[row];
+        if line[col].is_spacer() && col > 0 {
+            line[col - 1] = blank;
+        } else if line[col].width == 2 && col + 1 < line.len() {
+            line[col + 1] = blank;
+        }
+    }
+
+    fn goto(&mut self, row: usize, col: usize) {
+        self.cursor_row = row.min(self.rows - 1);
+        self.cursor_col = col.min(self.cols - 1);
+        self.wrap_pending = false;
+    }
+
+    fn linefeed(&mut self) {
+        if self.cursor_row == self.scroll_bottom {
+            self.scroll_up(1);
+        } else if self.cursor_row + 1 < self.rows {
+            self.cursor_row += 1;
+        }
+        self.wrap_pending = false;
+    }
+
+    fn reverse_index(&mut self) {
+        if self.cursor_row == self.scroll_top {
+            self.scroll_down(1);
+        } else {
+            self.cursor_row = self.cursor_row.saturating_sub(1);
+        }
+        self.wrap_pending = false;
+    }
+
+    fn carriage_return(&mut self) {
+        self.cursor_col = 0;
+        self.wrap_pending = false;
+    }
+
+    fn backspace(&mut self) {
+        self.cursor_col = self.cursor_col.saturating_sub(1);
+        self.wrap_pending = false;
+    }
+
+    fn tab(&mut self) {
+        let next = (self.cursor_col / TAB_WIDTH + 1) * TAB_WIDTH;
+        self.cursor_col = next.min(self.cols - 1);
+        self.wrap_pending = false;
+    }
+
+    fn scroll_up(&mut self, n: usize) {
+        let (top, bottom) = (self.scroll_top, self.scroll_bottom);
+        let n = n.min(bottom + 1 - top);
+        let blank = self.blank();
+        // Only full-width scrolls of the primary screen feed the scrollback,
+        // like xterm; the alternate screen keeps none
+        if top == 0 && self.saved_primary.is_none() {
+            self.push_scrollback(n);
+        }
+        self.lines[top..=bottom].rotate_left(n);
+        for line in &mut self.lines[bottom + 1 - n..=bottom] {
+            line.fill(blank.clone());
+        }
+    }
+
+    fn push_scrollback(&mut self, n: usize) {
+        if self.scrollback_limit == 0 {
+            return;
+        }
+        for line in &self.lines[..n] {
+            if self.scrollback.len() == self.scrollback_limit {
+                self.scrollback.pop_front();
+            }
+            self.scrollback.push_back(line.clone());
+        }
+    }
+
+    fn scroll_down(&mut self, n: usize) {
+        let (top, bottom) = (self.scroll_top, self.scroll_bottom);
+        let n = n.min(bottom + 1 - top);
+        let blank = self.blank();
+        self.lines[top..=bottom].rotate_right(n);
+        for line in &mut self.lines[top..top + n] {
+            line.fill(blank.clone());
+        }
+    }
+
+    fn insert_lines(&mut self, n: usize) {
+        if self.cursor_row < self.scroll_top || self.cursor_row > self.scroll_bottom {
+            return;
+        }
+        let (row, bottom) = (self.cursor_row, self.scroll_bottom);
+        let n = n.min(bottom + 1 - row);
+        let blank = self.blank();
+        self.lines[row..=bottom].rotate_right(n);
+        for line in &mut self.lines[row..row + n] {
+            line.fill(blank.clone());
+        }
+        self.carriage_return();
+    }
+
+    fn delete_lines(&mut self, n: usize) {
+        if self.cursor_row < self.scroll_top || self.cursor_row > self.scroll_bottom {
+            return;
+        }
+        let (row, bottom) = (self.cursor_row, self.scroll_bottom);
+        let n = n.min(bottom + 1 - row);
+        let blank = self.blank();
+        self.lines[row..=bottom].rotate_left(n);
+        for line in &mut self.lines[bottom + 1 - n..=bottom] {
+            line.fill(blank.clone());
+        }
+        self.carriage_return();
+    }
+
+    fn insert_chars(&mut self, n: usize) {
+        let col = self.cursor_col;
+        let n = n.min(self.cols - col);
+        let blank = self.blank();
+        let line = &mut self.lines[self.cursor_row];
+        line[col..].rotate_right(n);
+        line[col..col + n].fill(blank);
+        self.wrap_pending = false;
+    }
+
+    fn delete_chars(&mut self, n: usize) {
+        let col = self.cursor_col;
+        let n = n.min(self.cols - col);
+        let blank = self.blank();
+        let cols = self.cols;
+        let line = &mut self.lines[self.cursor_row];
+        line[col..].rotate_left(n);
+        line[cols - n..].fill(blank);
+        self.wrap_pending = false;
+    }
+
+    fn erase_chars(&mut self, n: usize) {
+        let col = self.cursor_col;
+        let end = (col + n).min(self.cols);
+        let blank = self.blank();
+        self.lines[self.cursor_row][col..end].fill(blank);
+        self.wrap_pending = false;
+    }
+
+    fn erase_in_display(&mut self, mode: u16) {
+        let blank = self.blank();
+        match mode {
+            0 => {
+                self.lines[self.cursor_row][self.cursor_col..].fill(blank.clone());
+                for line in &mut self.lines[self.cursor_row + 1..] {
+                    line.fill(blank.clone());
+                }
+            }
+            1 => {
+                for line in &mut self.lines[..self.cursor_row] {
+                    line.fill(blank.clone());
+                }
+                self.lines[self.cursor_row][..=self.cursor_col].fill(blank);
+            }
+            2 => {
+                for line in &mut self.lines {
+                    line.fill(blank.clone());
+                }
+            }
+            // xterm's "erase saved lines"
+            3 => self.scrollback.clear(),
+            _ => {}
+        }
+        self.wrap_pending = false;
+    }
+
+    fn erase_in_line(&mut self, mode: u16) {
+        let blank = self.blank();
+        let col = self.cursor_col;
+        let line = &mut self.lines[self.cursor_row];
+        match mode {
+            0 => line[col..].fill(blank),
+            1 => line[..=col].fill(blank),
+            2 => line.fill(blank),
+            _ => {}
+        }
+        self.wrap_pending = false;
+    }
+
+    fn save_cursor(&mut self) {
+        self.saved_cursor = Some(SavedCursor {
+            row: self.cursor_row,
+            col: self.cursor_col,
+            style: self.style,
+            charsets: self.charsets,
+            shift_out: self.shift_out,
+        });
+    }
+
+    fn restore_cursor(&mut self) {
+        if let Some(saved) = self.saved_cursor {
+            self.style = saved.style;
+            self.charsets = saved.charsets;
+            self.shift_out = saved.shift_out;
+            self.goto(saved.row, saved.col);
+        }
+    }
+
+    fn set_scroll_region(&mut self, top: usize, bottom: usize) {
+        let bottom = bottom.min(self.rows);
+        if top < bottom {
+            self.scroll_top = top - 1;
+            self.scroll_bottom = bottom - 1;
+            self.goto(0, 0);
+        }
+    }
+
+    fn enter_alternate_screen(&mut self, save_cursor: bool) {
+        if self.saved_primary.is_some() {
+            return;
+        }
+        if save_cursor {
+            self.save_cursor();
+        }
+        self.saved_cursor_look = (self.cursor_visible, self.cursor_shape, self.cursor_blinking);
+        let blank = vec![vec![Cell::default(); self.cols]; self.rows];
+        self.saved_primary = Some(std::mem::replace(&mut self.lines, blank));
+    }
+
+    fn exit_alternate_screen(&mut self, restore_cursor: bool) {
+        if let Some(primary) = self.saved_primary.take() {
+            self.lines = primary;
+            (self.cursor_visible, self.cursor_shape, self.cursor_blinking) = self.saved_cursor_look;
+            if restore_cursor {
+                self.restore_cursor();
+            }
+        }
+    }
+
+    /// DECSCUSR: 0 restores the default, odd values blink, 1-2 block,
+    /// 3-4 underline, 5-6 bar
+    fn set_cursor_style(&mut self, ps: u16) {
+        let shape = match ps {
+            0 => None,
+            1 | 2 => Some(CursorShape::Block),
+            3 | 4 => Some(CursorShape::Underline),
+            5 | 6 => Some(CursorShape::Bar),
+            _ => return,
+        };
+        self.cursor_shape = shape;
+        self.cursor_blinking = shape.map(|_| !ps.is_multiple_of(2));
+    }
+
+    /// Kitty keyboard flag stack of the screen being shown
+    fn kitty_keyboard(&mut self) -> &mut KittyKeyboard {
+        &mut self.kitty_keyboard[usize::from(self.saved_primary.is_some())]
+    }
+
+    fn set_private_mode(&mut self, mode: u16, enable: bool) {
+        if self.mouse.set_mode(mode, enable) {
+            return;
+        }
+        match mode {
+            1 => self.keyboard.application_cursor = enable,
+            7 => {
+                self.auto_wrap = enable;
+                if !enable {
+                    self.wrap_pending = false;
+                }
+            }
+            47 | 1047 | 1049 => {
+                let with_cursor = mode == 1049;
+                if enable {
+                    self.enter_alternate_screen(with_cursor);
+                } else {
+                    self.exit_alternate_screen(with_cursor);
+                }
+            }
+            12 => self.cursor_blinking = Some(enable),
+            25 => self.cursor_visible = enable,
+            1004 => self.focus_reporting = enable,
+            2004 => self.bracketed_paste = enable,
+            2026 => self.synchronized_output = enable,
+            _ => {}
+        }
+    }
+
+    /// Current state of a private mode, or `None` if it is not supported
+    fn private_mode(&self, mode: u16) -> Option<bool> {
+        if let Some(state) = self.mouse.mode(mode) {
+            return Some(state);
+        }
+        match mode {
+            1 => Some(self.keyboard.application_cursor),
+            7 => Some(self.auto_wrap),
+            12 => Some(self.cursor_blinking == Some(true)),
+            25 => Some(self.cursor_visible),
+            47 | 1047 | 1049 => Some(self.saved_primary.is_some()),
+            1004 => Some(self.focus_reporting),
+            2004 => Some(self.bracketed_paste),
+            2026 => Some(self.synchronized_output),
+            _ => None,
+        }
+    }
+
+    /// DECRQM (`CSI ? Ps $ p`): report a private mode as set (1), reset (2)
+    /// or not recognised (0)
+    fn report_private_mode(&mut self, mode: u16) {
+        let state = match self.private_mode(mode) {
+            Some(true) => 1,
+            Some(false) => 2,
+            None => 0,
+        };
+        self.responses
+            .extend_from_slice(format!("\x1b[?{};{}$y", mode, state).as_bytes());
+    }
+
+    fn answer(&mut self, query: Query) {
+        let reply = query.reply(&self.palette, (self.cursor_row, self.cursor_col));
+        self.responses.extend(reply);
+    }
+
+    /// DECALN: fill the screen with `E` for alignment tests
+    fn screen_alignment(&mut self) {
+        let fill = Cell::new('E', CellStyle::default(), 1);
+        for line in &mut self.lines {
+            line.fill(fill.clone());
+        }
+        self.scroll_top = 0;
+        self.scroll_bottom = self.rows - 1;
+        self.goto(0, 0);
+    }
+}
+
+fn resize_lines(lines: &mut Vec<Vec<Cell>>, rows: usize, cols: usize) {
+    lines.resize(rows, vec![Cell::default(); cols]);
+    for line in lines.iter_mut() {
+        line.resize(cols, Cell::default());
+    }
+}
+
+/// First value of parameter `idx`, treating zero or absence as `default`
+fn arg(params: &Params, idx: usize, default: usize) -> usize {
+    params
+        .iter()
+        .nth(idx)
+        .and_then(|group| group.first().copied())
+        .filter(|&v| v != 0)
+        .map(usize::from)
+        .unwrap_or(default)
+}
+
+/// First value of parameter `idx` as given, for selectors where zero is meaningful
+fn raw_arg(params: &Params, idx: usize) -> u16 {
+    params
+        .iter()
+        .nth(idx)
+        .and_then(|group| group.first().copied())
+        .unwrap_or(0)
+}
+
+impl Perform for Screen {
+    fn print(&mut self, c: char) {
+        let c = self.charsets[usize::from(self.shift_out)].map(c);
+        self.put_char(c);
+    }
+
+    fn execute(&mut self, byte: u8) {
+        match byte {
+            0x08 => self.backspace(),
+            0x09 => self.tab(),
+            0x0a..=0x0c => self.linefeed(),
+            0x0d => self.carriage_return(),
+            0x0e => self.shift_out = true,
+            0x0f => self.shift_out = false,
+            _ => {}
+        }
+    }
+
+    fn csi_dispatch(&mut self, params: &Params, intermediates: &[u8], ignore: bool, action: char) {
+        if ignore {
+            return;
+        }
+        let images_enabled = self.images.limits().enabled;
+        if let Some(query) = Query::from_csi(params, intermediates, action, images_enabled) {
+            self.answer(query);
+            return;
+        }
+        let private = intermediates.first() == Some(&b'?');
+        let plain = intermediates.is_empty();
+
+        match action {
+            'H' | 'f' if plain => {
+                self.goto(arg(params, 0, 1) - 1, arg(params, 1, 1) - 1);
+            }
+            'A' if plain => {
+                let n = arg(params, 0, 1);
+                let floor = if self.cursor_row >= self.scroll_top {
+                    self.scroll_top
+                } else {
+                    0
+                };
+                let row = self.cursor_row.saturating_sub(n).max(floor);
+                self.goto(row, self.cursor_col);
+            }
+            'B' | 'e' if plain => {
+                let n = arg(params, 0, 1);
+                let ceiling = if self.cursor_row <= self.scroll_bottom {
+                    self.scroll_bottom
+                } else {
+                    self.rows - 1
+                };
+                let row = (self.cursor_row + n).min(ceiling);
+                self.goto(row, self.cursor_col);
+            }
+            'C' | 'a' if plain => {
+                let n = arg(params, 0, 1);
+                self.goto(self.cursor_row, self.cursor_col + n);
+            }
+            'D' if plain => {
+                let n = arg(params, 0, 1);
+                self.goto(self.cursor_row, self.cursor_col.saturating_sub(n));
+            }
+            'E' if plain => {
+                let n = arg(params, 0, 1);
+                self.goto(self.cursor_row + n, 0);
+            }
+            'F' if plain => {
+                let n = arg(params, 0, 1);
+                self.goto(self.cursor_row.saturating_sub(n), 0);
+            }
+            'G' | '`' if plain => {
+                self.goto(self.cursor_row, arg(params, 0, 1) - 1);
+            }
+            'd' if plain => {
+                self.goto(arg(params, 0, 1) - 1, self.cursor_col);
+            }
+            'J' if plain || private => self.erase_in_display(raw_arg(params, 0)),
+            'K' if plain || private => self.erase_in_line(raw_arg(params, 0)),
+            'L' if plain => self.insert_lines(arg(params, 0, 1)),
+            'M' if plain => self.delete_lines(arg(params, 0, 1)),
+            '@' if plain => self.insert_chars(arg(params, 0, 1)),
+            'P' if plain => self.delete_chars(arg(params, 0, 1)),
+            'X' if plain => self.erase_chars(arg(params, 0, 1)),
+            'S' if plain => self.scroll_up(arg(params, 0, 1)),
+            'T' if plain => self.scroll_down(arg(params, 0, 1)),
+            'm' if plain => {
+                let groups: Vec<&[u16]> = params.iter().collect();
+                self.style.apply_sgr(&groups);
+            }
+            'r' if plain => {
+                let top = arg(params, 0, 1);
+                let bottom = arg(params, 1, self.rows);
+                self.set_scroll_region(top, bottom);
+            }
+            'm' if intermediates == [b'>'] => {
+                let value = params
+                    .iter()
+                    .nth(1)
+                    .and_then(|group| group.first().copied());
+                self.keyboard
+                    .set_key_modifier_option(raw_arg(params, 0), value);
+            }
+            'q' if intermediates == [b' '] => self.set_cursor_style(raw_arg(params, 0)),
+            's' if plain => self.save_cursor(),
+            'u' if plain => self.restore_cursor(),
+            'u' if intermediates == [b'>'] => {
+                self.kitty_keyboard().push(raw_arg(params, 0) as u8);
+            }
+            'u' if intermediates == [b'<'] => self.kitty_keyboard().pop(arg(params, 0, 1)),
+            'u' if intermediates == [b'='] => {
+                let mode = raw_arg(params, 1);
+                self.kitty_keyboard().set(raw_arg(params, 0) as u8, mode);
+            }
+            'u' if private => {
+                let flags = self.kitty_keyboard().flags();
+                self.responses
+                    .extend_from_slice(format!("\x1b[?{}u", flags).as_bytes());
+            }
+            'p' if intermediates == [b'?', b'$'] => {
+                self.report_private_mode(raw_arg(params, 0));
+            }
+            // No ANSI modes are supported
+            'p' if intermediates == [b'$'] => {
+                let reply = format!("\x1b[{};0$y", raw_arg(params, 0));
+                self.responses.extend_from_slice(reply.as_bytes());
+            }
+            'h' | 'l' if private => {
+                let enable = action == 'h';
+                for group in params.iter() {
+                    if let Some(&mode) = group.first() {
+                        self.set_private_mode(mode, enable);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn esc_dispatch(&mut self, intermediates: &[u8], _ignore: bool, byte: u8) {
+        match (intermediates, byte) {
+            ([], b'7') => self.save_cursor(),
+            ([], b'8') => self.restore_cursor(),
+            ([], b'D') => self.linefeed(),
+            ([], b'E') => {
+                self.carriage_return();
+                self.linefeed();
+            }
+            ([], b'M') => self.reverse_index(),
+            ([], b'=') => self.keyboard.application_keypad = true,
+            ([], b'>') => self.keyboard.application_keypad = false,
+            ([], b'c') => {
+                // RIS resets the screen but, as in xterm, keeps the scrollback
+                let mut screen = Screen::new(self.rows, self.cols);
+                screen.scrollback = std::mem::take(&mut self.scrollback);
+                screen.scrollback_limit = self.scrollback_limit;
+                screen.palette = std::mem::take(&mut self.palette);
+                screen.responses = std::mem::take(&mut self.responses);
+                screen.images = ImageDecoder::new(*self.images.limits());
+                screen.cell_pixels = self.cell_pixels;
+                *self = screen;
+            }
+            ([b'#'], b'8') => self.screen_alignment(),
+            ([b'('], byte) | ([b')'], byte) => {
+                if let Some(charset) = Charset::from_final(byte) {
+                    let slot = usize::from(intermediates == [b')']);
+                    self.charsets[slot] = charset;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn hook(&mut self, _params: &Params, intermediates: &[u8], _ignore: bool, action: char) {
+        if action == 'q' && intermediates.is_empty() {
+            self.images.sixel_start();
+        }
+    }
+
+    fn put(&mut self, byte: u8) {
+        self.images.sixel_put(byte);
+    }
+
+    fn unhook(&mut self) {
+        if let Some(placement) = self.images.sixel_finish() {
+            self.place_image(placement);
+        }
+    }
+
+    fn osc_dispatch(&mut self, params: &[&[u8]], bell_terminated: bool) {
+        // OSC 1337 ; File=args : data — the args are separated by ';'
+        if params.first() == Some(&&b"1337"[..]) {
+            if let Some(placement) = self.images.iterm2(&params[1..].join(&b';')) {
+                self.place_image(placement);
+            }
+            return;
+        }
+        // OSC 8 ; params ; URI — the URI may itself contain ';'
+        if params.first() == Some(&&b"8"[..]) && params.len() >= 3 {
+            let uri = params[2..].join(&b';');
+            self.link = (!uri.is_empty()).then(|| Arc::from(String::from_utf8_lossy(&uri)));
+            return;
+        }
+        if let Some(request) = ClipboardRequest::from_osc(params, bell_terminated) {
+            self.clipboard_requests.push(request);
+            return;
+        }
+        if params
+            .first()
+            .is_some_and(|p| matches!(*p, b"0" | b"1" | b"2"))
+        {
+            let body = params.join(&b';');
+            if let Some(change) = TitleChange::from_osc(&String::from_utf8_lossy(&body)) {
+                self.title_changes.push(change);
+            }
+            return;
+        }
+        for query in Query::from_osc(params, bell_terminated) {
+            self.answer(query);
+        }
+    }
+}
+
+/// Terminal emulator core: a `vte` parser feeding a styled character grid
+pub struct TerminalGrid {
+    parser: vte::Parser,
+    /// Takes kitty graphics out of the stream, as vte drops APC strings
+    apc: ApcSplitter,
+    screen: Screen,
+    /// Cursor style used until the application picks one
+    default_cursor: CursorStyle,
+}
+
+impl TerminalGrid {
+    /// Create a blank grid of the given size
+    pub fn new(rows: usize, cols: usize) -> Self {
+        Self {
+            parser: vte::Parser::new(),
+            apc: ApcSplitter::new(ImageLimits::default().max_payload_bytes()),
+            screen: Screen::new(rows, cols),
+            default_cursor: CursorStyle::default(),
+        }
+    }
+
+    /// Feed raw PTY output through the parser
+    pub fn advance(&mut self, bytes: &[u8]) {
+        for piece in self.apc.split(bytes) {
+            match piece {
+                ApcPiece::Bytes(bytes) => self.parser.advance(&mut self.screen, bytes),
+                ApcPiece::Apc(body) => self.screen.apc(&body),
+            }
+        }
+    }
+
+    /// Resize the grid, keeping the top-left content
+    pub fn resize(&mut self, rows: usize, cols: usize) {
+        self.screen.resize(rows, cols);
+    }
+
+    /// Reset to a blank screen with default modes and no scrollback, keeping
+    /// the size, scrollback limit, palette, image limits and default cursor
+    /// style
+    pub fn reset(&mut self) {
+        let limit = self.screen.scrollback_limit;
+        let palette = std::mem::take(&mut self.screen.palette);
+        let image_limits = *self.screen.images.limits();
+        let cell_pixels = self.screen.cell_pixels;
+        self.parser = vte::Parser::new();
+        self.apc = ApcSplitter::new(image_limits.max_payload_bytes());
+        self.screen = Screen::new(self.screen.rows, self.screen.cols);
+        self.screen.scrollback_limit = limit;
+        self.screen.palette = palette;
+        self.screen.images.set_limits(image_limits);
+        self.screen.cell_pixels = cell_pixels;
+    }
+
+    /// Set how large images may be, or turn them off
+    pub fn set_image_limits(&mut self, limits: ImageLimits) {
+        self.apc.set_max_len(limits.max_payload_bytes());
+        self.screen.images.set_limits(limits);
+    }
+
+    /// Set the size of a cell in pixels, which images are sized by
+    pub fn set_cell_pixels(&mut self, width: f32, height: f32) {
+        if width > 0.0 && height > 0.0 {
+            self.screen.cell_pixels = (width, height);
+        }
+    }
+
+    /// Colours used to answer colour queries
+    pub fn palette(&self) -> &Palette {
+        &self.screen.palette
+    }
+
+    /// Set the colours reported to colour queries
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.screen.palette = palette;
+    }
+
+    /// Take the replies to queries seen since the last call, to be written
+    /// back to the application
+    pub fn take_responses(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.screen.responses)
+    }
+
+    /// Take the clipboard requests (OSC 52) seen since the last call
+    pub fn take_clipboard_requests(&mut self) -> Vec<ClipboardRequest> {
+        std::mem::take(&mut self.screen.clipboard_requests)
+    }
+
+    /// Take the titles (OSC 0/1/2) set since the last call
+    pub fn take_title_changes(&mut self) -> Vec<TitleChange> {
+        std::mem::take(&mut self.screen.title_changes)
+    }
+
+    /// Keep at most `limit` lines of primary-screen scrollback (0 disables it)
+    pub fn set_scrollback_limit(&mut self, limit: usize) {
+        self.screen.scrollback_limit = limit;
+        let excess = self.screen.scrollback.len().saturating_sub(limit);
+        self.screen.scrollback.drain(..excess);
+    }
+
+    /// Number of lines held in the scrollback
+    pub fn scrollback_len(&self) -> usize {
+        self.screen.scrollback.len()
+    }
+
+    /// Memory taken by the images on the screens and in the scrollback, and
+    /// by those kitty clients stored for later placement
+    pub fn image_bytes(&self) -> usize {
+        let mut seen = HashSet::new();
+        self.screen
+            .placed
+            .iter()
+            .filter_map(Weak::upgrade)
+            .chain(self.screen.images.stored().cloned())
+            .filter(|image| seen.insert(image.id()))
+            .map(|image| image.byte_len())
+            .sum()
+    }
+
+    /// Forget images kitty clients stored for later placement, oldest first,
+    /// until `bytes` of them are gone; returns the memory they took
+    pub fn drop_stored_images(&mut self, bytes: usize) -> usize {
+        self.screen.images.drop_stored(bytes)
+    }
+
+    /// Blank the images in the scrollback, oldest first, until `bytes` of
+    /// memory are freed; returns the memory freed
+    pub fn drop_scrollback_images(&mut self, bytes: usize) -> usize {
+        let start = self.image_bytes();
+        let mut freed = 0;
+        for index in 0..self.screen.scrollback.len() {
+            if freed >= bytes {
+                break;
+            }
+            let line = &mut self.screen.scrollback[index];
+            let mut had_images = false;
+            for cell in line.iter_mut().filter(|cell| cell.image.is_some()) {
+                *cell = Cell::default();
+                had_images = true;
+            }
+            if had_images {
+                freed = start - self.image_bytes();
+            }
+        }
+        freed
+    }
+
+    /// Take the lines scrolled off the top since the last call, oldest first
+    pub fn take_scrollback(&mut self) -> Vec<Vec<Cell>> {
+        self.screen.scrollback.drain(..).collect()
+    }
+
+    /// The screen as seen `offset` lines up into the scrollback, top to bottom.
+    ///
+    /// Offset 0 is the live screen; larger offsets are clamped to the oldest
+    /// scrollback line.
+    pub fn view(&self, offset: usize) -> Vec<&[Cell]> {
+        let history = &self.screen.scrollback;
+        let offset = offset.min(history.len());
+        let start = history.len() - offset;
+        history
+            .range(start..)
+            .map(Vec::as_slice)
+            .chain(self.screen.lines.iter().map(Vec::as_slice))
+            .take(self.screen.rows)
+            .collect()
+    }
+
+    /// Target of the OSC 8 link at (`row`, `col`) of the view `offset`
+    /// lines up into the scrollback
+    pub fn link_at(&self, offset: usize, row: usize, col: usize) -> Option<Arc<str>> {
+        self.view(offset).get(row)?.get(col)?.link.clone()
+    }
+
+    /// Number of rows
+    pub fn rows(&self) -> usize {
+        self.screen.rows
+    }
+
+    /// Number of columns
+    pub fn cols(&self) -> usize {
+        self.screen.cols
+    }
+
+    /// Cursor position as (row, col), zero-based
+    pub fn cursor_position(&self) -> (usize, usize) {
+        (self.screen.cursor_row, self.screen.cursor_col)
+    }
+
+    /// Whether the application shows the cursor (DECTCEM)
+    pub fn cursor_visible(&self) -> bool {
+        self.screen.cursor_visible
+    }
+
+    /// Cursor style requested by the application, or the default
+    pub fn cursor_style(&self) -> CursorStyle {
+        CursorStyle {
+            shape: self
+                .screen
+                .cursor_shape
+                .unwrap_or(self.default_cursor.shape),
+            blinking: self
+                .screen
+                .cursor_blinking
+                .unwrap_or(self.default_cursor.blinking),
+        }
+    }
+
+    /// Set the cursor style used until the application picks one
+    pub fn set_default_cursor_style(&mut self, style: CursorStyle) {
+        self.default_cursor = style;
+    }
+
+    /// Style that will be applied to the next printed character
+    pub fn current_style(&self) -> CellStyle {
+        self.screen.style
+    }
+
+    /// Whether the alternate screen (modes 47/1047/1049) is active
+    pub fn is_alternate_screen(&self) -> bool {
+        self.screen.saved_primary.is_some()
+    }
+
+    /// Key encoding modes requested by the application (DECCKM, DECKPAM,
+    /// modifyOtherKeys)
+    pub fn keyboard_modes(&self) -> KeyboardModes {
+        self.screen.keyboard
+    }
+
+    /// Kitty keyboard protocol flags in effect on the screen being shown
+    pub fn kitty_keyboard_flags(&self) -> u8 {
+        self.screen.kitty_keyboard[usize::from(self.is_alternate_screen())].flags()
+    }
+
+    /// Mouse reporting requested by the application (modes 9/1000/1002/1003/1006)
+    pub fn mouse_protocol(&self) -> MouseProtocol {
+        self.screen.mouse
+    }
+
+    /// Whether the application asked for focus events (mode 1004)
+    pub fn focus_reporting(&self) -> bool {
+        self.screen.focus_reporting
+    }
+
+    /// Whether the application enabled bracketed paste (mode 2004)
+    pub fn bracketed_paste(&self) -> bool {
+        self.screen.bracketed_paste
+    }
+
+    /// Whether the application has begun an update with mode 2026 that it
+    /// has not yet finished
+    pub fn synchronized_output(&self) -> bool {
+        self.screen.synchronized_output
+    }
+
+    /// All visible lines, top to bottom
+    pub fn lines(&self) -> &[Vec<Cell>] {
+        &self.screen.lines
+    }
+
+    /// Cell at (row, col), if in bounds
+    pub fn cell(&self, row: usize, col: usize) -> Option<&Cell> {
+        self.screen.lines.get(row).and_then(|line| line.get(col))
+    }
+
+    /// Text of one row, including trailing blanks. A wide character stands
+    /// for both of its columns.
+    pub fn row_text(&self, row: usize) -> String {
+        let mut text = String::new();
+        if let Some(line) = self.screen.lines.get(row) {
+            for cell in line {
+                cell.push_text(&mut text);
+            }
+        }
+        text
+    }
+
+    /// Full-width dump of the visible screen, one row per line
+    pub fn dump(&self) -> String {
+        (0..self.screen.rows)
+            .map(|row| self.row_text(row))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+impl Default for TerminalGrid {
+    fn default() -> Self {
+        Self::new(24, 80)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid_with(rows: usize, cols: usize, input: &str) -> TerminalGrid {
+        let mut grid = TerminalGrid::new(rows, cols);
+        grid.advance(input.as_bytes());
+        grid
+    }
+
+    #[test]
+    fn test_new_grid() {
+        let grid = TerminalGrid::new(10, 20);
+        assert_eq!(grid.rows(), 10);
+        assert_eq!(grid.cols(), 20);
+        assert_eq!(grid.cursor_position(), (0, 0));
+    }
+
+    #[test]
+    fn test_print_advances_cursor() {
+        let grid = grid_with(5, 10, "ab");
+        assert_eq!(grid.cursor_position(), (0, 2));
+        assert_eq!(grid.row_text(0).trim_end(), "ab");
+    }
+
+    #[test]
+    fn test_osc8_links_on_cells() {
+        let grid = grid_with(
+            3,
+            20,
+            "a\x1b]8;id=x;https://example.com/?a=1;b=2\x1b\\link\x1b]8;;\x1b\\b\x1b]8;;file:///tmp\x07\u{4e2d}",
+        );
+        assert_eq!(grid.link_at(0, 0, 0), None);
+        assert_eq!(
+            grid.link_at(0, 0, 1).as_deref(),
+            Some("https://example.com/?a=1;b=2")
+        );
+        assert!(grid.link_at(0, 0, 4).is_some());
+        assert_eq!(grid.link_at(0, 0, 5), None);
+        // Both halves of a wide character carry the link
+        assert_eq!(grid.link_at(0, 0, 6).as_deref(), Some("file:///tmp"));
+        assert_eq!(grid.link_at(0, 0, 7).as_deref(), Some("file:///tmp"));
+        assert_eq!(grid.link_at(0, 5, 0), None);
+    }
+
+    #[test]
+    fn test_clipboard_requests_are_collected() {
+        let mut grid = grid_with(3, 10, "\x1b]52;c;aGk=\x07x\x1b]52;c;?\x1b\\");
+        assert_eq!(grid.row_text(0).trim_end(), "x");
+        assert_eq!(
+            grid.take_clipboard_requests(),
+            [
+                ClipboardRequest::Write("hi".to_string()),
+                ClipboardRequest::Read {
+                    targets: "c".to_string(),
+                    bell: false
+                }
+            ]
+        );
+        assert!(grid.take_clipboard_requests().is_empty());
+        assert!(grid.take_responses().is_empty());
+    }
+
+    #[test]
+    fn test_title_changes_are_collected() {
+        let mut grid = grid_with(3, 10, "\x1b]2;notes.md - VIM\x07x\x1b]1;vim\x1b\\");
+        assert_eq!(grid.row_text(0).trim_end(), "x");
+        assert_eq!(
+            grid.take_title_changes(),
+            [
+                TitleChange::Title("notes.md - VIM".to_string()),
+                TitleChange::Icon("vim".to_string())
+            ]
+        );
+        assert!(grid.take_title_changes().is_empty());
+    }
+
+    #[test]
+    fn test_images_cover_cells() {
+        use crate::terminal::base64;
+
+        // 16x32 pixels is 2x2 cells of the default 8x16
+        let mut grid = TerminalGrid::new(5, 10);
+        let kitty = format!(
+            "ab\x1b_Ga=T,f=32,s=16,v=32,i=4;{}\x1b\\",
+            base64::encode(&[7; 16 * 32 * 4])
+        );
+        grid.advance(kitty.as_bytes());
+        let view = grid.view(0);
+        let tile = view[1][3].image.as_ref().unwrap();
+        assert_eq!((tile.row, tile.col, tile.extent), (1, 1, (2.0, 2.0)));
+        assert!(view[0][2].image.is_some());
+        assert!(view[0][4].image.is_none() && view[2][2].image.is_none());
+        assert_eq!(grid.cursor_position(), (1, 4));
+        assert_eq!(grid.take_responses(), b"\x1b_Gi=4;OK\x1b\\");
+
+        // A Sixel image leaves the cursor below it
+        grid.advance(b"\r\n\x1bPq#1;2;100;0;0#1!12~\x1b\\");
+        let tile = grid.view(0)[2][1].image.clone().unwrap();
+        assert_eq!(tile.crop(), ([0.5, 0.375], [2.0 / 3.0, 0.0, 1.0, 1.0]));
+        assert_eq!(grid.cursor_position(), (3, 0));
+
+        // A raster size within the pixel cap whose bands take it past the
+        // cap is dropped rather than allocated
+        let mut huge = b"\x1bPq\"1;1;8000000;1".to_vec();
+        huge.extend(b"~-".repeat(1000));
+        huge.extend(b"~\x1b\\");
+        let mut grid = TerminalGrid::new(5, 10);
+        grid.advance(&huge);
+        let view = grid.view(0);
+        assert!(view.iter().all(|row| row.iter().all(|c| c.image.is_none())));
+
+        // Split across chunks, as large images always are
+        let mut grid = TerminalGrid::new(5, 10);
+        grid.advance(b"\x1b_Ga=q,i=1,s=1,v=1,f=24;AA");
+        grid.advance(b"AA\x1b");
+        grid.advance(b"\\x");
+        assert_eq!(grid.take_responses(), b"\x1b_Gi=1;OK\x1b\\");
+        assert_eq!(grid.row_text(0).trim_end(), "x");
+    }
+
+    #[test]
+    fn test_image_memory_is_counted_and_freed() {
+        use crate::terminal::base64;
+
+        // Two 1x1-cell images, one stored for later and one shown, each
+        // 8x16 pixels
+        let pixels = base64::encode(&[7; 8 * 16 * 4]);
+        let mut grid = TerminalGrid::new(2, 10);
+        grid.advance(format!("\x1b_Ga=t,f=32,s=8,v=16,i=1;{}\x1b\\", pixels).as_bytes());
+        grid.advance(format!("\x1b_Ga=T,f=32,s=8,v=16;{}\x1b\\", pixels).as_bytes());
+        assert_eq!(grid.image_bytes(), 2 * 512);
+
+        // Showing the stored image doesn't count it twice
+        grid.advance(b"\x1b_Ga=p,i=1\x1b\\");
+        assert_eq!(grid.image_bytes(), 2 * 512);
+        assert_eq!(grid.drop_stored_images(1), 512);
+        assert_eq!(grid.image_bytes(), 2 * 512);
+
+        // Once scrolled off, images can be blanked
+        grid.advance(b"\r\n\n\n");
+        assert_eq!(grid.scrollback_len(), 2);
+        assert_eq!(grid.drop_scrollback_images(1), 2 * 512);
+        assert_eq!(grid.image_bytes(), 0);
+    }
+
+    #[test]
+    fn test_linefeed_and_carriage_return() {
+        let grid = grid_with(5, 10, "ab\ncd\r\nef");
+        assert_eq!(grid.row_text(0).trim_end(), "ab");
+        assert_eq!(grid.row_text(1).trim_end(), "  cd");
+        assert_eq!(grid.row_text(2).trim_end(), "ef");
+    }
+
+    #[test]
+    fn test_tab() {
+        let grid = grid_with(5, 20, "\t");
+        assert_eq!(grid.cursor_position(), (0, 8));
+    }
+
+    #[test]
+    fn test_deferred_wrap() {
+        let mut grid = grid_with(5, 3, "abc");
+        assert_eq!(grid.cursor_position(), (0, 2));
+        grid.advance(b"d");
+        assert_eq!(grid.cursor_position(), (1, 1));
+        assert_eq!(grid.row_text(1), "d  ");
+    }
+
+    #[test]
+    fn test_autowrap_disabled() {
+        let grid = grid_with(2, 3, "\x1b[?7labcd");
+        assert_eq!(grid.row_text(0), "abd");
+        assert_eq!(grid.cursor_position(), (0, 2));
+    }
+
+    #[test]
+    fn test_mixed_width_line() {
+        let grid = grid_with(1, 10, "a中b😀c");
+        assert_eq!(grid.cursor_position(), (0, 7));
+        assert_eq!(grid.row_text(0), "a中b😀c   ");
+        assert_eq!(grid.cell(0, 1).unwrap().width, 2);
+        assert!(grid.cell(0, 2).unwrap().is_spacer());
+        assert_eq!(grid.cell(0, 3).unwrap().ch, 'b');
+        assert_eq!(grid.cell(0, 6).unwrap().ch, 'c');
+    }
+
+    #[test]
+    fn test_grapheme_clusters_share_a_cell() {
+        // Family emoji joined by ZWJ, then a skin-tone modifier
+        let grid = grid_with(1, 10, "👨\u{200D}👩\u{200D}👧|👍🏽|");
+        assert_eq!(grid.cursor_position(), (0, 6));
+        assert_eq!(grid.row_text(0).trim_end(), "👨\u{200D}👩\u{200D}👧|👍🏽|");
+
+        // VS16 turns a text-style symbol into a two-column emoji
+        let grid = grid_with(1, 10, "\u{2764}\u{FE0F}x");
+        assert_eq!(grid.cell(0, 0).unwrap().width, 2);
+        assert_eq!(grid.cell(0, 2).unwrap().ch, 'x');
+    }
+
+    #[test]
+    fn test_wide_char_without_autowrap_stays_on_line() {
+        let grid = grid_with(2, 4, "\x1b[?7labc中");
+        assert_eq!(grid.row_text(0), "ab中");
+        assert_eq!(grid.row_text(1).trim_end(), "");
+    }
+
+    #[test]
+    fn test_cursor_position() {
+        let grid = grid_with(10, 20, "\x1b[5;10Hx");
+        assert_eq!(grid.cell(4, 9).unwrap().ch, 'x');
+    }
+
+    #[test]
+    fn test_scroll_at_bottom() {
+        let grid = grid_with(2, 5, "one\r\ntwo\r\nthree");
+        assert_eq!(grid.row_text(0).trim_end(), "two");
+        assert_eq!(grid.row_text(1).trim_end(), "three");
+    }
+
+    #[test]
+    fn test_scroll_region() {
+        let grid = grid_with(4, 5, "top\x1b[2;3r\x1b[3;1Ha\nb\nc");
+        assert_eq!(grid.row_text(0).trim_end(), "top");
+        assert_eq!(grid.row_text(1).trim_end(), " b");
+        assert_eq!(grid.row_text(2).trim_end(), "  c");
+        assert_eq!(grid.row_text(3).trim_end(), "");
+    }
+
+    #[test]
+    fn test_insert_delete_lines() {
+        let mut grid = grid_with(5, 5, "AAA\r\nBBB\r\nCCC");
+        grid.advance(b"\x1b[2H\x1b[L");
+        assert_eq!(grid.row_text(1).trim_end(), "");
+        assert_eq!(grid.row_text(2).trim_end(), "BBB");
+        grid.advance(b"\x1b[M");
+        assert_eq!(grid.row_text(1).trim_end(), "BBB");
+    }
+
+    #[test]
+    fn test_insert_delete_chars() {
+        let mut grid = grid_with(1, 6, "abcdef\x1b[1;2H\x1b[2@");
+        assert_eq!(grid.row_text(0), "a  bcd");
+        grid.advance(b"\x1b[3P");
+        assert_eq!(grid.row_text(0), "acd   ");
+    }
+
+    #[test]
+    fn test_erase_modes() {
+        let grid = grid_with(2, 5, "ABCDE\x1b[1;3H\x1b[1K");
+        assert_eq!(grid.row_text(0), "   DE");
+        let grid = grid_with(2, 5, "ABCDE\x1b[1;3H\x1b[0J");
+        assert_eq!(grid.row_text(0), "AB   ");
+    }
+
+    #[test]
+    fn test_erase_display_keeps_cursor() {
+        let grid = grid_with(3, 10, "Hello\x1b[2JWorld");
+        assert!(!grid.dump().contains("Hello"));
+        assert_eq!(grid.row_text(0), "     World");
+    }
+
+    #[test]
+    fn test_save_restore_cursor() {
+        let grid = grid_with(5, 10, "\x1b[2;3H\x1b7\x1b[5;5H\x1b8x");
+        assert_eq!(grid.cell(1, 2).unwrap().ch, 'x');
+    }
+
+    #[test]
+    fn test_alternate_screen_restores_primary() {
+        let mut grid = grid_with(3, 10, "primary\x1b[?1049h");
+        assert!(grid.is_alternate_screen());
+        assert_eq!(grid.row_text(0).trim_end(), "");
+        grid.advance(b"alt");
+        grid.advance(b"\x1b[?1049l");
+        assert!(!grid.is_alternate_screen());
+        assert_eq!(grid.row_text(0).trim_end(), "primary");
+        assert_eq!(grid.cursor_position(), (0, 7));
+    }
+
+    #[test]
+    fn test_resize_keeps_content() {
+        let mut grid = grid_with(5, 10, "X");
+        grid.resize(10, 20);
+        assert_eq!(grid.rows(), 10);
+        assert_eq!(grid.cols(), 20);
+        assert_eq!(grid.cell(0, 0).unwrap().ch, 'X');
+    }
+
+    #[test]
+    fn test_split_sequence_across_chunks() {
+        let mut grid = TerminalGrid::new(5, 10);
+        grid.advance(b"\x1b[3");
+        grid.advance(b"1mR");
+        assert_eq!(grid.cell(0, 0).unwrap().style.fg, Color::Indexed(1));
+    }
+
+    #[test]
+    fn test_sgr_basic() {
+        let grid = grid_with(1, 20, "\x1b[1;7;31mA\x1b[0mB");
+        let a = grid.cell(0, 0).unwrap().style;
+        assert!(a.bold && a.reverse);
+        assert_eq!(a.fg, Color::Indexed(1));
+        assert_eq!(grid.cell(0, 1).unwrap().style, CellStyle::default());
+    }
+
+    #[test]
+    fn test_sgr_extended_colors() {
+        let grid = grid_with(1, 10, "\x1b[38;5;196mA\x1b[48;2;1;2;3mB\x1b[38:2::4:5:6mC");
+        assert_eq!(grid.cell(0, 0).unwrap().style.fg, Color::Indexed(196));
+        assert_eq!(
+            grid.cell(0, 1).unwrap().style.bg,
+            Color::Rgb(Rgb::new(1, 2, 3))
+        );
+        assert_eq!(
+            grid.cell(0, 2).unwrap().style.fg,
+            Color::Rgb(Rgb::new(4, 5, 6))
+        );
+    }
+
+    #[test]
+    fn test_sgr_extended_attributes() {
+        let grid = grid_with(
+            1,
+            10,
+            "\x1b[9;5;8mA\x1b[29;25;28mB\x1b[4:3;58:2::255:0:0mC\x1b[21;58;5;4mD\x1b[4:0;59mE",
+        );
+        let a = grid.cell(0, 0).unwrap().style;
+        assert!(a.strikethrough && a.blink && a.hidden);
+        let b = grid.cell(0, 1).unwrap().style;
+        assert!(!b.strikethrough && !b.blink && !b.hidden);
+        let c = grid.cell(0, 2).unwrap().style;
+        assert_eq!(c.underline, Underline::Curly);
+        assert_eq!(c.underline_color, Color::Rgb(Rgb::new(255, 0, 0)));
+        let d = grid.cell(0, 3).unwrap().style;
+        assert_eq!(d.underline, Underline::Double);
+        assert_eq!(d.underline_color, Color::Indexed(4));
+        let e = grid.cell(0, 4).unwrap().style;
+        assert_eq!(e.underline, Underline::None);
+        assert_eq!(e.underline_color, Color::Default);
+    }
+
+    #[test]
+    fn test_private_m_is_not_sgr() {
+        // xterm modifyOtherKeys (CSI > 4;1 m) must not change the rendition
+        let grid = grid_with(1, 10, "\x1b[>4;1mA");
+        assert_eq!(grid.cell(0, 0).unwrap().style, CellStyle::default());
+    }
+
+    #[test]
+    fn test_apply_sgr_sequence() {
+        let mut style = CellStyle::default();
+        assert!(style.apply_sgr_sequence("\x1b[4;92m"));
+        assert_eq!(style.underline, Underline::Single);
+        assert_eq!(style.fg, Color::Indexed(10));
+        assert!(style.apply_sgr_sequence("\x1b[m"));
+        assert_eq!(style, CellStyle::default());
+        assert!(!style.apply_sgr_sequence("\x1b[5G"));
+    }
+
+    #[test]
+    fn test_sgr_round_trips() {
+        for seq in [
+            "\x1b[1;3;4:3;9;31;42m",
+            "\x1b[2;7;38;5;200;48;2;1;2;3;58;5;9m",
+            "\x1b[95;104m",
+            "\x1b[m",
+        ] {
+            let mut style = CellStyle::default();
+            assert!(style.apply_sgr_sequence(seq));
+            let mut again = CellStyle {
+                bold: true,
+                fg: Color::Indexed(3),
+                ..CellStyle::default()
+            };
+            assert!(again.apply_sgr_sequence(&style.sgr()));
+            assert_eq!(again, style, "{}", seq);
+        }
+    }
+
+    #[test]
+    fn test_palette_indexed() {
+        let palette = Palette::default();
+        assert_eq!(palette.indexed(1), Rgb::new(205, 0, 0));
+        assert_eq!(palette.indexed(16), Rgb::new(0, 0, 0));
+        assert_eq!(palette.indexed(196), Rgb::new(255, 0, 0));
+        assert_eq!(palette.indexed(232), Rgb::new(8, 8, 8));
+    }
+
+    #[test]
+    fn test_mouse_modes() {
+        use crate::terminal::mouse::{MouseEncoding, MouseTracking};
+
+        let mut grid = grid_with(2, 10, "\x1b[?1002;1006h");
+        assert_eq!(grid.mouse_protocol().tracking, MouseTracking::ButtonEvent);
+        assert_eq!(grid.mouse_protocol().encoding, MouseEncoding::Sgr);
+
+        grid.advance(b"\x1b[?1002l");
+        assert!(!grid.mouse_protocol().is_enabled());
+
+        grid.advance(b"\x1b[?1000h\x1bc");
+        assert!(!grid.mouse_protocol().is_enabled());
+    }
+
+    #[test]
+    fn test_keyboard_modes() {
+        let mut grid = grid_with(2, 10, "\x1b[?1h\x1b=\x1b[>4;2m");
+        let modes = grid.keyboard_modes();
+        assert!(modes.application_cursor);
+        assert!(modes.application_keypad);
+        assert_eq!(modes.modify_other_keys, 2);
+        assert_eq!(grid.cell(0, 0).unwrap().style, CellStyle::default());
+
+        grid.advance(b"\x1b[?1l\x1b>\x1b[>4m");
+        assert_eq!(grid.keyboard_modes(), KeyboardModes::default());
+
+        grid.advance(b"\x1b[?1h\x1bc");
+        assert!(!grid.keyboard_modes().application_cursor);
+    }
+
+    #[test]
+    fn test_kitty_keyboard_stacks_per_screen() {
+        let mut grid = grid_with(2, 10, "\x1b[>1u");
+        assert_eq!(grid.kitty_keyboard_flags(), 1);
+
+        grid.advance(b"\x1b[?1049h\x1b[?u");
+        assert_eq!(grid.kitty_keyboard_flags(), 0);
+        assert_eq!(grid.take_responses(), b"\x1b[?0u");
+        grid.advance(b"\x1b[>11u\x1b[=4;2u");
+        assert_eq!(grid.kitty_keyboard_flags(), 15);
+
+        grid.advance(b"\x1b[?1049l");
+        assert_eq!(grid.kitty_keyboard_flags(), 1);
+        grid.advance(b"\x1b[<u");
+        assert_eq!(grid.kitty_keyboard_flags(), 0);
+    }
+
+    #[test]
+    fn test_cursor_shape_and_visibility() {
+        let mut grid = grid_with(2, 10, "");
+        grid.set_default_cursor_style(CursorStyle {
+            shape: CursorShape::Underline,
+            blinking: true,
+        });
+        assert!(grid.cursor_visible());
+        assert_eq!(grid.cursor_style().shape, CursorShape::Underline);
+
+        grid.advance(b"\x1b[?25l\x1b[6 q");
+        assert!(!grid.cursor_visible());
+        assert_eq!(
+            grid.cursor_style(),
+            CursorStyle {
+                shape: CursorShape::Bar,
+                blinking: false
+            }
+        );
+
+        grid.advance(b"\x1b[?12h\x1b[?25h");
+        assert!(grid.cursor_visible());
+        assert!(grid.cursor_style().blinking);
+
+        grid.advance(b"\x1b[0 q");
+        assert_eq!(grid.cursor_style().shape, CursorShape::Underline);
+        grid.advance(b"\x1b[2 q\x1bc");
+        assert_eq!(grid.cursor_style().shape, CursorShape::Underline);
+
+        // The primary screen gets its cursor back when the alternate one goes
+        grid.advance(b"\x1b[4 q\x1b[?1049h\x1b[?25l\x1b[5 q");
+        assert!(!grid.cursor_visible());
+        assert_eq!(grid.cursor_style().shape, CursorShape::Bar);
+        grid.advance(b"\x1b[?1049l");
+        assert!(grid.cursor_visible());
+        assert_eq!(
+            grid.cursor_style(),
+            CursorStyle {
+                shape: CursorShape::Underline,
+                blinking: false
+            }
+        );
+    }
+
+    #[test]
+    fn test_focus_reporting_mode() {
+        let mut grid = grid_with(2, 10, "\x1b[?1004h");
+        assert!(grid.focus_reporting());
+        grid.advance(b"\x1b[?1004$p\x1b[?1004l");
+        assert!(!grid.focus_reporting());
+        assert_eq!(grid.take_responses(), b"\x1b[?1004;1$y");
+    }
+
+    #[test]
+    fn test_bracketed_paste_mode() {
+        let mut grid = grid_with(2, 10, "\x1b[?2004h");
+        assert!(grid.bracketed_paste());
+        grid.advance(b"\x1b[?2004l");
+        assert!(!grid.bracketed_paste());
+    }
+
+    #[test]
+    fn test_synchronized_output_mode() {
+        let mut grid = grid_with(2, 10, "\x1b[?2026$p");
+        assert_eq!(grid.take_responses(), b"\x1b[?2026;2$y");
+        grid.advance(b"\x1b[?2026h");
+        assert!(grid.synchronized_output());
+        grid.advance(b"\x1b[?2026$p\x1b[?2026l");
+        assert_eq!(grid.take_responses(), b"\x1b[?2026;1$y");
+        assert!(!grid.synchronized_output());
+    }
+
+    #[test]
+    fn test_mode_reports() {
+        let mut grid = grid_with(2, 10, "\x1b[?1049h\x1b[?1000h");
+        grid.advance(b"\x1b[?1049$p\x1b[?1000$p\x1b[?2004$p\x1b[?9999$p\x1b[4$p");
+        assert_eq!(
+            grid.take_responses(),
+            b"\x1b[?1049;1$y\x1b[?1000;1$y\x1b[?2004;2$y\x1b[?9999;0$y\x1b[4;0$y"
+        );
+    }
+
+    #[test]
+    fn test_queries_are_answered() {
+        let mut grid = grid_with(5, 20, "\x1b[3;7H\x1b[6nabc\x1b[c");
+        assert_eq!(grid.take_responses(), b"\x1b[3;7R\x1b[?62;4;22c");
+        assert!(grid.take_responses().is_empty());
+        assert_eq!(grid.row_text(2).trim(), "abc");
+
+        grid.set_palette(Palette {
+            background: Rgb::new(0x12, 0x34, 0x56),
+            ..Palette::default()
+        });
+        grid.advance(b"\x1bc\x1b]11;?\x07");
+        assert_eq!(grid.take_responses(), b"\x1b]11;rgb:1212/3434/5656\x07");
+
+        grid.set_image_limits(ImageLimits {
+            enabled: false,
+            ..ImageLimits::default()
+        });
+        grid.advance(b"\x1b[c");
+        assert_eq!(grid.take_responses(), b"\x1b[?62;22c");
+    }
+
+    #[test]
+    fn test_scrollback_collects_primary_lines() {
+        let mut grid = grid_with(2, 5, "one\r\ntwo\r\nthree\r\nfour");
+        assert_eq!(grid.scrollback_len(), 2);
+        let text = |view: Vec<&[Cell]>| -> Vec<String> {
+            view.iter()
+                .map(|line| line.iter().map(|c| c.ch).collect::<String>())
+                .map(|line| line.trim_end().to_string())
+                .collect()
+        };
+        assert_eq!(text(grid.view(0)), ["three", "four"]);
+        assert_eq!(text(grid.view(1)), ["two", "three"]);
+        assert_eq!(text(grid.view(9)), ["one", "two"]);
+
+        grid.set_scrollback_limit(1);
+        assert_eq!(text(grid.view(9)), ["two", "three"]);
+        grid.advance(b"\x1b[3J");
+        assert_eq!(grid.scrollback_len(), 0);
+    }
+
+    #[test]
+    fn test_no_scrollback_from_alternate_screen_or_region() {
+        let grid = grid_with(2, 5, "\x1b[?1049ha\r\nb\r\nc\r\nd");
+        assert_eq!(grid.scrollback_len(), 0);
+        let grid = grid_with(3, 5, "\x1b[2;3ra\r\nb\r\nc\r\nd");
+        assert_eq!(grid.scrollback_len(), 0);
+    }
+
+    #[test]
+    fn test_charsets_saved_with_cursor() {
+        let grid = grid_with(1, 10, "\x1b(0\x1b7\x1b(Bq\x1b8q\x1b(A#");
+        assert_eq!(grid.row_text(0).trim_end(), "─£");
+    }
+
+    #[test]
+    fn test_reset() {
+        let mut grid = grid_with(3, 10, "\x1b[31mtext\x1bc");
+        assert_eq!(grid.dump().trim(), "");
+        assert_eq!(grid.current_style(), CellStyle::default());
+        grid.advance(b"x");
+        grid.reset();
+        assert_eq!(grid.cursor_position(), (0, 0));
+    }
+}
@@ -0,0 +1,161 @@
+//! Hyperlinks (OSC 8)
+//!
+//! Programs such as `ls --hyperlink`, `gcc`, `cargo`, `delta` and
+//! `systemctl` turn text into a link by writing `OSC 8 ; params ; URI ST`
+//! before it and `OSC 8 ; ; ST` after it. The output processor and the
+//! overlay grid keep the target with the text it covers; this module reads
+//! the sequence and hands targets to the system's handler.
+
+use crate::error::{Error, Result};
+use crate::terminal::semantic::{is_local_host, percent_decode};
+use std::process::{Command, Stdio};
+
+/// Target of an OSC 8 sequence from its body (`8;id=x;https://…`). An empty
+/// target ends the link in progress.
+pub fn osc8_target(body: &str) -> Option<&str> {
+    let (_params, uri) = body.strip_prefix("8;")?.split_once(';')?;
+    Some(uri)
+}
+
+/// Schemes a link may have to be opened
+const OPENABLE_SCHEMES: [&str; 4] = ["http", "https", "mailto", "file"];
+
+/// Check that `uri` may be opened here.
+///
+/// Only web, mail and file links are opened; any other scheme could start
+/// whatever handler the system has for it. `file://` links must name this
+/// machine, since a path printed on the other end of an SSH session would
+/// open the wrong file or none, and an absolute path.
+pub fn check_openable(uri: &str) -> Result<()> {
+    opener_target(uri).map(|_| ())
+}
+
+/// Argument to hand the system's handler for `uri`: the link itself, or the
+/// path of a local file
+fn opener_target(uri: &str) -> Result<String> {
+    let refuse = |reason: String| Error::HyperlinkOpenFailed {
+        uri: uri.to_string(),
+        reason,
+    };
+    let Some((scheme, rest)) = uri.split_once(':') else {
+        return Err(refuse("no scheme".to_string()));
+    };
+    if !OPENABLE_SCHEMES
+        .iter()
+        .any(|openable| scheme.eq_ignore_ascii_case(openable))
+    {
+        return Err(refuse(format!("'{}' links are not opened", scheme)));
+    }
+    if !scheme.eq_ignore_ascii_case("file") {
+        return Ok(uri.to_string());
+    }
+
+    let Some((host, path)) = file_parts(rest) else {
+        return Err(refuse("file links must start with file://".to_string()));
+    };
+    if !is_local_host(host) {
+        return Err(refuse(format!("the file is on {}", host)));
+    }
+    let path = percent_decode(path);
+    if !path.starts_with('/') {
+        return Err(refuse("not an absolute path".to_string()));
+    }
+    Ok(path)
+}
+
+/// Open `uri` with the system's handler for it. Local `file://` links are
+/// opened by path.
+pub fn open(uri: &str) -> Result<()> {
+    let target = opener_target(uri)?;
+    // Targets start with a scheme or `/`, so no opener can take one for an
+    // option; `open` is told where its options end as well
+    let mut command = if cfg!(target_os = "macos") {
+        let mut command = Command::new("open");
+        command.arg("--");
+        command
+    } else if cfg!(windows) {
+        let mut command = Command::new("rundll32");
+        command.arg("url.dll,FileProtocolHandler");
+        command
+    } else {
+        Command::new("xdg-open")
+    };
+    let mut child = command
+        .arg(&target)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| Error::HyperlinkOpenFailed {
+            uri: uri.to_string(),
+            reason: e.to_string(),
+        })?;
+    // Reap the handler without blocking the UI
+    std::thread::spawn(move || child.wait());
+    Ok(())
+}
+
+/// Host and path of a `file:` URL after the scheme, if it has an authority
+fn file_parts(rest: &str) -> Option<(&str, &str)> {
+    let url = rest.strip_prefix("//")?;
+    Some(url.find('/').map_or((url, ""), |slash| url.split_at(slash)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_osc8_target() {
+        assert_eq!(
+            osc8_target("8;;https://example.com"),
+            Some("https://example.com")
+        );
+        assert_eq!(
+            osc8_target("8;id=1;https://example.com/a;b"),
+            Some("https://example.com/a;b")
+        );
+        assert_eq!(osc8_target("8;;"), Some(""));
+        assert_eq!(osc8_target("8"), None);
+        assert_eq!(osc8_target("7;file:///tmp"), None);
+    }
+
+    #[test]
+    fn test_check_openable() {
+        let local = hostname::get().unwrap().into_string().unwrap();
+        assert!(check_openable("https://example.com").is_ok());
+        assert!(check_openable("file:///tmp/x").is_ok());
+        assert!(check_openable("file://localhost/tmp/x").is_ok());
+        assert!(check_openable(&format!("file://{}/tmp/x", local)).is_ok());
+        assert!(check_openable("file://some-other-host-4711/tmp/x").is_err());
+        assert!(check_openable("no scheme").is_err());
+        assert!(check_openable("-x:y").is_err());
+    }
+
+    #[test]
+    fn test_only_web_mail_and_file_links_open() {
+        assert!(check_openable("HTTP://example.com").is_ok());
+        assert!(check_openable("mailto:someone@example.com").is_ok());
+        assert!(check_openable("ssh://host").is_err());
+        assert!(check_openable("javascript:alert(1)").is_err());
+        assert!(check_openable("x-man-page://ls").is_err());
+        assert!(check_openable("smb://server/share").is_err());
+    }
+
+    #[test]
+    fn test_file_links_are_absolute_paths() {
+        assert_eq!(opener_target("file:///tmp/a%20b").unwrap(), "/tmp/a b");
+        assert_eq!(opener_target("file://localhost/tmp").unwrap(), "/tmp");
+
+        // Without `//` the rest is not a path, and would reach the opener
+        // as an option
+        assert!(opener_target("file:-a%20Calc").is_err());
+        assert!(opener_target("file:/tmp/x").is_err());
+        assert!(opener_target("file:relative").is_err());
+        assert!(opener_target("file://localhost").is_err());
+        assert!(opener_target("file://localhost-a").is_err());
+        // Decoding must not make a relative path or an option of it
+        assert!(opener_target("file:///%2Dx").is_ok());
+        assert!(opener_target("file://%2Dx/tmp").is_err());
+    }
+}
@@ -0,0 +1,1245 @@
+//! Inline Images
+//!
+//! Programs such as `viu`, `timg`, `chafa`, `imgcat` and matplotlib's
+//! terminal backends draw pictures with one of three protocols:
+//!
+//! - **kitty graphics**: `ESC _ G <key>=<value>,… ; <base64> ESC \`, sent in
+//!   chunks with `m=1` on every chunk but the last
+//! - **iTerm2 inline files**: `OSC 1337 ; File=<args> : <base64> BEL`, or in
+//!   parts with `MultipartFile=`, `FilePart=` and `FileEnd`
+//! - **Sixel**: `DCS <params> q <sixel data> ST`
+//!
+//! [`ImageDecoder`] turns them into [`Placement`]s within [`ImageLimits`].
+//! The output processor gives each image a line of its own in the block;
+//! the overlay grid lays it over the cells it covers. kitty clients may only
+//! send pixels directly: file and shared-memory transfers would let a
+//! program make the terminal read local files.
+
+use std::collections::VecDeque;
+use std::io::Read;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use super::base64;
+
+/// Images a kitty client transmitted for later placement that are kept
+const MAX_STORED_IMAGES: usize = 32;
+
+static NEXT_IMAGE_ID: AtomicU64 = AtomicU64::new(1);
+
+/// How large images may be, and whether they are decoded at all
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ImageLimits {
+    /// Decode images; when off they are dropped and kitty queries refused
+    pub enabled: bool,
+    /// Largest decoded image, in bytes of RGBA pixels
+    pub max_image_bytes: usize,
+}
+
+impl Default for ImageLimits {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_image_bytes: 32 * 1024 * 1024,
+        }
+    }
+}
+
+impl ImageLimits {
+    /// Most encoded bytes worth collecting for one image: its pixels in
+    /// base64, which no compressed format exceeds in practice
+    pub fn max_payload_bytes(&self) -> usize {
+        self.max_image_bytes / 3 * 4 + 4096
+    }
+}
+
+/// A decoded image, shared by every line or cell showing it
+pub struct InlineImage {
+    id: u64,
+    /// Width in pixels
+    pub width: u32,
+    /// Height in pixels
+    pub height: u32,
+    /// Pixels, row by row, four bytes each
+    pub rgba: Vec<u8>,
+}
+
+impl InlineImage {
+    /// Wrap `rgba`, which must hold `width * height` pixels
+    pub fn new(width: u32, height: u32, rgba: Vec<u8>) -> Self {
+        debug_assert_eq!(rgba.len(), width as usize * height as usize * 4);
+        Self {
+            id: NEXT_IMAGE_ID.fetch_add(1, Ordering::Relaxed),
+            width,
+            height,
+            rgba,
+        }
+    }
+
+    /// Identity for texture caches; unique within the process
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Memory the pixels take
+    pub fn byte_len(&self) -> usize {
+        self.rgba.len()
+    }
+}
+
+impl std::fmt::Debug for InlineImage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("InlineImage")
+            .field("id", &self.id)
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .finish()
+    }
+}
+
+impl PartialEq for InlineImage {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl Eq for InlineImage {}
+
+/// A requested width or height
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Extent {
+    /// Character cells
+    Cells(u32),
+    /// Pixels
+    Pixels(u32),
+    /// Percent of the space available
+    Percent(u32),
+}
+
+impl Extent {
+    /// Parse an iTerm2 size: `N`, `Npx`, `N%` or `auto` (`None`)
+    fn parse(text: &str) -> Option<Self> {
+        if let Some(n) = text.strip_suffix("px") {
+            n.parse().ok().map(Self::Pixels)
+        } else if let Some(n) = text.strip_suffix('%') {
+            n.parse().ok().map(Self::Percent)
+        } else {
+            text.parse().ok().map(Self::Cells)
+        }
+    }
+
+    /// Size in pixels, given the cell size and the space available
+    fn resolve(self, cell: f32, available: f32) -> f32 {
+        match self {
+            Self::Cells(n) => n as f32 * cell,
+            Self::Pixels(n) => n as f32,
+            Self::Percent(n) => available * n.min(100) as f32 / 100.0,
+        }
+    }
+}
+
+/// Where the cursor goes once an image is placed on the grid
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CursorAfter {
+    /// Where it was (kitty `C=1`)
+    Stay,
+    /// Past the image's bottom-right cell, as kitty and iTerm2 leave it
+    AfterImage,
+    /// At the start column on the row below the image, as Sixel leaves it
+    BelowImage,
+}
+
+/// An image and the size it was asked to be drawn at
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Placement {
+    pub image: Arc<InlineImage>,
+    pub width: Option<Extent>,
+    pub height: Option<Extent>,
+    /// Keep the image's proportions when both sizes are given
+    pub preserve_aspect: bool,
+    pub cursor: CursorAfter,
+}
+
+impl Placement {
+    /// Place `image` at its own size
+    pub fn new(image: InlineImage, cursor: CursorAfter) -> Self {
+        Self {
+            image: Arc::new(image),
+            width: None,
+            height: None,
+            preserve_aspect: true,
+            cursor,
+        }
+    }
+
+    /// Size to draw at, in pixels, for a cell of `cell` pixels and
+    /// `available` pixels of width; never wider than what is available
+    pub fn display_size(&self, cell: (f32, f32), available: f32) -> (f32, f32) {
+        let natural = (self.image.width as f32, self.image.height as f32);
+        let width = self.width.map(|w| w.resolve(cell.0, available));
+        // Heights in percent are of a 24-row screen; there is no other
+        // height to go by in a block
+        let height = self.height.map(|h| h.resolve(cell.1, cell.1 * 24.0));
+        let (mut w, mut h) = match (width, height) {
+            (None, None) => natural,
+            (Some(w), None) => (w, w * natural.1 / natural.0),
+            (None, Some(h)) => (h * natural.0 / natural.1, h),
+            (Some(w), Some(h)) if self.preserve_aspect => {
+                let scale = (w / natural.0).min(h / natural.1);
+                (natural.0 * scale, natural.1 * scale)
+            }
+            (Some(w), Some(h)) => (w, h),
+        };
+        if available > 0.0 && w > available {
+            h *= available / w;
+            w = available;
+        }
+        (w.max(1.0), h.max(1.0))
+    }
+}
+
+/// Decode a PNG, JPEG or GIF file's first frame
+pub fn decode_file(data: &[u8], limits: &ImageLimits) -> Option<InlineImage> {
+    let mut reader = image::ImageReader::new(std::io::Cursor::new(data))
+        .with_guessed_format()
+        .ok()?;
+    let mut decode_limits = image::Limits::default();
+    decode_limits.max_alloc = Some(limits.max_image_bytes as u64);
+    reader.limits(decode_limits);
+    let decoded = reader.decode().ok()?.into_rgba8();
+    if decoded.len() > limits.max_image_bytes || decoded.is_empty() {
+        return None;
+    }
+    Some(InlineImage::new(
+        decoded.width(),
+        decoded.height(),
+        decoded.into_raw(),
+    ))
+}
+
+/// Control data of a kitty graphics command
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KittyControl {
+    /// `a`: t(ransmit), T (transmit and display), p(ut), q(uery), d(elete)
+    pub action: u8,
+    /// `f`: 24 (RGB), 32 (RGBA) or 100 (PNG)
+    pub format: u32,
+    /// `t`: d(irect); f(ile), t(emporary file) and s(hared memory) are refused
+    pub medium: u8,
+    /// `s`, `v`: size of raw pixel data
+    pub width: u32,
+    pub height: u32,
+    /// `i`: client's image id; replies are sent only when it is set
+    pub id: u32,
+    /// `c`, `r`: cells to draw in
+    pub cols: u32,
+    pub rows: u32,
+    /// `m=1`: more chunks follow
+    pub more: bool,
+    /// `q`: 1 hides OK replies, 2 hides errors as well
+    pub quiet: u32,
+    /// `o=z`: payload is zlib-compressed
+    pub compressed: bool,
+    /// `C=1`: leave the cursor where it is
+    pub keep_cursor: bool,
+    /// `d`: what to delete
+    pub delete: u8,
+}
+
+impl Default for KittyControl {
+    fn default() -> Self {
+        Self {
+            action: b't',
+            format: 32,
+            medium: b'd',
+            width: 0,
+            height: 0,
+            id: 0,
+            cols: 0,
+            rows: 0,
+            more: false,
+            quiet: 0,
+            compressed: false,
+            keep_cursor: false,
+            delete: b'a',
+        }
+    }
+}
+
+impl KittyControl {
+    /// Split the body of an APC string (`Ga=T,f=100;<payload>`) into control
+    /// data and payload. `None` if it is not a graphics command.
+    pub fn parse(body: &[u8]) -> Option<(Self, &[u8])> {
+        let body = body.strip_prefix(b"G")?;
+        let (control, payload) = match body.iter().position(|&b| b == b';') {
+            Some(split) => (&body[..split], &body[split + 1..]),
+            None => (body, &[][..]),
+        };
+
+        let mut parsed = Self::default();
+        for pair in control.split(|&b| b == b',') {
+            let [key, b'=', value @ ..] = pair else {
+                continue;
+            };
+            let byte = value.first().copied().unwrap_or(0);
+            let number = || {
+                std::str::from_utf8(value)
+                    .ok()
+                    .and_then(|v| v.parse().ok())
+                    .unwrap_or(0)
+            };
+            match *key {
+                b'a' => parsed.action = byte,
+                b'f' => parsed.format = number(),
+                b't' => parsed.medium = byte,
+                b's' => parsed.width = number(),
+                b'v' => parsed.height = number(),
+                b'i' => parsed.id = number(),
+                b'c' => parsed.cols = number(),
+                b'r' => parsed.rows = number(),
+                b'm' => parsed.more = number() == 1,
+                b'q' => parsed.quiet = number(),
+                b'o' => parsed.compressed = byte == b'z',
+                b'C' => parsed.keep_cursor = number() == 1,
+                b'd' => parsed.delete = byte,
+                _ => {}
+            }
+        }
+        Some((parsed, payload))
+    }
+
+    /// Reply with `message` (`OK` or `ECODE:text`) unless the command has
+    /// no id or asked for quiet
+    pub fn reply(&self, message: &str) -> Option<Vec<u8>> {
+        let ok = message == "OK";
+        if self.id == 0 || self.quiet >= 2 || (ok && self.quiet == 1) {
+            return None;
+        }
+        Some(format!("\x1b_Gi={};{}\x1b\\", self.id, message).into_bytes())
+    }
+}
+
+/// iTerm2 file arguments (`name=…;size=…;width=…;inline=1`)
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct FileArgs {
+    inline: bool,
+    width: Option<Extent>,
+    height: Option<Extent>,
+    preserve_aspect: bool,
+}
+
+impl FileArgs {
+    fn parse(args: &[u8]) -> Self {
+        let mut parsed = Self {
+            inline: false,
+            width: None,
+            height: None,
+            preserve_aspect: true,
+        };
+        let args = String::from_utf8_lossy(args);
+        for (key, value) in args.split(';').filter_map(|arg| arg.split_once('=')) {
+            match key {
+                "inline" => parsed.inline = value == "1",
+                "width" => parsed.width = Extent::parse(value),
+                "height" => parsed.height = Extent::parse(value),
+                "preserveAspectRatio" => parsed.preserve_aspect = value != "0",
+                _ => {}
+            }
+        }
+        parsed
+    }
+}
+
+/// A kitty image arriving in chunks
+struct KittyTransfer {
+    control: KittyControl,
+    payload: Vec<u8>,
+    /// Went past the size limit; the remaining chunks are dropped
+    too_large: bool,
+}
+
+/// Turns image escape sequences into images
+pub struct ImageDecoder {
+    limits: ImageLimits,
+    kitty_transfer: Option<KittyTransfer>,
+    /// Images kitty clients transmitted by id, oldest first
+    kitty_stored: VecDeque<(u32, Arc<InlineImage>)>,
+    iterm_transfer: Option<(FileArgs, Vec<u8>)>,
+    sixel: Option<SixelDecoder>,
+    /// Replies for the program (kitty `OK`s and errors)
+    responses: Vec<u8>,
+}
+
+impl std::fmt::Debug for ImageDecoder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ImageDecoder")
+            .field("limits", &self.limits)
+            .field("stored", &self.kitty_stored.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for ImageDecoder {
+    fn default() -> Self {
+        Self::new(ImageLimits::default())
+    }
+}
+
+impl ImageDecoder {
+    pub fn new(limits: ImageLimits) -> Self {
+        Self {
+            limits,
+            kitty_transfer: None,
+            kitty_stored: VecDeque::new(),
+            iterm_transfer: None,
+            sixel: None,
+            responses: Vec::new(),
+        }
+    }
+
+    pub fn limits(&self) -> &ImageLimits {
+        &self.limits
+    }
+
+    pub fn set_limits(&mut self, limits: ImageLimits) {
+        self.limits = limits;
+    }
+
+    /// Replies collected since the last call
+    pub fn take_responses(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.responses)
+    }
+
+    /// Images kitty clients transmitted for later placement, oldest first
+    pub fn stored(&self) -> impl Iterator<Item = &Arc<InlineImage>> {
+        self.kitty_stored.iter().map(|(_, image)| image)
+    }
+
+    /// Memory the images kept for later placement take
+    pub fn stored_bytes(&self) -> usize {
+        self.stored().map(|image| image.byte_len()).sum()
+    }
+
+    /// Forget images kept for later placement, oldest first, until `bytes`
+    /// of them are gone; returns the memory they took
+    pub fn drop_stored(&mut self, bytes: usize) -> usize {
+        let mut dropped = 0;
+        while dropped < bytes {
+            let Some((_, image)) = self.kitty_stored.pop_front() else {
+                break;
+            };
+            dropped += image.byte_len();
+        }
+        dropped
+    }
+
+    /// Drop transfers in progress and stored images
+    pub fn reset(&mut self) {
+        self.kitty_transfer = None;
+        self.kitty_stored.clear();
+        self.iterm_transfer = None;
+        self.sixel = None;
+    }
+
+    /// Handle a kitty graphics APC body; returns the image to draw, if any
+    pub fn kitty(&mut self, body: &[u8]) -> Option<Placement> {
+        let (control, payload) = KittyControl::parse(body)?;
+        let max_payload = self.limits.max_payload_bytes();
+        let mut transfer = match self.kitty_transfer.take() {
+            // Later chunks carry only `m` (and perhaps `q`)
+            Some(mut transfer) => {
+                transfer.control.more = control.more;
+                transfer
+            }
+            None => KittyTransfer {
+                control,
+                payload: Vec::new(),
+                too_large: false,
+            },
+        };
+        if !transfer.too_large {
+            if transfer.payload.len() + payload.len() > max_payload {
+                transfer.too_large = true;
+                transfer.payload = Vec::new();
+            } else {
+                transfer.payload.extend_from_slice(payload);
+            }
+        }
+        if transfer.control.more {
+            self.kitty_transfer = Some(transfer);
+            return None;
+        }
+
+        let control = transfer.control;
+        if transfer.too_large {
+            self.kitty_reply(&control, "EFBIG:image is too large");
+            return None;
+        }
+        if !self.limits.enabled {
+            self.kitty_reply(&control, "ENOTSUPPORTED:images are turned off");
+            return None;
+        }
+        match control.action {
+            b'd' => {
+                if matches!(control.delete, b'i' | b'I') {
+                    self.kitty_stored.retain(|(id, _)| *id != control.id);
+                } else if matches!(control.delete, b'a' | b'A') {
+                    self.kitty_stored.clear();
+                }
+                None
+            }
+            b'p' => {
+                let image = self
+                    .kitty_stored
+                    .iter()
+                    .find(|(id, _)| *id == control.id)
+                    .map(|(_, image)| image.clone());
+                match image {
+                    Some(image) => {
+                        self.kitty_reply(&control, "OK");
+                        Some(kitty_placement(image, &control))
+                    }
+                    None => {
+                        self.kitty_reply(&control, "ENOENT:no such image");
+                        None
+                    }
+                }
+            }
+            b't' | b'T' | b'q' => {
+                let image = match self.decode_kitty(&control, &transfer.payload) {
+                    Ok(image) => image,
+                    Err(message) => {
+                        self.kitty_reply(&control, message);
+                        return None;
+                    }
+                };
+                self.kitty_reply(&control, "OK");
+                if control.action == b'q' {
+                    return None;
+                }
+                let image = Arc::new(image);
+                if control.id != 0 {
+                    self.store_kitty(control.id, image.clone());
+                }
+                (control.action == b'T').then(|| kitty_placement(image, &control))
+            }
+            _ => None,
+        }
+    }
+
+    fn kitty_reply(&mut self, control: &KittyControl, message: &str) {
+        if let Some(reply) = control.reply(message) {
+            self.responses.extend_from_slice(&reply);
+        }
+    }
+
+    fn decode_kitty(
+        &self,
+        control: &KittyControl,
+        payload: &[u8],
+    ) -> Result<InlineImage, &'static str> {
+        if control.medium != b'd' {
+            return Err("EINVAL:only direct transmission is supported");
+        }
+        let mut data = base64::decode(payload).ok_or("EINVAL:payload is not base64")?;
+        if control.compressed {
+            let mut inflated = Vec::new();
+            flate2::read::ZlibDecoder::new(data.as_slice())
+                .take(self.limits.max_image_bytes as u64 + 1)
+                .read_to_end(&mut inflated)
+                .map_err(|_| "EINVAL:payload does not inflate")?;
+            data = inflated;
+        }
+        if data.len() > self.limits.max_image_bytes {
+            return Err("EFBIG:image is too large");
+        }
+
+        let (width, height) = (control.width, control.height);
+        let pixels = width as usize * height as usize;
+        match control.format {
+            100 => decode_file(&data, &self.limits).ok_or("EBADPNG:cannot decode image"),
+            24 if pixels > 0 && data.len() == pixels * 3 => {
+                let mut rgba = Vec::with_capacity(pixels * 4);
+                for rgb in data.chunks_exact(3) {
+                    rgba.extend_from_slice(rgb);
+                    rgba.push(0xff);
+                }
+                Ok(InlineImage::new(width, height, rgba))
+            }
+            32 if pixels > 0 && data.len() == pixels * 4 => {
+                Ok(InlineImage::new(width, height, data))
+            }
+            24 | 32 => Err("ENODATA:payload does not match the size"),
+            _ => Err("EINVAL:unsupported format"),
+        }
+    }
+
+    fn store_kitty(&mut self, id: u32, image: Arc<InlineImage>) {
+        self.kitty_stored.retain(|(stored, _)| *stored != id);
+        self.kitty_stored.push_back((id, image));
+        while self.kitty_stored.len() > MAX_STORED_IMAGES {
+            self.kitty_stored.pop_front();
+        }
+    }
+
+    /// Handle the body of an iTerm2 `OSC 1337` after `1337;`; returns the
+    /// image to draw, if any
+    pub fn iterm2(&mut self, body: &[u8]) -> Option<Placement> {
+        if let Some(rest) = body.strip_prefix(b"File=") {
+            let split = rest.iter().position(|&b| b == b':')?;
+            let args = FileArgs::parse(&rest[..split]);
+            return self.iterm2_file(args, &rest[split + 1..]);
+        }
+        if let Some(args) = body.strip_prefix(b"MultipartFile=") {
+            self.iterm_transfer = Some((FileArgs::parse(args), Vec::new()));
+        } else if let Some(part) = body.strip_prefix(b"FilePart=") {
+            let max_payload = self.limits.max_payload_bytes();
+            if let Some((_, payload)) = &mut self.iterm_transfer {
+                if payload.len() + part.len() > max_payload {
+                    self.iterm_transfer = None;
+                } else {
+                    payload.extend_from_slice(part);
+                }
+            }
+        } else if body == b"FileEnd" {
+            let (args, payload) = self.iterm_transfer.take()?;
+            return self.iterm2_file(args, &payload);
+        }
+        None
+    }
+
+    fn iterm2_file(&self, args: FileArgs, payload: &[u8]) -> Option<Placement> {
+        // Files sent without `inline=1` are downloads, which MosaicTerm
+        // doesn't take
+        if !self.limits.enabled || !args.inline || payload.len() > self.limits.max_payload_bytes() {
+            return None;
+        }
+        let data = base64::decode(payload)?;
+        let image = decode_file(&data, &self.limits)?;
+        Some(Placement {
+            width: args.width,
+            height: args.height,
+            preserve_aspect: args.preserve_aspect,
+            ..Placement::new(image, CursorAfter::AfterImage)
+        })
+    }
+
+    /// Begin a Sixel image
+    pub fn sixel_start(&mut self) {
+        self.sixel = self
+            .limits
+            .enabled
+            .then(|| SixelDecoder::new(self.limits.max_image_bytes / 4));
+    }
+
+    /// Feed a byte of Sixel data
+    pub fn sixel_put(&mut self, byte: u8) {
+        if let Some(sixel) = &mut self.sixel {
+            sixel.put(byte);
+        }
+    }
+
+    /// Finish the Sixel image; returns it unless it was empty or too large
+    pub fn sixel_finish(&mut self) -> Option<Placement> {
+        let image = self.sixel.take()?.finish()?;
+        Some(Placement::new(image, CursorAfter::BelowImage))
+    }
+}
+
+fn kitty_placement(image: Arc<InlineImage>, control: &KittyControl) -> Placement {
+    Placement {
+        image,
+        width: (control.cols > 0).then_some(Extent::Cells(control.cols)),
+        height: (control.rows > 0).then_some(Extent::Cells(control.rows)),
+        preserve_aspect: false,
+        cursor: if control.keep_cursor {
+            CursorAfter::Stay
+        } else {
+            CursorAfter::AfterImage
+        },
+    }
+}
+
+/// Pixel no sixel has painted
+const TRANSPARENT: [u8; 4] = [0, 0, 0, 0];
+
+/// The VT340's sixteen colours, in percent
+const VT340_COLORS: [[u32; 3]; 16] = [
+    [0, 0, 0],
+    [20, 20, 80],
+    [80, 13, 13],
+    [20, 80, 20],
+    [80, 20, 80],
+    [20, 80, 80],
+    [80, 80, 20],
+    [53, 53, 53],
+    [26, 26, 26],
+    [33, 33, 60],
+    [60, 26, 26],
+    [33, 60, 33],
+    [60, 33, 60],
+    [33, 60, 60],
+    [60, 60, 33],
+    [80, 80, 80],
+];
+
+/// Sixel data, decoded a byte at a time. Pixels no sixel sets stay
+/// transparent, whatever the background parameter asks for, so the image
+/// sits on the block or screen background.
+struct SixelDecoder {
+    palette: Vec<[u8; 4]>,
+    color: usize,
+    x: usize,
+    y: usize,
+    rows: Vec<Vec<[u8; 4]>>,
+    /// Size from the raster attributes, or how far drawing has reached
+    width: usize,
+    height: usize,
+    max_pixels: usize,
+    too_large: bool,
+    /// `#`, `!` or `"` with the parameters read so far
+    command: Option<u8>,
+    params: Vec<u32>,
+}
+
+impl SixelDecoder {
+    fn new(max_pixels: usize) -> Self {
+        let mut palette = vec![[0, 0, 0, 0xff]; 256];
+        for (slot, color) in palette.iter_mut().zip(VT340_COLORS) {
+            *slot = percent_rgb(color);
+        }
+        Self {
+            palette,
+            color: 0,
+            x: 0,
+            y: 0,
+            rows: Vec::new(),
+            width: 0,
+            height: 0,
+            max_pixels,
+            too_large: false,
+            command: None,
+            params: Vec::new(),
+        }
+    }
+
+    fn put(&mut self, byte: u8) {
+        if self.command.is_some() {
+            match byte {
+                b'0'..=b'9' => {
+                    if let Some(param) = self.params.last_mut() {
+                        *param = param
+                            .saturating_mul(10)
+                            .saturating_add((byte - b'0') as u32);
+                    }
+                    return;
+                }
+                b';' => {
+                    self.params.push(0);
+                    return;
+                }
+                _ => {}
+            }
+        }
+
+        let repeat = match self.command.take() {
+            Some(b'!') => self.params[0].max(1) as usize,
+            Some(b'#') => {
+                self.color_command();
+                1
+            }
+            Some(b'"') => {
+                self.raster_attributes();
+                1
+            }
+            _ => 1,
+        };
+        match byte {
+            b'#' | b'!' | b'"' => {
+                self.command = Some(byte);
+                self.params = vec![0];
+            }
+            b'$' => self.x = 0,
+            b'-' => {
+                self.x = 0;
+                self.y = self.y.saturating_add(6);
+                self.fits(self.width, self.height.max(self.y));
+            }
+            0x3f..=0x7e => self.sixel(byte - 0x3f, repeat),
+            _ => {}
+        }
+    }
+
+    /// `#Pc` selects a colour; `#Pc;Pu;Px;Py;Pz` defines it as HLS (1) or
+    /// RGB (2) and selects it
+    fn color_command(&mut self) {
+        let index = self.params[0] as usize % self.palette.len();
+        if let [_, system, a, b, c] = self.params[..] {
+            self.palette[index] = match system {
+                1 => hls_rgb(a, b, c),
+                _ => percent_rgb([a, b, c]),
+            };
+        }
+        self.color = index;
+    }
+
+    /// Whether an image `width` by `height` stays within the pixel cap;
+    /// once the image has outgrown it, it is dropped
+    fn fits(&mut self, width: usize, height: usize) -> bool {
+        if width.saturating_mul(height) > self.max_pixels {
+            self.too_large = true;
+        }
+        !self.too_large
+    }
+
+    /// `"Pan;Pad;Ph;Pv` gives the image size up front
+    fn raster_attributes(&mut self) {
+        if let [_, _, width, height] = self.params[..] {
+            let width = self.width.max(width as usize);
+            let height = self.height.max(height as usize).max(self.rows.len());
+            if self.fits(width, height) {
+                self.width = width;
+                self.height = height;
+            }
+        }
+    }
+
+    fn sixel(&mut self, bits: u8, repeat: usize) {
+        let end = self.x.saturating_add(repeat);
+        let height = self.height.max(self.y.saturating_add(6));
+        if !self.fits(self.width.max(end), height) {
+            return;
+        }
+        if bits != 0 {
+            let color = self.palette[self.color];
+            for bit in 0..6 {
+                if bits & (1 << bit) == 0 {
+                    continue;
+                }
+                let y = self.y + bit;
+                if self.rows.len() <= y {
+                    self.rows.resize_with(y + 1, Vec::new);
+                }
+                let row = &mut self.rows[y];
+                if row.len() < end {
+                    row.resize(end, TRANSPARENT);
+                }
+                row[self.x..end].fill(color);
+            }
+        }
+        self.x = end;
+        self.width = self.width.max(end);
+    }
+
+    fn finish(self) -> Option<InlineImage> {
+        let height = self.height.max(self.rows.len());
+        if self.too_large
+            || self.width == 0
+            || height == 0
+            || self.width.saturating_mul(height) > self.max_pixels
+        {
+            return None;
+        }
+        let mut rgba = Vec::with_capacity(self.width * height * 4);
+        for y in 0..height {
+            let row = self.rows.get(y).map(Vec::as_slice).unwrap_or(&[]);
+            for x in 0..self.width {
+                rgba.extend_from_slice(row.get(x).unwrap_or(&TRANSPARENT));
+            }
+        }
+        Some(InlineImage::new(self.width as u32, height as u32, rgba))
+    }
+}
+
+fn percent_rgb([r, g, b]: [u32; 3]) -> [u8; 4] {
+    let channel = |p: u32| (p.min(100) * 255 / 100) as u8;
+    [channel(r), channel(g), channel(b), 0xff]
+}
+
+/// DEC HLS, whose hue starts at blue rather than red, to RGBA
+fn hls_rgb(hue: u32, lightness: u32, saturation: u32) -> [u8; 4] {
+    let h = ((hue + 240) % 360) as f32 / 360.0;
+    let l = lightness.min(100) as f32 / 100.0;
+    let s = saturation.min(100) as f32 / 100.0;
+    let q = if l < 0.5 {
+        l * (1.0 + s)
+    } else {
+        l + s - l * s
+    };
+    let p = 2.0 * l - q;
+    let channel = |t: f32| {
+        let t = t.rem_euclid(1.0);
+        let v = if t < 1.0 / 6.0 {
+            p + (q - p) * 6.0 * t
+        } else if t < 0.5 {
+            q
+        } else if t < 2.0 / 3.0 {
+            p + (q - p) * (2.0 / 3.0 - t) * 6.0
+        } else {
+            p
+        };
+        (v * 255.0).round() as u8
+    };
+    [
+        channel(h + 1.0 / 3.0),
+        channel(h),
+        channel(h - 1.0 / 3.0),
+        0xff,
+    ]
+}
+
+/// Part of a byte stream split by [`ApcSplitter`]
+#[derive(Debug, PartialEq, Eq)]
+pub enum ApcPiece<'a> {
+    /// Bytes to pass on
+    Bytes(&'a [u8]),
+    /// The body of an APC string, between `ESC _` and `ESC \`
+    Apc(Vec<u8>),
+}
+
+/// Takes APC strings (`ESC _ … ESC \`) out of a byte stream. vte parses them
+/// but discards their contents, and kitty graphics travel in them.
+#[derive(Debug, Default)]
+pub struct ApcSplitter {
+    state: ApcState,
+    body: Vec<u8>,
+    /// Longest body kept; longer ones are cut short
+    max_len: usize,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum ApcState {
+    #[default]
+    Ground,
+    /// `ESC` seen at the end of the previous chunk
+    Escape,
+    Body,
+    /// `ESC` seen in the body
+    BodyEscape,
+}
+
+impl ApcSplitter {
+    pub fn new(max_len: usize) -> Self {
+        Self {
+            max_len,
+            ..Self::default()
+        }
+    }
+
+    pub fn set_max_len(&mut self, max_len: usize) {
+        self.max_len = max_len;
+    }
+
+    /// Split `bytes` into runs to pass on and APC bodies, in order
+    pub fn split<'a>(&mut self, bytes: &'a [u8]) -> Vec<ApcPiece<'a>> {
+        let mut pieces = Vec::new();
+        let mut start = 0;
+        for (i, &byte) in bytes.iter().enumerate() {
+            match self.state {
+                ApcState::Ground => {
+                    if byte == 0x1b {
+                        self.state = ApcState::Escape;
+                    }
+                }
+                ApcState::Escape => {
+                    if byte == b'_' {
+                        // The ESC may have ended the previous chunk
+                        let esc = i.checked_sub(1);
+                        match esc {
+                            Some(esc) if esc >= start => {
+                                if esc > start {
+                                    pieces.push(ApcPiece::Bytes(&bytes[start..esc]));
+                                }
+                            }
+                            _ => {
+                                if i > start {
+                                    pieces.push(ApcPiece::Bytes(&bytes[start..i]));
+                                }
+                            }
+                        }
+                        self.state = ApcState::Body;
+                        self.body.clear();
+                    } else {
+                        if i == 0 {
+                            // Hand on the ESC held back from the last chunk
+                            pieces.push(ApcPiece::Bytes(b"\x1b"));
+                        }
+                        self.state = if byte == 0x1b {
+                            ApcState::Escape
+                        } else {
+                            ApcState::Ground
+                        };
+                    }
+                }
+                ApcState::Body => {
+                    if byte == 0x1b {
+                        self.state = ApcState::BodyEscape;
+                    } else if self.body.len() < self.max_len {
+                        self.body.push(byte);
+                    }
+                }
+                ApcState::BodyEscape => {
+                    pieces.push(ApcPiece::Apc(std::mem::take(&mut self.body)));
+                    if byte == b'\\' {
+                        self.state = ApcState::Ground;
+                        start = i + 1;
+                    } else {
+                        // Not a string terminator: the string ends and the
+                        // ESC begins the next sequence
+                        pieces.push(ApcPiece::Bytes(b"\x1b"));
+                        self.state = if byte == 0x1b {
+                            ApcState::Escape
+                        } else {
+                            ApcState::Ground
+                        };
+                        start = i;
+                    }
+                }
+            }
+        }
+
+        match self.state {
+            ApcState::Ground if start < bytes.len() => {
+                pieces.push(ApcPiece::Bytes(&bytes[start..]));
+            }
+            // Hold back a trailing ESC until it is known whether an APC follows
+            ApcState::Escape if start + 1 < bytes.len() => {
+                pieces.push(ApcPiece::Bytes(&bytes[start..bytes.len() - 1]));
+            }
+            _ => {}
+        }
+        pieces
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kitty_rgb(control: &str, pixels: &[u8]) -> Vec<u8> {
+        format!("G{};{}", control, base64::encode(pixels)).into_bytes()
+    }
+
+    #[test]
+    fn test_kitty_transmit_and_display() {
+        let mut decoder = ImageDecoder::default();
+        let placement = decoder
+            .kitty(&kitty_rgb("a=T,f=24,s=2,v=1,c=4", &[255, 0, 0, 0, 255, 0]))
+            .unwrap();
+        assert_eq!((placement.image.width, placement.image.height), (2, 1));
+        assert_eq!(placement.image.rgba, vec![255, 0, 0, 255, 0, 255, 0, 255]);
+        assert_eq!(placement.width, Some(Extent::Cells(4)));
+        assert_eq!(placement.cursor, CursorAfter::AfterImage);
+        assert!(decoder.take_responses().is_empty());
+    }
+
+    #[test]
+    fn test_kitty_chunks_and_replies() {
+        let mut decoder = ImageDecoder::default();
+        let payload = base64::encode(&[9; 16]);
+        let (first, second) = payload.split_at(8);
+        let first = format!("Ga=t,f=32,s=2,v=2,i=7,m=1;{}", first);
+        assert!(decoder.kitty(first.as_bytes()).is_none());
+        assert!(decoder
+            .kitty(format!("Gm=0;{}", second).as_bytes())
+            .is_none());
+        assert_eq!(decoder.take_responses(), b"\x1b_Gi=7;OK\x1b\\");
+
+        let placed = decoder.kitty(b"Ga=p,i=7,C=1").unwrap();
+        assert_eq!(placed.image.rgba, vec![9; 16]);
+        assert_eq!(placed.cursor, CursorAfter::Stay);
+
+        assert!(decoder.kitty(b"Ga=p,i=8").is_none());
+        assert!(decoder
+            .take_responses()
+            .ends_with(b"i=8;ENOENT:no such image\x1b\\"));
+    }
+
+    #[test]
+    fn test_kitty_query_and_refusals() {
+        let mut decoder = ImageDecoder::default();
+        assert!(decoder.kitty(b"Gi=31,s=1,v=1,a=q,t=d,f=24;AAAA").is_none());
+        assert_eq!(decoder.take_responses(), b"\x1b_Gi=31;OK\x1b\\");
+
+        decoder.kitty(b"Gi=2,a=T,t=f;L2V0Yy9wYXNzd2Q=");
+        assert!(decoder.take_responses().starts_with(b"\x1b_Gi=2;EINVAL"));
+
+        decoder.kitty(b"Gi=3,a=T,f=24,s=2,v=2,q=2;AAAA");
+        assert!(decoder.take_responses().is_empty());
+
+        decoder.set_limits(ImageLimits {
+            enabled: false,
+            ..ImageLimits::default()
+        });
+        decoder.kitty(b"Gi=31,s=1,v=1,a=q,t=d,f=24;AAAA");
+        assert!(decoder
+            .take_responses()
+            .starts_with(b"\x1b_Gi=31;ENOTSUPPORTED"));
+    }
+
+    #[test]
+    fn test_kitty_compressed_and_too_large() {
+        use std::io::Write;
+        let mut encoder =
+            flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(&[1, 2, 3, 4]).unwrap();
+        let compressed = encoder.finish().unwrap();
+        let body = format!("Ga=T,f=32,s=1,v=1,o=z;{}", base64::encode(&compressed));
+        let mut decoder = ImageDecoder::default();
+        assert_eq!(
+            decoder.kitty(body.as_bytes()).unwrap().image.rgba,
+            vec![1, 2, 3, 4]
+        );
+
+        let mut decoder = ImageDecoder::new(ImageLimits {
+            enabled: true,
+            max_image_bytes: 16,
+        });
+        let body = kitty_rgb("a=T,f=32,s=4,v=4,i=1", &[0; 64]);
+        assert!(decoder.kitty(&body).is_none());
+        assert!(decoder.take_responses().starts_with(b"\x1b_Gi=1;EFBIG"));
+    }
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut data = Vec::new();
+        image::RgbaImage::from_pixel(width, height, image::Rgba([10, 20, 30, 255]))
+            .write_to(
+                &mut std::io::Cursor::new(&mut data),
+                image::ImageFormat::Png,
+            )
+            .unwrap();
+        data
+    }
+
+    #[test]
+    fn test_kitty_png() {
+        let body = format!("Ga=T,f=100;{}", base64::encode(&png(3, 2)));
+        let placement = ImageDecoder::default().kitty(body.as_bytes()).unwrap();
+        assert_eq!((placement.image.width, placement.image.height), (3, 2));
+        assert_eq!(&placement.image.rgba[..4], &[10, 20, 30, 255]);
+    }
+
+    #[test]
+    fn test_iterm2_file() {
+        let mut decoder = ImageDecoder::default();
+        let body = format!(
+            "File=name=eC5wbmc=;size=100;width=50%;preserveAspectRatio=0;inline=1:{}",
+            base64::encode(&png(4, 4))
+        );
+        let placement = decoder.iterm2(body.as_bytes()).unwrap();
+        assert_eq!(placement.image.width, 4);
+        assert_eq!(placement.width, Some(Extent::Percent(50)));
+        assert!(!placement.preserve_aspect);
+
+        // Downloads aren't images to show
+        let body = format!("File=size=100:{}", base64::encode(&png(4, 4)));
+        assert!(decoder.iterm2(body.as_bytes()).is_none());
+
+        let data = base64::encode(&png(2, 5));
+        let (first, second) = data.split_at(20);
+        assert!(decoder.iterm2(b"MultipartFile=inline=1").is_none());
+        assert!(decoder
+            .iterm2(format!("FilePart={}", first).as_bytes())
+            .is_none());
+        assert!(decoder
+            .iterm2(format!("FilePart={}", second).as_bytes())
+            .is_none());
+        assert_eq!(decoder.iterm2(b"FileEnd").unwrap().image.height, 5);
+    }
+
+    fn sixel(decoder: &mut ImageDecoder, data: &[u8]) -> Option<Placement> {
+        decoder.sixel_start();
+        for &byte in data {
+            decoder.sixel_put(byte);
+        }
+        decoder.sixel_finish()
+    }
+
+    #[test]
+    fn test_sixel() {
+        let mut decoder = ImageDecoder::default();
+        // Two red columns six pixels high, then a green band below
+        let placement = sixel(&mut decoder, b"\"1;1;3;12#1;2;100;0;0#1~~-#2;2;0;100;0!3@").unwrap();
+        let image = &placement.image;
+        assert_eq!((image.width, image.height), (3, 12));
+        assert_eq!(&image.rgba[..4], &[255, 0, 0, 255]);
+        assert_eq!(&image.rgba[8..12], &TRANSPARENT);
+        let green_row = 6 * 3 * 4;
+        assert_eq!(
+            &image.rgba[green_row + 8..green_row + 12],
+            &[0, 255, 0, 255]
+        );
+        assert_eq!(&image.rgba[green_row + 12..green_row + 16], &TRANSPARENT);
+        assert_eq!(placement.cursor, CursorAfter::BelowImage);
+
+        // HLS hue 120 is red on DEC terminals
+        let placement = sixel(&mut decoder, b"#5;1;120;50;100#5@").unwrap();
+        assert_eq!(&placement.image.rgba[..4], &[255, 0, 0, 255]);
+
+        assert!(sixel(&mut decoder, b"").is_none());
+        assert!(sixel(&mut decoder, b"!99999999~").is_none());
+    }
+
+    #[test]
+    fn test_sixel_pixel_cap_holds_as_the_image_grows() {
+        let mut decoder = ImageDecoder::default();
+        let max_pixels = decoder.limits.max_image_bytes / 4;
+
+        // A wide raster size within the cap, then bands that take the height
+        // past it one at a time
+        let mut data = format!("\"1;1;{};1", max_pixels / 2).into_bytes();
+        data.extend(b"~-".repeat(1000));
+        data.push(b'~');
+        assert!(sixel(&mut decoder, &data).is_none());
+
+        // Bands first, then a raster size that makes them too large
+        let mut data = b"~-".repeat(10);
+        data.extend(format!("\"1;1;{};1~", max_pixels / 10).into_bytes());
+        assert!(sixel(&mut decoder, &data).is_none());
+
+        // Drawing the whole cap is fine
+        let data = format!("!{}~", max_pixels / 6);
+        assert!(sixel(&mut decoder, data.as_bytes()).is_some());
+    }
+
+    #[test]
+    fn test_display_size() {
+        let image = InlineImage::new(200, 100, vec![0; 200 * 100 * 4]);
+        let mut placement = Placement::new(image, CursorAfter::AfterImage);
+        assert_eq!(placement.display_size((10.0, 20.0), 1000.0), (200.0, 100.0));
+        assert_eq!(placement.display_size((10.0, 20.0), 100.0), (100.0, 50.0));
+
+        placement.width = Some(Extent::Cells(10));
+        assert_eq!(placement.display_size((10.0, 20.0), 1000.0), (100.0, 50.0));
+        placement.height = Some(Extent::Cells(10));
+        assert_eq!(placement.display_size((10.0, 20.0), 1000.0), (100.0, 50.0));
+        placement.preserve_aspect = false;
+        assert_eq!(placement.display_size((10.0, 20.0), 1000.0), (100.0, 200.0));
+    }
+
+    #[test]
+    fn test_apc_splitter() {
+        let mut splitter = ApcSplitter::new(1024);
+        assert_eq!(
+            splitter.split(b"ab\x1b_Gi=1\x1b\\cd\x1b[0m"),
+            vec![
+                ApcPiece::Bytes(b"ab"),
+                ApcPiece::Apc(b"Gi=1".to_vec()),
+                ApcPiece::Bytes(b"cd\x1b[0m"),
+            ]
+        );
+
+        // An ESC at the end of a chunk waits for the next one
+        assert_eq!(splitter.split(b"x\x1b"), vec![ApcPiece::Bytes(b"x")]);
+        assert_eq!(
+            splitter.split(b"[1m"),
+            vec![ApcPiece::Bytes(b"\x1b"), ApcPiece::Bytes(b"[1m")]
+        );
+        assert_eq!(splitter.split(b"\x1b"), vec![]);
+        assert_eq!(splitter.split(b"_Gm=1;AA"), vec![]);
+        assert_eq!(
+            splitter.split(b"AA\x1b\\"),
+            vec![ApcPiece::Apc(b"Gm=1;AAAA".to_vec())]
+        );
+    }
+}
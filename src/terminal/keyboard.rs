@@ -0,0 +1,161 @@
+//! Keyboard Modes
+//!
+//! Tracks how the application wants keys encoded: DECCKM (mode 1) switches
+//! the cursor keys to their `SS3` forms, DECKPAM / DECKPNM (`ESC =` /
+//! `ESC >`) select the application keypad, and xterm's modifyOtherKeys
+//! (`CSI > 4 ; n m`) asks for modified keys that have no control-character
+//! form to be sent as `CSI 27 ; mods ; code ~`.
+//!
+//! Applications that understand the kitty keyboard protocol push, pop and set
+//! its progressive enhancement flags (`CSI > flags u`, `CSI < n u`,
+//! `CSI = flags ; mode u`); [`KittyKeyboard`] is one screen's stack of them.
+
+/// Kitty flag: report Escape, Alt and Ctrl combinations as unambiguous `CSI u`
+pub const KITTY_DISAMBIGUATE: u8 = 1;
+/// Kitty flag: report key repeats and releases
+pub const KITTY_REPORT_EVENT_TYPES: u8 = 2;
+/// Kitty flag: report the shifted key alongside the base key
+pub const KITTY_REPORT_ALTERNATE_KEYS: u8 = 4;
+/// Kitty flag: report every key, including text keys, as an escape code
+pub const KITTY_REPORT_ALL_KEYS: u8 = 8;
+/// Kitty flag: include the typed text in reports
+pub const KITTY_REPORT_TEXT: u8 = 16;
+
+/// Deepest the flag stack may grow; older entries are dropped beyond this
+const KITTY_STACK_LIMIT: usize = 16;
+
+/// Key encoding modes set by the application
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct KeyboardModes {
+    /// DECCKM: cursor keys and Home/End send `SS3 A` rather than `CSI A`
+    pub application_cursor: bool,
+    /// DECKPAM: the numeric keypad sends application sequences
+    pub application_keypad: bool,
+    /// modifyOtherKeys level: 0 off, 1 for otherwise ambiguous keys, 2 for all
+    /// modified keys
+    pub modify_other_keys: u8,
+}
+
+impl KeyboardModes {
+    /// Apply an xterm key modifier option (`CSI > Pp ; Pv m`). Only
+    /// modifyOtherKeys (Pp = 4) is tracked; a missing value resets it.
+    pub fn set_key_modifier_option(&mut self, resource: u16, value: Option<u16>) {
+        if resource == 4 {
+            self.modify_other_keys = value.unwrap_or(0).min(2) as u8;
+        }
+    }
+}
+
+/// One screen's stack of kitty keyboard flags. The top entry is in effect;
+/// with nothing pushed the flags are those last set on the base entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KittyKeyboard {
+    stack: Vec<u8>,
+}
+
+impl Default for KittyKeyboard {
+    fn default() -> Self {
+        Self { stack: vec![0] }
+    }
+}
+
+impl KittyKeyboard {
+    /// Flags currently in effect
+    pub fn flags(&self) -> u8 {
+        self.stack.last().copied().unwrap_or(0)
+    }
+
+    /// `CSI > flags u`: push new flags
+    pub fn push(&mut self, flags: u8) {
+        if self.stack.len() > KITTY_STACK_LIMIT {
+            self.stack.remove(1);
+        }
+        self.stack.push(flags & 0x1f);
+    }
+
+    /// `CSI < n u`: pop `n` entries; popping everything resets the flags
+    pub fn pop(&mut self, n: usize) {
+        let keep = self.stack.len().saturating_sub(n).max(1);
+        if n >= self.stack.len() {
+            self.stack[0] = 0;
+        }
+        self.stack.truncate(keep);
+    }
+
+    /// `CSI = flags ; mode u`: replace (1), add (2) or remove (3) flags in
+    /// the current entry
+    pub fn set(&mut self, flags: u8, mode: u16) {
+        let flags = flags & 0x1f;
+        if let Some(top) = self.stack.last_mut() {
+            match mode {
+                2 => *top |= flags,
+                3 => *top &= !flags,
+                _ => *top = flags,
+            }
+        }
+    }
+}
+
+/// xterm modifier parameter: 1 plus 1 for Shift, 2 for Alt and 4 for Ctrl.
+/// A value of 1 means no modifier is held.
+pub fn modifier_param(shift: bool, alt: bool, ctrl: bool) -> u8 {
+    1 + u8::from(shift) + 2 * u8::from(alt) + 4 * u8::from(ctrl)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_modifier_param() {
+        assert_eq!(modifier_param(false, false, false), 1);
+        assert_eq!(modifier_param(true, false, false), 2);
+        assert_eq!(modifier_param(false, true, false), 3);
+        assert_eq!(modifier_param(false, false, true), 5);
+        assert_eq!(modifier_param(true, true, true), 8);
+    }
+
+    #[test]
+    fn test_kitty_flag_stack() {
+        let mut kitty = KittyKeyboard::default();
+        assert_eq!(kitty.flags(), 0);
+        kitty.push(KITTY_DISAMBIGUATE);
+        kitty.push(KITTY_DISAMBIGUATE | KITTY_REPORT_ALL_KEYS);
+        assert_eq!(kitty.flags(), 9);
+        kitty.pop(1);
+        assert_eq!(kitty.flags(), 1);
+
+        kitty.set(KITTY_REPORT_EVENT_TYPES, 2);
+        assert_eq!(kitty.flags(), 3);
+        kitty.set(KITTY_DISAMBIGUATE, 3);
+        assert_eq!(kitty.flags(), 2);
+        kitty.set(KITTY_REPORT_TEXT, 1);
+        assert_eq!(kitty.flags(), 16);
+
+        kitty.pop(5);
+        assert_eq!(kitty, KittyKeyboard::default());
+    }
+
+    #[test]
+    fn test_kitty_stack_is_bounded() {
+        let mut kitty = KittyKeyboard::default();
+        for flags in 0..100 {
+            kitty.push(flags % 32);
+        }
+        assert!(kitty.stack.len() <= KITTY_STACK_LIMIT + 1);
+        assert_eq!(kitty.flags(), 99 % 32);
+        kitty.pop(1);
+        assert_eq!(kitty.flags(), 98 % 32);
+    }
+
+    #[test]
+    fn test_modify_other_keys_option() {
+        let mut modes = KeyboardModes::default();
+        modes.set_key_modifier_option(4, Some(2));
+        assert_eq!(modes.modify_other_keys, 2);
+        modes.set_key_modifier_option(1, Some(1));
+        assert_eq!(modes.modify_other_keys, 2);
+        modes.set_key_modifier_option(4, None);
+        assert_eq!(modes.modify_other_keys, 0);
+    }
+}
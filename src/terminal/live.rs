@@ -0,0 +1,433 @@
+//! Live Block Output
+//!
+//! The unsettled end of a command's block output, kept on a small
+//! [`TerminalGrid`] as tall as the screen, so programs rewrite it as they
+//! would on a terminal: `\r`, backspace and cursor movement reach back over
+//! it and erasing clears it. Progress bars from cargo, pip, wget and npm, and
+//! docker's bar per layer, collapse to what a terminal would show.
+//!
+//! A line settles once it scrolls off the top of the grid, since no program
+//! can reach it any more. Settled lines become [`OutputLine`]s and never
+//! change again.
+
+use crate::models::output_line::{AnsiCode, Hyperlink};
+use crate::models::OutputLine;
+use crate::terminal::grid::{Cell, CellStyle, TerminalGrid};
+use crate::terminal::width;
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+/// Columns the grid starts with; it widens as lines grow past them
+const START_COLS: usize = 256;
+
+/// Columns the grid widens to at most; longer lines wrap
+const MAX_COLS: usize = 4096;
+
+/// Lines of block output that programs can still rewrite
+pub struct LiveLines {
+    /// The lines programs can reach, the cursor among them
+    grid: TerminalGrid,
+    /// Columns printed to on each line scrolled off and on the screen, top
+    /// to bottom, so spaces a program printed aren't trimmed as blanks
+    printed: VecDeque<usize>,
+}
+
+impl std::fmt::Debug for LiveLines {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LiveLines")
+            .field("rows", &self.grid.rows())
+            .field("cols", &self.grid.cols())
+            .field("cursor", &self.grid.cursor_position())
+            .finish()
+    }
+}
+
+impl LiveLines {
+    /// Live lines of which programs can reach the last `height`
+    pub fn new(height: usize) -> Self {
+        let height = height.max(1);
+        Self {
+            grid: TerminalGrid::new(height, START_COLS),
+            printed: VecDeque::from(vec![0; height]),
+        }
+    }
+
+    /// Change how many lines programs can reach, as when the screen resizes.
+    /// Lines above the cursor that fall out of reach settle with the next.
+    pub fn set_height(&mut self, height: usize) {
+        let height = height.max(1);
+        let (row, _) = self.grid.cursor_position();
+        if row >= height {
+            self.scroll_off(row + 1 - height);
+        }
+        self.grid.resize(height, self.grid.cols());
+        self.printed.resize(self.grid.scrollback_len() + height, 0);
+    }
+
+    /// Forget every line, the rendition and any open link
+    pub fn clear(&mut self) {
+        *self = Self::new(self.grid.rows());
+    }
+
+    /// Whether no text is waiting to settle
+    pub fn is_empty(&self) -> bool {
+        self.grid.scrollback_len() == 0 && self.grid.lines().iter().all(|line| is_blank(line))
+    }
+
+    /// Column the cursor is in
+    pub fn cursor_col(&self) -> usize {
+        self.grid.cursor_position().1
+    }
+
+    /// Start or end the OSC 8 link new text is printed in
+    pub fn set_link(&mut self, link: Option<Arc<str>>) {
+        let uri = link.as_deref().unwrap_or_default();
+        self.advance(format!("\x1b]8;;{}\x1b\\", uri).as_bytes());
+    }
+
+    /// Print a character at the cursor, over whatever is there
+    pub fn print(&mut self, c: char) {
+        // Widen rather than wrap, so a line stays one line
+        let cols = self.grid.cols();
+        if self.cursor_col() + width::char_width(c) >= cols && cols < MAX_COLS {
+            self.grid.resize(self.grid.rows(), (cols * 2).min(MAX_COLS));
+        }
+        let mut buf = [0; 4];
+        self.advance(c.encode_utf8(&mut buf).as_bytes());
+        let (row, col) = self.grid.cursor_position();
+        let line = self.grid.scrollback_len() + row;
+        self.printed[line] = self.printed[line].max(col);
+    }
+
+    /// Return to the start of the line
+    pub fn carriage_return(&mut self) {
+        self.advance(b"\r");
+    }
+
+    /// Move back a column
+    pub fn backspace(&mut self) {
+        self.advance(b"\x08");
+    }
+
+    /// Move to the next tab stop
+    pub fn tab(&mut self) {
+        self.advance(b"\t");
+    }
+
+    /// Move to the start of the next line, scrolling the top line off if the
+    /// cursor is on the last
+    pub fn newline(&mut self) {
+        self.advance(b"\r\n");
+    }
+
+    /// Apply a complete CSI sequence. Mode changes and scroll regions belong
+    /// to the TUI overlay and are ignored, so lines always settle off the
+    /// top of the primary screen; replies to queries are dropped, as the
+    /// output processor's caller answers those.
+    pub fn csi(&mut self, seq: &str) {
+        let body = seq.strip_prefix("\x1b[").unwrap_or_default();
+        let mode = body.starts_with('?') && (body.ends_with('h') || body.ends_with('l'));
+        if mode || body.ends_with('r') {
+            return;
+        }
+        self.advance(seq.as_bytes());
+        self.grid.take_responses();
+        // Erasing and moving lines leave the columns printed to unknown;
+        // trailing blanks are trimmed from then on
+        if !body.ends_with('m') {
+            self.printed.iter_mut().for_each(|cols| *cols = 0);
+        }
+    }
+
+    /// Feed the grid, opening a line's worth of printed columns for each
+    /// line scrolled on
+    fn advance(&mut self, bytes: &[u8]) {
+        self.grid.advance(bytes);
+        self.printed
+            .resize(self.grid.scrollback_len() + self.grid.rows(), 0);
+    }
+
+    /// Scroll `count` lines off the top, keeping the cursor on its line
+    fn scroll_off(&mut self, count: usize) {
+        if count > 0 {
+            self.advance(format!("\x1b[{count}S\x1b[{count}A").as_bytes());
+        }
+    }
+
+    /// Take the lines out of programs' reach
+    pub fn settle(&mut self) -> Vec<OutputLine> {
+        let lines = self.grid.take_scrollback();
+        lines
+            .iter()
+            .zip(self.printed.drain(..lines.len()))
+            .map(|(line, printed)| output_line(line, printed))
+            .collect()
+    }
+
+    /// Take every line above the cursor's, which may still be in progress
+    pub fn settle_above_last(&mut self) -> Vec<OutputLine> {
+        let (row, _) = self.grid.cursor_position();
+        self.scroll_off(row);
+        self.settle()
+    }
+
+    /// Take every line, leaving the cursor at the start of a new one
+    pub fn settle_all(&mut self) -> Vec<OutputLine> {
+        let mut settled = self.settle();
+        settled.extend(self.lines());
+        self.advance(b"\x1b[2J\x1b[H");
+        self.printed.iter_mut().for_each(|cols| *cols = 0);
+        if self.grid.cols() > START_COLS {
+            self.grid.resize(self.grid.rows(), START_COLS);
+        }
+        settled
+    }
+
+    /// The unsettled lines as they stand: down to the cursor's line or the
+    /// last one written, whichever is lower
+    pub fn lines(&self) -> Vec<OutputLine> {
+        let printed = self.printed.range(self.grid.scrollback_len()..);
+        self.grid.lines()[..self.used_rows()]
+            .iter()
+            .zip(printed)
+            .map(|(line, &printed)| output_line(line, printed))
+            .collect()
+    }
+
+    /// The last line, the one in progress
+    pub fn last_line(&self) -> OutputLine {
+        let row = self.used_rows() - 1;
+        let printed = self.printed[self.grid.scrollback_len() + row];
+        output_line(&self.grid.lines()[row], printed)
+    }
+
+    /// Rows from the top down to the cursor's or the last written
+    fn used_rows(&self) -> usize {
+        let (row, _) = self.grid.cursor_position();
+        let printed = self.printed.range(self.grid.scrollback_len()..);
+        let written = self
+            .grid
+            .lines()
+            .iter()
+            .zip(printed)
+            .rposition(|(line, &printed)| printed > 0 || !is_blank(line))
+            .unwrap_or(0);
+        row.max(written) + 1
+    }
+}
+
+impl Default for LiveLines {
+    fn default() -> Self {
+        Self::new(1)
+    }
+}
+
+/// Whether a cell shows nothing: a space with no link and no rendition but
+/// perhaps the background erasing left behind
+fn is_blank_cell(cell: &Cell) -> bool {
+    let style = CellStyle {
+        bg: CellStyle::default().bg,
+        ..cell.style
+    };
+    cell.ch == ' ' && cell.extra.is_none() && cell.link.is_none() && style == CellStyle::default()
+}
+
+/// Whether a line shows nothing
+fn is_blank(line: &[Cell]) -> bool {
+    line.iter().all(is_blank_cell)
+}
+
+/// A line's text without the blanks trailing it past the `printed` columns,
+/// with an SGR code wherever the rendition changes and a reset at the end, so
+/// no style runs on into the next line
+fn output_line(cells: &[Cell], printed: usize) -> OutputLine {
+    let end = cells
+        .iter()
+        .rposition(|cell| !is_blank_cell(cell))
+        .map_or(0, |last| last + 1)
+        .max(printed.min(cells.len()));
+    let mut text = String::new();
+    let mut codes = Vec::new();
+    let mut links: Vec<Hyperlink> = Vec::new();
+    let mut style = CellStyle::default();
+    for cell in cells[..end].iter().filter(|cell| !cell.is_spacer()) {
+        if cell.style != style {
+            codes.push(AnsiCode {
+                code: cell.style.sgr(),
+                position: text.len(),
+            });
+            style = cell.style;
+        }
+        let start = text.len();
+        cell.push_text(&mut text);
+        if let Some(uri) = &cell.link {
+            match links.last_mut() {
+                Some(link) if link.range.end == start && *link.uri == **uri => {
+                    link.range.end = text.len();
+                }
+                _ => links.push(Hyperlink {
+                    range: start..text.len(),
+                    uri: uri.to_string(),
+                }),
+            }
+        }
+    }
+    if style != CellStyle::default() {
+        codes.push(AnsiCode {
+            code: CellStyle::default().sgr(),
+            position: text.len(),
+        });
+    }
+    let mut line = OutputLine::with_ansi_codes(text, codes, 0);
+    line.hyperlinks = links;
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed(live: &mut LiveLines, text: &str) {
+        let mut chars = text.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\r' => live.carriage_return(),
+                '\n' => live.newline(),
+                '\x08' => live.backspace(),
+                '\t' => live.tab(),
+                '\x1b' => {
+                    let mut seq = String::from(c);
+                    for c in chars.by_ref() {
+                        seq.push(c);
+                        if c.is_ascii_alphabetic() {
+                            break;
+                        }
+                    }
+                    live.csi(&seq);
+                }
+                _ => live.print(c),
+            }
+        }
+    }
+
+    fn texts(lines: Vec<OutputLine>) -> Vec<String> {
+        lines.into_iter().map(|line| line.text).collect()
+    }
+
+    #[test]
+    fn test_carriage_return_overwrites() {
+        let mut live = LiveLines::new(24);
+        feed(&mut live, "abc\rX");
+        assert_eq!(live.last_line().text, "Xbc");
+        feed(&mut live, "\r 50%\r\x1b[K100%");
+        assert_eq!(live.last_line().text, "100%");
+        feed(&mut live, "\x08\x08!");
+        assert_eq!(live.last_line().text, "10!%");
+    }
+
+    #[test]
+    fn test_progress_lines_collapse() {
+        // docker pull: a line per layer, rewritten from below
+        let mut live = LiveLines::new(24);
+        feed(&mut live, "a: Waiting\nb: Waiting\nc: Waiting\n");
+        feed(&mut live, "\x1b[2A\x1b[2Kb: Downloading\r\x1b[2B");
+        feed(&mut live, "\x1b[3A\x1b[2Ka: Done\r\x1b[3B");
+        feed(&mut live, "\x1b[1F\x1b[Kc: Done\x1b[1E");
+        assert_eq!(
+            texts(live.lines()),
+            ["a: Done", "b: Downloading", "c: Done", ""]
+        );
+        assert!(live.settle().is_empty());
+        assert_eq!(
+            texts(live.settle_all()),
+            ["a: Done", "b: Downloading", "c: Done", ""]
+        );
+        assert!(live.is_empty());
+    }
+
+    #[test]
+    fn test_lines_settle_out_of_reach() {
+        let mut live = LiveLines::new(2);
+        feed(&mut live, "1\n2\n3\n");
+        assert_eq!(texts(live.settle()), ["1", "2"]);
+        // The cursor can't reach back past the top; the blank line it left
+        // holds nothing yet
+        feed(&mut live, "\x1b[5Ax");
+        assert_eq!(texts(live.lines()), ["x"]);
+        assert!(live.settle_above_last().is_empty());
+        feed(&mut live, "\x1b[1E4");
+        assert_eq!(texts(live.settle_above_last()), ["x"]);
+        assert_eq!(live.last_line().text, "4");
+    }
+
+    #[test]
+    fn test_wide_characters_and_erase() {
+        let mut live = LiveLines::new(24);
+        feed(&mut live, "中文\rx");
+        assert_eq!(live.last_line().text, "x 文");
+        // Erased cells are blank again, and blanks don't trail a line
+        feed(&mut live, "\x1b[4G\x1b[1K");
+        assert_eq!(live.last_line().text, "");
+        feed(&mut live, "\te\u{301}");
+        assert_eq!(live.last_line().text, "        e\u{301}");
+        assert_eq!(live.cursor_col(), 9);
+    }
+
+    #[test]
+    fn test_styles_and_links_follow_cells() {
+        let mut live = LiveLines::new(24);
+        live.set_link(Some(Arc::from("https://example.com")));
+        feed(&mut live, "\x1b[32mok\x1b[0m");
+        live.set_link(None);
+        feed(&mut live, " done\rO");
+        let line = live.last_line();
+        assert_eq!(line.text, "Ok done");
+        let codes: Vec<(&str, usize)> = line
+            .ansi_codes
+            .iter()
+            .map(|code| (code.code.as_str(), code.position))
+            .collect();
+        assert_eq!(codes, [("\x1b[0;32m", 1), ("\x1b[0m", 2)]);
+        assert_eq!(line.hyperlinks.len(), 1);
+        assert_eq!(line.hyperlinks[0].range, 1..2);
+
+        feed(&mut live, "\r\x1b[1;31mE");
+        let line = live.last_line();
+        assert_eq!(line.ansi_codes[0].code, "\x1b[0;1;31m");
+        assert_eq!(line.ansi_codes.last().unwrap().code, "\x1b[0m");
+    }
+
+    #[test]
+    fn test_long_lines_widen_the_grid() {
+        let mut live = LiveLines::new(24);
+        let long = "x".repeat(START_COLS * 3);
+        feed(&mut live, &long);
+        assert_eq!(live.last_line().text, long);
+        feed(&mut live, "\n");
+        assert_eq!(texts(live.settle_all()), [long.as_str(), ""]);
+
+        // Past the widest the grid goes, lines wrap rather than lose text
+        let longest = "y".repeat(MAX_COLS + 10);
+        feed(&mut live, &longest);
+        let lines = texts(live.settle_all());
+        assert_eq!(lines.concat(), longest);
+        assert_eq!(lines[0].len(), MAX_COLS);
+    }
+
+    #[test]
+    fn test_printed_spaces_are_kept() {
+        let mut live = LiveLines::new(2);
+        feed(&mut live, "  \nab  \n");
+        assert_eq!(texts(live.settle_all()), ["  ", "ab  ", ""]);
+        feed(&mut live, "x   \x1b[3D\x1b[K");
+        assert_eq!(live.last_line().text, "x");
+    }
+
+    #[test]
+    fn test_screen_modes_and_queries_are_ignored() {
+        let mut live = LiveLines::new(2);
+        feed(&mut live, "\x1b[?1049h\x1b[2;2r\x1b[6n1\n2\n3\n");
+        assert_eq!(texts(live.settle()), ["1", "2"]);
+        assert!(live.grid.take_responses().is_empty());
+    }
+}
@@ -4,20 +4,44 @@
 //! for processing commands and managing terminal state.
 
 pub mod ansi_parser;
+pub mod base64;
+pub mod clipboard;
+pub mod fullscreen;
+pub mod grid;
+pub mod hyperlink;
+pub mod images;
 pub mod input;
+pub mod keyboard;
+pub mod live;
+pub mod mouse;
 pub mod output;
 pub mod prompt;
+pub mod queries;
+pub mod resize;
+pub mod semantic;
 pub mod state;
+pub mod title;
+pub mod width;
 
 // Re-exports for convenience
 pub use ansi_parser::{AnsiAttribute, AnsiColor, AnsiParser, ParsedText};
+pub use clipboard::ClipboardRequest;
+pub use fullscreen::AltScreenDetector;
+pub use grid::{CellStyle, CursorShape, CursorStyle, Palette, TerminalGrid, Underline};
+pub use images::{ImageDecoder, ImageLimits, InlineImage, Placement};
 pub use input::{validation, CommandInputProcessor, InputResult};
+pub use keyboard::KeyboardModes;
+pub use mouse::{MouseButton, MouseEvent, MouseEventKind, MouseModifiers, MouseProtocol};
 pub use output::{segmentation, BufferStats, OutputChunk, OutputProcessor, StreamType};
 pub use prompt::{utils as prompt_utils, CommandCompletionDetector, PromptDetector};
+pub use queries::{ColorSlot, Query, QueryScanner};
+pub use resize::{GridSize, ResizeDebouncer};
+pub use semantic::{PromptMark, ReportedCwd, SemanticZone};
 pub use state::{
     BufferLine, Cursor, ScreenBuffer, TerminalDimensions, TerminalMode, TerminalState,
     TerminalStatus,
 };
+pub use title::{ProgramTitle, TitleChange};
 
 use crate::error::Result;
 use crate::models::{OutputLine, ShellType, TerminalSession};
@@ -41,19 +65,34 @@ pub struct Terminal {
     pty_manager: Arc<PtyManager>,
     /// Current PTY handle
     pty_handle: Option<PtyHandle>,
+    /// Whether the shell reports its working directory with OSC 7
+    reports_cwd: bool,
+    /// Host of the working directory when the shell reported a remote one
+    remote_host: Option<String>,
+    /// Title and icon name set by the shell and its programs
+    title: ProgramTitle,
+    /// Whether the terminal has focus, as last told by the app
+    focused: bool,
 }
 
 impl Terminal {
     /// Create a new terminal emulator
     pub fn new(session: TerminalSession, pty_manager: Arc<PtyManager>) -> Self {
+        // Programs can move up to rewrite output as far as the screen reaches
+        let mut output_processor = OutputProcessor::new();
+        output_processor.set_live_rows(TerminalDimensions::default().rows);
         Self {
             state: TerminalState::new(session),
             input_processor: CommandInputProcessor::new(),
-            output_processor: OutputProcessor::new(),
+            output_processor,
             prompt_detector: PromptDetector::new(),
             completion_detector: CommandCompletionDetector::new(),
             pty_manager,
             pty_handle: None,
+            reports_cwd: false,
+            remote_host: None,
+            title: ProgramTitle::default(),
+            focused: true,
         }
     }
 
@@ -329,10 +368,38 @@ impl Terminal {
     /// output processor.  This is useful for prompt detection: the shell
     /// writes a prompt string without a trailing newline, so it will sit
     /// in the processor's internal buffer until more data arrives.
-    pub fn peek_partial_line(&self) -> Option<&str> {
+    pub fn peek_partial_line(&self) -> Option<String> {
         self.output_processor.peek_partial_line()
     }
 
+    /// Column the cursor is in on the partial line
+    pub fn cursor_col(&self) -> usize {
+        self.output_processor.cursor_col()
+    }
+
+    /// Output lines the running program can still rewrite, as they stand
+    pub fn live_output_lines(&self) -> Vec<OutputLine> {
+        self.output_processor.live_lines()
+    }
+
+    /// Settle the output above the partial line, once the shell is back at
+    /// its prompt, and return the lines ready with it
+    pub fn settle_output(&mut self) -> Vec<OutputLine> {
+        self.output_processor.settle_lines();
+        self.output_processor.take_ready_lines()
+    }
+
+    /// Drain the OSC 133 prompt marks the shell has sent since the last call
+    pub fn take_prompt_marks(&mut self) -> Vec<PromptMark> {
+        self.output_processor.take_prompt_marks()
+    }
+
+    /// Whether the shell marks its prompts (OSC 133), so command blocks can
+    /// be split and finished from the marks instead of prompt heuristics
+    pub fn has_prompt_marks(&self) -> bool {
+        self.output_processor.has_prompt_marks()
+    }
+
     /// Check if terminal has pending output
     pub fn has_pending_output(&self) -> bool {
         self.output_processor.has_pending_lines()
@@ -362,6 +429,18 @@ impl Terminal {
     /// Resize terminal
     pub fn resize(&mut self, rows: usize, cols: usize) {
         self.state.set_dimensions(rows, cols);
+        self.output_processor.set_live_rows(rows);
+    }
+
+    /// Resize the terminal and its PTY so the child process sees the new size
+    pub async fn resize_pty(&mut self, size: GridSize) -> Result<()> {
+        self.resize(size.rows as usize, size.cols as usize);
+        if let Some(handle) = &self.pty_handle {
+            self.pty_manager
+                .resize_pty(handle, size.rows, size.cols)
+                .await?;
+        }
+        Ok(())
     }
 
     /// Get detected shell type
@@ -443,6 +522,86 @@ impl Terminal {
     pub fn set_working_directory(&mut self, path: std::path::PathBuf) {
         self.state.session.working_directory = path;
     }
+
+    /// Take the working directory the shell last reported with OSC 7
+    pub fn take_reported_cwd(&mut self) -> Option<ReportedCwd> {
+        let cwd = self.output_processor.take_reported_cwd();
+        self.reports_cwd |= cwd.is_some();
+        cwd
+    }
+
+    /// Whether the shell has reported its working directory with OSC 7, so
+    /// it need not be probed from the OS
+    pub fn reports_cwd(&self) -> bool {
+        self.reports_cwd
+    }
+
+    /// Host of the working directory, when it is on another machine
+    pub fn remote_host(&self) -> Option<&str> {
+        self.remote_host.as_deref()
+    }
+
+    /// Set the host of the working directory (`None` when it is local)
+    pub fn set_remote_host(&mut self, host: Option<String>) {
+        self.remote_host = host;
+    }
+
+    /// Title and icon name the shell and its programs have set
+    pub fn title(&self) -> &ProgramTitle {
+        &self.title
+    }
+
+    /// Apply titles set in block output (OSC 0/1/2); returns whether the
+    /// title changed
+    pub fn update_title(&mut self) -> bool {
+        let changes = self.output_processor.take_title_changes();
+        self.apply_title_changes(changes)
+    }
+
+    /// Apply titles set elsewhere, such as by an app in the TUI overlay;
+    /// returns whether the title changed
+    pub fn apply_title_changes(&mut self, changes: Vec<TitleChange>) -> bool {
+        changes
+            .into_iter()
+            .fold(false, |changed, change| self.title.apply(change) | changed)
+    }
+
+    /// Set how large images in block output may be, or turn them off
+    pub fn set_image_limits(&mut self, limits: ImageLimits) {
+        self.output_processor.set_image_limits(limits);
+    }
+
+    /// Memory taken by images block output stored for later placement
+    pub fn stored_image_bytes(&self) -> usize {
+        self.output_processor.stored_image_bytes()
+    }
+
+    /// Forget images block output stored for later placement, oldest first,
+    /// until `bytes` of them are gone; returns the memory they took
+    pub fn drop_stored_images(&mut self, bytes: usize) -> usize {
+        self.output_processor.drop_stored_images(bytes)
+    }
+
+    /// Forget the modes the program of a finished command set, such as
+    /// focus reporting
+    pub fn reset_modes(&mut self) {
+        self.output_processor.reset_modes();
+    }
+
+    /// Record whether the terminal has focus. Returns the focus event to
+    /// write to the PTY when focus changed and a program enabled focus
+    /// reporting (mode 1004).
+    pub fn set_focused(&mut self, focused: bool) -> Option<Vec<u8>> {
+        if focused == self.focused {
+            return None;
+        }
+        self.focused = focused;
+        if !self.output_processor.focus_reporting() {
+            return None;
+        }
+        let report: &[u8] = if focused { b"\x1b[I" } else { b"\x1b[O" };
+        Some(report.to_vec())
+    }
 }
 
 /// Terminal factory for creating terminals with different configurations
@@ -714,4 +873,42 @@ mod tests {
         assert_eq!(stats.current_line_length, 0);
         assert_eq!(stats.ansi_codes_count, 0);
     }
+
+    #[tokio::test]
+    async fn test_focus_events() {
+        let mut terminal = Terminal::new(create_test_session(), create_test_pty_manager());
+        assert_eq!(terminal.set_focused(false), None);
+        assert_eq!(terminal.set_focused(true), None);
+
+        terminal
+            .process_output(b"\x1b[?1004h", StreamType::Stdout)
+            .await
+            .unwrap();
+        assert_eq!(terminal.set_focused(true), None);
+        assert_eq!(terminal.set_focused(false), Some(b"\x1b[O".to_vec()));
+        assert_eq!(terminal.set_focused(false), None);
+        assert_eq!(terminal.set_focused(true), Some(b"\x1b[I".to_vec()));
+
+        terminal
+            .process_output(b"\x1b[?25;1004l", StreamType::Stdout)
+            .await
+            .unwrap();
+        assert_eq!(terminal.set_focused(false), None);
+
+        // Focus reporting ends with the command that turned it on
+        terminal
+            .process_output(b"\x1b[?1004h", StreamType::Stdout)
+            .await
+            .unwrap();
+        terminal.reset_modes();
+        assert_eq!(terminal.set_focused(true), None);
+        terminal
+            .process_output(
+                b"\x1b]133;C\x07\x1b[?1004h\x1b]133;D;0\x07",
+                StreamType::Stdout,
+            )
+            .await
+            .unwrap();
+        assert_eq!(terminal.set_focused(false), None);
+    }
 }
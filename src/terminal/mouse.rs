@@ -0,0 +1,319 @@
+//! xterm Mouse Reporting
+//!
+//! Tracks which mouse protocol the application asked for (DECSET 9, 1000,
+//! 1002, 1003 and the 1006 SGR encoding) and encodes pointer events into the
+//! bytes written back to the PTY.
+
+/// Which pointer events the application wants reported
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MouseTracking {
+    /// Mouse reporting disabled
+    #[default]
+    Off,
+    /// Mode 9: button presses only, no modifiers
+    X10,
+    /// Mode 1000: presses and releases
+    Normal,
+    /// Mode 1002: also motion while a button is held
+    ButtonEvent,
+    /// Mode 1003: all motion
+    AnyEvent,
+}
+
+/// How reports are encoded
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MouseEncoding {
+    /// `CSI M Cb Cx Cy` with each value offset by 32 (coordinates up to 223)
+    #[default]
+    Default,
+    /// Mode 1006: `CSI < Cb ; Cx ; Cy M` / `m`
+    Sgr,
+}
+
+/// Mouse protocol state set by the application
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MouseProtocol {
+    pub tracking: MouseTracking,
+    pub encoding: MouseEncoding,
+}
+
+impl MouseProtocol {
+    /// Apply a DECSET/DECRST private mode. Returns false for non-mouse modes.
+    pub fn set_mode(&mut self, mode: u16, enable: bool) -> bool {
+        let tracking = match mode {
+            9 => MouseTracking::X10,
+            1000 => MouseTracking::Normal,
+            1002 => MouseTracking::ButtonEvent,
+            1003 => MouseTracking::AnyEvent,
+            1006 => {
+                self.encoding = if enable {
+                    MouseEncoding::Sgr
+                } else {
+                    MouseEncoding::Default
+                };
+                return true;
+            }
+            _ => return false,
+        };
+        if enable {
+            self.tracking = tracking;
+        } else if self.tracking == tracking {
+            self.tracking = MouseTracking::Off;
+        }
+        true
+    }
+
+    /// Whether a DECSET mouse mode is on, or `None` for non-mouse modes
+    pub fn mode(&self, mode: u16) -> Option<bool> {
+        let tracking = match mode {
+            9 => MouseTracking::X10,
+            1000 => MouseTracking::Normal,
+            1002 => MouseTracking::ButtonEvent,
+            1003 => MouseTracking::AnyEvent,
+            1006 => return Some(self.encoding == MouseEncoding::Sgr),
+            _ => return None,
+        };
+        Some(self.tracking == tracking)
+    }
+
+    /// Whether any mouse reporting is enabled
+    pub fn is_enabled(&self) -> bool {
+        self.tracking != MouseTracking::Off
+    }
+
+    /// Encode an event for the PTY, or `None` if the current mode does not
+    /// report it (or the position cannot be expressed in the encoding)
+    pub fn encode(&self, event: &MouseEvent) -> Option<Vec<u8>> {
+        let reported = match (self.tracking, event.kind) {
+            (MouseTracking::Off, _) => false,
+            (MouseTracking::X10, kind) => kind == MouseEventKind::Press && !event.button.is_wheel(),
+            (_, MouseEventKind::Press | MouseEventKind::Release) => true,
+            (MouseTracking::ButtonEvent, MouseEventKind::Motion) => {
+                event.button != MouseButton::None
+            }
+            (MouseTracking::AnyEvent, MouseEventKind::Motion) => true,
+            (MouseTracking::Normal, MouseEventKind::Motion) => false,
+        };
+        // Wheel "buttons" have no release
+        if !reported || (event.kind == MouseEventKind::Release && event.button.is_wheel()) {
+            return None;
+        }
+
+        let mut code = event.button.code();
+        if event.kind == MouseEventKind::Motion {
+            code += 32;
+        }
+        if self.tracking != MouseTracking::X10 {
+            code += event.modifiers.code();
+        }
+
+        // Reports are 1-based
+        let col = event.col as u32 + 1;
+        let row = event.row as u32 + 1;
+
+        match self.encoding {
+            MouseEncoding::Sgr => {
+                let last = if event.kind == MouseEventKind::Release {
+                    'm'
+                } else {
+                    'M'
+                };
+                Some(format!("\x1b[<{};{};{}{}", code, col, row, last).into_bytes())
+            }
+            MouseEncoding::Default => {
+                // The legacy encoding cannot say which button was released
+                if event.kind == MouseEventKind::Release {
+                    code = (code & !0b11) | 3;
+                }
+                let byte = |value: u32| u8::try_from(value + 32).ok();
+                Some(vec![
+                    0x1b,
+                    b'[',
+                    b'M',
+                    byte(code as u32)?,
+                    byte(col)?,
+                    byte(row)?,
+                ])
+            }
+        }
+    }
+}
+
+/// Button involved in a mouse event
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MouseButton {
+    Left,
+    Middle,
+    Right,
+    WheelUp,
+    WheelDown,
+    /// Motion with no button held
+    None,
+}
+
+impl MouseButton {
+    fn code(self) -> u8 {
+        match self {
+            MouseButton::Left => 0,
+            MouseButton::Middle => 1,
+            MouseButton::Right => 2,
+            MouseButton::None => 3,
+            MouseButton::WheelUp => 64,
+            MouseButton::WheelDown => 65,
+        }
+    }
+
+    fn is_wheel(self) -> bool {
+        matches!(self, MouseButton::WheelUp | MouseButton::WheelDown)
+    }
+}
+
+/// What happened to the pointer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MouseEventKind {
+    Press,
+    Release,
+    Motion,
+}
+
+/// Modifier keys held during a mouse event
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MouseModifiers {
+    pub shift: bool,
+    pub alt: bool,
+    pub ctrl: bool,
+}
+
+impl MouseModifiers {
+    fn code(self) -> u8 {
+        (self.shift as u8) * 4 + (self.alt as u8) * 8 + (self.ctrl as u8) * 16
+    }
+}
+
+/// A pointer event in grid coordinates (zero-based)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MouseEvent {
+    pub kind: MouseEventKind,
+    pub button: MouseButton,
+    pub row: usize,
+    pub col: usize,
+    pub modifiers: MouseModifiers,
+}
+
+impl MouseEvent {
+    /// Create an event with no modifiers
+    pub fn new(kind: MouseEventKind, button: MouseButton, row: usize, col: usize) -> Self {
+        Self {
+            kind,
+            button,
+            row,
+            col,
+            modifiers: MouseModifiers::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn protocol(modes: &[u16]) -> MouseProtocol {
+        let mut protocol = MouseProtocol::default();
+        for &mode in modes {
+            protocol.set_mode(mode, true);
+        }
+        protocol
+    }
+
+    fn press(button: MouseButton, row: usize, col: usize) -> MouseEvent {
+        MouseEvent::new(MouseEventKind::Press, button, row, col)
+    }
+
+    #[test]
+    fn test_off_reports_nothing() {
+        let protocol = MouseProtocol::default();
+        assert!(!protocol.is_enabled());
+        assert_eq!(protocol.encode(&press(MouseButton::Left, 0, 0)), None);
+    }
+
+    #[test]
+    fn test_normal_press_release() {
+        let protocol = protocol(&[1000]);
+        assert_eq!(
+            protocol.encode(&press(MouseButton::Left, 4, 9)),
+            Some(b"\x1b[M *%".to_vec())
+        );
+        let release = MouseEvent::new(MouseEventKind::Release, MouseButton::Right, 0, 0);
+        assert_eq!(protocol.encode(&release), Some(b"\x1b[M#!!".to_vec()));
+    }
+
+    #[test]
+    fn test_x10_ignores_release_and_modifiers() {
+        let protocol = protocol(&[9]);
+        let mut event = press(MouseButton::Middle, 0, 0);
+        event.modifiers.ctrl = true;
+        assert_eq!(protocol.encode(&event), Some(b"\x1b[M!!!".to_vec()));
+        let release = MouseEvent::new(MouseEventKind::Release, MouseButton::Middle, 0, 0);
+        assert_eq!(protocol.encode(&release), None);
+    }
+
+    #[test]
+    fn test_motion_modes() {
+        let drag = MouseEvent::new(MouseEventKind::Motion, MouseButton::Left, 0, 0);
+        let hover = MouseEvent::new(MouseEventKind::Motion, MouseButton::None, 0, 0);
+
+        assert_eq!(protocol(&[1000]).encode(&drag), None);
+        assert_eq!(protocol(&[1002]).encode(&drag), Some(b"\x1b[M@!!".to_vec()));
+        assert_eq!(protocol(&[1002]).encode(&hover), None);
+        assert_eq!(
+            protocol(&[1003]).encode(&hover),
+            Some(b"\x1b[MC!!".to_vec())
+        );
+    }
+
+    #[test]
+    fn test_sgr_encoding() {
+        let protocol = protocol(&[1002, 1006]);
+        let mut event = press(MouseButton::Left, 299, 249);
+        event.modifiers = MouseModifiers {
+            shift: true,
+            alt: false,
+            ctrl: true,
+        };
+        assert_eq!(protocol.encode(&event), Some(b"\x1b[<20;250;300M".to_vec()));
+        let release = MouseEvent::new(MouseEventKind::Release, MouseButton::Right, 2, 3);
+        assert_eq!(protocol.encode(&release), Some(b"\x1b[<2;4;3m".to_vec()));
+    }
+
+    #[test]
+    fn test_wheel() {
+        let protocol = protocol(&[1000, 1006]);
+        assert_eq!(
+            protocol.encode(&press(MouseButton::WheelUp, 0, 0)),
+            Some(b"\x1b[<64;1;1M".to_vec())
+        );
+        assert_eq!(
+            protocol.encode(&press(MouseButton::WheelDown, 0, 0)),
+            Some(b"\x1b[<65;1;1M".to_vec())
+        );
+        let release = MouseEvent::new(MouseEventKind::Release, MouseButton::WheelUp, 0, 0);
+        assert_eq!(protocol.encode(&release), None);
+    }
+
+    #[test]
+    fn test_default_encoding_out_of_range() {
+        let protocol = protocol(&[1000]);
+        assert_eq!(protocol.encode(&press(MouseButton::Left, 0, 223)), None);
+        assert!(protocol.encode(&press(MouseButton::Left, 0, 222)).is_some());
+    }
+
+    #[test]
+    fn test_reset_only_matching_mode() {
+        let mut protocol = protocol(&[1003]);
+        protocol.set_mode(1000, false);
+        assert_eq!(protocol.tracking, MouseTracking::AnyEvent);
+        protocol.set_mode(1003, false);
+        assert_eq!(protocol.tracking, MouseTracking::Off);
+        assert!(!protocol.set_mode(25, true));
+    }
+}
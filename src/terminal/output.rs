@@ -1,14 +1,20 @@
 //! Output Processing and Segmentation
 //!
 //! Processes terminal output, segments it into logical chunks,
-//! and handles ANSI escape sequence parsing.
+//! and handles ANSI escape sequence parsing. Text goes through
+//! [`LiveLines`] first, so progress bars rewritten in place reach blocks in
+//! their final state.
 
 use crate::error::Result;
-use crate::models::output_line::AnsiCode;
 use crate::models::OutputLine;
-use crate::terminal::ansi_parser::{AnsiParser, ParsedText};
+use crate::terminal::hyperlink;
+use crate::terminal::images::{ImageDecoder, ImageLimits, Placement};
+use crate::terminal::live::LiveLines;
+use crate::terminal::semantic::{PromptMark, ReportedCwd, SemanticZone};
+use crate::terminal::title::TitleChange;
 use chrono::{DateTime, Utc};
 use std::collections::VecDeque;
+use std::sync::Arc;
 
 /// Tracks what kind of escape sequence we are accumulating.
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -23,21 +29,29 @@ enum EscapeState {
     Osc,
     /// Saw ESC inside an OSC sequence (possible ST = ESC \).
     OscEscapeSeen,
+    /// Inside an APC string: ESC _ ... ESC \ (kitty graphics).
+    Apc,
+    /// Saw ESC inside an APC string.
+    ApcEscapeSeen,
+    /// Inside a DCS string: ESC P ... ESC \ (Sixel).
+    Dcs,
+    /// Saw ESC inside a DCS string.
+    DcsEscapeSeen,
 }
 
+/// Longest OSC kept, apart from iTerm2 images
+const MAX_OSC_LEN: usize = 4096;
+
 /// Output processor for terminal streams
 #[derive(Debug)]
 pub struct OutputProcessor {
-    /// ANSI parser for escape sequences
-    ansi_parser: AnsiParser,
     /// Buffer for incoming raw output
     raw_buffer: Vec<u8>,
     /// Processed output lines
     processed_lines: VecDeque<OutputLine>,
-    /// Current line being built
-    current_line: String,
-    /// Current ANSI codes for the line
-    current_ansi_codes: Vec<AnsiCode>,
+    /// Lines programs can still rewrite, the last being the one in progress;
+    /// only that one until `set_live_rows` gives the screen's height
+    live: LiveLines,
     /// Line number counter
     line_counter: usize,
     /// State machine for escape sequence parsing
@@ -45,6 +59,24 @@ pub struct OutputProcessor {
     /// Buffer for the escape sequence currently being accumulated (so it can
     /// be discarded or handed to the ANSI parser once complete).
     escape_buf: String,
+    /// The sequence outgrew its limit; the rest of it is dropped
+    escape_too_long: bool,
+    /// Decoder for inline images
+    images: ImageDecoder,
+    /// Part of the prompt cycle the shell is in, once it has sent an OSC 133
+    /// mark
+    zone: Option<SemanticZone>,
+    /// Whether an output-start mark has been seen, so the input zone can be
+    /// trusted to hold only the command echo
+    seen_output_mark: bool,
+    /// Marks seen since they were last taken
+    prompt_marks: Vec<PromptMark>,
+    /// Latest OSC 7 working directory not yet taken
+    reported_cwd: Option<ReportedCwd>,
+    /// OSC 0/1/2 titles not yet taken, in order
+    title_changes: Vec<TitleChange>,
+    /// Mode 1004: the program asked for `CSI I` / `CSI O` focus events
+    focus_reporting: bool,
     /// Maximum buffer size
     max_buffer_size: usize,
 }
@@ -73,14 +105,20 @@ impl OutputProcessor {
     /// Create a new output processor
     pub fn new() -> Self {
         Self {
-            ansi_parser: AnsiParser::new(),
             raw_buffer: Vec::new(),
             processed_lines: VecDeque::new(),
-            current_line: String::new(),
-            current_ansi_codes: Vec::new(),
+            live: LiveLines::default(),
             line_counter: 0,
             escape_state: EscapeState::None,
             escape_buf: String::new(),
+            escape_too_long: false,
+            images: ImageDecoder::default(),
+            zone: None,
+            seen_output_mark: false,
+            prompt_marks: Vec::new(),
+            reported_cwd: None,
+            title_changes: Vec::new(),
+            focus_reporting: false,
             max_buffer_size: 10 * 1024 * 1024, // 10MB
         }
     }
@@ -139,7 +177,7 @@ impl OutputProcessor {
         &mut self,
         data: &[u8],
         timestamp: DateTime<Utc>,
-        stream_type: StreamType,
+        _stream_type: StreamType,
     ) -> Result<()> {
         let text = String::from_utf8_lossy(data);
 
@@ -158,16 +196,16 @@ impl OutputProcessor {
                         self.escape_buf.clear();
                         self.escape_buf.push(ch);
                     }
-                    '\n' => self.emit_line(timestamp, stream_type)?,
-                    '\r' => {
-                        let next_is_newline = i + 1 < len && chars[i + 1] == '\n';
-                        if !next_is_newline {
-                            self.current_line.clear();
-                            self.current_ansi_codes.clear();
-                        }
-                    }
-                    '\x07' => {} // BEL outside of escape — ignore
-                    _ => self.current_line.push(ch),
+                    '\n' => self.newline(timestamp),
+                    // Return to the start of the line, to write over it
+                    '\r' => self.live.carriage_return(),
+                    '\x08' => self.live.backspace(),
+                    // Tab stops count display columns, so columns after wide
+                    // characters line up
+                    '\t' => self.live.tab(),
+                    // BEL and other controls outside of escapes — ignore
+                    _ if ch.is_control() => {}
+                    _ => self.live.print(ch),
                 },
 
                 // ----- Saw ESC, decide which family -----
@@ -176,6 +214,8 @@ impl OutputProcessor {
                     match ch {
                         '[' => self.escape_state = EscapeState::Csi,
                         ']' => self.escape_state = EscapeState::Osc,
+                        '_' => self.escape_state = EscapeState::Apc,
+                        'P' => self.escape_state = EscapeState::Dcs,
                         // Two-character sequences: ESC ( ESC ) ESC = ESC > ESC M etc.
                         '(' | ')' | '*' | '+' | '=' | '>' | 'M' | '7' | '8' | 'c' | 'D' | 'E'
                         | 'H' | 'N' | 'O' => {
@@ -196,14 +236,8 @@ impl OutputProcessor {
                     self.escape_buf.push(ch);
                     if ch.is_ascii_alphabetic() || ch == '@' || ch == '`' {
                         let seq = std::mem::take(&mut self.escape_buf);
-                        let text_pos = self.current_line.len();
-                        if let Ok(parsed) = self.ansi_parser.parse(&seq) {
-                            for code in &parsed.ansi_codes {
-                                let mut c = code.clone();
-                                c.position = text_pos;
-                                self.current_ansi_codes.push(c);
-                            }
-                        }
+                        self.set_modes(&seq);
+                        self.live.csi(&seq);
                         self.escape_state = EscapeState::None;
                     }
                 }
@@ -211,9 +245,8 @@ impl OutputProcessor {
                 // ----- OSC sequence: ESC ] ... BEL  or  ESC ] ... ESC \ -----
                 EscapeState::Osc => match ch {
                     '\x07' => {
-                        // BEL terminates OSC — discard entire sequence
-                        self.escape_buf.clear();
-                        self.escape_state = EscapeState::None;
+                        // BEL terminates OSC — only shell reports are kept
+                        self.finish_osc(timestamp);
                     }
                     '\x1b' => {
                         // Might be the start of ST (ESC \)
@@ -223,26 +256,28 @@ impl OutputProcessor {
                         // Newline inside an OSC is unusual; abort the sequence
                         // and emit the line (the OSC content is discarded).
                         self.escape_buf.clear();
+                        self.escape_too_long = false;
                         self.escape_state = EscapeState::None;
-                        self.emit_line(timestamp, stream_type)?;
+                        self.newline(timestamp);
                     }
                     _ => {
-                        self.escape_buf.push(ch);
-                        // Safety valve: if the OSC sequence is absurdly long,
-                        // discard it to avoid unbounded memory growth.
-                        if self.escape_buf.len() > 4096 {
-                            self.escape_buf.clear();
-                            self.escape_state = EscapeState::None;
-                        }
+                        // Safety valve: the rest of an absurdly long OSC is
+                        // discarded to avoid unbounded memory growth. iTerm2
+                        // images may run to the image size limit.
+                        let limit = if self.escape_buf.starts_with("\x1b]1337;") {
+                            self.images.limits().max_payload_bytes()
+                        } else {
+                            MAX_OSC_LEN
+                        };
+                        self.push_string_char(ch, limit);
                     }
                 },
 
                 // ----- Inside OSC, saw ESC — check for backslash (ST) -----
                 EscapeState::OscEscapeSeen => {
                     if ch == '\\' {
-                        // ST received — discard the OSC sequence
-                        self.escape_buf.clear();
-                        self.escape_state = EscapeState::None;
+                        // ST received — only shell reports are kept
+                        self.finish_osc(timestamp);
                     } else {
                         // Was not ST; the ESC starts a new sequence
                         self.escape_buf.clear();
@@ -253,6 +288,33 @@ impl OutputProcessor {
                         continue;
                     }
                 }
+
+                // ----- APC / DCS string: ESC _ ... ST  or  ESC P ... ST -----
+                EscapeState::Apc | EscapeState::Dcs => {
+                    if ch == '\x1b' {
+                        self.escape_state = if self.escape_state == EscapeState::Apc {
+                            EscapeState::ApcEscapeSeen
+                        } else {
+                            EscapeState::DcsEscapeSeen
+                        };
+                    } else {
+                        self.push_string_char(ch, self.images.limits().max_payload_bytes());
+                    }
+                }
+
+                // ----- Inside APC / DCS, saw ESC — check for ST -----
+                EscapeState::ApcEscapeSeen | EscapeState::DcsEscapeSeen => {
+                    if ch == '\\' {
+                        self.finish_image_string(timestamp);
+                    } else {
+                        // Was not ST; the ESC starts a new sequence
+                        self.escape_buf.clear();
+                        self.escape_too_long = false;
+                        self.escape_state = EscapeState::Escape;
+                        self.escape_buf.push('\x1b');
+                        continue;
+                    }
+                }
             }
 
             i += 1;
@@ -261,92 +323,275 @@ impl OutputProcessor {
         Ok(())
     }
 
-    /// Finish the current line and push it into `processed_lines`.
-    ///
-    /// Any residual CSI sequences that were not fully consumed by the state
-    /// machine (e.g. embedded in the text) are re-parsed here; the codes
-    /// already tracked in `current_ansi_codes` are merged in.
-    fn emit_line(&mut self, timestamp: DateTime<Utc>, _stream_type: StreamType) -> Result<()> {
-        if !self.current_line.is_empty() || !self.current_ansi_codes.is_empty() {
-            let parsed = self.ansi_parser.parse(&self.current_line)?;
+    /// End a complete OSC sequence. Working directory reports and titles are
+    /// kept for the caller, hyperlinks are kept with the text they cover,
+    /// iTerm2 images get a line of their own, prompt marks end the line in
+    /// progress and move to the next zone; everything else is discarded.
+    fn finish_osc(&mut self, timestamp: DateTime<Utc>) {
+        let seq = std::mem::take(&mut self.escape_buf);
+        self.escape_state = EscapeState::None;
+        if std::mem::take(&mut self.escape_too_long) {
+            return;
+        }
+        let body = seq.strip_prefix("\x1b]").unwrap_or(&seq);
+        if let Some(image) = body.strip_prefix("1337;") {
+            if let Some(placement) = self.images.iterm2(image.as_bytes()) {
+                self.push_image(placement, timestamp);
+            }
+            return;
+        }
+        if let Some(cwd) = ReportedCwd::from_osc(body) {
+            self.reported_cwd = Some(cwd);
+            return;
+        }
+        if let Some(change) = TitleChange::from_osc(body) {
+            self.title_changes.push(change);
+            return;
+        }
+        if let Some(uri) = hyperlink::osc8_target(body) {
+            self.live
+                .set_link((!uri.is_empty()).then(|| Arc::from(uri)));
+            return;
+        }
+        let Some(mark) = PromptMark::from_osc(body) else {
+            return;
+        };
+
+        self.settle_all(timestamp);
+        match mark {
+            // A finish outside a command (the hook also runs before the very
+            // first prompt) is not reported
+            PromptMark::CommandFinished { .. }
+                if !matches!(self.zone, Some(SemanticZone::Input | SemanticZone::Output)) => {}
+            PromptMark::CommandFinished { .. } => {
+                self.reset_modes();
+                self.prompt_marks.push(mark);
+            }
+            PromptMark::OutputStart => {
+                self.seen_output_mark = true;
+                self.prompt_marks.push(mark);
+            }
+            _ => self.prompt_marks.push(mark),
+        }
+        self.zone = Some(SemanticZone::after(mark));
+    }
+
+    /// Add a character to the OSC, APC or DCS string being collected, unless
+    /// it has outgrown `limit`
+    fn push_string_char(&mut self, ch: char, limit: usize) {
+        if self.escape_too_long {
+            return;
+        }
+        if self.escape_buf.len() >= limit {
+            self.escape_too_long = true;
+            self.escape_buf.clear();
+        } else {
+            self.escape_buf.push(ch);
+        }
+    }
 
-            let mut codes = std::mem::take(&mut self.current_ansi_codes);
-            codes.extend(parsed.ansi_codes);
+    /// End a complete APC or DCS string, keeping the kitty or Sixel image it
+    /// carries
+    fn finish_image_string(&mut self, timestamp: DateTime<Utc>) {
+        let seq = std::mem::take(&mut self.escape_buf);
+        let apc = self.escape_state == EscapeState::ApcEscapeSeen;
+        self.escape_state = EscapeState::None;
+        if std::mem::take(&mut self.escape_too_long) {
+            return;
+        }
 
-            let text = if parsed.clean_text.is_empty() && self.current_line.is_empty() {
-                String::new()
-            } else {
-                parsed.clean_text
-            };
-
-            if !text.is_empty() || !codes.is_empty() {
-                let mut output_line = OutputLine::with_ansi_codes(text, codes, self.line_counter);
-                output_line.timestamp = timestamp;
-                self.processed_lines.push_back(output_line);
-                self.line_counter += 1;
+        let placement = if apc {
+            let placement = self.images.kitty(&seq.as_bytes()[2..]);
+            // Block output answers kitty support queries through the query
+            // scanner, before the DA1 reply clients wait for after them;
+            // replies to transmissions are not sent from blocks
+            self.images.take_responses();
+            placement
+        } else {
+            // DCS parameters, then the final byte: `q` for Sixel
+            let body = seq[2..].trim_start_matches(|c: char| c.is_ascii_digit() || c == ';');
+            match body.strip_prefix('q') {
+                Some(data) => {
+                    self.images.sixel_start();
+                    data.bytes().for_each(|byte| self.images.sixel_put(byte));
+                    self.images.sixel_finish()
+                }
+                None => None,
             }
+        };
+        if let Some(placement) = placement {
+            self.push_image(placement, timestamp);
         }
+    }
 
-        self.current_line.clear();
-        self.current_ansi_codes.clear();
+    /// Put an image on a line of its own
+    fn push_image(&mut self, placement: Placement, timestamp: DateTime<Utc>) {
+        self.settle_all(timestamp);
+        if self.in_hidden_zone() {
+            return;
+        }
+        let mut line = OutputLine::with_image(placement, self.line_counter);
+        line.timestamp = timestamp;
+        self.processed_lines.push_back(line);
+        self.line_counter += 1;
+    }
 
-        Ok(())
+    /// Set how large images may be, or turn them off
+    pub fn set_image_limits(&mut self, limits: ImageLimits) {
+        self.images.set_limits(limits);
+    }
+
+    /// Memory taken by images kitty clients stored for later placement
+    pub fn stored_image_bytes(&self) -> usize {
+        self.images.stored_bytes()
+    }
+
+    /// Forget stored kitty images, oldest first, until `bytes` of them are
+    /// gone; returns the memory they took
+    pub fn drop_stored_images(&mut self, bytes: usize) -> usize {
+        self.images.drop_stored(bytes)
+    }
+
+    /// Note the modes block output keeps track of from a private mode set or
+    /// reset (`CSI ? Pm h` / `CSI ? Pm l`)
+    fn set_modes(&mut self, seq: &str) {
+        let Some(body) = seq.strip_prefix("\x1b[?") else {
+            return;
+        };
+        let (params, enable) = match body.strip_suffix('h') {
+            Some(params) => (params, true),
+            None => match body.strip_suffix('l') {
+                Some(params) => (params, false),
+                None => return,
+            },
+        };
+        if params.split(';').any(|mode| mode == "1004") {
+            self.focus_reporting = enable;
+        }
+    }
+
+    /// Forget the modes set by the program of a command that finished
+    pub fn reset_modes(&mut self) {
+        self.focus_reporting = false;
+    }
+
+    /// Whether the program asked for focus events (mode 1004)
+    pub fn focus_reporting(&self) -> bool {
+        self.focus_reporting
+    }
+
+    /// Whether text in the current zone is prompt or command echo rather than
+    /// output
+    fn in_hidden_zone(&self) -> bool {
+        match self.zone {
+            Some(SemanticZone::Prompt) => true,
+            Some(SemanticZone::Input) => self.seen_output_mark,
+            _ => false,
+        }
+    }
+
+    /// Start a new line, settling the lines that moves out of programs' reach
+    fn newline(&mut self, timestamp: DateTime<Utc>) {
+        self.live.newline();
+        let settled = self.live.settle();
+        self.push_settled(settled, timestamp);
+    }
+
+    /// Settle every line, the one in progress too, as when the prompt cycle
+    /// moves on
+    fn settle_all(&mut self, timestamp: DateTime<Utc>) {
+        let settled = self.live.settle_all();
+        self.push_settled(settled, timestamp);
+    }
+
+    /// Keep the settled lines that hold output
+    fn push_settled(&mut self, lines: Vec<OutputLine>, timestamp: DateTime<Utc>) {
+        if self.in_hidden_zone() {
+            return;
+        }
+        for mut line in lines.into_iter().filter(|line| !line.text.is_empty()) {
+            line.line_number = self.line_counter;
+            line.timestamp = timestamp;
+            self.processed_lines.push_back(line);
+            self.line_counter += 1;
+        }
     }
 
     /// Flush all pending lines
     pub fn flush_lines(&mut self) -> Vec<OutputLine> {
-        let mut result = Vec::new();
-
         // Discard any in-progress escape sequence
         self.escape_state = EscapeState::None;
         self.escape_buf.clear();
+        self.escape_too_long = false;
 
-        // Add any remaining content as a line
-        if !self.current_line.is_empty() {
-            let parsed = self
-                .ansi_parser
-                .parse(&self.current_line)
-                .unwrap_or_else(|_| ParsedText {
-                    original_text: self.current_line.clone(),
-                    clean_text: self.current_line.clone(),
-                    ansi_codes: Vec::new(),
-                    position_map: Vec::new(),
-                });
-
-            let output_line = OutputLine::with_ansi_codes(
-                parsed.clean_text,
-                parsed.ansi_codes,
-                self.line_counter,
-            );
-
-            result.push(output_line);
-            self.line_counter += 1;
-            self.current_line.clear();
-            self.current_ansi_codes.clear();
-        }
+        // Add any remaining content as lines
+        self.settle_all(Utc::now());
 
         // Move all processed lines to result
-        while let Some(line) = self.processed_lines.pop_front() {
-            result.push(line);
-        }
-
-        result
+        self.processed_lines.drain(..).collect()
     }
 
     /// Return the partial line currently being accumulated (no newline yet).
     /// Useful for prompt detection when the shell writes a prompt without a
     /// trailing newline.  Returns the cleaned text with ANSI/OSC stripped.
-    pub fn peek_partial_line(&self) -> Option<&str> {
-        if self.current_line.is_empty() && self.current_ansi_codes.is_empty() {
-            None
-        } else {
-            Some(&self.current_line)
+    pub fn peek_partial_line(&self) -> Option<String> {
+        let line = self.live.last_line();
+        (!line.text.is_empty()).then_some(line.text)
+    }
+
+    /// Column the cursor is in on the line in progress
+    pub fn cursor_col(&self) -> usize {
+        self.live.cursor_col()
+    }
+
+    /// Lines programs can still rewrite, as they stand, for showing under a
+    /// running command; none while the shell is drawing its prompt
+    pub fn live_lines(&self) -> Vec<OutputLine> {
+        if self.in_hidden_zone() {
+            return Vec::new();
         }
+        let mut lines = self.live.lines();
+        lines.retain(|line| !line.text.is_empty());
+        lines
+    }
+
+    /// Settle every line above the one in progress, as when the shell is
+    /// back at its prompt
+    pub fn settle_lines(&mut self) {
+        let settled = self.live.settle_above_last();
+        self.push_settled(settled, Utc::now());
+    }
+
+    /// Set how far up programs can move to rewrite output: the screen's height
+    pub fn set_live_rows(&mut self, rows: usize) {
+        self.live.set_height(rows);
+    }
+
+    /// Drain the OSC 133 prompt marks seen so far, in order. A finish mark is
+    /// only reported when it ends a command.
+    pub fn take_prompt_marks(&mut self) -> Vec<PromptMark> {
+        std::mem::take(&mut self.prompt_marks)
+    }
+
+    /// Take the latest working directory the shell reported with OSC 7
+    pub fn take_reported_cwd(&mut self) -> Option<ReportedCwd> {
+        self.reported_cwd.take()
+    }
+
+    /// Drain the titles programs set with OSC 0/1/2, in order
+    pub fn take_title_changes(&mut self) -> Vec<TitleChange> {
+        std::mem::take(&mut self.title_changes)
+    }
+
+    /// Whether the shell marks its prompts, so blocks can be split and
+    /// finished from the marks alone
+    pub fn has_prompt_marks(&self) -> bool {
+        self.zone.is_some()
     }
 
     /// Check if there are pending lines
     pub fn has_pending_lines(&self) -> bool {
-        !self.processed_lines.is_empty() || !self.current_line.is_empty()
+        !self.processed_lines.is_empty() || !self.live.is_empty()
     }
 
     /// Get the number of processed lines
@@ -358,20 +603,24 @@ impl OutputProcessor {
     pub fn clear(&mut self) {
         self.raw_buffer.clear();
         self.processed_lines.clear();
-        self.current_line.clear();
-        self.current_ansi_codes.clear();
+        self.live.clear();
         self.line_counter = 0;
         self.escape_state = EscapeState::None;
         self.escape_buf.clear();
+        self.escape_too_long = false;
+        self.images.reset();
+        self.prompt_marks.clear();
+        self.title_changes.clear();
     }
 
     /// Get buffer statistics
     pub fn buffer_stats(&self) -> BufferStats {
+        let current_line = self.live.last_line();
         BufferStats {
             raw_buffer_size: self.raw_buffer.len(),
             processed_lines: self.processed_lines.len(),
-            current_line_length: self.current_line.len(),
-            ansi_codes_count: self.current_ansi_codes.len(),
+            current_line_length: current_line.text.len(),
+            ansi_codes_count: current_line.ansi_codes.len(),
         }
     }
 }
@@ -462,6 +711,8 @@ pub enum SegmentType {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::models::output_line::Hyperlink;
+    use crate::terminal::width;
 
     #[test]
     fn test_output_processor_creation() {
@@ -486,6 +737,25 @@ mod tests {
         assert_eq!(lines[0].text, "Hello, World!");
     }
 
+    #[test]
+    fn test_tabs_expand_by_display_width() {
+        let mut processor = OutputProcessor::new();
+
+        let chunk = OutputChunk {
+            data: "ab\tx\n中文\tx\n😀\x1b[1m\tx\n".as_bytes().to_vec(),
+            timestamp: Utc::now(),
+            stream_type: StreamType::Stdout,
+            is_complete: true,
+        };
+
+        let lines = processor.process_chunk(chunk).unwrap();
+        let columns: Vec<usize> = lines
+            .iter()
+            .map(|line| width::str_width(line.text.split('x').next().unwrap()))
+            .collect();
+        assert_eq!(columns, [8, 8, 8]);
+    }
+
     #[test]
     fn test_process_multiple_lines() {
         let mut processor = OutputProcessor::new();
@@ -692,6 +962,11 @@ mod tests {
         let lines = processor.process_chunk(chunk).unwrap();
         // Should produce no visible output
         assert!(lines.is_empty() || lines.iter().all(|l| l.text.is_empty()));
+        // ...but the directory is kept, once
+        let cwd = processor.take_reported_cwd().unwrap();
+        assert_eq!(cwd.host.as_deref(), Some("host"));
+        assert_eq!(cwd.path, std::path::PathBuf::from("/Users/user"));
+        assert!(processor.take_reported_cwd().is_none());
     }
 
     #[test]
@@ -771,6 +1046,14 @@ mod tests {
         assert_eq!(lines.len(), 2);
         assert_eq!(lines[0].text, "git status");
         assert_eq!(lines[1].text, "On branch main");
+        assert_eq!(
+            processor.take_title_changes(),
+            [
+                TitleChange::Title("user@host:~/proj".to_string()),
+                TitleChange::Icon("proj".to_string())
+            ]
+        );
+        assert!(processor.take_title_changes().is_empty());
     }
 
     #[test]
@@ -825,4 +1108,213 @@ mod tests {
         assert_eq!(lines[0].text, "Red text");
         assert!(!lines[0].ansi_codes.is_empty());
     }
+
+    fn feed(processor: &mut OutputProcessor, data: &[u8]) -> Vec<String> {
+        let chunk = OutputChunk {
+            data: data.to_vec(),
+            timestamp: Utc::now(),
+            stream_type: StreamType::Stdout,
+            is_complete: false,
+        };
+        let lines = processor.process_chunk(chunk).unwrap();
+        lines.into_iter().map(|line| line.text).collect()
+    }
+
+    #[test]
+    fn test_prompt_marks_split_output() {
+        let mut processor = OutputProcessor::new();
+        assert!(!processor.has_prompt_marks());
+
+        // Startup: the hook's finish mark before the first prompt is ignored
+        feed(
+            &mut processor,
+            b"\x1b]133;D;0\x07\x1b]133;A\x07$ \x1b]133;B\x07",
+        );
+        assert!(processor.has_prompt_marks());
+        assert_eq!(
+            processor.take_prompt_marks(),
+            [PromptMark::PromptStart, PromptMark::InputStart]
+        );
+
+        let lines = feed(
+            &mut processor,
+            b"ls\r\n\x1b]133;C\x07a\nb\x1b]133;D;2\x07\x1b]133;A\x07user@host$ \x1b]133;B\x07",
+        );
+        assert_eq!(lines, ["ls", "a", "b"]);
+        assert_eq!(
+            processor.take_prompt_marks(),
+            [
+                PromptMark::OutputStart,
+                PromptMark::CommandFinished { exit_code: Some(2) },
+                PromptMark::PromptStart,
+                PromptMark::InputStart,
+            ]
+        );
+        assert!(processor.flush_lines().is_empty());
+
+        // Once output marks are known, the echo is dropped too
+        let lines = feed(
+            &mut processor,
+            b"pwd\r\n\x1b]133;C\x1b\\/tmp\r\n\x1b]133;D;0\x1b\\",
+        );
+        assert_eq!(lines, ["/tmp"]);
+    }
+
+    #[test]
+    fn test_prompt_mark_split_across_chunks() {
+        let mut processor = OutputProcessor::new();
+        feed(&mut processor, b"\x1b]133;B\x07x\r\n\x1b]13");
+        feed(&mut processor, b"3;C\x07done\x1b]133;");
+        assert!(feed(&mut processor, b"D;130\x07").contains(&"done".to_string()));
+        assert_eq!(
+            processor.take_prompt_marks().last(),
+            Some(&PromptMark::CommandFinished {
+                exit_code: Some(130)
+            })
+        );
+    }
+
+    #[test]
+    fn test_hyperlinks_kept_on_lines() {
+        let mut processor = OutputProcessor::new();
+        let chunk = OutputChunk {
+            data: b"see \x1b]8;id=1;https://example.com\x1b\\docs\x1b]8;;\x1b\\ now\n\
+                    \x1b]8;;file:///tmp/a\x07a\nb\x1b]8;;\x07c\n"
+                .to_vec(),
+            timestamp: Utc::now(),
+            stream_type: StreamType::Stdout,
+            is_complete: true,
+        };
+        let lines = processor.process_chunk(chunk).unwrap();
+        let texts: Vec<&str> = lines.iter().map(|line| line.text.as_str()).collect();
+        assert_eq!(texts, ["see docs now", "a", "bc"]);
+
+        let link = |range: std::ops::Range<usize>, uri: &str| Hyperlink {
+            range,
+            uri: uri.to_string(),
+        };
+        assert_eq!(lines[0].hyperlinks, [link(4..8, "https://example.com")]);
+        // A link left open at a line break continues on the next line
+        assert_eq!(lines[1].hyperlinks, [link(0..1, "file:///tmp/a")]);
+        assert_eq!(lines[2].hyperlinks, [link(0..1, "file:///tmp/a")]);
+    }
+
+    #[test]
+    fn test_images_get_lines_of_their_own() {
+        let mut processor = OutputProcessor::new();
+        let chunk = OutputChunk {
+            data: b"before\x1b_Ga=T,f=24,s=1,v=1;AAAA\x1b\\after\n\
+                    \x1bPq#1;2;100;0;0#1~~\x1b\\\n\
+                    \x1b]1337;File=inline=1:bm90IGFuIGltYWdl\x07done\n"
+                .to_vec(),
+            timestamp: Utc::now(),
+            stream_type: StreamType::Stdout,
+            is_complete: true,
+        };
+        let lines = processor.process_chunk(chunk).unwrap();
+        let texts: Vec<&str> = lines.iter().map(|line| line.text.as_str()).collect();
+        // The iTerm2 file is not an image, so it is dropped
+        assert_eq!(texts, ["before", "", "after", "", "done"]);
+
+        let kitty = lines[1].image.as_ref().unwrap();
+        assert_eq!((kitty.image.width, kitty.image.height), (1, 1));
+        let sixel = lines[3].image.as_ref().unwrap();
+        assert_eq!((sixel.image.width, sixel.image.height), (2, 6));
+        assert!(lines[0].image.is_none());
+
+        // A Sixel image that outgrows the pixel cap is dropped, not allocated
+        let mut data = b"\x1bPq\"1;1;8000000;1".to_vec();
+        data.extend(b"~-".repeat(1000));
+        data.extend(b"~\x1b\\shown\n");
+        let lines = processor
+            .process_chunk(OutputChunk {
+                data,
+                timestamp: Utc::now(),
+                stream_type: StreamType::Stdout,
+                is_complete: true,
+            })
+            .unwrap();
+        assert!(lines.iter().all(|line| line.image.is_none()));
+        assert_eq!(lines.last().unwrap().text, "shown");
+    }
+
+    #[test]
+    fn test_long_osc_is_dropped_whole() {
+        let mut processor = OutputProcessor::new();
+        let mut data = b"\x1b]99;".to_vec();
+        data.extend(std::iter::repeat_n(b'x', MAX_OSC_LEN * 2));
+        data.extend_from_slice(b"\x07shown\n");
+        assert_eq!(feed(&mut processor, &data), ["shown"]);
+
+        processor.set_image_limits(ImageLimits {
+            enabled: false,
+            ..ImageLimits::default()
+        });
+        assert_eq!(
+            feed(&mut processor, b"\x1b_Ga=T,f=24,s=1,v=1;AAAA\x1b\\text\n"),
+            ["text"]
+        );
+    }
+
+    #[test]
+    fn test_progress_bars_settle_in_final_state() {
+        let mut processor = OutputProcessor::new();
+        processor.set_live_rows(24);
+
+        // cargo: a status line rewritten with \r and CSI K
+        let lines = feed(
+            &mut processor,
+            b"   Compiling a\n    Building [=>  ] 1/3\r\x1b[K    Building [==> ] 2/3",
+        );
+        assert!(lines.is_empty(), "nothing settles while reachable");
+        let live: Vec<String> = processor
+            .live_lines()
+            .into_iter()
+            .map(|line| line.text)
+            .collect();
+        assert_eq!(live, ["   Compiling a", "    Building [==> ] 2/3"]);
+        assert_eq!(
+            processor.peek_partial_line().as_deref(),
+            Some("    Building [==> ] 2/3")
+        );
+
+        // docker pull: one bar per layer, rewritten from below
+        feed(&mut processor, b"\r\x1b[K\na: Waiting\nb: Waiting\n");
+        feed(&mut processor, b"\x1b[2A\x1b[2Ka: Pull complete\r\x1b[2B");
+        feed(&mut processor, b"\x1b[1A\x1b[2Kb: Pull complete\r\x1b[1B");
+        let texts: Vec<String> = processor
+            .flush_lines()
+            .into_iter()
+            .map(|line| line.text)
+            .collect();
+        assert_eq!(
+            texts,
+            ["   Compiling a", "a: Pull complete", "b: Pull complete"]
+        );
+    }
+
+    #[test]
+    fn test_lines_settle_out_of_reach_or_at_prompt() {
+        let mut processor = OutputProcessor::new();
+        processor.set_live_rows(2);
+        assert_eq!(feed(&mut processor, b"1\n2\n3\n"), ["1", "2"]);
+
+        processor.settle_lines();
+        assert_eq!(
+            processor
+                .take_ready_lines()
+                .into_iter()
+                .map(|line| line.text)
+                .collect::<Vec<_>>(),
+            ["3"]
+        );
+
+        // Marks settle everything, the line in progress too
+        let lines = feed(
+            &mut processor,
+            b"\x1b]133;C\x0750%\r100%\x1b]133;D;0\x07\x1b]133;A\x07$ ",
+        );
+        assert_eq!(lines, ["100%"]);
+        assert!(processor.live_lines().is_empty());
+    }
 }
@@ -0,0 +1,397 @@
+//! Terminal Queries
+//!
+//! Requests an application makes of its terminal and the replies written back
+//! on the PTY: device attributes (DA1/DA2), status and cursor position reports
+//! (DSR/CPR), the terminal version (XTVERSION) and the default and palette
+//! colours (OSC 4/10/11/12). Programs such as fish, vim and neovim wait for
+//! these answers, so leaving them unanswered stalls or degrades them.
+//!
+//! The grid answers queries made inside the TUI overlay itself;
+//! [`QueryScanner`] picks them out of block-mode output, where there is no
+//! screen to ask. It also collects clipboard requests (OSC 52), which the
+//! application answers according to its clipboard policy, and answers kitty
+//! graphics support queries, which clients such as `viu` send just before a
+//! DA1 and judge by which reply comes first.
+
+use crate::terminal::clipboard::ClipboardRequest;
+use crate::terminal::grid::{Palette, Rgb};
+use crate::terminal::images::{ApcPiece, ApcSplitter, KittyControl};
+use vte::{Params, Perform};
+
+/// Name reported by XTVERSION
+const TERMINAL_NAME: &str = "MosaicTerm";
+
+/// Longest APC string the scanner looks at; graphics queries are short
+const MAX_QUERY_APC_LEN: usize = 4096;
+
+/// A colour an application can ask about with OSC 4/10/11/12
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorSlot {
+    /// OSC 4: an entry of the 256-colour palette
+    Indexed(u8),
+    /// OSC 10: default foreground
+    Foreground,
+    /// OSC 11: default background
+    Background,
+    /// OSC 12: cursor colour
+    Cursor,
+}
+
+/// A request that expects a reply on the application's input
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Query {
+    /// DA1 (`CSI c`), listing Sixel graphics when images are `sixel`
+    PrimaryAttributes { sixel: bool },
+    /// DA2 (`CSI > c`)
+    SecondaryAttributes,
+    /// DSR operating status (`CSI 5 n`)
+    Status,
+    /// CPR (`CSI 6 n`), or DECXCPR (`CSI ? 6 n`) when `private`
+    CursorPosition { private: bool },
+    /// XTVERSION (`CSI > q`)
+    Version,
+    /// OSC 4/10/11/12 with `?`; `bell` replies with BEL instead of ST, as the
+    /// query was terminated
+    Color { slot: ColorSlot, bell: bool },
+    /// kitty graphics query (`ESC _ G a=q,i=<id>;… ESC \`), answered `OK`
+    /// when images are `supported`
+    KittyGraphics { id: u32, supported: bool },
+}
+
+impl Query {
+    /// Recognise a query among CSI sequences
+    pub fn from_csi(
+        params: &Params,
+        intermediates: &[u8],
+        action: char,
+        images_enabled: bool,
+    ) -> Option<Self> {
+        let first = params
+            .iter()
+            .next()
+            .and_then(|group| group.first().copied())
+            .unwrap_or(0);
+        match (intermediates, action, first) {
+            ([], 'c', 0) => Some(Self::PrimaryAttributes {
+                sixel: images_enabled,
+            }),
+            ([b'>'], 'c', 0) => Some(Self::SecondaryAttributes),
+            ([], 'n', 5) => Some(Self::Status),
+            ([], 'n', 6) => Some(Self::CursorPosition { private: false }),
+            ([b'?'], 'n', 6) => Some(Self::CursorPosition { private: true }),
+            ([b'>'], 'q', 0) => Some(Self::Version),
+            _ => None,
+        }
+    }
+
+    /// Recognise colour queries in an OSC sequence. One sequence may ask
+    /// about several colours (`OSC 4 ; 1 ; ? ; 2 ; ?`, `OSC 10 ; ? ; ?`).
+    pub fn from_osc(params: &[&[u8]], bell: bool) -> Vec<Self> {
+        let Some((&command, args)) = params.split_first() else {
+            return Vec::new();
+        };
+        let color = |slot| Self::Color { slot, bell };
+        match command {
+            b"4" => args
+                .chunks_exact(2)
+                .filter(|pair| pair[1] == b"?")
+                .filter_map(|pair| std::str::from_utf8(pair[0]).ok()?.parse().ok())
+                .map(|idx| color(ColorSlot::Indexed(idx)))
+                .collect(),
+            // Each `?` after OSC 10 asks for the next dynamic colour in turn
+            b"10" | b"11" | b"12" => {
+                let dynamic = [
+                    ColorSlot::Foreground,
+                    ColorSlot::Background,
+                    ColorSlot::Cursor,
+                ];
+                let start = usize::from(command[1] - b'0');
+                args.iter()
+                    .zip(&dynamic[start..])
+                    .take_while(|(arg, _)| **arg == b"?")
+                    .map(|(_, &slot)| color(slot))
+                    .collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Recognise a kitty graphics query in the body of an APC string, unless
+    /// its reply is one the client asked not to get
+    pub fn from_apc(body: &[u8], images_enabled: bool) -> Option<Self> {
+        let (control, _) = KittyControl::parse(body)?;
+        let supported = images_enabled && control.medium == b'd';
+        let quiet = if supported { 1 } else { 2 };
+        (control.action == b'q' && control.id != 0 && control.quiet < quiet).then_some(
+            Self::KittyGraphics {
+                id: control.id,
+                supported,
+            },
+        )
+    }
+
+    /// Reply bytes, given the colours in use and the zero-based cursor
+    /// position as (row, col)
+    pub fn reply(&self, palette: &Palette, cursor: (usize, usize)) -> Vec<u8> {
+        let (row, col) = (cursor.0 + 1, cursor.1 + 1);
+        let text = match *self {
+            // VT220 with Sixel graphics and ANSI colour
+            Self::PrimaryAttributes { sixel: true } => "\x1b[?62;4;22c".to_string(),
+            Self::PrimaryAttributes { sixel: false } => "\x1b[?62;22c".to_string(),
+            Self::SecondaryAttributes => format!("\x1b[>1;{};0c", version_number()),
+            Self::Status => "\x1b[0n".to_string(),
+            Self::CursorPosition { private: false } => format!("\x1b[{};{}R", row, col),
+            Self::CursorPosition { private: true } => format!("\x1b[?{};{};1R", row, col),
+            Self::Version => format!(
+                "\x1bP>|{} {}\x1b\\",
+                TERMINAL_NAME,
+                env!("CARGO_PKG_VERSION")
+            ),
+            Self::Color { slot, bell } => {
+                let (prefix, rgb) = match slot {
+                    ColorSlot::Indexed(idx) => (format!("4;{}", idx), palette.indexed(idx)),
+                    ColorSlot::Foreground => ("10".to_string(), palette.foreground),
+                    ColorSlot::Background => ("11".to_string(), palette.background),
+                    ColorSlot::Cursor => ("12".to_string(), palette.foreground),
+                };
+                let terminator = if bell { "\x07" } else { "\x1b\\" };
+                format!("\x1b]{};{}{}", prefix, x11_color(rgb), terminator)
+            }
+            Self::KittyGraphics { id, supported } => {
+                let message = if supported {
+                    "OK"
+                } else {
+                    "ENOTSUPPORTED:images are turned off"
+                };
+                format!("\x1b_Gi={};{}\x1b\\", id, message)
+            }
+        };
+        text.into_bytes()
+    }
+}
+
+/// Crate version as one number (0.5.1 -> 501), as DA2 reports it
+fn version_number() -> u32 {
+    env!("CARGO_PKG_VERSION")
+        .split('.')
+        .take(3)
+        .map(|part| part.parse::<u32>().unwrap_or(0))
+        .fold(0, |acc, part| acc * 100 + part)
+}
+
+/// XParseColor form with 16-bit components, as xterm answers colour queries
+fn x11_color(rgb: Rgb) -> String {
+    format!(
+        "rgb:{0:02x}{0:02x}/{1:02x}{1:02x}/{2:02x}{2:02x}",
+        rgb.r, rgb.g, rgb.b
+    )
+}
+
+/// Picks queries out of block-mode output
+pub struct QueryScanner {
+    parser: vte::Parser,
+    /// Takes APC strings out of the stream, as vte drops them
+    apc: ApcSplitter,
+    collector: Collector,
+    /// Whether graphics queries are answered as supported
+    images_enabled: bool,
+}
+
+/// Collects the queries seen by the parser
+#[derive(Default)]
+struct Collector {
+    queries: Vec<Query>,
+    clipboard: Vec<ClipboardRequest>,
+    /// Whether DA1 lists Sixel graphics
+    images_enabled: bool,
+}
+
+impl Perform for Collector {
+    fn csi_dispatch(&mut self, params: &Params, intermediates: &[u8], ignore: bool, action: char) {
+        if ignore {
+            return;
+        }
+        if let Some(query) = Query::from_csi(params, intermediates, action, self.images_enabled) {
+            self.queries.push(query);
+        }
+    }
+
+    fn osc_dispatch(&mut self, params: &[&[u8]], bell_terminated: bool) {
+        if let Some(request) = ClipboardRequest::from_osc(params, bell_terminated) {
+            self.clipboard.push(request);
+            return;
+        }
+        self.queries
+            .extend(Query::from_osc(params, bell_terminated));
+    }
+}
+
+impl Default for QueryScanner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl QueryScanner {
+    /// Create a scanner in the ground state
+    pub fn new() -> Self {
+        Self {
+            parser: vte::Parser::new(),
+            apc: ApcSplitter::new(MAX_QUERY_APC_LEN),
+            collector: Collector {
+                images_enabled: true,
+                ..Collector::default()
+            },
+            images_enabled: true,
+        }
+    }
+
+    /// Set whether graphics queries are answered as supported
+    pub fn set_images_enabled(&mut self, enabled: bool) {
+        self.images_enabled = enabled;
+        self.collector.images_enabled = enabled;
+    }
+
+    /// Feed a chunk of output and return the queries completed in it, in order
+    pub fn scan(&mut self, bytes: &[u8]) -> Vec<Query> {
+        for piece in self.apc.split(bytes) {
+            match piece {
+                ApcPiece::Bytes(bytes) => self.parser.advance(&mut self.collector, bytes),
+                ApcPiece::Apc(body) => {
+                    let query = Query::from_apc(&body, self.images_enabled);
+                    self.collector.queries.extend(query);
+                }
+            }
+        }
+        std::mem::take(&mut self.collector.queries)
+    }
+
+    /// Take the clipboard requests (OSC 52) completed so far, in order
+    pub fn take_clipboard_requests(&mut self) -> Vec<ClipboardRequest> {
+        std::mem::take(&mut self.collector.clipboard)
+    }
+
+    /// Forget any partial sequence (e.g. when a new command starts)
+    pub fn reset(&mut self) {
+        self.parser = vte::Parser::new();
+        self.apc = ApcSplitter::new(MAX_QUERY_APC_LEN);
+        self.collector.queries.clear();
+        self.collector.clipboard.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn replies(input: &str) -> Vec<String> {
+        let mut scanner = QueryScanner::new();
+        scanner
+            .scan(input.as_bytes())
+            .iter()
+            .map(|q| String::from_utf8(q.reply(&Palette::default(), (4, 9))).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_device_attributes() {
+        assert_eq!(replies("\x1b[c"), ["\x1b[?62;4;22c"]);
+        assert_eq!(replies("\x1b[0c"), ["\x1b[?62;4;22c"]);
+        assert_eq!(
+            replies("\x1b[>c"),
+            [format!("\x1b[>1;{};0c", version_number())]
+        );
+    }
+
+    #[test]
+    fn test_status_and_cursor_reports() {
+        assert_eq!(replies("\x1b[5n"), ["\x1b[0n"]);
+        assert_eq!(replies("\x1b[6n"), ["\x1b[5;10R"]);
+        assert_eq!(replies("\x1b[?6n"), ["\x1b[?5;10;1R"]);
+    }
+
+    #[test]
+    fn test_version() {
+        let reply = &replies("\x1b[>q")[0];
+        assert!(reply.starts_with("\x1bP>|MosaicTerm "));
+        assert!(reply.ends_with("\x1b\\"));
+    }
+
+    #[test]
+    fn test_color_queries_keep_terminator() {
+        assert_eq!(replies("\x1b]11;?\x07"), ["\x1b]11;rgb:1e1e/1e1e/2e2e\x07"]);
+        assert_eq!(
+            replies("\x1b]10;?\x1b\\"),
+            ["\x1b]10;rgb:cccc/cccc/cccc\x1b\\"]
+        );
+    }
+
+    #[test]
+    fn test_multiple_color_queries() {
+        assert_eq!(
+            replies("\x1b]10;?;?\x07"),
+            [
+                "\x1b]10;rgb:cccc/cccc/cccc\x07",
+                "\x1b]11;rgb:1e1e/1e1e/2e2e\x07"
+            ]
+        );
+        assert_eq!(
+            replies("\x1b]4;1;?;196;?\x07"),
+            [
+                "\x1b]4;1;rgb:cdcd/0000/0000\x07",
+                "\x1b]4;196;rgb:ffff/0000/0000\x07"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ignores_non_queries() {
+        assert!(replies("\x1b[31mred\x1b[2J\x1b]11;#000000\x07\x1b[?25h\x1b[1;5n").is_empty());
+    }
+
+    #[test]
+    fn test_clipboard_requests_in_block_output() {
+        let mut scanner = QueryScanner::new();
+        assert!(scanner.scan(b"\x1b]52;c;Y29w").is_empty());
+        assert!(scanner.scan(b"aWVk\x07").is_empty());
+        assert_eq!(
+            scanner.take_clipboard_requests(),
+            [ClipboardRequest::Write("copied".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_query_split_across_chunks() {
+        let mut scanner = QueryScanner::new();
+        assert!(scanner.scan(b"prompt \x1b[").is_empty());
+        assert_eq!(
+            scanner.scan(b"6n"),
+            [Query::CursorPosition { private: false }]
+        );
+    }
+
+    #[test]
+    fn test_kitty_graphics_query_answered_before_da1() {
+        // What viu sends to find out whether kitty graphics work
+        assert_eq!(
+            replies("\x1b_Gi=31,s=1,v=1,a=q,t=d,f=24;AAAA\x1b\\\x1b[c"),
+            ["\x1b_Gi=31;OK\x1b\\", "\x1b[?62;4;22c"]
+        );
+        // Transmissions and quiet queries get nothing here
+        assert!(replies("\x1b_Ga=T,f=24,s=1,v=1;AAAA\x1b\\").is_empty());
+        assert!(replies("\x1b_Gi=1,a=q,q=1;AAAA\x1b\\").is_empty());
+
+        let mut scanner = QueryScanner::new();
+        scanner.set_images_enabled(false);
+        assert_eq!(
+            scanner.scan(b"\x1b_Gi=1,a=q,q=1;AAAA\x1b\\"),
+            [Query::KittyGraphics {
+                id: 1,
+                supported: false
+            }]
+        );
+        // Nor is Sixel listed among the device attributes
+        let da1 = scanner.scan(b"\x1b[c");
+        assert_eq!(da1[0].reply(&Palette::default(), (0, 0)), b"\x1b[?62;22c");
+    }
+}
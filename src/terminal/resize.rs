@@ -0,0 +1,214 @@
+//! PTY Resize Tracking
+//!
+//! Converts a pixel area into terminal rows and columns from font metrics, and
+//! debounces size changes so a window drag does not flood the child process
+//! with `SIGWINCH`.
+
+use std::time::{Duration, Instant};
+
+/// Terminal size in character cells
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GridSize {
+    pub rows: u16,
+    pub cols: u16,
+}
+
+impl GridSize {
+    /// Create a size from rows and columns
+    pub const fn new(rows: u16, cols: u16) -> Self {
+        Self { rows, cols }
+    }
+
+    /// Number of whole cells that fit in `width` x `height` pixels.
+    ///
+    /// Both dimensions are at least one cell.
+    pub fn from_pixels(width: f32, height: f32, cell_width: f32, cell_height: f32) -> Self {
+        let fit = |extent: f32, cell: f32| {
+            if cell > 0.0 && extent.is_finite() {
+                (extent / cell).floor().clamp(1.0, u16::MAX as f32) as u16
+            } else {
+                1
+            }
+        };
+        Self {
+            rows: fit(height, cell_height),
+            cols: fit(width, cell_width),
+        }
+    }
+}
+
+/// Debounces size changes before they are sent to a PTY
+#[derive(Debug, Clone)]
+pub struct ResizeDebouncer {
+    /// How long a size must stay unchanged before it is applied
+    delay: Duration,
+    /// Size last handed out by [`poll`](Self::poll)
+    applied: Option<GridSize>,
+    /// Latest observed size and when it was first seen
+    pending: Option<(GridSize, Instant)>,
+}
+
+impl Default for ResizeDebouncer {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_DELAY)
+    }
+}
+
+impl ResizeDebouncer {
+    /// Default settle time for interactive resizes
+    pub const DEFAULT_DELAY: Duration = Duration::from_millis(50);
+
+    /// Create a debouncer with the given settle time
+    pub fn new(delay: Duration) -> Self {
+        Self {
+            delay,
+            applied: None,
+            pending: None,
+        }
+    }
+
+    /// Record the size measured this frame
+    pub fn observe(&mut self, size: GridSize, now: Instant) {
+        if self.applied == Some(size) {
+            self.pending = None;
+        } else if self.pending.map(|(pending, _)| pending) != Some(size) {
+            self.pending = Some((size, now));
+        }
+    }
+
+    /// Size that should be sent to the PTY now, if any.
+    ///
+    /// The first size is released immediately so a freshly spawned child does
+    /// not start at the 80x24 default; later sizes once they have settled.
+    pub fn poll(&mut self, now: Instant) -> Option<GridSize> {
+        let (size, since) = self.pending?;
+        if self.applied.is_some() && now.duration_since(since) < self.delay {
+            return None;
+        }
+        self.pending = None;
+        self.applied = Some(size);
+        Some(size)
+    }
+
+    /// Whether a size change is waiting to settle
+    pub fn has_pending(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    /// Time left until the waiting size change settles, if there is one
+    pub fn due_in(&self, now: Instant) -> Option<Duration> {
+        let (_, since) = self.pending?;
+        if self.applied.is_none() {
+            return Some(Duration::ZERO);
+        }
+        Some(self.delay.saturating_sub(now.duration_since(since)))
+    }
+
+    /// Size last applied to the PTY
+    pub fn applied(&self) -> Option<GridSize> {
+        self.applied
+    }
+
+    /// Forget the applied size so the next observation is sent immediately.
+    ///
+    /// Used when another view (e.g. the TUI overlay) has resized the same PTY.
+    pub fn reset(&mut self) {
+        self.applied = None;
+        self.pending = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_pixels() {
+        let size = GridSize::from_pixels(805.0, 410.0, 8.0, 16.0);
+        assert_eq!(size, GridSize::new(25, 100));
+    }
+
+    #[test]
+    fn test_from_pixels_minimum() {
+        assert_eq!(
+            GridSize::from_pixels(0.0, 0.0, 8.0, 16.0),
+            GridSize::new(1, 1)
+        );
+        assert_eq!(
+            GridSize::from_pixels(100.0, 100.0, 0.0, 0.0),
+            GridSize::new(1, 1)
+        );
+    }
+
+    #[test]
+    fn test_first_size_applies_immediately() {
+        let mut debouncer = ResizeDebouncer::default();
+        let now = Instant::now();
+        debouncer.observe(GridSize::new(24, 80), now);
+        assert_eq!(debouncer.poll(now), Some(GridSize::new(24, 80)));
+        assert_eq!(debouncer.poll(now), None);
+    }
+
+    #[test]
+    fn test_changes_wait_for_delay() {
+        let mut debouncer = ResizeDebouncer::new(Duration::from_millis(50));
+        let start = Instant::now();
+        debouncer.observe(GridSize::new(24, 80), start);
+        debouncer.poll(start);
+
+        debouncer.observe(GridSize::new(30, 100), start);
+        assert_eq!(debouncer.poll(start + Duration::from_millis(10)), None);
+        assert!(debouncer.has_pending());
+
+        // A further change restarts the timer
+        debouncer.observe(GridSize::new(31, 100), start + Duration::from_millis(40));
+        assert_eq!(debouncer.poll(start + Duration::from_millis(60)), None);
+        assert_eq!(
+            debouncer.poll(start + Duration::from_millis(90)),
+            Some(GridSize::new(31, 100))
+        );
+        assert_eq!(debouncer.applied(), Some(GridSize::new(31, 100)));
+    }
+
+    #[test]
+    fn test_due_in_counts_down_delay() {
+        let mut debouncer = ResizeDebouncer::new(Duration::from_millis(50));
+        let start = Instant::now();
+        assert_eq!(debouncer.due_in(start), None);
+        debouncer.observe(GridSize::new(24, 80), start);
+        assert_eq!(debouncer.due_in(start), Some(Duration::ZERO));
+        debouncer.poll(start);
+
+        debouncer.observe(GridSize::new(30, 100), start);
+        assert_eq!(
+            debouncer.due_in(start + Duration::from_millis(20)),
+            Some(Duration::from_millis(30))
+        );
+        assert_eq!(
+            debouncer.due_in(start + Duration::from_millis(80)),
+            Some(Duration::ZERO)
+        );
+    }
+
+    #[test]
+    fn test_returning_to_applied_size_cancels() {
+        let mut debouncer = ResizeDebouncer::default();
+        let now = Instant::now();
+        debouncer.observe(GridSize::new(24, 80), now);
+        debouncer.poll(now);
+        debouncer.observe(GridSize::new(25, 80), now);
+        debouncer.observe(GridSize::new(24, 80), now);
+        assert!(!debouncer.has_pending());
+    }
+
+    #[test]
+    fn test_reset_reapplies_immediately() {
+        let mut debouncer = ResizeDebouncer::default();
+        let now = Instant::now();
+        debouncer.observe(GridSize::new(24, 80), now);
+        debouncer.poll(now);
+        debouncer.reset();
+        debouncer.observe(GridSize::new(24, 80), now);
+        assert_eq!(debouncer.poll(now), Some(GridSize::new(24, 80)));
+    }
+}
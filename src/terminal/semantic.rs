@@ -0,0 +1,216 @@
+//! Shell Integration Reports
+//!
+//! The shell hooks written by [`crate::pty::shell_state`] mark each prompt
+//! cycle with FinalTerm / OSC 133 sequences: `A` where the prompt starts, `B`
+//! where command input starts, `C` where the command's output starts and
+//! `D ; exit` once it has finished. The output processor reads them out of
+//! the stream, so blocks are split where the shell says they are and take
+//! their exit code from it instead of guessing from prompts and error text.
+//!
+//! Shells also report their working directory with OSC 7
+//! (`file://host/path`); [`ReportedCwd`] is one such report. Unlike probing
+//! the shell process, it keeps working inside SSH sessions and containers,
+//! and the host tells local directories from remote ones.
+
+use std::path::PathBuf;
+
+/// One OSC 133 mark
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PromptMark {
+    /// `A`: the prompt is about to be drawn
+    PromptStart,
+    /// `B`: the prompt is drawn and the user's input follows
+    InputStart,
+    /// `C`: the command was accepted and its output follows
+    OutputStart,
+    /// `D`: the command finished, with its exit status when the shell sent one
+    CommandFinished { exit_code: Option<i32> },
+}
+
+impl PromptMark {
+    /// Recognise a mark from the body of an OSC sequence (`133;D;0`). Any
+    /// `key=value` options after the mark are ignored.
+    pub fn from_osc(body: &str) -> Option<Self> {
+        let mut fields = body.strip_prefix("133;")?.split(';');
+        match fields.next()? {
+            "A" => Some(Self::PromptStart),
+            "B" => Some(Self::InputStart),
+            "C" => Some(Self::OutputStart),
+            "D" => Some(Self::CommandFinished {
+                exit_code: fields.next().and_then(|code| code.parse().ok()),
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// The part of the prompt cycle the shell is writing
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SemanticZone {
+    /// Between `A` and `B`: prompt text
+    Prompt,
+    /// Between `B` and `C`: the echo of the command line
+    Input,
+    /// Between `C` and `D`: the command's own output
+    Output,
+}
+
+impl SemanticZone {
+    /// Zone the stream is in once `mark` has been seen
+    pub fn after(mark: PromptMark) -> Self {
+        match mark {
+            PromptMark::PromptStart | PromptMark::CommandFinished { .. } => Self::Prompt,
+            PromptMark::InputStart => Self::Input,
+            PromptMark::OutputStart => Self::Output,
+        }
+    }
+}
+
+/// A working directory reported by the shell with OSC 7
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReportedCwd {
+    /// Host the directory is on; `None` when the report left it out
+    pub host: Option<String>,
+    /// The directory, percent-decoded
+    pub path: PathBuf,
+}
+
+impl ReportedCwd {
+    /// Recognise a report from the body of an OSC sequence
+    /// (`7;file://host/path`)
+    pub fn from_osc(body: &str) -> Option<Self> {
+        let url = body.strip_prefix("7;")?.strip_prefix("file://")?;
+        let slash = url.find('/')?;
+        let (host, path) = url.split_at(slash);
+        Some(Self {
+            host: (!host.is_empty()).then(|| host.to_string()),
+            path: PathBuf::from(percent_decode(path)),
+        })
+    }
+
+    /// Whether the directory is on another machine than this one
+    pub fn is_remote(&self) -> bool {
+        self.host
+            .as_deref()
+            .is_some_and(|host| !is_local_host(host))
+    }
+
+    /// Host to label the directory with, if it is remote
+    pub fn remote_host(&self) -> Option<&str> {
+        self.host.as_deref().filter(|_| self.is_remote())
+    }
+}
+
+/// Whether `host`, as named in a `file://` URL, is this machine
+pub fn is_local_host(host: &str) -> bool {
+    if host.is_empty() || host.eq_ignore_ascii_case("localhost") {
+        return true;
+    }
+    let local = hostname::get()
+        .ok()
+        .and_then(|name| name.into_string().ok())
+        .unwrap_or_default();
+    // Shells differ on whether they report the short or the full name
+    let short = |name: &str| name.split('.').next().unwrap_or("").to_ascii_lowercase();
+    short(host) == short(&local)
+}
+
+/// Decode `%XX` escapes; malformed escapes are kept as they are
+pub(crate) fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|pair| std::str::from_utf8(pair).ok())
+            .and_then(|pair| u8::from_str_radix(pair, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_marks() {
+        assert_eq!(PromptMark::from_osc("133;A"), Some(PromptMark::PromptStart));
+        assert_eq!(PromptMark::from_osc("133;B"), Some(PromptMark::InputStart));
+        assert_eq!(
+            PromptMark::from_osc("133;C;cmdline=ls"),
+            Some(PromptMark::OutputStart)
+        );
+        assert_eq!(
+            PromptMark::from_osc("133;D;127"),
+            Some(PromptMark::CommandFinished {
+                exit_code: Some(127)
+            })
+        );
+        assert_eq!(
+            PromptMark::from_osc("133;D"),
+            Some(PromptMark::CommandFinished { exit_code: None })
+        );
+    }
+
+    #[test]
+    fn test_ignores_other_osc() {
+        assert_eq!(PromptMark::from_osc("0;title"), None);
+        assert_eq!(PromptMark::from_osc("1337;SetMark"), None);
+        assert_eq!(PromptMark::from_osc("133;Z"), None);
+        assert_eq!(PromptMark::from_osc("133"), None);
+    }
+
+    #[test]
+    fn test_parse_cwd_reports() {
+        assert_eq!(
+            ReportedCwd::from_osc("7;file://box/home/me/My%20Files"),
+            Some(ReportedCwd {
+                host: Some("box".to_string()),
+                path: PathBuf::from("/home/me/My Files"),
+            })
+        );
+        assert_eq!(
+            ReportedCwd::from_osc("7;file:///tmp"),
+            Some(ReportedCwd {
+                host: None,
+                path: PathBuf::from("/tmp"),
+            })
+        );
+        assert_eq!(
+            ReportedCwd::from_osc("7;file:///100%"),
+            Some(ReportedCwd {
+                host: None,
+                path: PathBuf::from("/100%"),
+            })
+        );
+        assert_eq!(ReportedCwd::from_osc("7;https://host/x"), None);
+        assert_eq!(ReportedCwd::from_osc("133;A"), None);
+    }
+
+    #[test]
+    fn test_remote_cwd() {
+        let local = hostname::get().unwrap().into_string().unwrap();
+        let report = |host: Option<&str>| ReportedCwd {
+            host: host.map(str::to_string),
+            path: PathBuf::from("/"),
+        };
+        assert!(!report(None).is_remote());
+        assert!(!report(Some("localhost")).is_remote());
+        assert!(!report(Some(&local)).is_remote());
+        assert!(!report(Some(&format!("{}.example.org", local))).is_remote());
+        let remote = report(Some("some-other-host-4711"));
+        assert!(remote.is_remote());
+        assert_eq!(remote.remote_host(), Some("some-other-host-4711"));
+    }
+}
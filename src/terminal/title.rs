@@ -0,0 +1,135 @@
+//! Window Titles (OSC 0/1/2)
+//!
+//! Shells and programs name the window with `OSC 2 ; text ST` and the icon
+//! (the tab, in most terminals) with `OSC 1 ; text ST`; `OSC 0` sets both.
+//! zsh with Oh My Zsh sets the title to the running command and back to
+//! `user@host:dir` at each prompt, and editors name the file being edited.
+//! The output processor and the overlay grid collect the changes; each
+//! [`crate::terminal::Terminal`] keeps the resulting [`ProgramTitle`].
+
+/// Longest title kept, in characters
+pub const MAX_TITLE_CHARS: usize = 256;
+
+/// A title set by a program
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TitleChange {
+    /// `OSC 0`: title and icon name
+    Both(String),
+    /// `OSC 1`: icon name
+    Icon(String),
+    /// `OSC 2`: title
+    Title(String),
+}
+
+impl TitleChange {
+    /// Recognise a title from the body of an OSC sequence (`2;text`)
+    pub fn from_osc(body: &str) -> Option<Self> {
+        let (kind, text) = body.split_once(';')?;
+        let text = sanitize(text);
+        match kind {
+            "0" => Some(Self::Both(text)),
+            "1" => Some(Self::Icon(text)),
+            "2" => Some(Self::Title(text)),
+            _ => None,
+        }
+    }
+}
+
+/// Title and icon name a terminal's programs have set. An empty one hands
+/// the name back to MosaicTerm.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ProgramTitle {
+    title: Option<String>,
+    icon: Option<String>,
+}
+
+impl ProgramTitle {
+    /// Apply a change; returns whether anything differs
+    pub fn apply(&mut self, change: TitleChange) -> bool {
+        let before = self.clone();
+        let set = |slot: &mut Option<String>, text: &str| {
+            *slot = (!text.is_empty()).then(|| text.to_string());
+        };
+        match change {
+            TitleChange::Both(text) => {
+                set(&mut self.title, &text);
+                set(&mut self.icon, &text);
+            }
+            TitleChange::Icon(text) => set(&mut self.icon, &text),
+            TitleChange::Title(text) => set(&mut self.title, &text),
+        }
+        *self != before
+    }
+
+    /// Window title, falling back to the icon name
+    pub fn title(&self) -> Option<&str> {
+        self.title.as_deref().or(self.icon.as_deref())
+    }
+
+    /// Icon name, falling back to the title
+    pub fn icon(&self) -> Option<&str> {
+        self.icon.as_deref().or(self.title.as_deref())
+    }
+
+    /// Forget both names
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// Drop control characters and cap the length, so a title can't break the
+/// window decoration or the status bar
+fn sanitize(text: &str) -> String {
+    text.chars()
+        .filter(|c| !c.is_control())
+        .take(MAX_TITLE_CHARS)
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_osc() {
+        assert_eq!(
+            TitleChange::from_osc("2;user@host:~/proj"),
+            Some(TitleChange::Title("user@host:~/proj".to_string()))
+        );
+        assert_eq!(
+            TitleChange::from_osc("1;proj"),
+            Some(TitleChange::Icon("proj".to_string()))
+        );
+        assert_eq!(
+            TitleChange::from_osc("0;a;b\x07c"),
+            Some(TitleChange::Both("a;bc".to_string()))
+        );
+        assert_eq!(TitleChange::from_osc("7;file:///tmp"), None);
+        assert_eq!(TitleChange::from_osc("2"), None);
+
+        let long = format!("2;{}", "x".repeat(MAX_TITLE_CHARS + 10));
+        let Some(TitleChange::Title(text)) = TitleChange::from_osc(&long) else {
+            panic!("not a title");
+        };
+        assert_eq!(text.chars().count(), MAX_TITLE_CHARS);
+    }
+
+    #[test]
+    fn test_program_title() {
+        let mut title = ProgramTitle::default();
+        assert_eq!(title.title(), None);
+
+        assert!(title.apply(TitleChange::Icon("vim".to_string())));
+        assert_eq!(title.title(), Some("vim"));
+        assert!(title.apply(TitleChange::Title("notes.md - VIM".to_string())));
+        assert_eq!(title.title(), Some("notes.md - VIM"));
+        assert_eq!(title.icon(), Some("vim"));
+        assert!(!title.apply(TitleChange::Title("notes.md - VIM".to_string())));
+
+        assert!(title.apply(TitleChange::Both(String::new())));
+        assert_eq!(title.title(), None);
+        assert_eq!(title.icon(), None);
+    }
+}
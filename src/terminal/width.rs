@@ -0,0 +1,73 @@
+//! Display Width
+//!
+//! Column widths for terminal text, shared by the grid emulator and block
+//! output so both agree on where a character lands. East Asian wide
+//! characters and emoji take two columns; combining marks, joiners and
+//! variation selectors take none and belong to the cluster before them.
+
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// Columns taken by a single character (0 for controls and combining marks)
+pub fn char_width(c: char) -> usize {
+    c.width().unwrap_or(0)
+}
+
+/// Columns taken by a grapheme cluster, at most two
+pub fn cluster_width(cluster: &str) -> usize {
+    cluster.width().min(2)
+}
+
+/// Columns taken by a string
+pub fn str_width(text: &str) -> usize {
+    if text.is_ascii() {
+        return text.len();
+    }
+    text.graphemes(true).map(cluster_width).sum()
+}
+
+/// Whether `c` continues the grapheme cluster `cluster` rather than starting a
+/// new one (combining marks, ZWJ sequences, skin tones, flag pairs)
+pub fn extends_cluster(cluster: &str, c: char) -> bool {
+    if c.is_ascii() || cluster.is_empty() {
+        return false;
+    }
+    let mut joined = String::with_capacity(cluster.len() + c.len_utf8());
+    joined.push_str(cluster);
+    joined.push(c);
+    joined.graphemes(true).nth(1).is_none()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_char_widths() {
+        assert_eq!(char_width('a'), 1);
+        assert_eq!(char_width('中'), 2);
+        assert_eq!(char_width('😀'), 2);
+        assert_eq!(char_width('\u{0301}'), 0);
+        assert_eq!(char_width('\x07'), 0);
+    }
+
+    #[test]
+    fn test_str_width_mixed() {
+        assert_eq!(str_width("abc"), 3);
+        assert_eq!(str_width("a中b"), 4);
+        assert_eq!(str_width("e\u{0301}"), 1);
+        assert_eq!(str_width("👍🏽"), 2);
+        assert_eq!(str_width("🇸🇪"), 2);
+    }
+
+    #[test]
+    fn test_extends_cluster() {
+        assert!(extends_cluster("e", '\u{0301}'));
+        assert!(extends_cluster("👨", '\u{200D}'));
+        assert!(extends_cluster("👨\u{200D}", '👩'));
+        assert!(extends_cluster("🇸", '🇪'));
+        assert!(!extends_cluster("🇸🇪", '🇸'));
+        assert!(!extends_cluster("中", '文'));
+        assert!(!extends_cluster("a", 'b'));
+    }
+}
@@ -0,0 +1,694 @@
+//! Virtualised History Layout
+//!
+//! Row heights of the command history, kept as prefix sums so a frame lays
+//! out only the rows inside the viewport. A block is a header row followed by
+//! one row per output line, each as tall as the screen rows it wraps to at the
+//! current width. Every block keeps running offsets of its lines and the
+//! history keeps running offsets of its blocks, so finding what is on screen
+//! is two binary searches, and output streaming into a block only appends.
+//!
+//! The layout also anchors the viewport: while the view is scrolled up it
+//! remembers the row at its top and where that row is now after blocks above
+//! it change height, so the text being read stays put.
+
+use crate::models::{CommandBlock, ExecutionStatus, OutputLine};
+use crate::terminal::width;
+use std::collections::HashMap;
+use std::ops::Range;
+
+/// Space above the header and below the output inside a block
+pub const BLOCK_PADDING: f32 = 6.0;
+
+/// Padding of a block without output
+pub const COMPACT_BLOCK_PADDING: f32 = 4.0;
+
+/// Space between the header and the first output row
+pub const HEADER_GAP: f32 = 3.0;
+
+/// Space between blocks
+pub const BLOCK_SPACING: f32 = 4.0;
+
+/// Sizes the history is laid out with, in points
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RowMetrics {
+    /// Height of a screen row of output
+    pub line_height: f32,
+    /// Width of an output cell
+    pub char_width: f32,
+    /// Height of a block's header row
+    pub header_height: f32,
+    /// Width output wraps at
+    pub wrap_width: f32,
+    /// Physical pixels per point, for sizing images
+    pub pixels_per_point: f32,
+}
+
+impl RowMetrics {
+    /// Cells that fit on a screen row
+    pub fn columns(&self) -> usize {
+        if self.char_width <= 0.0 {
+            return 1;
+        }
+        ((self.wrap_width / self.char_width) as usize).max(1)
+    }
+
+    /// Height of an output row `cells` wide
+    fn text_height(&self, cells: u32) -> f64 {
+        let rows = (cells as usize).div_ceil(self.columns()).max(1);
+        rows as f64 * self.line_height as f64
+    }
+
+    /// Height `line` takes in a block whose images are hidden or not
+    fn line_height(&self, line: &OutputLine, images_hidden: bool) -> f64 {
+        match &line.image {
+            Some(_) if images_hidden => self.line_height as f64,
+            Some(placement) => {
+                let ppp = self.pixels_per_point.max(f32::EPSILON);
+                let (_, height) = placement.display_size(
+                    (self.char_width * ppp, self.line_height * ppp),
+                    self.wrap_width * ppp,
+                );
+                (height / ppp) as f64
+            }
+            None => self.text_height(text_cells(line)),
+        }
+    }
+}
+
+impl Default for RowMetrics {
+    fn default() -> Self {
+        Self {
+            line_height: 16.0,
+            char_width: 8.0,
+            header_height: 18.0,
+            wrap_width: 640.0,
+            pixels_per_point: 1.0,
+        }
+    }
+}
+
+/// Display width of a text line, in cells
+fn text_cells(line: &OutputLine) -> u32 {
+    width::str_width(&line.text).min(u32::MAX as usize) as u32
+}
+
+/// Extent of an output row, kept so rows can be rewrapped without measuring
+/// their text again
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RowExtent {
+    /// Text this many cells wide
+    Cells(u32),
+    /// An image, sized from its placement
+    Image,
+}
+
+/// Row offsets of one block
+#[derive(Debug, Clone)]
+struct BlockRows {
+    id: String,
+    /// Extents of the settled output lines indexed so far
+    extents: Vec<RowExtent>,
+    /// Offsets of rows from the first output row: row `i` spans
+    /// `offsets[i]..offsets[i + 1]`; the last entries are live output
+    offsets: Vec<f64>,
+    /// Whether images were hidden when the rows were measured
+    images_hidden: bool,
+    /// Whether the block was running, and so showing its live output
+    running: bool,
+    /// Output generation of the block the rows were measured from
+    generation: u64,
+    /// Lines the block printed before its first output row, spilled to disk
+    /// or dropped
+    first_line: usize,
+}
+
+impl BlockRows {
+    fn new(block: &CommandBlock, metrics: &RowMetrics) -> Self {
+        let mut rows = Self {
+            id: block.id.clone(),
+            extents: Vec::with_capacity(block.output.len()),
+            offsets: Vec::with_capacity(block.output.len() + 1),
+            images_hidden: block.images_hidden,
+            running: false,
+            generation: block.output_generation,
+            first_line: block.scrollback.first_line(),
+        };
+        rows.offsets.push(0.0);
+        rows.sync(block, metrics);
+        rows
+    }
+
+    /// Output rows, live ones included
+    fn len(&self) -> usize {
+        self.offsets.len() - 1
+    }
+
+    /// Height of the output rows
+    fn rows_height(&self) -> f64 {
+        self.offsets[self.len()]
+    }
+
+    /// Height of the whole block, not counting the space after it
+    fn height(&self, metrics: &RowMetrics) -> f64 {
+        let chrome = if self.len() == 0 {
+            2.0 * COMPACT_BLOCK_PADDING
+        } else {
+            2.0 * BLOCK_PADDING + HEADER_GAP
+        };
+        (chrome + metrics.header_height) as f64 + self.rows_height()
+    }
+
+    /// Top of the first output row from the top of the block
+    fn rows_top(metrics: &RowMetrics) -> f64 {
+        (BLOCK_PADDING + metrics.header_height + HEADER_GAP) as f64
+    }
+
+    /// Bring the rows up to date with `block`; returns whether they changed
+    fn sync(&mut self, block: &CommandBlock, metrics: &RowMetrics) -> bool {
+        let settled = self.extents.len();
+        let live = block.status == ExecutionStatus::Running;
+        if block.images_hidden != self.images_hidden
+            || block.output_generation != self.generation
+            || block.output.len() < settled
+        {
+            // Settled output only grows unless lines were dropped from the
+            // front; anything else is a different view
+            *self = Self::new(block, metrics);
+            return true;
+        }
+        if block.output.len() == settled && !live && !self.running {
+            return false;
+        }
+
+        // Live rows are few and rewritten in place, so they are measured
+        // again every time
+        self.offsets.truncate(settled + 1);
+        let mut top = self.offsets[settled];
+        for line in &block.output[settled..] {
+            self.extents.push(match line.image {
+                Some(_) => RowExtent::Image,
+                None => RowExtent::Cells(text_cells(line)),
+            });
+            top += metrics.line_height(line, self.images_hidden);
+            self.offsets.push(top);
+        }
+        if live {
+            for line in &block.live_output {
+                top += metrics.line_height(line, self.images_hidden);
+                self.offsets.push(top);
+            }
+        }
+        self.running = live;
+        true
+    }
+
+    /// Measure the rows again for new metrics, reusing the extents of text
+    fn rewrap(&mut self, block: &CommandBlock, metrics: &RowMetrics) {
+        self.offsets.truncate(1);
+        let mut top = 0.0;
+        for (extent, line) in self.extents.iter().zip(&block.output) {
+            top += match *extent {
+                RowExtent::Cells(cells) => metrics.text_height(cells),
+                RowExtent::Image => metrics.line_height(line, self.images_hidden),
+            };
+            self.offsets.push(top);
+        }
+        if self.running {
+            for line in &block.live_output {
+                top += metrics.line_height(line, self.images_hidden);
+                self.offsets.push(top);
+            }
+        }
+    }
+
+    /// Row at `offset` from the first output row, clamped to the rows
+    fn row_at(&self, offset: f64) -> usize {
+        let upper = self.offsets.partition_point(|&top| top <= offset);
+        upper.saturating_sub(1).min(self.len().saturating_sub(1))
+    }
+}
+
+/// Part of a block inside the viewport
+#[derive(Debug, Clone, PartialEq)]
+pub struct VisibleBlock {
+    /// Index of the block in the history
+    pub index: usize,
+    /// Top of the block from the top of the history
+    pub top: f32,
+    /// Height of the block
+    pub height: f32,
+    /// Output rows inside the viewport: settled lines first, then live ones
+    pub rows: Range<usize>,
+}
+
+impl Default for HistoryLayout {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Row at the top of the viewport, and how far it is scrolled past
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryAnchor {
+    /// Block the row is in
+    pub block_id: String,
+    /// Output line, counted from the first one the block printed so it
+    /// holds while older lines are spilled or read back, or `None` for the
+    /// block's header
+    pub row: Option<usize>,
+    /// Distance from the top of the row to the top of the viewport
+    pub offset: f32,
+}
+
+/// Prefix-sum index of the command history's row heights
+#[derive(Debug, Clone)]
+pub struct HistoryLayout {
+    metrics: RowMetrics,
+    blocks: Vec<BlockRows>,
+    /// Offsets of blocks: block `i` starts at `tops[i]`, and `tops` ends
+    /// with the height of the history
+    tops: Vec<f64>,
+    /// Row to keep in place while scrolled up; `None` follows the bottom
+    anchor: Option<HistoryAnchor>,
+}
+
+impl HistoryLayout {
+    /// Create an empty layout
+    pub fn new() -> Self {
+        Self {
+            metrics: RowMetrics::default(),
+            blocks: Vec::new(),
+            tops: vec![0.0],
+            anchor: None,
+        }
+    }
+
+    /// Bring the index up to date with `blocks` laid out with `metrics`.
+    ///
+    /// Only blocks that changed are measured, and only their new rows unless
+    /// the width or fonts changed.
+    pub fn sync(&mut self, blocks: &[CommandBlock], metrics: RowMetrics) {
+        let same_blocks = self.blocks.len() == blocks.len()
+            && self
+                .blocks
+                .iter()
+                .zip(blocks)
+                .all(|(rows, b)| rows.id == b.id);
+        if !same_blocks {
+            self.reorder(blocks, &metrics);
+        }
+
+        let mut first_changed = if same_blocks { None } else { Some(0) };
+        if metrics != self.metrics {
+            self.metrics = metrics;
+            for (rows, block) in self.blocks.iter_mut().zip(blocks) {
+                rows.rewrap(block, &metrics);
+            }
+            first_changed = Some(0);
+        }
+        for (i, (rows, block)) in self.blocks.iter_mut().zip(blocks).enumerate() {
+            if rows.sync(block, &metrics) && first_changed.is_none() {
+                first_changed = Some(i);
+            }
+        }
+        if let Some(first) = first_changed {
+            self.retop(first);
+        }
+    }
+
+    /// Match the indexed blocks to `blocks`, keeping the rows of blocks that
+    /// are still there
+    fn reorder(&mut self, blocks: &[CommandBlock], metrics: &RowMetrics) {
+        let mut old: HashMap<String, BlockRows> = self
+            .blocks
+            .drain(..)
+            .map(|rows| (rows.id.clone(), rows))
+            .collect();
+        self.blocks = blocks
+            .iter()
+            .map(|block| {
+                old.remove(&block.id)
+                    .unwrap_or_else(|| BlockRows::new(block, metrics))
+            })
+            .collect();
+    }
+
+    /// Recompute block offsets from block `first` on
+    fn retop(&mut self, first: usize) {
+        self.tops.truncate(first + 1);
+        let mut top = self.tops[first];
+        let last = self.blocks.len().saturating_sub(1);
+        for (i, rows) in self.blocks.iter().enumerate().skip(first) {
+            top += rows.height(&self.metrics);
+            if i < last {
+                top += BLOCK_SPACING as f64;
+            }
+            self.tops.push(top);
+        }
+    }
+
+    /// Metrics the index was last synced with
+    pub fn metrics(&self) -> &RowMetrics {
+        &self.metrics
+    }
+
+    /// Height of the whole history
+    pub fn total_height(&self) -> f32 {
+        self.tops.last().copied().unwrap_or(0.0) as f32
+    }
+
+    /// Number of output rows in block `index`, live ones included
+    pub fn row_count(&self, index: usize) -> usize {
+        self.blocks.get(index).map_or(0, BlockRows::len)
+    }
+
+    /// Top of output row `row` of block `index` from the top of the history.
+    ///
+    /// `row` may be one past the last row, giving the bottom of the rows.
+    pub fn row_top(&self, index: usize, row: usize) -> Option<f32> {
+        let rows = self.blocks.get(index)?;
+        let offset = rows.offsets.get(row)?;
+        Some((self.tops[index] + BlockRows::rows_top(&self.metrics) + offset) as f32)
+    }
+
+    /// Height of output row `row` of block `index`
+    pub fn row_height(&self, index: usize, row: usize) -> Option<f32> {
+        let offsets = &self.blocks.get(index)?.offsets;
+        let bottom = offsets.get(row + 1)?;
+        Some((bottom - offsets[row]) as f32)
+    }
+
+    /// Index of the block at `offset` from the top of the history, if any
+    fn block_at(&self, offset: f64) -> Option<usize> {
+        if self.blocks.is_empty() {
+            return None;
+        }
+        let upper = self.tops[..self.blocks.len()].partition_point(|&top| top <= offset);
+        Some(upper.saturating_sub(1))
+    }
+
+    /// Blocks and rows between `top` and `bottom`, in order
+    pub fn visible(&self, top: f32, bottom: f32) -> Vec<VisibleBlock> {
+        let (top, bottom) = (top as f64, bottom as f64);
+        let Some(first) = self.block_at(top) else {
+            return Vec::new();
+        };
+        let mut visible = Vec::new();
+        for index in first..self.blocks.len() {
+            let block_top = self.tops[index];
+            if block_top >= bottom {
+                break;
+            }
+            let rows = &self.blocks[index];
+            let rows_top = block_top + BlockRows::rows_top(&self.metrics);
+            let range = if rows_top + rows.rows_height() <= top || rows_top >= bottom {
+                0..0
+            } else {
+                let start = rows.row_at(top - rows_top);
+                let end = rows.row_at(bottom - rows_top) + 1;
+                start..end
+            };
+            visible.push(VisibleBlock {
+                index,
+                top: block_top as f32,
+                height: rows.height(&self.metrics) as f32,
+                rows: range,
+            });
+        }
+        visible
+    }
+
+    /// Remember the viewport the history was just shown in: follow the
+    /// bottom if it reaches it, otherwise keep the row at its top in place
+    pub fn set_viewport(&mut self, offset: f32, height: f32) {
+        if offset + height >= self.total_height() - 1.0 {
+            self.anchor = None;
+            return;
+        }
+        self.anchor = self.anchor_at(offset);
+    }
+
+    /// Offset that keeps the anchored row where it was, or `None` while
+    /// following the bottom
+    pub fn anchored_offset(&self) -> Option<f32> {
+        self.anchor
+            .as_ref()
+            .and_then(|anchor| self.offset_of(anchor))
+    }
+
+    /// Row at `offset` from the top of the history
+    pub fn anchor_at(&self, offset: f32) -> Option<HistoryAnchor> {
+        let index = self.block_at(offset as f64)?;
+        let rows = &self.blocks[index];
+        let rows_top = self.tops[index] + BlockRows::rows_top(&self.metrics);
+        let (row, row_top) = if rows.len() == 0 || (offset as f64) < rows_top {
+            (None, self.tops[index])
+        } else {
+            let row = rows.row_at(offset as f64 - rows_top);
+            (Some(rows.first_line + row), rows_top + rows.offsets[row])
+        };
+        Some(HistoryAnchor {
+            block_id: rows.id.clone(),
+            row,
+            offset: (offset as f64 - row_top) as f32,
+        })
+    }
+
+    /// Where `anchor` puts the top of the viewport now, if its block is
+    /// still in the history
+    pub fn offset_of(&self, anchor: &HistoryAnchor) -> Option<f32> {
+        let index = self.blocks.iter().position(|b| b.id == anchor.block_id)?;
+        // A row spilled or dropped from under the anchor holds to the
+        // nearest one left
+        let rows = &self.blocks[index];
+        let last = rows.len().checked_sub(1);
+        let row_top = match anchor.row.zip(last) {
+            Some((row, last)) => {
+                let row = row.saturating_sub(rows.first_line).min(last);
+                self.row_top(index, row)? as f64
+            }
+            None => self.tops[index],
+        };
+        Some((row_top + anchor.offset as f64) as f32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn metrics() -> RowMetrics {
+        RowMetrics {
+            line_height: 10.0,
+            char_width: 5.0,
+            header_height: 20.0,
+            wrap_width: 50.0,
+            pixels_per_point: 1.0,
+        }
+    }
+
+    fn block(lines: &[&str]) -> CommandBlock {
+        let mut block = CommandBlock::new("cmd".to_string(), PathBuf::from("/tmp"));
+        block.mark_completed(std::time::Duration::ZERO);
+        for line in lines {
+            block.add_output_line(OutputLine::new(*line));
+        }
+        block
+    }
+
+    #[test]
+    fn test_heights_count_wrapped_rows() {
+        let mut layout = HistoryLayout::new();
+        // 10 columns: the second line wraps to three rows
+        layout.sync(&[block(&["short", &"x".repeat(25)])], metrics());
+
+        assert_eq!(layout.row_count(0), 2);
+        assert_eq!(layout.row_height(0, 0), Some(10.0));
+        assert_eq!(layout.row_height(0, 1), Some(30.0));
+        let expected = 2.0 * BLOCK_PADDING + 20.0 + HEADER_GAP + 40.0;
+        assert_eq!(layout.total_height(), expected);
+    }
+
+    #[test]
+    fn test_compact_block_without_output() {
+        let mut layout = HistoryLayout::new();
+        layout.sync(&[block(&[]), block(&[])], metrics());
+        let compact = 2.0 * COMPACT_BLOCK_PADDING + 20.0;
+        assert_eq!(layout.total_height(), 2.0 * compact + BLOCK_SPACING);
+    }
+
+    #[test]
+    fn test_visible_rows_only() {
+        let lines: Vec<String> = (0..1000).map(|i| i.to_string()).collect();
+        let lines: Vec<&str> = lines.iter().map(String::as_str).collect();
+        let blocks = vec![block(&lines), block(&lines)];
+        let mut layout = HistoryLayout::new();
+        layout.sync(&blocks, metrics());
+
+        let top = layout.row_top(0, 500).unwrap();
+        let visible = layout.visible(top, top + 95.0);
+        assert_eq!(visible.len(), 1);
+        assert_eq!(visible[0].index, 0);
+        assert_eq!(visible[0].rows, 500..510);
+
+        // Straddling two blocks
+        let top = layout.row_top(0, 998).unwrap();
+        let visible = layout.visible(top, top + 60.0);
+        assert_eq!(visible.len(), 2);
+        assert_eq!(visible[0].rows, 998..1000);
+        assert_eq!(visible[1].rows, 0..1);
+    }
+
+    #[test]
+    fn test_streaming_appends_rows() {
+        let mut blocks = vec![block(&["a"]), block(&["b"])];
+        let mut layout = HistoryLayout::new();
+        layout.sync(&blocks, metrics());
+        let before = layout.total_height();
+
+        blocks[1].add_output_line(OutputLine::new("c"));
+        blocks[1].add_output_line(OutputLine::new("d"));
+        layout.sync(&blocks, metrics());
+        assert_eq!(layout.row_count(1), 3);
+        assert_eq!(layout.total_height(), before + 20.0);
+    }
+
+    #[test]
+    fn test_live_output_is_remeasured() {
+        let mut running = block(&["settled"]);
+        running.mark_running();
+        running.live_output = vec![OutputLine::new("1%")];
+        let mut blocks = vec![running];
+        let mut layout = HistoryLayout::new();
+        layout.sync(&blocks, metrics());
+        assert_eq!(layout.row_count(0), 2);
+
+        blocks[0].live_output = vec![OutputLine::new("x".repeat(15))];
+        layout.sync(&blocks, metrics());
+        assert_eq!(layout.row_height(0, 1), Some(20.0));
+
+        blocks[0].mark_completed(std::time::Duration::ZERO);
+        layout.sync(&blocks, metrics());
+        assert_eq!(layout.row_count(0), 1);
+    }
+
+    #[test]
+    fn test_rewrap_on_width_change() {
+        let blocks = vec![block(&[&"x".repeat(20)])];
+        let mut layout = HistoryLayout::new();
+        layout.sync(&blocks, metrics());
+        assert_eq!(layout.row_height(0, 0), Some(20.0));
+
+        let narrow = RowMetrics {
+            wrap_width: 25.0,
+            ..metrics()
+        };
+        layout.sync(&blocks, narrow);
+        assert_eq!(layout.row_height(0, 0), Some(40.0));
+    }
+
+    #[test]
+    fn test_removed_blocks_keep_the_rest() {
+        let mut blocks = vec![block(&["a"]), block(&["b", "c"]), block(&["d"])];
+        let mut layout = HistoryLayout::new();
+        layout.sync(&blocks, metrics());
+
+        blocks.remove(0);
+        layout.sync(&blocks, metrics());
+        assert_eq!(layout.row_count(0), 2);
+        assert_eq!(layout.row_count(1), 1);
+        assert_eq!(layout.visible(0.0, 1.0)[0].index, 0);
+    }
+
+    #[test]
+    fn test_rows_out_of_range() {
+        let mut layout = HistoryLayout::new();
+        assert_eq!(layout.row_top(0, 0), None);
+        assert_eq!(layout.row_height(0, 0), None);
+
+        layout.sync(&[block(&["a", "b"])], metrics());
+        let bottom = layout.row_top(0, 0).unwrap() + 20.0;
+        assert_eq!(layout.row_top(0, 2), Some(bottom));
+        assert_eq!(layout.row_top(0, 3), None);
+        assert_eq!(layout.row_height(0, 1), Some(10.0));
+        assert_eq!(layout.row_height(0, 2), None);
+        assert_eq!(layout.row_top(1, 0), None);
+    }
+
+    #[test]
+    fn test_front_truncation_remeasures_block() {
+        let mut blocks = vec![block(&["a", &"x".repeat(25), "b", "c"])];
+        let mut layout = HistoryLayout::new();
+        layout.sync(&blocks, metrics());
+
+        // Same number of lines, but the wrapped one is gone
+        blocks[0].drop_output_front(2, Some(OutputLine::new("[cut]")));
+        blocks[0].add_output_line(OutputLine::new("d"));
+        layout.sync(&blocks, metrics());
+
+        let mut fresh = HistoryLayout::new();
+        fresh.sync(&blocks, metrics());
+        assert_eq!(layout.total_height(), fresh.total_height());
+        for row in 0..4 {
+            assert_eq!(layout.row_height(0, row), Some(10.0));
+        }
+    }
+
+    #[test]
+    fn test_anchor_holds_while_pages_are_read_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let lines: Vec<String> = (0..3000).map(|i| i.to_string()).collect();
+        let lines: Vec<&str> = lines.iter().map(String::as_str).collect();
+        let mut blocks = vec![block(&lines)];
+        blocks[0]
+            .spill_output_front(2000, Some(dir.path()))
+            .unwrap();
+        let mut layout = HistoryLayout::new();
+        layout.sync(&blocks, metrics());
+
+        // Line 2010 is row 10 until a page is read back in front of it
+        let offset = layout.row_top(0, 10).unwrap() + 3.0;
+        layout.set_viewport(offset, 100.0);
+        blocks[0].page_in_output().unwrap();
+        layout.sync(&blocks, metrics());
+        let moved = layout.row_top(0, 1010).unwrap() + 3.0;
+        assert_eq!(layout.anchored_offset(), Some(moved));
+
+        blocks[0].page_out_output();
+        layout.sync(&blocks, metrics());
+        assert_eq!(layout.anchored_offset(), Some(offset));
+    }
+
+    #[test]
+    fn test_anchor_holds_while_rows_above_grow() {
+        let lines: Vec<String> = (0..100).map(|i| i.to_string()).collect();
+        let lines: Vec<&str> = lines.iter().map(String::as_str).collect();
+        let mut blocks = vec![block(&lines), block(&lines)];
+        let mut layout = HistoryLayout::new();
+        layout.sync(&blocks, metrics());
+
+        let offset = layout.row_top(1, 40).unwrap() + 3.0;
+        layout.set_viewport(offset, 100.0);
+
+        // Output above the viewport moves the row down by two lines
+        blocks[0].add_output_line(OutputLine::new("more"));
+        blocks[0].add_output_line(OutputLine::new("more"));
+        layout.sync(&blocks, metrics());
+        assert_eq!(layout.anchored_offset(), Some(offset + 20.0));
+    }
+
+    #[test]
+    fn test_bottom_is_followed() {
+        let mut blocks = vec![block(&["a", "b", "c"])];
+        let mut layout = HistoryLayout::new();
+        layout.sync(&blocks, metrics());
+        let height = 20.0;
+        layout.set_viewport(layout.total_height() - height, height);
+        assert_eq!(layout.anchored_offset(), None);
+
+        blocks[0].add_output_line(OutputLine::new("d"));
+        layout.sync(&blocks, metrics());
+        assert_eq!(layout.anchored_offset(), None);
+    }
+}
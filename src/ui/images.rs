@@ -0,0 +1,85 @@
+//! Inline Image Textures
+//!
+//! Uploads the images programs draw (see [`crate::terminal::images`]) as egui
+//! textures the first time they are shown, and keeps each for as long as it
+//! is drawn. A texture left undrawn for [`IDLE_FRAMES`] frames is released by
+//! [`release_idle`], which the app calls every frame: its block was hidden or
+//! dropped its images, or the overlay moved on.
+
+use crate::terminal::images::InlineImage;
+use eframe::egui;
+use std::collections::HashMap;
+
+/// Frames a texture may go undrawn before it is released
+const IDLE_FRAMES: u64 = 120;
+
+/// Textures by image id, with the frame each was last drawn in
+#[derive(Clone, Default)]
+struct ImageTextures(HashMap<u64, (egui::TextureHandle, u64)>);
+
+/// Where the textures are kept in the context's memory
+fn store() -> egui::Id {
+    egui::Id::new("inline_image_textures")
+}
+
+/// Release the textures left undrawn for [`IDLE_FRAMES`] frames, whether or
+/// not any image is drawn this frame
+pub fn release_idle(ctx: &egui::Context) {
+    let frame = ctx.cumulative_frame_nr();
+    ctx.data_mut(|data| {
+        data.get_temp_mut_or_default::<ImageTextures>(store())
+            .0
+            .retain(|_, (_, last)| frame.saturating_sub(*last) <= IDLE_FRAMES);
+    });
+}
+
+/// Texture showing `image`, uploading it if needed
+pub fn texture(ctx: &egui::Context, image: &InlineImage) -> egui::TextureHandle {
+    let frame = ctx.cumulative_frame_nr();
+    let cached = ctx.data_mut(|data| {
+        let textures = data.get_temp_mut_or_default::<ImageTextures>(store());
+        textures.0.get_mut(&image.id()).map(|(texture, last)| {
+            *last = frame;
+            texture.clone()
+        })
+    });
+    if let Some(texture) = cached {
+        return texture;
+    }
+
+    let texture = ctx.load_texture(
+        format!("inline_image_{}", image.id()),
+        color_image(image, ctx.input(|i| i.max_texture_side)),
+        egui::TextureOptions::LINEAR,
+    );
+    ctx.data_mut(|data| {
+        data.get_temp_mut_or_default::<ImageTextures>(store())
+            .0
+            .insert(image.id(), (texture.clone(), frame));
+    });
+    texture
+}
+
+/// Pixels of `image`, scaled down to fit a texture of `max_side` if larger
+fn color_image(image: &InlineImage, max_side: usize) -> egui::ColorImage {
+    let (width, height) = (image.width as usize, image.height as usize);
+    if width.max(height) <= max_side {
+        return egui::ColorImage::from_rgba_unmultiplied([width, height], &image.rgba);
+    }
+    let scale = max_side as f64 / width.max(height) as f64;
+    let fitted =
+        image::RgbaImage::from_raw(image.width, image.height, image.rgba.clone()).map(|pixels| {
+            image::imageops::thumbnail(
+                &pixels,
+                ((width as f64 * scale) as u32).max(1),
+                ((height as f64 * scale) as u32).max(1),
+            )
+        });
+    match fitted {
+        Some(fitted) => egui::ColorImage::from_rgba_unmultiplied(
+            [fitted.width() as usize, fitted.height() as usize],
+            fitted.as_raw(),
+        ),
+        None => egui::ColorImage::new([1, 1], vec![egui::Color32::TRANSPARENT]),
+    }
+}
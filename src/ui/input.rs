@@ -369,6 +369,11 @@ impl InputPrompt {
         &self.prompt_text
     }
 
+    /// Whether the buffer holds several lines (e.g. after a multi-line paste)
+    pub fn is_multiline(&self) -> bool {
+        self.current_input.contains('\n')
+    }
+
     /// Get current input text
     pub fn current_input(&self) -> &str {
         &self.current_input
@@ -448,6 +453,17 @@ pub mod utils {
             .collect()
     }
 
+    /// Normalise pasted text for the prompt buffer.
+    ///
+    /// Line endings become `\n` and trailing line breaks are dropped, so a
+    /// copied line does not leave an empty last line in the buffer.
+    pub fn normalize_paste(text: &str) -> String {
+        text.replace("\r\n", "\n")
+            .replace('\r', "\n")
+            .trim_end_matches('\n')
+            .to_string()
+    }
+
     /// Get suggested completions for input
     pub fn get_completions(input: &str, history: &VecDeque<String>) -> Vec<String> {
         if input.is_empty() {
@@ -589,6 +605,22 @@ mod tests {
         assert_eq!(result, "echohello\tworld");
     }
 
+    #[test]
+    fn test_utils_normalize_paste() {
+        assert_eq!(utils::normalize_paste("ls\r\ncd /tmp\r\n"), "ls\ncd /tmp");
+        assert_eq!(utils::normalize_paste("a\rb\n\n"), "a\nb");
+        assert_eq!(utils::normalize_paste("single"), "single");
+    }
+
+    #[test]
+    fn test_multiline_input() {
+        let mut prompt = InputPrompt::new();
+        prompt.set_input("echo one".to_string());
+        assert!(!prompt.is_multiline());
+        prompt.set_input("echo one\necho two".to_string());
+        assert!(prompt.is_multiline());
+    }
+
     #[test]
     fn test_utils_get_completions() {
         let mut history = VecDeque::new();
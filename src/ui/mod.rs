@@ -6,8 +6,11 @@
 pub mod blocks;
 pub mod colors;
 pub mod completion_popup;
+pub mod history_layout;
+pub mod images;
 pub mod input;
 pub mod metrics;
+pub mod repaint;
 pub mod scroll;
 pub mod ssh_prompt_overlay;
 pub mod text;
@@ -18,6 +21,7 @@ pub mod viewport;
 pub use blocks::{BlockConfig, CommandBlocks, RenderedBlock, StatusIcon};
 pub use colors::{ToEguiColor, UiColors};
 pub use completion_popup::CompletionPopup;
+pub use history_layout::HistoryLayout;
 pub use input::{InputConfig, InputPrompt};
 pub use metrics::MetricsPanel;
 pub use scroll::{ScrollState, ScrollableHistory, ScrollbarConfig};
@@ -0,0 +1,61 @@
+//! Repaint Scheduling
+//!
+//! How a frame asks for the next one. The UI draws again at once while PTY
+//! output is still waiting to be drained, otherwise when its earliest timer
+//! is due; with neither it asks for nothing and sleeps until an event (PTY
+//! output, finished background work, input) wakes it.
+
+use eframe::egui;
+use std::time::Duration;
+
+/// Ask `ctx` for the next frame given whether output is still pending and
+/// the time left on each running timer
+pub fn schedule(
+    ctx: &egui::Context,
+    pending_output: bool,
+    timers: impl IntoIterator<Item = Duration>,
+) {
+    match next_frame_in(pending_output, timers) {
+        Some(Duration::ZERO) => ctx.request_repaint(),
+        Some(delay) => ctx.request_repaint_after(delay),
+        None => {}
+    }
+}
+
+/// Time until the next frame is needed, or `None` when idle
+pub fn next_frame_in(
+    pending_output: bool,
+    timers: impl IntoIterator<Item = Duration>,
+) -> Option<Duration> {
+    if pending_output {
+        return Some(Duration::ZERO);
+    }
+    timers.into_iter().min()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_idle_needs_no_frame() {
+        assert_eq!(next_frame_in(false, []), None);
+    }
+
+    #[test]
+    fn test_pending_output_draws_at_once() {
+        assert_eq!(
+            next_frame_in(true, [Duration::from_millis(200)]),
+            Some(Duration::ZERO)
+        );
+    }
+
+    #[test]
+    fn test_earliest_timer_wins() {
+        let timers = [Duration::from_millis(200), Duration::from_millis(30)];
+        assert_eq!(
+            next_frame_in(false, timers),
+            Some(Duration::from_millis(30))
+        );
+    }
+}
@@ -1,9 +1,10 @@
 //! Scrollable history area
 //!
-//! This module manages the scrollable history region that displays
-//! past command blocks in the MosaicTerm interface.
+//! This module manages the scroll state of the history region: position,
+//! smooth scrolling and the scrollbar. Command blocks themselves are laid out
+//! by [`HistoryLayout`](crate::ui::HistoryLayout), which only lays out the
+//! rows inside the viewport.
 
-use crate::models::CommandBlock;
 use eframe::egui;
 use std::collections::HashMap;
 
@@ -126,73 +127,6 @@ impl ScrollableHistory {
         }
     }
 
-    /// Render command blocks with enhanced scrolling
-    /// Returns information about context menu state: (block_id, position) if a context menu should be shown
-    pub fn render_command_blocks(
-        &mut self,
-        ui: &mut egui::Ui,
-        command_blocks: &[CommandBlock],
-        blocks_renderer: &mut crate::ui::CommandBlocks,
-    ) -> Option<(String, egui::Pos2)> {
-        // Calculate total content height for command blocks
-        self.calculate_command_blocks_height(command_blocks);
-
-        // Create enhanced scroll area with momentum and smooth scrolling
-        let scroll_area = egui::ScrollArea::vertical()
-            .auto_shrink([false; 2])
-            .stick_to_bottom(true);
-
-        // Handle mouse wheel events for smooth scrolling
-        ui.input(|input| {
-            if input.smooth_scroll_delta.y != 0.0 {
-                self.handle_mouse_wheel(input.smooth_scroll_delta);
-            }
-        });
-
-        let mut context_menu_info = None;
-
-        scroll_area.show(ui, |ui| {
-            ui.vertical(|ui| {
-                if command_blocks.is_empty() {
-                    blocks_renderer.render_placeholder(ui);
-                    return;
-                }
-
-                // Render each command block with enhanced styling
-                for (index, block) in command_blocks.iter().enumerate() {
-                    blocks_renderer.render_block(ui, block).ok(); // Ignore render errors for now
-
-                    // Add spacing between blocks
-                    if index < command_blocks.len() - 1 {
-                        ui.add_space(4.0);
-                    }
-                }
-            });
-        });
-
-        // Render custom scrollbar if needed
-        if self.needs_scrollbar() {
-            self.render_scrollbar(ui);
-        }
-
-        // Check if any block should show a context menu
-        for block in command_blocks {
-            if blocks_renderer.should_show_context_menu(block) {
-                if let Some(pos) = blocks_renderer.context_menu_pos() {
-                    context_menu_info = Some((block.id.clone(), pos));
-                    break;
-                }
-            }
-        }
-
-        // Update scroll animation
-        ui.input(|input| {
-            self.update_scroll_animation(input.stable_dt);
-        });
-
-        context_menu_info
-    }
-
     /// Render the scrollable content
     fn render_content(&mut self, ui: &mut egui::Ui, blocks: &[String]) {
         ui.vertical(|ui| {
@@ -344,51 +278,6 @@ impl ScrollableHistory {
         }
     }
 
-    /// Calculate total content height for command blocks
-    fn calculate_command_blocks_height(&mut self, command_blocks: &[CommandBlock]) {
-        self.total_height = 0.0;
-
-        for (index, block) in command_blocks.iter().enumerate() {
-            let block_id = format!("cmd_block_{}", index);
-            let height = self.get_command_block_height(&block_id, block);
-            self.total_height += height;
-        }
-
-        // Add spacing between blocks
-        if !command_blocks.is_empty() {
-            self.total_height += (command_blocks.len() - 1) as f32 * 4.0; // 4px spacing
-        }
-    }
-
-    /// Get cached command block height or calculate it
-    fn get_command_block_height(&mut self, block_id: &str, block: &CommandBlock) -> f32 {
-        if let Some(&height) = self.block_heights.get(block_id) {
-            height
-        } else {
-            let height = self.calculate_command_block_height(block);
-            self.block_heights.insert(block_id.to_string(), height);
-            height
-        }
-    }
-
-    /// Calculate height for a command block
-    fn calculate_command_block_height(&self, block: &CommandBlock) -> f32 {
-        // Estimate height based on command and output content
-        let command_lines = (block.command.len() as f32 / 80.0).ceil().max(1.0);
-        let output_lines = block
-            .output
-            .iter()
-            .map(|line| (line.text.len() as f32 / 80.0).ceil().max(1.0))
-            .sum::<f32>();
-
-        let total_lines = command_lines + output_lines;
-        let base_height = 40.0; // Header height
-        let line_height = 16.0; // Height per line
-        let padding = 24.0; // Top and bottom padding
-
-        base_height + (total_lines * line_height) + padding
-    }
-
     /// Get cached block height or calculate it
     fn get_block_height(&mut self, block_id: &str, block: &str) -> f32 {
         if let Some(&height) = self.block_heights.get(block_id) {
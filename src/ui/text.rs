@@ -5,9 +5,11 @@
 
 use crate::error::Result;
 use crate::models::output_line::AnsiCode;
+use crate::terminal::grid::{CellStyle, Color, Palette, Rgb, Underline};
 use crate::terminal::AnsiColor;
 use eframe::egui;
 use std::collections::HashMap;
+use std::ops::Range;
 
 /// ANSI-aware text renderer
 pub struct AnsiTextRenderer {
@@ -119,6 +121,60 @@ impl Default for ColorScheme {
     }
 }
 
+impl ColorScheme {
+    /// Resolve a grid color, using this scheme's ANSI mappings for the 16 base colors
+    /// and the xterm palette for the rest
+    pub fn resolve(&self, color: Color, default: egui::Color32) -> egui::Color32 {
+        let to_color32 = |rgb: Rgb| egui::Color32::from_rgb(rgb.r, rgb.g, rgb.b);
+        match color {
+            Color::Default => default,
+            Color::Indexed(idx) => AnsiColor::from_index(idx)
+                .and_then(|ansi| self.ansi_colors.get(&ansi).copied())
+                .unwrap_or_else(|| to_color32(Palette::default().indexed(idx))),
+            Color::Rgb(rgb) => to_color32(rgb),
+        }
+    }
+
+    /// Text and background colors for a cell style.
+    ///
+    /// egui has no separate bold face, so bold brightens the text color and dim
+    /// darkens it.
+    pub fn style_colors(&self, style: &CellStyle) -> (egui::Color32, egui::Color32) {
+        let mut fg = self.resolve(style.fg, self.default_text);
+        let mut bg = self.resolve(style.bg, self.default_background);
+        if style.reverse {
+            let reversed_fg = if style.bg == Color::Default {
+                self.opaque_background()
+            } else {
+                bg
+            };
+            bg = fg;
+            fg = reversed_fg;
+        }
+        if style.bold {
+            fg = scale_color(fg, 1.2);
+        }
+        if style.dim {
+            fg = scale_color(fg, 0.7);
+        }
+        (fg, bg)
+    }
+
+    /// The default background, or black when it is transparent
+    fn opaque_background(&self) -> egui::Color32 {
+        if self.default_background.a() == 0 {
+            egui::Color32::BLACK
+        } else {
+            self.default_background
+        }
+    }
+}
+
+fn scale_color(color: egui::Color32, factor: f32) -> egui::Color32 {
+    let scale = |c: u8| (c as f32 * factor).min(255.0) as u8;
+    egui::Color32::from_rgb(scale(color.r()), scale(color.g()), scale(color.b()))
+}
+
 impl AnsiTextRenderer {
     /// Create a new ANSI text renderer
     pub fn new() -> Self {
@@ -190,10 +246,8 @@ impl AnsiTextRenderer {
     /// Render text with ANSI formatting
     fn render_text_with_ansi(&self, text: &str, ansi_codes: &[AnsiCode]) -> Result<RenderedText> {
         let mut layout = egui::epaint::text::LayoutJob::default();
-        let mut current_color = self.color_scheme.default_text;
-        let mut current_bg_color = self.color_scheme.default_background;
-        let mut current_font =
-            egui::FontId::new(self.font_config.size, self.font_config.family.clone());
+        let mut style = CellStyle::default();
+        let font = egui::FontId::new(self.font_config.size, self.font_config.family.clone());
         let mut ansi_code_count = 0;
 
         // Sort ANSI codes by position
@@ -206,22 +260,12 @@ impl AnsiTextRenderer {
             // Add text before this ANSI code
             if code.position > last_pos {
                 let text_before = &text[last_pos..code.position];
-                self.add_text_section(
-                    &mut layout,
-                    text_before,
-                    &current_font,
-                    current_color,
-                    current_bg_color,
-                );
+                let (color, bg_color) = self.color_scheme.style_colors(&style);
+                self.add_text_section(&mut layout, text_before, &font, color, bg_color);
             }
 
             // Apply ANSI formatting
-            self.apply_ansi_formatting(
-                code,
-                &mut current_color,
-                &mut current_bg_color,
-                &mut current_font,
-            );
+            style.apply_sgr_sequence(&code.code);
             ansi_code_count += 1;
             last_pos = code.position;
         }
@@ -229,13 +273,8 @@ impl AnsiTextRenderer {
         // Add remaining text
         if last_pos < text.len() {
             let remaining_text = &text[last_pos..];
-            self.add_text_section(
-                &mut layout,
-                remaining_text,
-                &current_font,
-                current_color,
-                current_bg_color,
-            );
+            let (color, bg_color) = self.color_scheme.style_colors(&style);
+            self.add_text_section(&mut layout, remaining_text, &font, color, bg_color);
         }
 
         // Calculate approximate dimensions (simplified)
@@ -279,321 +318,6 @@ impl AnsiTextRenderer {
         );
     }
 
-    /// Apply ANSI formatting to current style
-    fn apply_ansi_formatting(
-        &self,
-        ansi_code: &AnsiCode,
-        current_color: &mut egui::Color32,
-        current_bg_color: &mut egui::Color32,
-        current_font: &mut egui::FontId,
-    ) {
-        use std::sync::LazyLock;
-
-        static ANSI_CODE_REGEX: LazyLock<regex::Regex> =
-            LazyLock::new(|| regex::Regex::new(r"\x1b\[([0-9;]+)m").unwrap());
-
-        let code_regex = &*ANSI_CODE_REGEX;
-
-        if let Some(captures) = code_regex.captures(&ansi_code.code) {
-            if let Some(codes_str) = captures.get(1) {
-                // Split by semicolon and parse each code
-                for code_str in codes_str.as_str().split(';') {
-                    if let Ok(code) = code_str.parse::<u8>() {
-                        match code {
-                            0 => {
-                                // Reset all formatting
-                                *current_color = self.color_scheme.default_text;
-                                *current_bg_color = self.color_scheme.default_background;
-                                *current_font = egui::FontId::new(
-                                    self.font_config.size,
-                                    self.font_config.family.clone(),
-                                );
-                            }
-                            1 => {
-                                // Bold - make color brighter (egui doesn't have separate bold fonts)
-                                // We simulate bold by making the color more vibrant
-                                let r = (current_color.r() as f32 * 1.2).min(255.0) as u8;
-                                let g = (current_color.g() as f32 * 1.2).min(255.0) as u8;
-                                let b = (current_color.b() as f32 * 1.2).min(255.0) as u8;
-                                *current_color = egui::Color32::from_rgb(r, g, b);
-                            }
-                            2 => {
-                                // Dim - reduce brightness
-                                let r = (current_color.r() as f32 * 0.7) as u8;
-                                let g = (current_color.g() as f32 * 0.7) as u8;
-                                let b = (current_color.b() as f32 * 0.7) as u8;
-                                *current_color = egui::Color32::from_rgb(r, g, b);
-                            }
-                            // Standard foreground colors (30-37)
-                            30 => {
-                                *current_color = self
-                                    .color_scheme
-                                    .ansi_colors
-                                    .get(&AnsiColor::Black)
-                                    .copied()
-                                    .unwrap_or(egui::Color32::BLACK)
-                            }
-                            31 => {
-                                *current_color = self
-                                    .color_scheme
-                                    .ansi_colors
-                                    .get(&AnsiColor::Red)
-                                    .copied()
-                                    .unwrap_or(egui::Color32::RED)
-                            }
-                            32 => {
-                                *current_color = self
-                                    .color_scheme
-                                    .ansi_colors
-                                    .get(&AnsiColor::Green)
-                                    .copied()
-                                    .unwrap_or(egui::Color32::GREEN)
-                            }
-                            33 => {
-                                *current_color = self
-                                    .color_scheme
-                                    .ansi_colors
-                                    .get(&AnsiColor::Yellow)
-                                    .copied()
-                                    .unwrap_or(egui::Color32::YELLOW)
-                            }
-                            34 => {
-                                *current_color = self
-                                    .color_scheme
-                                    .ansi_colors
-                                    .get(&AnsiColor::Blue)
-                                    .copied()
-                                    .unwrap_or(egui::Color32::BLUE)
-                            }
-                            35 => {
-                                *current_color = self
-                                    .color_scheme
-                                    .ansi_colors
-                                    .get(&AnsiColor::Magenta)
-                                    .copied()
-                                    .unwrap_or(egui::Color32::from_rgb(255, 0, 255))
-                            }
-                            36 => {
-                                *current_color = self
-                                    .color_scheme
-                                    .ansi_colors
-                                    .get(&AnsiColor::Cyan)
-                                    .copied()
-                                    .unwrap_or(egui::Color32::from_rgb(0, 255, 255))
-                            }
-                            37 => {
-                                *current_color = self
-                                    .color_scheme
-                                    .ansi_colors
-                                    .get(&AnsiColor::White)
-                                    .copied()
-                                    .unwrap_or(egui::Color32::WHITE)
-                            }
-                            // Standard background colors (40-47)
-                            40 => {
-                                *current_bg_color = self
-                                    .color_scheme
-                                    .ansi_colors
-                                    .get(&AnsiColor::Black)
-                                    .copied()
-                                    .unwrap_or(egui::Color32::BLACK)
-                            }
-                            41 => {
-                                *current_bg_color = self
-                                    .color_scheme
-                                    .ansi_colors
-                                    .get(&AnsiColor::Red)
-                                    .copied()
-                                    .unwrap_or(egui::Color32::RED)
-                            }
-                            42 => {
-                                *current_bg_color = self
-                                    .color_scheme
-                                    .ansi_colors
-                                    .get(&AnsiColor::Green)
-                                    .copied()
-                                    .unwrap_or(egui::Color32::GREEN)
-                            }
-                            43 => {
-                                *current_bg_color = self
-                                    .color_scheme
-                                    .ansi_colors
-                                    .get(&AnsiColor::Yellow)
-                                    .copied()
-                                    .unwrap_or(egui::Color32::YELLOW)
-                            }
-                            44 => {
-                                *current_bg_color = self
-                                    .color_scheme
-                                    .ansi_colors
-                                    .get(&AnsiColor::Blue)
-                                    .copied()
-                                    .unwrap_or(egui::Color32::BLUE)
-                            }
-                            45 => {
-                                *current_bg_color = self
-                                    .color_scheme
-                                    .ansi_colors
-                                    .get(&AnsiColor::Magenta)
-                                    .copied()
-                                    .unwrap_or(egui::Color32::from_rgb(255, 0, 255))
-                            }
-                            46 => {
-                                *current_bg_color = self
-                                    .color_scheme
-                                    .ansi_colors
-                                    .get(&AnsiColor::Cyan)
-                                    .copied()
-                                    .unwrap_or(egui::Color32::from_rgb(0, 255, 255))
-                            }
-                            47 => {
-                                *current_bg_color = self
-                                    .color_scheme
-                                    .ansi_colors
-                                    .get(&AnsiColor::White)
-                                    .copied()
-                                    .unwrap_or(egui::Color32::WHITE)
-                            }
-                            // Bright foreground colors (90-97)
-                            90 => {
-                                *current_color = self
-                                    .color_scheme
-                                    .ansi_colors
-                                    .get(&AnsiColor::BrightBlack)
-                                    .copied()
-                                    .unwrap_or(egui::Color32::DARK_GRAY)
-                            }
-                            91 => {
-                                *current_color = self
-                                    .color_scheme
-                                    .ansi_colors
-                                    .get(&AnsiColor::BrightRed)
-                                    .copied()
-                                    .unwrap_or(egui::Color32::LIGHT_RED)
-                            }
-                            92 => {
-                                *current_color = self
-                                    .color_scheme
-                                    .ansi_colors
-                                    .get(&AnsiColor::BrightGreen)
-                                    .copied()
-                                    .unwrap_or(egui::Color32::LIGHT_GREEN)
-                            }
-                            93 => {
-                                *current_color = self
-                                    .color_scheme
-                                    .ansi_colors
-                                    .get(&AnsiColor::BrightYellow)
-                                    .copied()
-                                    .unwrap_or(egui::Color32::LIGHT_YELLOW)
-                            }
-                            94 => {
-                                *current_color = self
-                                    .color_scheme
-                                    .ansi_colors
-                                    .get(&AnsiColor::BrightBlue)
-                                    .copied()
-                                    .unwrap_or(egui::Color32::LIGHT_BLUE)
-                            }
-                            95 => {
-                                *current_color = self
-                                    .color_scheme
-                                    .ansi_colors
-                                    .get(&AnsiColor::BrightMagenta)
-                                    .copied()
-                                    .unwrap_or(egui::Color32::from_rgb(255, 100, 255))
-                            }
-                            96 => {
-                                *current_color = self
-                                    .color_scheme
-                                    .ansi_colors
-                                    .get(&AnsiColor::BrightCyan)
-                                    .copied()
-                                    .unwrap_or(egui::Color32::from_rgb(100, 255, 255))
-                            }
-                            97 => {
-                                *current_color = self
-                                    .color_scheme
-                                    .ansi_colors
-                                    .get(&AnsiColor::BrightWhite)
-                                    .copied()
-                                    .unwrap_or(egui::Color32::WHITE)
-                            }
-                            // Bright background colors (100-107)
-                            100 => {
-                                *current_bg_color = self
-                                    .color_scheme
-                                    .ansi_colors
-                                    .get(&AnsiColor::BrightBlack)
-                                    .copied()
-                                    .unwrap_or(egui::Color32::DARK_GRAY)
-                            }
-                            101 => {
-                                *current_bg_color = self
-                                    .color_scheme
-                                    .ansi_colors
-                                    .get(&AnsiColor::BrightRed)
-                                    .copied()
-                                    .unwrap_or(egui::Color32::LIGHT_RED)
-                            }
-                            102 => {
-                                *current_bg_color = self
-                                    .color_scheme
-                                    .ansi_colors
-                                    .get(&AnsiColor::BrightGreen)
-                                    .copied()
-                                    .unwrap_or(egui::Color32::LIGHT_GREEN)
-                            }
-                            103 => {
-                                *current_bg_color = self
-                                    .color_scheme
-                                    .ansi_colors
-                                    .get(&AnsiColor::BrightYellow)
-                                    .copied()
-                                    .unwrap_or(egui::Color32::LIGHT_YELLOW)
-                            }
-                            104 => {
-                                *current_bg_color = self
-                                    .color_scheme
-                                    .ansi_colors
-                                    .get(&AnsiColor::BrightBlue)
-                                    .copied()
-                                    .unwrap_or(egui::Color32::LIGHT_BLUE)
-                            }
-                            105 => {
-                                *current_bg_color = self
-                                    .color_scheme
-                                    .ansi_colors
-                                    .get(&AnsiColor::BrightMagenta)
-                                    .copied()
-                                    .unwrap_or(egui::Color32::from_rgb(255, 100, 255))
-                            }
-                            106 => {
-                                *current_bg_color = self
-                                    .color_scheme
-                                    .ansi_colors
-                                    .get(&AnsiColor::BrightCyan)
-                                    .copied()
-                                    .unwrap_or(egui::Color32::from_rgb(100, 255, 255))
-                            }
-                            107 => {
-                                *current_bg_color = self
-                                    .color_scheme
-                                    .ansi_colors
-                                    .get(&AnsiColor::BrightWhite)
-                                    .copied()
-                                    .unwrap_or(egui::Color32::WHITE)
-                            }
-                            _ => {
-                                // Unknown code, ignore
-                            }
-                        }
-                    }
-                }
-            }
-        }
-    }
-
     /// Render cached text
     fn render_cached_text(&self, ui: &mut egui::Ui, rendered: &RenderedText) {
         ui.label(rendered.layout.clone());
@@ -749,16 +473,18 @@ pub struct CacheStats {
 ///
 /// Colors are resolved from the pre-parsed `line.ansi_codes` vector
 /// (position + raw SGR sequence) so this works even when `line.text` has
-/// already been stripped of escape codes by the OutputProcessor.
+/// already been stripped of escape codes by the OutputProcessor. Blinking
+/// text is shown only when `blink_on`; underlines egui can't draw itself
+/// and the lines' OSC 8 links come back as [`TextDecorations`] to paint over
+/// the laid-out galley.
 pub fn build_output_layout_job(
     output_lines: &[crate::models::OutputLine],
     font: egui::FontId,
     default_color: egui::Color32,
-) -> (String, egui::epaint::text::LayoutJob) {
+    blink_on: bool,
+) -> (String, egui::epaint::text::LayoutJob, TextDecorations) {
     use egui::epaint::text::{LayoutJob, TextFormat};
 
-    let scheme = ColorScheme::default();
-
     let mut plain_text = String::new();
     let mut job = LayoutJob {
         wrap: egui::epaint::text::TextWrapping {
@@ -768,26 +494,44 @@ pub fn build_output_layout_job(
         ..Default::default()
     };
 
-    let fmt = |color: egui::Color32, fnt: &egui::FontId| TextFormat {
-        font_id: fnt.clone(),
-        color,
-        background: egui::Color32::TRANSPARENT,
-        ..Default::default()
+    let scheme = ColorScheme {
+        default_text: default_color,
+        ..ColorScheme::default()
+    };
+    let mut decorations = TextDecorations::new(blink_on);
+    let mut append = |job: &mut LayoutJob, text: &str, style: &CellStyle| {
+        let (color, background) = scheme.style_colors(style);
+        let format = TextFormat {
+            font_id: font.clone(),
+            color,
+            background,
+            ..Default::default()
+        };
+        let underline_color = scheme.resolve(style.underline_color, color);
+        decorations.append(job, text, format, style, underline_color);
     };
 
-    let mut cur_color = default_color;
+    let mut style = CellStyle::default();
+    let mut links = Vec::new();
 
     for (line_idx, line) in output_lines.iter().enumerate() {
         if line_idx > 0 {
             plain_text.push('\n');
-            job.append("\n", 0.0, fmt(cur_color, &font));
+            append(&mut job, "\n", &style);
         }
 
         let text = &line.text;
+        let line_start = plain_text.len();
+        for link in &line.hyperlinks {
+            let end = link.range.end.min(text.len());
+            if link.range.start < end {
+                links.push((line_start + link.range.start..line_start + end, &link.uri));
+            }
+        }
 
         if line.ansi_codes.is_empty() {
             plain_text.push_str(text);
-            job.append(text, 0.0, fmt(cur_color, &font));
+            append(&mut job, text, &style);
         } else {
             let mut sorted_codes = line.ansi_codes.clone();
             sorted_codes.sort_by_key(|c| c.position);
@@ -798,147 +542,329 @@ pub fn build_output_layout_job(
                 if pos > last_pos {
                     let segment = &text[last_pos..pos];
                     plain_text.push_str(segment);
-                    job.append(segment, 0.0, fmt(cur_color, &font));
+                    append(&mut job, segment, &style);
                 }
-                apply_sgr_string(&code.code, &mut cur_color, default_color, &scheme);
+                style.apply_sgr_sequence(&code.code);
                 last_pos = pos;
             }
             if last_pos < text.len() {
                 let segment = &text[last_pos..];
                 plain_text.push_str(segment);
-                job.append(segment, 0.0, fmt(cur_color, &font));
+                append(&mut job, segment, &style);
             }
         }
     }
 
-    (plain_text, job)
+    for (range, uri) in links {
+        decorations.add_link(range, uri);
+    }
+    (plain_text, job, decorations)
 }
 
-fn apply_sgr_string(
-    raw: &str,
-    cur_color: &mut egui::Color32,
-    default_color: egui::Color32,
-    scheme: &ColorScheme,
-) {
-    use std::sync::LazyLock;
-    static SGR_RE: LazyLock<regex::Regex> =
-        LazyLock::new(|| regex::Regex::new(r"\x1b\[([0-9;]*)m").unwrap());
-
-    if let Some(caps) = SGR_RE.captures(raw) {
-        if let Some(params) = caps.get(1) {
-            for code_str in params.as_str().split(';') {
-                if let Ok(code) = code_str.parse::<u8>() {
-                    *cur_color = ansi_code_to_color(code, *cur_color, default_color, scheme);
+/// Seconds blinking text (SGR 5) spends shown or hidden
+pub const TEXT_BLINK_INTERVAL: f64 = 0.5;
+
+/// Whether blinking text is shown `time` seconds into the session
+pub fn text_blink_on(time: f64) -> bool {
+    ((time / TEXT_BLINK_INTERVAL) as u64).is_multiple_of(2)
+}
+
+/// Text attributes that egui's own text layout cannot draw: double, curly,
+/// dotted and dashed underlines, by byte range of the layout job's text.
+/// Collected while a job is built with [`TextDecorations::append`] and
+/// painted over the resulting galley with [`TextDecorations::paint`].
+///
+/// Hyperlinks are kept the same way, so the link under the pointer can be
+/// found with [`TextDecorations::link_at`] and underlined on hover.
+#[derive(Clone, Debug, Default)]
+pub struct TextDecorations {
+    blink_on: bool,
+    has_blink: bool,
+    underlines: Vec<(Range<usize>, Underline, egui::Color32)>,
+    links: Vec<(Range<usize>, String)>,
+}
+
+impl TextDecorations {
+    /// Start collecting; blinking text is shown only when `blink_on`
+    pub fn new(blink_on: bool) -> Self {
+        Self {
+            blink_on,
+            ..Self::default()
+        }
+    }
+
+    /// Whether any appended text blinks, so the view needs repainting
+    pub fn has_blink(&self) -> bool {
+        self.has_blink
+    }
+
+    /// Append `text` to `job` in `format`, whose colours are already
+    /// resolved, adding the attributes of `style` beyond colour
+    pub fn append(
+        &mut self,
+        job: &mut egui::epaint::text::LayoutJob,
+        text: &str,
+        mut format: egui::epaint::text::TextFormat,
+        style: &CellStyle,
+        underline_color: egui::Color32,
+    ) {
+        self.has_blink |= style.blink;
+        let shown = !style.hidden && (!style.blink || self.blink_on);
+        if !shown {
+            format.color = egui::Color32::TRANSPARENT;
+        }
+        format.italics |= style.italic;
+        if style.strikethrough && shown {
+            format.strikethrough = egui::Stroke::new(1.0, format.color);
+        }
+
+        let start = job.text.len();
+        job.append(text, 0.0, format.clone());
+        if !shown {
+            return;
+        }
+        match style.underline {
+            Underline::None => {}
+            Underline::Single => {
+                if let Some(section) = job.sections.last_mut() {
+                    section.format.underline = egui::Stroke::new(1.0, underline_color);
+                }
+            }
+            kind => {
+                let end = job.text.len();
+                match self.underlines.last_mut() {
+                    Some((range, last, color))
+                        if range.end == start && *last == kind && *color == underline_color =>
+                    {
+                        range.end = end;
+                    }
+                    _ => self.underlines.push((start..end, kind, underline_color)),
+                }
+            }
+        }
+    }
+
+    /// Mark `range` of the job's text as a link to `uri`
+    pub fn add_link(&mut self, range: Range<usize>, uri: &str) {
+        self.links.push((range, uri.to_string()));
+    }
+
+    /// The link under `pos` in `galley`, drawn at `origin`, with its range
+    pub fn link_at(
+        &self,
+        galley: &egui::Galley,
+        origin: egui::Pos2,
+        pos: egui::Pos2,
+    ) -> Option<(Range<usize>, &str)> {
+        if self.links.is_empty() {
+            return None;
+        }
+        let pos = pos - origin.to_vec2();
+        let (byte, _) = placed_glyphs(galley)
+            .into_iter()
+            .find(|(_, rect)| rect.contains(pos))?;
+        self.links
+            .iter()
+            .find(|(range, _)| range.contains(&byte))
+            .map(|(range, uri)| (range.clone(), uri.as_str()))
+    }
+
+    /// Underline the text in `range` of `galley`, drawn at `origin`, as a
+    /// hovered link
+    pub fn paint_link(
+        &self,
+        painter: &egui::Painter,
+        galley: &egui::Galley,
+        origin: egui::Pos2,
+        range: Range<usize>,
+        color: egui::Color32,
+    ) {
+        let stroke = egui::Stroke::new(1.0, color);
+        let mut run: Option<(f32, f32, f32)> = None;
+        for (byte, rect) in placed_glyphs(galley) {
+            if !range.contains(&byte) {
+                continue;
+            }
+            let rect = rect.translate(origin.to_vec2());
+            let y = rect.bottom() - 1.5;
+            match &mut run {
+                Some((_, x1, run_y)) if *run_y == y => *x1 = rect.right(),
+                _ => {
+                    if let Some((x0, x1, y)) = run.take() {
+                        painter.hline(x0..=x1, y, stroke);
+                    }
+                    run = Some((rect.left(), rect.right(), y));
+                }
+            }
+        }
+        if let Some((x0, x1, y)) = run {
+            painter.hline(x0..=x1, y, stroke);
+        }
+    }
+
+    /// Paint the collected underlines under `galley`, laid out from the job
+    /// they were collected for and drawn at `origin`
+    pub fn paint(&self, painter: &egui::Painter, galley: &egui::Galley, origin: egui::Pos2) {
+        if self.underlines.is_empty() {
+            return;
+        }
+
+        // Glyphs follow the job's characters in order; newlines have none
+        let mut chars = galley.job.text.char_indices().map(|(idx, _)| idx);
+        let mut next = 0;
+        for row in &galley.rows {
+            // (underline index, left x, right x, underline y)
+            let mut run: Option<(usize, f32, f32, f32)> = None;
+            for glyph in &row.glyphs {
+                let Some(byte) = chars.next() else {
+                    break;
+                };
+                while next < self.underlines.len() && self.underlines[next].0.end <= byte {
+                    next += 1;
                 }
+                let current = self
+                    .underlines
+                    .get(next)
+                    .filter(|(range, ..)| range.start <= byte)
+                    .map(|_| next);
+
+                let left = origin.x + row.pos.x + glyph.pos.x;
+                let right = origin.x + row.pos.x + glyph.max_x();
+                match (&mut run, current) {
+                    (Some((idx, _, x1, _)), Some(cur)) if *idx == cur => *x1 = right,
+                    _ => {
+                        if let Some((idx, x0, x1, y)) = run.take() {
+                            self.paint_underline(painter, idx, x0, x1, y);
+                        }
+                        let y = origin.y + row.pos.y + glyph.logical_rect().bottom() - 1.5;
+                        run = current.map(|cur| (cur, left, right, y));
+                    }
+                }
+            }
+            if let Some((idx, x0, x1, y)) = run {
+                self.paint_underline(painter, idx, x0, x1, y);
+            }
+            if row.ends_with_newline {
+                chars.next();
             }
         }
     }
+
+    fn paint_underline(&self, painter: &egui::Painter, idx: usize, x0: f32, x1: f32, y: f32) {
+        let (_, kind, color) = &self.underlines[idx];
+        let stroke = egui::Stroke::new(1.0, *color);
+        let line = [egui::pos2(x0, y), egui::pos2(x1, y)];
+        match kind {
+            Underline::Double => {
+                painter.hline(x0..=x1, y, stroke);
+                painter.hline(x0..=x1, y - 2.0, stroke);
+            }
+            Underline::Curly => {
+                // Two-pixel wave with a four-pixel period
+                let steps = (x1 - x0).ceil().max(1.0) as usize;
+                let points = (0..=steps)
+                    .map(|step| {
+                        let x = (x0 + step as f32).min(x1);
+                        let phase = (x - x0) * std::f32::consts::FRAC_PI_2;
+                        egui::pos2(x, y - 1.0 + phase.sin())
+                    })
+                    .collect();
+                painter.add(egui::Shape::line(points, stroke));
+            }
+            Underline::Dotted => {
+                painter.extend(egui::Shape::dotted_line(&line, *color, 2.0, 0.5));
+            }
+            Underline::Dashed => {
+                painter.extend(egui::Shape::dashed_line(&line, stroke, 3.0, 2.0));
+            }
+            Underline::None | Underline::Single => {}
+        }
+    }
 }
 
-fn ansi_code_to_color(
-    code: u8,
-    current: egui::Color32,
-    default_color: egui::Color32,
-    scheme: &ColorScheme,
-) -> egui::Color32 {
-    use crate::terminal::AnsiColor;
-    match code {
-        0 => default_color,
-        1 => {
-            let r = (current.r() as f32 * 1.2).min(255.0) as u8;
-            let g = (current.g() as f32 * 1.2).min(255.0) as u8;
-            let b = (current.b() as f32 * 1.2).min(255.0) as u8;
-            egui::Color32::from_rgb(r, g, b)
+/// Each glyph of `galley` with the byte offset of its character in the job's
+/// text and its rectangle relative to the galley
+fn placed_glyphs(galley: &egui::Galley) -> Vec<(usize, egui::Rect)> {
+    // Glyphs follow the job's characters in order; newlines have none
+    let mut chars = galley.job.text.char_indices().map(|(idx, _)| idx);
+    let mut placed = Vec::new();
+    for row in &galley.rows {
+        for glyph in &row.glyphs {
+            let Some(byte) = chars.next() else {
+                return placed;
+            };
+            placed.push((byte, glyph.logical_rect().translate(row.pos.to_vec2())));
         }
-        2 => {
-            let r = (current.r() as f32 * 0.7) as u8;
-            let g = (current.g() as f32 * 0.7) as u8;
-            let b = (current.b() as f32 * 0.7) as u8;
-            egui::Color32::from_rgb(r, g, b)
+        if row.ends_with_newline {
+            chars.next();
         }
-        30 => scheme
-            .ansi_colors
-            .get(&AnsiColor::Black)
-            .copied()
-            .unwrap_or(egui::Color32::BLACK),
-        31 => scheme
-            .ansi_colors
-            .get(&AnsiColor::Red)
-            .copied()
-            .unwrap_or(egui::Color32::RED),
-        32 => scheme
-            .ansi_colors
-            .get(&AnsiColor::Green)
-            .copied()
-            .unwrap_or(egui::Color32::GREEN),
-        33 => scheme
-            .ansi_colors
-            .get(&AnsiColor::Yellow)
-            .copied()
-            .unwrap_or(egui::Color32::YELLOW),
-        34 => scheme
-            .ansi_colors
-            .get(&AnsiColor::Blue)
-            .copied()
-            .unwrap_or(egui::Color32::BLUE),
-        35 => scheme
-            .ansi_colors
-            .get(&AnsiColor::Magenta)
-            .copied()
-            .unwrap_or(egui::Color32::from_rgb(188, 63, 188)),
-        36 => scheme
-            .ansi_colors
-            .get(&AnsiColor::Cyan)
-            .copied()
-            .unwrap_or(egui::Color32::from_rgb(17, 168, 205)),
-        37 => scheme
-            .ansi_colors
-            .get(&AnsiColor::White)
-            .copied()
-            .unwrap_or(egui::Color32::WHITE),
-        39 => default_color,
-        90 => scheme
-            .ansi_colors
-            .get(&AnsiColor::BrightBlack)
-            .copied()
-            .unwrap_or(egui::Color32::GRAY),
-        91 => scheme
-            .ansi_colors
-            .get(&AnsiColor::BrightRed)
-            .copied()
-            .unwrap_or(egui::Color32::from_rgb(241, 76, 76)),
-        92 => scheme
-            .ansi_colors
-            .get(&AnsiColor::BrightGreen)
-            .copied()
-            .unwrap_or(egui::Color32::from_rgb(35, 209, 139)),
-        93 => scheme
-            .ansi_colors
-            .get(&AnsiColor::BrightYellow)
-            .copied()
-            .unwrap_or(egui::Color32::from_rgb(245, 245, 67)),
-        94 => scheme
-            .ansi_colors
-            .get(&AnsiColor::BrightBlue)
-            .copied()
-            .unwrap_or(egui::Color32::from_rgb(59, 142, 234)),
-        95 => scheme
-            .ansi_colors
-            .get(&AnsiColor::BrightMagenta)
-            .copied()
-            .unwrap_or(egui::Color32::from_rgb(214, 112, 214)),
-        96 => scheme
-            .ansi_colors
-            .get(&AnsiColor::BrightCyan)
-            .copied()
-            .unwrap_or(egui::Color32::from_rgb(41, 184, 219)),
-        97 => scheme
-            .ansi_colors
-            .get(&AnsiColor::BrightWhite)
-            .copied()
-            .unwrap_or(egui::Color32::WHITE),
-        _ => current,
     }
+    placed
+}
+
+/// Make every grapheme cluster in `job` advance by exactly its terminal width
+/// in monospace cells.
+///
+/// Fallback fonts rarely draw CJK or emoji at twice the width of the monospace
+/// cell, and combining marks may get an advance of their own, so columns after
+/// such text drift. Sections are split after each cluster whose glyphs miss
+/// its cell width and the next piece gets the difference as leading space.
+/// ASCII-only sections are left untouched.
+pub fn align_wide_chars(
+    mut job: egui::epaint::text::LayoutJob,
+    fonts: &mut egui::epaint::text::FontsView<'_>,
+) -> egui::epaint::text::LayoutJob {
+    use crate::terminal::width;
+    use egui::epaint::text::LayoutSection;
+    use unicode_segmentation::UnicodeSegmentation;
+
+    if job.text.is_ascii() {
+        return job;
+    }
+
+    let mut sections = Vec::with_capacity(job.sections.len());
+    let mut correction = 0.0;
+    for section in std::mem::take(&mut job.sections) {
+        let text = &job.text[section.byte_range.clone()];
+        let mut start = section.byte_range.start;
+        let mut leading_space = section.leading_space + correction;
+        correction = 0.0;
+
+        if !text.is_ascii() {
+            let font_id = &section.format.font_id;
+            let cell_width = fonts.glyph_width(font_id, 'M');
+            for (offset, cluster) in text.grapheme_indices(true) {
+                if cluster.is_ascii() {
+                    continue;
+                }
+                let drawn: f32 = cluster.chars().map(|c| fonts.glyph_width(font_id, c)).sum();
+                let wanted = width::cluster_width(cluster) as f32 * cell_width;
+                if (wanted - drawn).abs() < 0.01 {
+                    continue;
+                }
+                let end = section.byte_range.start + offset + cluster.len();
+                sections.push(LayoutSection {
+                    leading_space,
+                    byte_range: start..end,
+                    format: section.format.clone(),
+                });
+                start = end;
+                leading_space = wanted - drawn;
+            }
+        }
+
+        if start < section.byte_range.end {
+            sections.push(LayoutSection {
+                leading_space,
+                byte_range: start..section.byte_range.end,
+                format: section.format,
+            });
+        } else {
+            correction = leading_space;
+        }
+    }
+    job.sections = sections;
+    job
 }
 
 /// Text rendering utilities
@@ -1244,4 +1170,154 @@ mod tests {
 
         assert_eq!(renderer.render_cache.len(), 2);
     }
+
+    #[test]
+    fn test_color_scheme_resolve() {
+        let scheme = ColorScheme::default();
+        let default = egui::Color32::from_rgb(1, 2, 3);
+        assert_eq!(scheme.resolve(Color::Default, default), default);
+        assert_eq!(
+            scheme.resolve(Color::Indexed(1), default),
+            scheme.ansi_colors[&AnsiColor::Red]
+        );
+        assert_eq!(
+            scheme.resolve(Color::Indexed(196), default),
+            egui::Color32::from_rgb(255, 0, 0)
+        );
+        assert_eq!(
+            scheme.resolve(Color::Rgb(Rgb::new(10, 20, 30)), default),
+            egui::Color32::from_rgb(10, 20, 30)
+        );
+    }
+
+    #[test]
+    fn test_build_output_layout_job_extended_colors() {
+        use crate::models::output_line::{AnsiCode, OutputLine};
+
+        let mut code = AnsiCode::new("\x1b[38;5;196m");
+        code.position = 2;
+        let mut reset = AnsiCode::new("\x1b[0m");
+        reset.position = 4;
+        let line = OutputLine::with_ansi_codes("abcdef".to_string(), vec![code, reset], 0);
+
+        let default = egui::Color32::from_rgb(200, 200, 200);
+        let (plain, job, _) =
+            build_output_layout_job(&[line], egui::FontId::monospace(12.0), default, true);
+
+        assert_eq!(plain, "abcdef");
+        let colors: Vec<egui::Color32> = job.sections.iter().map(|s| s.format.color).collect();
+        assert_eq!(
+            colors,
+            vec![default, egui::Color32::from_rgb(255, 0, 0), default]
+        );
+    }
+
+    #[test]
+    fn test_build_output_layout_job_text_attributes() {
+        use crate::models::output_line::{AnsiCode, OutputLine};
+
+        let codes = [
+            (0, "\x1b[9m"),
+            (1, "\x1b[0;8m"),
+            (2, "\x1b[0;5m"),
+            (3, "\x1b[0;4m"),
+        ];
+        let codes = codes
+            .iter()
+            .map(|&(position, seq)| {
+                let mut code = AnsiCode::new(seq);
+                code.position = position;
+                code
+            })
+            .collect();
+        let line = OutputLine::with_ansi_codes("abcd".to_string(), codes, 0);
+        let default = egui::Color32::from_rgb(200, 200, 200);
+
+        let (_, job, decorations) = build_output_layout_job(
+            std::slice::from_ref(&line),
+            egui::FontId::monospace(12.0),
+            default,
+            false,
+        );
+        let formats: Vec<_> = job.sections.iter().map(|s| &s.format).collect();
+        assert_eq!(formats[0].strikethrough, egui::Stroke::new(1.0, default));
+        assert_eq!(formats[1].color, egui::Color32::TRANSPARENT);
+        assert_eq!(formats[2].color, egui::Color32::TRANSPARENT);
+        assert_eq!(formats[3].underline, egui::Stroke::new(1.0, default));
+        assert!(decorations.has_blink());
+
+        let (_, job, _) =
+            build_output_layout_job(&[line], egui::FontId::monospace(12.0), default, true);
+        assert_eq!(job.sections[2].format.color, default);
+    }
+
+    #[test]
+    fn test_build_output_layout_job_links() {
+        use crate::models::output_line::{Hyperlink, OutputLine};
+
+        let mut first = OutputLine::new("plain");
+        first.hyperlinks.push(Hyperlink {
+            range: 0..20,
+            uri: "https://a.example".to_string(),
+        });
+        let mut second = OutputLine::new("src/main.rs:3");
+        second.hyperlinks.push(Hyperlink {
+            range: 0..11,
+            uri: "file:///src/main.rs".to_string(),
+        });
+
+        let (plain, _, decorations) = build_output_layout_job(
+            &[first, second],
+            egui::FontId::monospace(12.0),
+            egui::Color32::WHITE,
+            true,
+        );
+        // Ranges are clamped to their line and offset into the whole text
+        assert_eq!(
+            decorations.links,
+            vec![
+                (0..5, "https://a.example".to_string()),
+                (6..17, "file:///src/main.rs".to_string())
+            ]
+        );
+        assert_eq!(&plain[6..17], "src/main.rs");
+    }
+
+    #[test]
+    fn test_decorations_merge_fancy_underlines() {
+        let mut job = egui::epaint::text::LayoutJob::default();
+        let mut decorations = TextDecorations::new(true);
+        let red = egui::Color32::RED;
+        let curly = CellStyle {
+            underline: Underline::Curly,
+            ..CellStyle::default()
+        };
+        let format = egui::epaint::text::TextFormat::default();
+        decorations.append(&mut job, "ab", format.clone(), &curly, red);
+        decorations.append(&mut job, "cd", format.clone(), &curly, red);
+        decorations.append(&mut job, "ef", format.clone(), &CellStyle::default(), red);
+        let dashed = CellStyle {
+            underline: Underline::Dashed,
+            ..CellStyle::default()
+        };
+        decorations.append(&mut job, "gh", format, &dashed, red);
+
+        assert_eq!(
+            decorations.underlines,
+            vec![
+                (0..4, Underline::Curly, red),
+                (6..8, Underline::Dashed, red)
+            ]
+        );
+        assert!(job
+            .sections
+            .iter()
+            .all(|s| s.format.underline == egui::Stroke::NONE));
+    }
+
+    #[test]
+    fn test_text_blink_phase() {
+        assert!(text_blink_on(0.1));
+        assert!(!text_blink_on(TEXT_BLINK_INTERVAL + 0.1));
+    }
 }
@@ -2,468 +2,152 @@
 //!
 //! This module provides a fullscreen overlay for running interactive TUI applications
 //! like vim, htop, top, etc. The overlay captures all input and renders a virtual
-//! terminal screen buffer with ANSI color and cursor positioning support.
-
+//! terminal screen (see [`crate::terminal::grid`]) with ANSI color and cursor
+//! positioning support. Pointer events are forwarded when the app enables xterm
+//! mouse reporting (see [`crate::terminal::mouse`]). Otherwise the mouse wheel
+//! and Shift+PageUp/PageDown browse the primary screen's scrollback. OSC 8
+//! links are underlined on hover and opened with Ctrl/Cmd+Click.
+
+use crate::terminal::clipboard::ClipboardRequest;
+use crate::terminal::grid::{
+    Cell, Color, CursorShape, CursorStyle, ImageTile, Palette, Rgb, TerminalGrid,
+};
+use crate::terminal::images::ImageLimits;
+use crate::terminal::keyboard::{
+    modifier_param, KeyboardModes, KITTY_DISAMBIGUATE, KITTY_REPORT_ALL_KEYS,
+    KITTY_REPORT_ALTERNATE_KEYS, KITTY_REPORT_EVENT_TYPES, KITTY_REPORT_TEXT,
+};
+use crate::terminal::mouse::{MouseButton, MouseEvent, MouseEventKind, MouseModifiers};
+use crate::terminal::resize::{GridSize, ResizeDebouncer};
+use crate::terminal::title::TitleChange;
+use crate::ui::text::{align_wide_chars, text_blink_on, TextDecorations, TEXT_BLINK_INTERVAL};
 use eframe::egui;
-
-#[derive(Clone, Copy, Debug, PartialEq)]
-struct AnsiColor {
-    r: u8,
-    g: u8,
-    b: u8,
-}
-
-impl AnsiColor {
-    const fn rgb(r: u8, g: u8, b: u8) -> Self {
-        Self { r, g, b }
-    }
-
-    fn to_color32(self) -> egui::Color32 {
-        egui::Color32::from_rgb(self.r, self.g, self.b)
-    }
-
-    fn from_256(idx: u8) -> Self {
-        match idx {
-            0 => Self::rgb(0, 0, 0),
-            1 => Self::rgb(205, 0, 0),
-            2 => Self::rgb(0, 205, 0),
-            3 => Self::rgb(205, 205, 0),
-            4 => Self::rgb(0, 0, 238),
-            5 => Self::rgb(205, 0, 205),
-            6 => Self::rgb(0, 205, 205),
-            7 => Self::rgb(229, 229, 229),
-            8 => Self::rgb(127, 127, 127),
-            9 => Self::rgb(255, 0, 0),
-            10 => Self::rgb(0, 255, 0),
-            11 => Self::rgb(255, 255, 0),
-            12 => Self::rgb(92, 92, 255),
-            13 => Self::rgb(255, 0, 255),
-            14 => Self::rgb(0, 255, 255),
-            15 => Self::rgb(255, 255, 255),
-            16..=231 => {
-                let idx = idx - 16;
-                let r = (idx / 36) % 6;
-                let g = (idx / 6) % 6;
-                let b = idx % 6;
-                let to_val = |v: u8| if v == 0 { 0 } else { 55 + 40 * v };
-                Self::rgb(to_val(r), to_val(g), to_val(b))
-            }
-            232..=255 => {
-                let gray = 8 + 10 * (idx - 232);
-                Self::rgb(gray, gray, gray)
-            }
-        }
-    }
+use tracing::warn;
+
+/// Seconds the cursor spends in each half of a blink
+const CURSOR_BLINK_INTERVAL: f64 = 0.53;
+
+/// Longest a synchronized update (mode 2026) may hold back repaints
+const SYNC_OUTPUT_TIMEOUT: std::time::Duration = std::time::Duration::from_millis(150);
+
+/// Screen contents as last drawn, reshown while a synchronized update is open
+#[derive(Clone)]
+struct ScreenFrame {
+    galley: std::sync::Arc<egui::Galley>,
+    decorations: TextDecorations,
+    /// Cursor cell (row, col) in view, unless hidden
+    cursor: Option<(usize, usize)>,
+    cursor_style: CursorStyle,
+    /// Image tiles in view, with their cells (row, col)
+    images: Vec<(usize, usize, ImageTile)>,
 }
 
-const DEFAULT_FG: AnsiColor = AnsiColor::rgb(204, 204, 204);
-const DEFAULT_BG: AnsiColor = AnsiColor::rgb(30, 30, 46);
-
-const ANSI_COLORS: [AnsiColor; 8] = [
-    AnsiColor::rgb(0, 0, 0),
-    AnsiColor::rgb(205, 0, 0),
-    AnsiColor::rgb(0, 205, 0),
-    AnsiColor::rgb(205, 205, 0),
-    AnsiColor::rgb(0, 0, 238),
-    AnsiColor::rgb(205, 0, 205),
-    AnsiColor::rgb(0, 205, 205),
-    AnsiColor::rgb(229, 229, 229),
-];
-
-const ANSI_BRIGHT_COLORS: [AnsiColor; 8] = [
-    AnsiColor::rgb(127, 127, 127),
-    AnsiColor::rgb(255, 0, 0),
-    AnsiColor::rgb(0, 255, 0),
-    AnsiColor::rgb(255, 255, 0),
-    AnsiColor::rgb(92, 92, 255),
-    AnsiColor::rgb(255, 0, 255),
-    AnsiColor::rgb(0, 255, 255),
-    AnsiColor::rgb(255, 255, 255),
-];
-
-#[derive(Clone, Copy, Debug, PartialEq)]
-struct CellStyle {
-    fg: AnsiColor,
-    bg: AnsiColor,
-    bold: bool,
-    dim: bool,
-    italic: bool,
-    underline: bool,
-    reverse: bool,
+fn to_color32(rgb: Rgb) -> egui::Color32 {
+    egui::Color32::from_rgb(rgb.r, rgb.g, rgb.b)
 }
 
-impl Default for CellStyle {
-    fn default() -> Self {
-        Self {
-            fg: DEFAULT_FG,
-            bg: DEFAULT_BG,
-            bold: false,
-            dim: false,
-            italic: false,
-            underline: false,
-            reverse: false,
-        }
-    }
-}
-
-impl CellStyle {
-    fn effective_fg(&self) -> AnsiColor {
-        if self.reverse {
-            self.bg
-        } else {
-            self.fg
-        }
-    }
-
-    fn effective_bg(&self) -> AnsiColor {
-        if self.reverse {
-            self.fg
-        } else {
-            self.bg
+/// Area of the cell `cell` covered by a cursor of `shape`
+fn cursor_rect(cell: egui::Rect, shape: CursorShape) -> egui::Rect {
+    match shape {
+        CursorShape::Block => cell,
+        CursorShape::Underline => {
+            let height = (cell.height() * 0.12).max(2.0);
+            egui::Rect::from_min_max(egui::pos2(cell.min.x, cell.max.y - height), cell.max)
         }
-    }
-}
-
-#[derive(Clone, Copy, Debug)]
-struct Cell {
-    ch: char,
-    style: CellStyle,
-}
-
-impl Default for Cell {
-    fn default() -> Self {
-        Self {
-            ch: ' ',
-            style: CellStyle::default(),
+        CursorShape::Bar => {
+            let width = (cell.width() * 0.15).max(2.0);
+            egui::Rect::from_min_max(cell.min, egui::pos2(cell.min.x + width, cell.max.y))
         }
     }
 }
 
-/// Virtual screen buffer for TUI apps with per-cell styling
-struct ScreenBuffer {
-    grid: Vec<Vec<Cell>>,
-    cursor_row: usize,
-    cursor_col: usize,
-    rows: usize,
-    cols: usize,
-    current_style: CellStyle,
-    scroll_top: usize,
-    scroll_bottom: usize,
-    saved_cursor: Option<(usize, usize)>,
+/// Whether a blinking cursor is lit `time` seconds into the session
+fn cursor_blink_on(time: f64) -> bool {
+    ((time / CURSOR_BLINK_INTERVAL) as u64).is_multiple_of(2)
 }
 
-impl ScreenBuffer {
-    fn new(rows: usize, cols: usize) -> Self {
-        Self {
-            grid: vec![vec![Cell::default(); cols]; rows],
-            cursor_row: 0,
-            cursor_col: 0,
-            rows,
-            cols,
-            current_style: CellStyle::default(),
-            scroll_top: 0,
-            scroll_bottom: rows.saturating_sub(1),
-            saved_cursor: None,
-        }
-    }
-
-    fn resize(&mut self, new_rows: usize, new_cols: usize) {
-        if new_rows == self.rows && new_cols == self.cols {
-            return;
-        }
-        let mut new_grid = vec![vec![Cell::default(); new_cols]; new_rows];
-        let copy_rows = self.rows.min(new_rows);
-        let copy_cols = self.cols.min(new_cols);
-        for (new_row, old_row) in new_grid.iter_mut().zip(self.grid.iter()).take(copy_rows) {
-            new_row[..copy_cols].copy_from_slice(&old_row[..copy_cols]);
-        }
-        self.grid = new_grid;
-        self.rows = new_rows;
-        self.cols = new_cols;
-        self.scroll_bottom = new_rows.saturating_sub(1);
-        self.cursor_row = self.cursor_row.min(new_rows.saturating_sub(1));
-        self.cursor_col = self.cursor_col.min(new_cols.saturating_sub(1));
-    }
-
-    fn clear(&mut self) {
-        let blank = Cell {
-            ch: ' ',
-            style: self.current_style,
-        };
-        for row in &mut self.grid {
-            row.fill(blank);
-        }
-        self.cursor_row = 0;
-        self.cursor_col = 0;
-    }
-
-    fn clear_from_cursor_to_end_of_screen(&mut self) {
-        let blank = Cell {
-            ch: ' ',
-            style: self.current_style,
-        };
-        if self.cursor_row < self.rows {
-            for c in self.cursor_col..self.cols {
-                self.grid[self.cursor_row][c] = blank;
-            }
-            for r in (self.cursor_row + 1)..self.rows {
-                self.grid[r].fill(blank);
-            }
-        }
-    }
-
-    fn clear_from_start_to_cursor(&mut self) {
-        let blank = Cell {
-            ch: ' ',
-            style: self.current_style,
-        };
-        for r in 0..self.cursor_row {
-            self.grid[r].fill(blank);
-        }
-        if self.cursor_row < self.rows {
-            for c in 0..=self.cursor_col.min(self.cols - 1) {
-                self.grid[self.cursor_row][c] = blank;
+/// Image tiles among `lines`, with the (row, col) of their cells
+fn image_tiles(lines: &[&[Cell]]) -> Vec<(usize, usize, ImageTile)> {
+    let mut tiles = Vec::new();
+    for (row, cells) in lines.iter().enumerate() {
+        for (col, cell) in cells.iter().enumerate() {
+            if let Some(tile) = &cell.image {
+                tiles.push((row, col, (**tile).clone()));
             }
         }
     }
+    tiles
+}
 
-    fn clear_line_from_cursor(&mut self) {
-        if self.cursor_row < self.rows {
-            let blank = Cell {
-                ch: ' ',
-                style: self.current_style,
-            };
-            for c in self.cursor_col..self.cols {
-                self.grid[self.cursor_row][c] = blank;
+/// Build a layout job covering `lines`, one galley row per screen row,
+/// with blinking text shown only when `blink_on`
+fn grid_layout_job(
+    lines: &[&[Cell]],
+    palette: &Palette,
+    font_id: egui::FontId,
+    blink_on: bool,
+) -> (egui::text::LayoutJob, TextDecorations) {
+    let mut decorations = TextDecorations::new(blink_on);
+    let mut job = egui::text::LayoutJob {
+        wrap: egui::text::TextWrapping {
+            max_width: f32::INFINITY,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    for (row_idx, row) in lines.iter().enumerate() {
+        let cols = row.len();
+        // Render full row width (including trailing spaces) so the galley
+        // fills the entire terminal area — critical for correct sizing.
+        let mut run_start = 0;
+        while run_start < cols {
+            let style = row[run_start].style;
+            let mut run_end = run_start + 1;
+            while run_end < cols && row[run_end].style == style {
+                run_end += 1;
             }
-        }
-    }
 
-    fn clear_line_to_cursor(&mut self) {
-        if self.cursor_row < self.rows {
-            let blank = Cell {
-                ch: ' ',
-                style: self.current_style,
-            };
-            for c in 0..=self.cursor_col.min(self.cols - 1) {
-                self.grid[self.cursor_row][c] = blank;
+            let mut text = String::new();
+            for cell in &row[run_start..run_end] {
+                cell.push_text(&mut text);
             }
-        }
-    }
+            let (fg, bg) = palette.resolve_style(&style);
 
-    fn clear_entire_line(&mut self) {
-        if self.cursor_row < self.rows {
-            let blank = Cell {
-                ch: ' ',
-                style: self.current_style,
+            let mut text_format = egui::TextFormat {
+                font_id: font_id.clone(),
+                color: to_color32(fg),
+                ..Default::default()
             };
-            self.grid[self.cursor_row].fill(blank);
-        }
-    }
-
-    fn write_char(&mut self, ch: char) {
-        match ch {
-            '\n' => {
-                if self.cursor_row >= self.scroll_bottom {
-                    self.scroll_up(1);
-                } else {
-                    self.cursor_row += 1;
-                }
-                self.cursor_col = 0;
-            }
-            '\r' => {
-                self.cursor_col = 0;
-            }
-            '\t' => {
-                let next_tab = ((self.cursor_col / 8) + 1) * 8;
-                self.cursor_col = next_tab.min(self.cols.saturating_sub(1));
-            }
-            '\x08' => {
-                self.cursor_col = self.cursor_col.saturating_sub(1);
-            }
-            c if c >= ' ' => {
-                if self.cursor_row < self.rows && self.cursor_col < self.cols {
-                    self.grid[self.cursor_row][self.cursor_col] = Cell {
-                        ch: c,
-                        style: self.current_style,
-                    };
-                    self.cursor_col += 1;
-                    if self.cursor_col >= self.cols {
-                        self.cursor_col = 0;
-                        if self.cursor_row >= self.scroll_bottom {
-                            self.scroll_up(1);
-                        } else {
-                            self.cursor_row += 1;
-                        }
-                    }
-                }
-            }
-            _ => {}
-        }
-    }
-
-    fn scroll_up(&mut self, n: usize) {
-        let blank = Cell {
-            ch: ' ',
-            style: CellStyle::default(),
-        };
-        for _ in 0..n {
-            if self.scroll_top < self.scroll_bottom && self.scroll_bottom < self.rows {
-                for r in self.scroll_top..self.scroll_bottom {
-                    self.grid[r] = self.grid[r + 1].clone();
-                }
-                self.grid[self.scroll_bottom].fill(blank);
-            }
-        }
-    }
-
-    fn scroll_down(&mut self, n: usize) {
-        let blank = Cell {
-            ch: ' ',
-            style: CellStyle::default(),
-        };
-        for _ in 0..n {
-            if self.scroll_top < self.scroll_bottom && self.scroll_bottom < self.rows {
-                for r in (self.scroll_top + 1..=self.scroll_bottom).rev() {
-                    self.grid[r] = self.grid[r - 1].clone();
-                }
-                self.grid[self.scroll_top].fill(blank);
+            if bg != palette.background {
+                text_format.background = to_color32(bg);
             }
-        }
-    }
-
-    fn insert_lines(&mut self, n: usize) {
-        let blank = Cell {
-            ch: ' ',
-            style: CellStyle::default(),
-        };
-        for _ in 0..n {
-            if self.cursor_row <= self.scroll_bottom && self.scroll_bottom < self.rows {
-                for r in (self.cursor_row + 1..=self.scroll_bottom).rev() {
-                    self.grid[r] = self.grid[r - 1].clone();
-                }
-                self.grid[self.cursor_row].fill(blank);
-            }
-        }
-    }
-
-    fn delete_lines(&mut self, n: usize) {
-        let blank = Cell {
-            ch: ' ',
-            style: CellStyle::default(),
-        };
-        for _ in 0..n {
-            if self.cursor_row <= self.scroll_bottom && self.scroll_bottom < self.rows {
-                for r in self.cursor_row..self.scroll_bottom {
-                    self.grid[r] = self.grid[r + 1].clone();
-                }
-                self.grid[self.scroll_bottom].fill(blank);
+            if style.bold {
+                text_format.color = brighten(text_format.color);
             }
-        }
-    }
-
-    fn insert_chars(&mut self, n: usize) {
-        if self.cursor_row < self.rows {
-            let blank = Cell {
-                ch: ' ',
-                style: self.current_style,
+            let underline_color = match style.underline_color {
+                Color::Default => text_format.color,
+                color => to_color32(palette.resolve_fg(color)),
             };
-            for _ in 0..n {
-                if self.cursor_col < self.cols {
-                    self.grid[self.cursor_row].pop();
-                    self.grid[self.cursor_row].insert(self.cursor_col, blank);
-                }
-            }
-            self.grid[self.cursor_row].truncate(self.cols);
-        }
-    }
 
-    fn delete_chars(&mut self, n: usize) {
-        if self.cursor_row < self.rows {
-            let blank = Cell {
-                ch: ' ',
-                style: self.current_style,
-            };
-            for _ in 0..n {
-                if self.cursor_col < self.grid[self.cursor_row].len() {
-                    self.grid[self.cursor_row].remove(self.cursor_col);
-                    self.grid[self.cursor_row].push(blank);
-                }
-            }
-            self.grid[self.cursor_row].truncate(self.cols);
+            decorations.append(&mut job, &text, text_format, &style, underline_color);
+            run_start = run_end;
         }
-    }
-
-    fn move_cursor(&mut self, row: usize, col: usize) {
-        self.cursor_row = row.saturating_sub(1).min(self.rows.saturating_sub(1));
-        self.cursor_col = col.saturating_sub(1).min(self.cols.saturating_sub(1));
-    }
 
-    fn render_to_layout_job(&self, font_id: egui::FontId) -> egui::text::LayoutJob {
-        let mut job = egui::text::LayoutJob {
-            wrap: egui::text::TextWrapping {
-                max_width: f32::INFINITY,
-                ..Default::default()
-            },
-            ..Default::default()
-        };
-
-        for (row_idx, row) in self.grid.iter().enumerate() {
-            // Render full row width (including trailing spaces) so the galley
-            // fills the entire terminal area — critical for correct sizing.
-            let mut run_start = 0;
-            while run_start < self.cols {
-                let style = row[run_start].style;
-                let mut run_end = run_start + 1;
-                while run_end < self.cols && row[run_end].style == style {
-                    run_end += 1;
-                }
-
-                let text: String = row[run_start..run_end].iter().map(|c| c.ch).collect();
-                let fg = style.effective_fg();
-                let bg = style.effective_bg();
-
-                let mut text_format = egui::TextFormat {
+        if row_idx + 1 < lines.len() {
+            job.append(
+                "\n",
+                0.0,
+                egui::TextFormat {
                     font_id: font_id.clone(),
-                    color: fg.to_color32(),
+                    color: to_color32(palette.foreground),
                     ..Default::default()
-                };
-                if bg != DEFAULT_BG {
-                    text_format.background = bg.to_color32();
-                }
-                if style.bold {
-                    text_format.color = brighten(text_format.color);
-                }
-                if style.underline {
-                    text_format.underline = egui::Stroke::new(1.0, text_format.color);
-                }
-                if style.italic {
-                    text_format.italics = true;
-                }
-
-                job.append(&text, 0.0, text_format);
-                run_start = run_end;
-            }
-
-            if row_idx < self.rows - 1 {
-                job.append(
-                    "\n",
-                    0.0,
-                    egui::TextFormat {
-                        font_id: font_id.clone(),
-                        color: DEFAULT_FG.to_color32(),
-                        ..Default::default()
-                    },
-                );
-            }
+                },
+            );
         }
-
-        job
     }
 
-    fn cursor_position(&self) -> (usize, usize) {
-        (self.cursor_row, self.cursor_col)
-    }
+    (job, decorations)
 }
 
 fn brighten(color: egui::Color32) -> egui::Color32 {
@@ -484,20 +168,36 @@ pub struct TuiOverlay {
     command: Option<String>,
     /// PTY handle ID for the running TUI app
     pty_handle_id: Option<String>,
-    /// Virtual screen buffer
-    screen_buffer: ScreenBuffer,
+    /// Virtual terminal screen
+    grid: TerminalGrid,
     /// Whether the TUI app has exited
     has_exited: bool,
     /// Last measured available size in character cells (rows, cols)
     last_char_size: Option<(usize, usize)>,
     /// Timestamp of last Escape press for double-Escape detection
     last_escape_time: Option<std::time::Instant>,
-    /// Pending resize (rows, cols) that the caller should apply to the PTY
-    pending_resize: Option<(u16, u16)>,
+    /// Debounced PTY size the caller should apply
+    pty_size: ResizeDebouncer,
     /// When the overlay was activated (for grace-period exit detection)
     started_at: Option<std::time::Instant>,
     /// Whether the TUI app has entered alternate screen buffer
     saw_alt_screen_enter: bool,
+    /// Screen area and cell size from the last render, for mapping pointer positions
+    cell_area: Option<(egui::Rect, egui::Vec2)>,
+    /// Button currently held down while mouse reporting is on
+    mouse_button: Option<MouseButton>,
+    /// Cell of the last reported pointer position, to skip duplicate motion reports
+    last_mouse_cell: Option<(usize, usize)>,
+    /// Fractional wheel movement not yet sent, in lines
+    wheel_remainder: f32,
+    /// How many lines the view is scrolled up into the scrollback (0 = live screen)
+    scroll_offset: usize,
+    /// When the app began the synchronized update still open, if any
+    sync_started: Option<std::time::Instant>,
+    /// What the last repaint drew
+    last_frame: Option<ScreenFrame>,
+    /// Whether the overlay's terminal has focus, as last told by the app
+    focused: bool,
 }
 
 impl Default for TuiOverlay {
@@ -513,13 +213,21 @@ impl TuiOverlay {
             active: false,
             command: None,
             pty_handle_id: None,
-            screen_buffer: ScreenBuffer::new(50, 120),
+            grid: TerminalGrid::new(50, 120),
             has_exited: false,
             last_char_size: None,
             last_escape_time: None,
-            pending_resize: None,
+            pty_size: ResizeDebouncer::default(),
             started_at: None,
             saw_alt_screen_enter: false,
+            cell_area: None,
+            mouse_button: None,
+            last_mouse_cell: None,
+            wheel_remainder: 0.0,
+            scroll_offset: 0,
+            sync_started: None,
+            last_frame: None,
+            focused: true,
         }
     }
 
@@ -533,10 +241,22 @@ impl TuiOverlay {
         self.active = true;
         self.command = Some(command);
         self.pty_handle_id = Some(pty_handle_id);
-        self.screen_buffer.clear();
+        self.grid.reset();
+        self.sync_started = None;
+        self.last_frame = None;
         self.has_exited = false;
         self.started_at = Some(std::time::Instant::now());
+        // The PTY was sized for the block view; send the overlay size right away
+        self.pty_size.reset();
+        if let Some((rows, cols)) = self.last_char_size {
+            self.pty_size.observe(
+                GridSize::new(rows as u16, cols as u16),
+                std::time::Instant::now(),
+            );
+        }
         self.saw_alt_screen_enter = false;
+        self.scroll_offset = 0;
+        self.reset_mouse();
     }
 
     /// Stop the overlay
@@ -544,10 +264,20 @@ impl TuiOverlay {
         self.active = false;
         self.command = None;
         self.pty_handle_id = None;
-        self.screen_buffer.clear();
+        self.grid.reset();
+        self.sync_started = None;
+        self.last_frame = None;
         self.has_exited = false;
         self.started_at = None;
         self.saw_alt_screen_enter = false;
+        self.scroll_offset = 0;
+        self.reset_mouse();
+    }
+
+    fn reset_mouse(&mut self) {
+        self.mouse_button = None;
+        self.last_mouse_cell = None;
+        self.wheel_remainder = 0.0;
     }
 
     /// Whether the overlay is still in the startup grace period (ignore exit sequences)
@@ -589,7 +319,7 @@ impl TuiOverlay {
 
     /// Get the current buffer dimensions for PTY size reporting
     pub fn buffer_size(&self) -> (usize, usize) {
-        (self.screen_buffer.rows, self.screen_buffer.cols)
+        (self.grid.rows(), self.grid.cols())
     }
 
     /// Get the last measured character cell size (rows, cols), if available.
@@ -597,359 +327,124 @@ impl TuiOverlay {
         self.last_char_size
     }
 
-    /// Take the pending resize request, if any.
+    /// Take the pending resize request once it has settled, if any.
     /// Returns (rows, cols) as u16 suitable for PTY resize calls.
     pub fn take_pending_resize(&mut self) -> Option<(u16, u16)> {
-        self.pending_resize.take()
+        self.pty_size
+            .poll(std::time::Instant::now())
+            .map(|size| (size.rows, size.cols))
+    }
+
+    /// Time left until a pending resize settles, if one is waiting
+    pub fn resize_due_in(&self) -> Option<std::time::Duration> {
+        self.pty_size.due_in(std::time::Instant::now())
     }
 
     /// Add raw output data and process ANSI sequences
     pub fn add_raw_output(&mut self, data: &[u8]) {
-        let text = String::from_utf8_lossy(data);
-        self.process_ansi_text(&text);
-    }
-
-    /// Process text with ANSI escape sequences and update screen buffer
-    fn process_ansi_text(&mut self, text: &str) {
-        let mut chars = text.chars().peekable();
-
-        while let Some(ch) = chars.next() {
-            if ch == '\x1b' {
-                if chars.peek() == Some(&'[') {
-                    chars.next();
-                    let mut params = String::new();
-
-                    while let Some(&next_ch) = chars.peek() {
-                        if next_ch.is_ascii_digit()
-                            || next_ch == ';'
-                            || next_ch == '?'
-                            || next_ch == ' '
-                            || next_ch == '>'
-                            || next_ch == '!'
-                        {
-                            params.push(next_ch);
-                            chars.next();
-                        } else {
-                            break;
-                        }
-                    }
+        let history = self.grid.scrollback_len();
+        self.grid.advance(data);
+        if self.grid.synchronized_output() {
+            self.sync_started
+                .get_or_insert_with(std::time::Instant::now);
+        } else {
+            self.sync_started = None;
+        }
+        if self.scroll_offset > 0 {
+            // Keep the browsed lines in place while new output arrives
+            let added = self.grid.scrollback_len().saturating_sub(history);
+            self.scroll_offset = (self.scroll_offset + added).min(self.grid.scrollback_len());
+        }
+    }
 
-                    if let Some(cmd) = chars.next() {
-                        self.handle_csi_command(&params, cmd);
-                    }
-                } else if chars.peek() == Some(&']') {
-                    chars.next();
-                    while let Some(&next_ch) = chars.peek() {
-                        chars.next();
-                        if next_ch == '\x07' || (next_ch == '\x1b' && chars.peek() == Some(&'\\')) {
-                            if next_ch == '\x1b' {
-                                chars.next();
-                            }
-                            break;
-                        }
-                    }
-                } else if chars.peek() == Some(&'P')
-                    || chars.peek() == Some(&'^')
-                    || chars.peek() == Some(&'_')
-                {
-                    chars.next();
-                    while let Some(&next_ch) = chars.peek() {
-                        chars.next();
-                        if next_ch == '\x1b' && chars.peek() == Some(&'\\') {
-                            chars.next();
-                            break;
-                        }
-                    }
-                } else if chars.peek() == Some(&'(') || chars.peek() == Some(&')') {
-                    chars.next();
-                    chars.next();
-                } else if chars.peek() == Some(&'7') {
-                    chars.next();
-                    self.screen_buffer.saved_cursor =
-                        Some((self.screen_buffer.cursor_row, self.screen_buffer.cursor_col));
-                } else if chars.peek() == Some(&'8') {
-                    chars.next();
-                    if let Some((r, c)) = self.screen_buffer.saved_cursor {
-                        self.screen_buffer.cursor_row = r;
-                        self.screen_buffer.cursor_col = c;
-                    }
-                } else if chars.peek() == Some(&'M') {
-                    chars.next();
-                    if self.screen_buffer.cursor_row == self.screen_buffer.scroll_top {
-                        self.screen_buffer.scroll_down(1);
-                    } else {
-                        self.screen_buffer.cursor_row =
-                            self.screen_buffer.cursor_row.saturating_sub(1);
-                    }
-                } else if chars.peek() == Some(&'D') {
-                    chars.next();
-                    if self.screen_buffer.cursor_row >= self.screen_buffer.scroll_bottom {
-                        self.screen_buffer.scroll_up(1);
-                    } else {
-                        self.screen_buffer.cursor_row += 1;
-                    }
-                } else if chars.peek() == Some(&'E') {
-                    chars.next();
-                    if self.screen_buffer.cursor_row >= self.screen_buffer.scroll_bottom {
-                        self.screen_buffer.scroll_up(1);
-                    } else {
-                        self.screen_buffer.cursor_row += 1;
-                    }
-                    self.screen_buffer.cursor_col = 0;
-                } else if chars.peek() == Some(&'c') {
-                    chars.next();
-                    self.screen_buffer.clear();
-                    self.screen_buffer.current_style = CellStyle::default();
-                } else {
-                    chars.next();
-                }
-            } else {
-                self.screen_buffer.write_char(ch);
-            }
+    /// Record whether the terminal has focus. Returns the focus event to
+    /// write to the app's PTY when focus changed while the overlay is showing
+    /// an app that enabled focus reporting (mode 1004).
+    pub fn set_focused(&mut self, focused: bool) -> Option<Vec<u8>> {
+        if focused == self.focused {
+            return None;
+        }
+        self.focused = focused;
+        if !self.active || !self.grid.focus_reporting() {
+            return None;
         }
+        let report: &[u8] = if focused { b"\x1b[I" } else { b"\x1b[O" };
+        Some(report.to_vec())
     }
 
-    /// Handle CSI (Control Sequence Introducer) commands
-    fn handle_csi_command(&mut self, params: &str, cmd: char) {
-        let clean_params = params.trim_start_matches('?').trim_start_matches('>');
+    /// Set the colours used to draw the screen and answer colour queries
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.grid.set_palette(palette);
+    }
 
-        match cmd {
-            'H' | 'f' => {
-                let parts: Vec<&str> = clean_params.split(';').collect();
-                let row = parts.first().and_then(|s| s.parse().ok()).unwrap_or(1);
-                let col = parts.get(1).and_then(|s| s.parse().ok()).unwrap_or(1);
-                self.screen_buffer.move_cursor(row, col);
-            }
-            'A' => {
-                let n: usize = clean_params.parse().unwrap_or(1).max(1);
-                self.screen_buffer.cursor_row = self.screen_buffer.cursor_row.saturating_sub(n);
-            }
-            'B' | 'e' => {
-                let n: usize = clean_params.parse().unwrap_or(1).max(1);
-                self.screen_buffer.cursor_row = (self.screen_buffer.cursor_row + n)
-                    .min(self.screen_buffer.rows.saturating_sub(1));
-            }
-            'C' | 'a' => {
-                let n: usize = clean_params.parse().unwrap_or(1).max(1);
-                self.screen_buffer.cursor_col = (self.screen_buffer.cursor_col + n)
-                    .min(self.screen_buffer.cols.saturating_sub(1));
-            }
-            'D' => {
-                let n: usize = clean_params.parse().unwrap_or(1).max(1);
-                self.screen_buffer.cursor_col = self.screen_buffer.cursor_col.saturating_sub(n);
-            }
-            'E' => {
-                let n: usize = clean_params.parse().unwrap_or(1).max(1);
-                self.screen_buffer.cursor_row = (self.screen_buffer.cursor_row + n)
-                    .min(self.screen_buffer.rows.saturating_sub(1));
-                self.screen_buffer.cursor_col = 0;
-            }
-            'F' => {
-                let n: usize = clean_params.parse().unwrap_or(1).max(1);
-                self.screen_buffer.cursor_row = self.screen_buffer.cursor_row.saturating_sub(n);
-                self.screen_buffer.cursor_col = 0;
-            }
-            'G' | '`' => {
-                let col: usize = clean_params.parse().unwrap_or(1);
-                self.screen_buffer.cursor_col = col
-                    .saturating_sub(1)
-                    .min(self.screen_buffer.cols.saturating_sub(1));
-            }
-            'd' => {
-                let row: usize = clean_params.parse().unwrap_or(1);
-                self.screen_buffer.cursor_row = row
-                    .saturating_sub(1)
-                    .min(self.screen_buffer.rows.saturating_sub(1));
-            }
-            'J' => {
-                let mode: u8 = clean_params.parse().unwrap_or(0);
-                match mode {
-                    0 => self.screen_buffer.clear_from_cursor_to_end_of_screen(),
-                    1 => self.screen_buffer.clear_from_start_to_cursor(),
-                    2 | 3 => self.screen_buffer.clear(),
-                    _ => {}
-                }
-            }
-            'K' => {
-                let mode: u8 = clean_params.parse().unwrap_or(0);
-                match mode {
-                    0 => self.screen_buffer.clear_line_from_cursor(),
-                    1 => self.screen_buffer.clear_line_to_cursor(),
-                    2 => self.screen_buffer.clear_entire_line(),
-                    _ => {}
-                }
-            }
-            'm' => {
-                self.handle_sgr(clean_params);
-            }
-            'r' => {
-                let parts: Vec<&str> = clean_params.split(';').collect();
-                let top = parts
-                    .first()
-                    .and_then(|s| s.parse::<usize>().ok())
-                    .unwrap_or(1);
-                let bottom = parts
-                    .get(1)
-                    .and_then(|s| s.parse::<usize>().ok())
-                    .unwrap_or(self.screen_buffer.rows);
-                self.screen_buffer.scroll_top = top.saturating_sub(1);
-                self.screen_buffer.scroll_bottom =
-                    (bottom.saturating_sub(1)).min(self.screen_buffer.rows.saturating_sub(1));
-                self.screen_buffer.cursor_row = 0;
-                self.screen_buffer.cursor_col = 0;
-            }
-            'S' => {
-                let n: usize = clean_params.parse().unwrap_or(1).max(1);
-                self.screen_buffer.scroll_up(n);
-            }
-            'T' => {
-                let n: usize = clean_params.parse().unwrap_or(1).max(1);
-                self.screen_buffer.scroll_down(n);
-            }
-            'L' => {
-                let n: usize = clean_params.parse().unwrap_or(1).max(1);
-                self.screen_buffer.insert_lines(n);
-            }
-            'M' => {
-                let n: usize = clean_params.parse().unwrap_or(1).max(1);
-                self.screen_buffer.delete_lines(n);
-            }
-            '@' => {
-                let n: usize = clean_params.parse().unwrap_or(1).max(1);
-                self.screen_buffer.insert_chars(n);
-            }
-            'P' => {
-                let n: usize = clean_params.parse().unwrap_or(1).max(1);
-                self.screen_buffer.delete_chars(n);
-            }
-            'X' => {
-                let n: usize = clean_params.parse().unwrap_or(1).max(1);
-                let row = self.screen_buffer.cursor_row;
-                let col = self.screen_buffer.cursor_col;
-                if row < self.screen_buffer.rows {
-                    let blank = Cell {
-                        ch: ' ',
-                        style: self.screen_buffer.current_style,
-                    };
-                    for c in col..(col + n).min(self.screen_buffer.cols) {
-                        self.screen_buffer.grid[row][c] = blank;
-                    }
-                }
-            }
-            's' => {
-                self.screen_buffer.saved_cursor =
-                    Some((self.screen_buffer.cursor_row, self.screen_buffer.cursor_col));
-            }
-            'u' => {
-                if let Some((r, c)) = self.screen_buffer.saved_cursor {
-                    self.screen_buffer.cursor_row = r;
-                    self.screen_buffer.cursor_col = c;
-                }
-            }
-            'h' | 'l' => {
-                // DEC private mode set/reset
-                let set = cmd == 'h';
-                for p in params.replace('?', "").split(';') {
-                    match p.trim() {
-                        "1049" | "47" | "1047" => {
-                            // Alternate screen buffer
-                            if set {
-                                self.screen_buffer.clear();
-                            }
-                        }
-                        "25" => {
-                            // Cursor visibility (not visually rendered yet)
-                        }
-                        "1" => {
-                            // Application cursor keys mode
-                        }
-                        "7" => {
-                            // Auto-wrap mode
-                        }
-                        _ => {}
-                    }
-                }
-            }
-            'n' | 'c' => {
-                // Device status reports / device attributes - consumed
-            }
-            _ => {}
-        }
+    /// Set the cursor style used until the app picks one with DECSCUSR
+    pub fn set_cursor_style(&mut self, style: CursorStyle) {
+        self.grid.set_default_cursor_style(style);
     }
 
-    /// Handle SGR (Select Graphic Rendition) parameters
-    fn handle_sgr(&mut self, params: &str) {
-        if params.is_empty() {
-            self.screen_buffer.current_style = CellStyle::default();
-            return;
-        }
+    /// Take the replies to terminal queries made by the app, to be written to its PTY
+    pub fn take_responses(&mut self) -> Vec<u8> {
+        self.grid.take_responses()
+    }
 
-        let codes: Vec<u16> = params.split(';').filter_map(|s| s.parse().ok()).collect();
-
-        let mut i = 0;
-        while i < codes.len() {
-            match codes[i] {
-                0 => self.screen_buffer.current_style = CellStyle::default(),
-                1 => self.screen_buffer.current_style.bold = true,
-                2 => self.screen_buffer.current_style.dim = true,
-                3 => self.screen_buffer.current_style.italic = true,
-                4 => self.screen_buffer.current_style.underline = true,
-                7 => self.screen_buffer.current_style.reverse = true,
-                21 | 22 => {
-                    self.screen_buffer.current_style.bold = false;
-                    self.screen_buffer.current_style.dim = false;
-                }
-                23 => self.screen_buffer.current_style.italic = false,
-                24 => self.screen_buffer.current_style.underline = false,
-                27 => self.screen_buffer.current_style.reverse = false,
-                30..=37 => {
-                    self.screen_buffer.current_style.fg = ANSI_COLORS[(codes[i] - 30) as usize];
-                }
-                38 => {
-                    if i + 2 < codes.len() && codes[i + 1] == 5 {
-                        self.screen_buffer.current_style.fg =
-                            AnsiColor::from_256(codes[i + 2] as u8);
-                        i += 2;
-                    } else if i + 4 < codes.len() && codes[i + 1] == 2 {
-                        self.screen_buffer.current_style.fg = AnsiColor::rgb(
-                            codes[i + 2] as u8,
-                            codes[i + 3] as u8,
-                            codes[i + 4] as u8,
-                        );
-                        i += 4;
-                    }
-                }
-                39 => self.screen_buffer.current_style.fg = DEFAULT_FG,
-                40..=47 => {
-                    self.screen_buffer.current_style.bg = ANSI_COLORS[(codes[i] - 40) as usize];
-                }
-                48 => {
-                    if i + 2 < codes.len() && codes[i + 1] == 5 {
-                        self.screen_buffer.current_style.bg =
-                            AnsiColor::from_256(codes[i + 2] as u8);
-                        i += 2;
-                    } else if i + 4 < codes.len() && codes[i + 1] == 2 {
-                        self.screen_buffer.current_style.bg = AnsiColor::rgb(
-                            codes[i + 2] as u8,
-                            codes[i + 3] as u8,
-                            codes[i + 4] as u8,
-                        );
-                        i += 4;
-                    }
-                }
-                49 => self.screen_buffer.current_style.bg = DEFAULT_BG,
-                90..=97 => {
-                    self.screen_buffer.current_style.fg =
-                        ANSI_BRIGHT_COLORS[(codes[i] - 90) as usize];
-                }
-                100..=107 => {
-                    self.screen_buffer.current_style.bg =
-                        ANSI_BRIGHT_COLORS[(codes[i] - 100) as usize];
-                }
-                _ => {}
-            }
-            i += 1;
-        }
+    /// Take the app's clipboard requests (OSC 52)
+    pub fn take_clipboard_requests(&mut self) -> Vec<ClipboardRequest> {
+        self.grid.take_clipboard_requests()
+    }
+
+    /// Take the titles the app set (OSC 0/1/2)
+    pub fn take_title_changes(&mut self) -> Vec<TitleChange> {
+        self.grid.take_title_changes()
+    }
+
+    /// Set how large the app's images may be, or turn them off
+    pub fn set_image_limits(&mut self, limits: ImageLimits) {
+        self.grid.set_image_limits(limits);
+    }
+
+    /// Memory taken by the app's images, shown, scrolled back or stored
+    pub fn image_bytes(&self) -> usize {
+        self.grid.image_bytes()
+    }
+
+    /// Forget images the app stored for later placement, oldest first,
+    /// until `bytes` of them are gone
+    pub fn drop_stored_images(&mut self, bytes: usize) -> usize {
+        self.grid.drop_stored_images(bytes)
+    }
+
+    /// Blank images in the scrollback, oldest first, until `bytes` of
+    /// memory are freed; returns the memory freed
+    pub fn drop_scrollback_images(&mut self, bytes: usize) -> usize {
+        self.grid.drop_scrollback_images(bytes)
+    }
+
+    /// Keep at most `lines` of primary-screen scrollback
+    pub fn set_scrollback_limit(&mut self, lines: usize) {
+        self.grid.set_scrollback_limit(lines);
+        self.scroll_offset = self.scroll_offset.min(self.grid.scrollback_len());
+    }
+
+    /// How much longer repaints are held for the app's open synchronized
+    /// update, or `None` when the screen should be drawn as it is
+    fn sync_hold_remaining(&self, now: std::time::Instant) -> Option<std::time::Duration> {
+        let started = self.sync_started?;
+        SYNC_OUTPUT_TIMEOUT
+            .checked_sub(now.saturating_duration_since(started))
+            .filter(|remaining| !remaining.is_zero())
+    }
+
+    /// How many lines the view is scrolled back (0 when showing the live screen)
+    pub fn scroll_offset(&self) -> usize {
+        self.scroll_offset
+    }
+
+    /// Move the view `lines` up (positive) or down (negative) through the scrollback
+    pub fn scroll_view(&mut self, lines: isize) {
+        self.scroll_offset = self
+            .scroll_offset
+            .saturating_add_signed(lines)
+            .min(self.grid.scrollback_len());
     }
 
     /// Render the overlay using the full window area with top and bottom bars
@@ -1030,65 +525,206 @@ impl TuiOverlay {
                 });
             });
 
+        let background = to_color32(self.grid.palette().background);
+        let foreground = to_color32(self.grid.palette().foreground);
+
         // Central terminal area fills the rest
         egui::CentralPanel::default()
             .frame(
                 egui::Frame::new()
-                    .fill(DEFAULT_BG.to_color32())
+                    .fill(background)
                     .inner_margin(egui::Margin::symmetric(4, 2)),
             )
             .show_inside(host_ui, |ui| {
                 let mono_font = egui::FontId::new(13.0, egui::FontFamily::Monospace);
-                let char_width = ui.fonts_mut(|fonts| fonts.glyph_width(&mono_font, 'M'));
-                let line_height = ui.text_style_height(&egui::TextStyle::Monospace);
+                let (char_width, line_height) = ui.fonts_mut(|fonts| {
+                    (
+                        fonts.glyph_width(&mono_font, 'M'),
+                        fonts.row_height(&mono_font),
+                    )
+                });
 
                 let available = ui.available_size();
-                let cols = ((available.x / char_width) as usize).max(40);
-                let rows = ((available.y / line_height) as usize).max(10);
+                let measured =
+                    GridSize::from_pixels(available.x, available.y, char_width, line_height);
+                let rows = (measured.rows as usize).max(10);
+                let cols = (measured.cols as usize).max(40);
 
                 let new_size = (rows, cols);
                 if self.last_char_size != Some(new_size) {
                     self.last_char_size = Some(new_size);
-                    self.screen_buffer.resize(rows, cols);
-                    self.pending_resize = Some((rows as u16, cols as u16));
+                    self.grid.resize(rows, cols);
                 }
-
-                let job = self.screen_buffer.render_to_layout_job(mono_font.clone());
-                let galley = ui.fonts_mut(|fonts| fonts.layout_job(job));
-                let (response, painter) = ui.allocate_painter(available, egui::Sense::hover());
-                painter.rect_filled(
-                    response.rect,
-                    egui::CornerRadius::ZERO,
-                    DEFAULT_BG.to_color32(),
-                );
-                painter.galley(response.rect.min, galley, DEFAULT_FG.to_color32());
-
-                // Draw cursor block at current position
-                let (cursor_row, cursor_col) = self.screen_buffer.cursor_position();
-                let cursor_x = response.rect.min.x + (cursor_col as f32) * char_width;
-                let cursor_y = response.rect.min.y + (cursor_row as f32) * line_height;
-                let cursor_rect = egui::Rect::from_min_size(
-                    egui::pos2(cursor_x, cursor_y),
-                    egui::vec2(char_width, line_height),
+                self.pty_size.observe(
+                    GridSize::new(rows as u16, cols as u16),
+                    std::time::Instant::now(),
                 );
-                painter.rect_filled(
-                    cursor_rect,
-                    egui::CornerRadius::ZERO,
-                    egui::Color32::from_rgba_premultiplied(200, 200, 200, 180),
+
+                let offset = self.scroll_offset.min(self.grid.scrollback_len());
+                let time = ui.input(|i| i.time);
+                let pixels_per_point = ctx.pixels_per_point();
+                self.grid.set_cell_pixels(
+                    char_width * pixels_per_point,
+                    line_height * pixels_per_point,
                 );
-            });
 
-        // Double-Escape to close overlay (single Escape is forwarded to the app)
-        if ctx.input(|i| i.key_pressed(egui::Key::Escape)) {
-            let now = std::time::Instant::now();
-            if let Some(last) = self.last_escape_time {
-                if now.duration_since(last).as_millis() < 400 {
-                    should_close = true;
-                    self.last_escape_time = None;
-                } else {
-                    self.last_escape_time = Some(now);
+                // While the app has a synchronized update open, keep showing
+                // the last frame so its partial redraw never appears
+                let held = match (
+                    self.sync_hold_remaining(std::time::Instant::now()),
+                    &self.last_frame,
+                ) {
+                    (Some(remaining), Some(frame)) => {
+                        ctx.request_repaint_after(remaining);
+                        Some(frame.clone())
+                    }
+                    _ => None,
+                };
+                let frame = held.unwrap_or_else(|| {
+                    let view = self.grid.view(offset);
+                    let (job, decorations) = grid_layout_job(
+                        &view,
+                        self.grid.palette(),
+                        mono_font.clone(),
+                        text_blink_on(time),
+                    );
+                    if decorations.has_blink() {
+                        let remaining = TEXT_BLINK_INTERVAL - time % TEXT_BLINK_INTERVAL;
+                        ctx.request_repaint_after(std::time::Duration::from_secs_f64(remaining));
+                    }
+                    let galley = ui.fonts_mut(|fonts| {
+                        let job = align_wide_chars(job, fonts);
+                        fonts.layout_job(job)
+                    });
+                    // The cursor moves down (possibly out of view) while
+                    // scrolled back
+                    let (cursor_row, cursor_col) = self.grid.cursor_position();
+                    let frame = ScreenFrame {
+                        galley,
+                        decorations,
+                        cursor: (self.grid.cursor_visible()
+                            && cursor_row + offset < self.grid.rows())
+                        .then_some((cursor_row + offset, cursor_col)),
+                        cursor_style: self.grid.cursor_style(),
+                        images: image_tiles(&view),
+                    };
+                    self.last_frame = Some(frame.clone());
+                    frame
+                });
+
+                let (response, painter) = ui.allocate_painter(available, egui::Sense::hover());
+                painter.rect_filled(response.rect, egui::CornerRadius::ZERO, background);
+                painter.galley(response.rect.min, frame.galley.clone(), foreground);
+                frame
+                    .decorations
+                    .paint(&painter, &frame.galley, response.rect.min);
+                for (row, col, tile) in &frame.images {
+                    let texture = crate::ui::images::texture(ctx, &tile.image);
+                    let ([width, height], [left, top, right, bottom]) = tile.crop();
+                    let min = response.rect.min
+                        + egui::vec2(*col as f32 * char_width, *row as f32 * line_height);
+                    painter.image(
+                        texture.id(),
+                        egui::Rect::from_min_size(
+                            min,
+                            egui::vec2(width * char_width, height * line_height),
+                        ),
+                        egui::Rect::from_min_max(egui::pos2(left, top), egui::pos2(right, bottom)),
+                        egui::Color32::WHITE,
+                    );
                 }
-            } else {
+                self.cell_area = Some((response.rect, egui::vec2(char_width, line_height)));
+
+                // Underline the hovered link; Ctrl/Cmd+Click opens it
+                if let Some(link) = response.hover_pos().and_then(|pos| self.link_at(pos)) {
+                    let stroke = egui::Stroke::new(1.0, foreground);
+                    for (row, cells) in self.grid.view(offset).iter().enumerate() {
+                        let y = response.rect.min.y + (row + 1) as f32 * line_height - 1.5;
+                        let mut col = 0;
+                        while col < cells.len() {
+                            let start = col;
+                            while col < cells.len() && cells[col].link.as_ref() == Some(&link) {
+                                col += 1;
+                            }
+                            if col > start {
+                                let x = |col: usize| response.rect.min.x + col as f32 * char_width;
+                                painter.hline(x(start)..=x(col), y, stroke);
+                            } else {
+                                col += 1;
+                            }
+                        }
+                    }
+                    let (command, clicked) =
+                        ctx.input(|i| (i.modifiers.command, i.pointer.primary_clicked()));
+                    if command {
+                        ctx.set_cursor_icon(egui::CursorIcon::PointingHand);
+                        if clicked {
+                            if let Err(e) = crate::terminal::hyperlink::open(&link) {
+                                warn!("{}", e);
+                            }
+                        }
+                    }
+                }
+
+                // Draw the cursor in the app's chosen shape. Hidden cursors
+                // (DECTCEM) are not drawn.
+                let style = frame.cursor_style;
+                let lit = if style.blinking {
+                    let remaining = CURSOR_BLINK_INTERVAL - time % CURSOR_BLINK_INTERVAL;
+                    ctx.request_repaint_after(std::time::Duration::from_secs_f64(remaining));
+                    cursor_blink_on(time)
+                } else {
+                    true
+                };
+                if let Some((cursor_row, cursor_col)) = frame.cursor.filter(|_| lit) {
+                    let cursor_x = response.rect.min.x + (cursor_col as f32) * char_width;
+                    let cursor_y = response.rect.min.y + (cursor_row as f32) * line_height;
+                    let cell = egui::Rect::from_min_size(
+                        egui::pos2(cursor_x, cursor_y),
+                        egui::vec2(char_width, line_height),
+                    );
+                    painter.rect_filled(
+                        cursor_rect(cell, style.shape),
+                        egui::CornerRadius::ZERO,
+                        egui::Color32::from_rgba_premultiplied(200, 200, 200, 180),
+                    );
+                }
+
+                // Scroll position indicator in the top-right corner
+                if offset > 0 {
+                    let label = format!("{} / {}", offset, self.grid.scrollback_len());
+                    let galley = painter.layout_no_wrap(
+                        label,
+                        egui::FontId::proportional(11.0),
+                        egui::Color32::from_rgb(200, 200, 220),
+                    );
+                    let badge = egui::Rect::from_min_size(
+                        egui::pos2(
+                            response.rect.max.x - galley.size().x - 16.0,
+                            response.rect.min.y + 4.0,
+                        ),
+                        galley.size() + egui::vec2(12.0, 6.0),
+                    );
+                    painter.rect_filled(
+                        badge,
+                        egui::CornerRadius::same(4),
+                        egui::Color32::from_rgba_unmultiplied(60, 60, 90, 220),
+                    );
+                    painter.galley(badge.min + egui::vec2(6.0, 3.0), galley, foreground);
+                }
+            });
+
+        // Double-Escape to close overlay (single Escape is forwarded to the app)
+        if ctx.input(|i| i.key_pressed(egui::Key::Escape)) {
+            let now = std::time::Instant::now();
+            if let Some(last) = self.last_escape_time {
+                if now.duration_since(last).as_millis() < 400 {
+                    should_close = true;
+                    self.last_escape_time = None;
+                } else {
+                    self.last_escape_time = Some(now);
+                }
+            } else {
                 self.last_escape_time = Some(now);
             }
         }
@@ -1103,7 +739,7 @@ impl TuiOverlay {
 
     /// Handle keyboard input for the TUI app.
     /// Double-Escape closes the overlay; single Escape is forwarded to the app.
-    pub fn handle_input(&self, ctx: &egui::Context) -> Option<Vec<u8>> {
+    pub fn handle_input(&mut self, ctx: &egui::Context) -> Option<Vec<u8>> {
         if !self.active {
             return None;
         }
@@ -1111,32 +747,104 @@ impl TuiOverlay {
         let mut input_data = Vec::new();
 
         ctx.input(|i| {
+            // The platform paste shortcut (Ctrl+V on Linux/Windows) also arrives
+            // as a key event; don't send it as a literal ^V as well
+            let pasted = i.events.iter().any(|e| matches!(e, egui::Event::Paste(_)));
+            let kitty_flags = self.grid.kitty_keyboard_flags();
+
             for event in &i.events {
                 if let egui::Event::Key {
                     key,
-                    pressed: true,
+                    pressed,
+                    repeat,
                     modifiers,
                     ..
                 } = event
                 {
-                    if *key == egui::Key::D && modifiers.ctrl {
-                        input_data.extend_from_slice(b"\x04");
-                    } else if *key == egui::Key::Escape {
-                        // Forward Escape to the PTY (double-Escape close is handled in render())
-                        input_data.extend_from_slice(b"\x1b");
-                    } else {
-                        let terminal_seq = key_to_terminal_sequence(*key, modifiers);
-                        if !terminal_seq.is_empty() {
-                            input_data.extend_from_slice(&terminal_seq);
+                    if pasted && *key == egui::Key::V && modifiers.command {
+                        continue;
+                    }
+                    // Shift+PageUp/PageDown browse the scrollback instead of reaching the app
+                    if modifiers.shift
+                        && matches!(key, egui::Key::PageUp | egui::Key::PageDown)
+                        && !self.grid.is_alternate_screen()
+                    {
+                        if !*pressed {
+                            continue;
                         }
+                        let page = self.grid.rows().saturating_sub(1).max(1) as isize;
+                        let lines = if *key == egui::Key::PageUp {
+                            page
+                        } else {
+                            -page
+                        };
+                        self.scroll_view(lines);
+                        continue;
+                    }
+                    // Escape is forwarded too; double-Escape close is handled in render()
+                    if kitty_flags != 0 {
+                        let kind = match (*pressed, *repeat) {
+                            (false, _) => KeyEventKind::Release,
+                            (true, true) => KeyEventKind::Repeat,
+                            (true, false) => KeyEventKind::Press,
+                        };
+                        input_data.extend_from_slice(&kitty_key_sequence(
+                            *key,
+                            modifiers,
+                            kind,
+                            kitty_flags,
+                        ));
+                    } else if *pressed {
+                        let terminal_seq =
+                            key_to_terminal_sequence(*key, modifiers, &self.grid.keyboard_modes());
+                        input_data.extend_from_slice(&terminal_seq);
                     }
                 }
             }
 
-            // Handle text input (regular character typing)
+            // Handle text input (regular character typing) and pastes
             for event in &i.events {
-                if let egui::Event::Text(s) = event {
-                    input_data.extend_from_slice(s.as_bytes());
+                match event {
+                    egui::Event::Text(s) if kitty_flags != 0 => {
+                        // Keys that do not type text were reported as key events,
+                        // and so were ASCII keys when every key is reported
+                        let reported = !types_text(&i.modifiers)
+                            || (kitty_flags & KITTY_REPORT_ALL_KEYS != 0
+                                && s.len() == 1
+                                && s.is_ascii());
+                        if !reported {
+                            input_data.extend_from_slice(s.as_bytes());
+                        }
+                    }
+                    egui::Event::Text(s) => {
+                        // Alt+key arrives as text: send ESC first (xterm's
+                        // metaSendsEscape). Option composes characters on macOS.
+                        if i.modifiers.alt && !cfg!(target_os = "macos") {
+                            input_data.push(0x1b);
+                        }
+                        input_data.extend_from_slice(s.as_bytes());
+                    }
+                    egui::Event::Paste(s) => input_data
+                        .extend_from_slice(&paste_sequence(s, self.grid.bracketed_paste())),
+                    _ => {}
+                }
+            }
+
+            if self.grid.mouse_protocol().is_enabled() {
+                for event in &i.events {
+                    self.encode_pointer_event(
+                        event,
+                        i.pointer.hover_pos(),
+                        i.modifiers,
+                        &mut input_data,
+                    );
+                }
+            } else if !self.grid.is_alternate_screen() {
+                for event in &i.events {
+                    if let egui::Event::MouseWheel { unit, delta, .. } = event {
+                        let lines = self.wheel_lines(*unit, *delta);
+                        self.scroll_view(lines);
+                    }
                 }
             }
         });
@@ -1144,264 +852,489 @@ impl TuiOverlay {
         if input_data.is_empty() {
             None
         } else {
+            // Like xterm, sending input to the app returns to the live screen
+            self.scroll_offset = 0;
             Some(input_data)
         }
     }
 }
 
-/// Convert egui key to terminal escape sequence
-fn key_to_terminal_sequence(key: egui::Key, modifiers: &egui::Modifiers) -> Vec<u8> {
-    match key {
-        egui::Key::Enter => vec![b'\r'],
-        egui::Key::Backspace => vec![b'\x7f'],
-        egui::Key::Tab => vec![b'\t'],
-        egui::Key::Escape => vec![b'\x1b'],
-        egui::Key::ArrowUp => vec![b'\x1b', b'[', b'A'],
-        egui::Key::ArrowDown => vec![b'\x1b', b'[', b'B'],
-        egui::Key::ArrowRight => vec![b'\x1b', b'[', b'C'],
-        egui::Key::ArrowLeft => vec![b'\x1b', b'[', b'D'],
-        egui::Key::Home => vec![b'\x1b', b'[', b'H'],
-        egui::Key::End => vec![b'\x1b', b'[', b'F'],
-        egui::Key::PageUp => vec![b'\x1b', b'[', b'5', b'~'],
-        egui::Key::PageDown => vec![b'\x1b', b'[', b'6', b'~'],
-        egui::Key::Delete => vec![b'\x1b', b'[', b'3', b'~'],
-        egui::Key::Insert => vec![b'\x1b', b'[', b'2', b'~'],
-        // Function keys
-        egui::Key::F1 => vec![b'\x1b', b'O', b'P'],
-        egui::Key::F2 => vec![b'\x1b', b'O', b'Q'],
-        egui::Key::F3 => vec![b'\x1b', b'O', b'R'],
-        egui::Key::F4 => vec![b'\x1b', b'O', b'S'],
-        egui::Key::F5 => vec![b'\x1b', b'[', b'1', b'5', b'~'],
-        egui::Key::F6 => vec![b'\x1b', b'[', b'1', b'7', b'~'],
-        egui::Key::F7 => vec![b'\x1b', b'[', b'1', b'8', b'~'],
-        egui::Key::F8 => vec![b'\x1b', b'[', b'1', b'9', b'~'],
-        egui::Key::F9 => vec![b'\x1b', b'[', b'2', b'0', b'~'],
-        egui::Key::F10 => vec![b'\x1b', b'[', b'2', b'1', b'~'],
-        egui::Key::F11 => vec![b'\x1b', b'[', b'2', b'3', b'~'],
-        egui::Key::F12 => vec![b'\x1b', b'[', b'2', b'4', b'~'],
-        // Ctrl key combinations
-        egui::Key::A if modifiers.ctrl => vec![b'\x01'],
-        egui::Key::B if modifiers.ctrl => vec![b'\x02'],
-        egui::Key::C if modifiers.ctrl => vec![b'\x03'],
-        egui::Key::E if modifiers.ctrl => vec![b'\x05'],
-        egui::Key::F if modifiers.ctrl => vec![b'\x06'],
-        egui::Key::G if modifiers.ctrl => vec![b'\x07'],
-        egui::Key::H if modifiers.ctrl => vec![b'\x08'],
-        egui::Key::K if modifiers.ctrl => vec![b'\x0b'],
-        egui::Key::L if modifiers.ctrl => vec![b'\x0c'],
-        egui::Key::N if modifiers.ctrl => vec![b'\x0e'],
-        egui::Key::O if modifiers.ctrl => vec![b'\x0f'],
-        egui::Key::P if modifiers.ctrl => vec![b'\x10'],
-        egui::Key::R if modifiers.ctrl => vec![b'\x12'],
-        egui::Key::S if modifiers.ctrl => vec![b'\x13'],
-        egui::Key::T if modifiers.ctrl => vec![b'\x14'],
-        egui::Key::U if modifiers.ctrl => vec![b'\x15'],
-        egui::Key::V if modifiers.ctrl => vec![b'\x16'],
-        egui::Key::W if modifiers.ctrl => vec![b'\x17'],
-        egui::Key::X if modifiers.ctrl => vec![b'\x18'],
-        egui::Key::Y if modifiers.ctrl => vec![b'\x19'],
-        egui::Key::Z if modifiers.ctrl => vec![b'\x1a'],
-        _ => Vec::new(),
+impl TuiOverlay {
+    /// Whole lines of wheel movement (positive towards the top), carrying the
+    /// fractional part over to the next event
+    fn wheel_lines(&mut self, unit: egui::MouseWheelUnit, delta: egui::Vec2) -> isize {
+        let line_height = self.cell_area.map_or(1.0, |(_, size)| size.y).max(1.0);
+        self.wheel_remainder += match unit {
+            egui::MouseWheelUnit::Line => delta.y,
+            egui::MouseWheelUnit::Point => delta.y / line_height,
+            egui::MouseWheelUnit::Page => delta.y * self.grid.rows() as f32,
+        };
+        let lines = self.wheel_remainder.trunc();
+        self.wheel_remainder -= lines;
+        lines as isize
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
 
-    fn render_text(buffer: &ScreenBuffer) -> String {
-        buffer
-            .grid
-            .iter()
-            .map(|row| {
-                row.iter()
-                    .map(|c| c.ch)
-                    .collect::<String>()
-                    .trim_end()
-                    .to_string()
-            })
-            .collect::<Vec<_>>()
-            .join("\n")
-    }
+    /// Grid cell under `pos`. Positions outside the screen give `None` unless
+    /// `clamp` is set, in which case they snap to the nearest edge cell.
+    fn cell_at(&self, pos: egui::Pos2, clamp: bool) -> Option<(usize, usize)> {
+        let (rect, cell) = self.cell_area?;
+        if !clamp && !rect.contains(pos) {
+            return None;
+        }
+        let offset = pos - rect.min;
+        let index = |offset: f32, size: f32, count: usize| {
+            ((offset / size).floor().max(0.0) as usize).min(count.saturating_sub(1))
+        };
+        Some((
+            index(offset.y, cell.y, self.grid.rows()),
+            index(offset.x, cell.x, self.grid.cols()),
+        ))
+    }
+
+    /// Target of the OSC 8 link under `pos`, if any
+    fn link_at(&self, pos: egui::Pos2) -> Option<std::sync::Arc<str>> {
+        let (row, col) = self.cell_at(pos, false)?;
+        let offset = self.scroll_offset.min(self.grid.scrollback_len());
+        self.grid.link_at(offset, row, col)
+    }
+
+    /// Translate one egui pointer event into mouse reports for the app
+    fn encode_pointer_event(
+        &mut self,
+        event: &egui::Event,
+        hover_pos: Option<egui::Pos2>,
+        current_modifiers: egui::Modifiers,
+        out: &mut Vec<u8>,
+    ) {
+        let protocol = self.grid.mouse_protocol();
+        let mut send = |kind, button, (row, col), modifiers: egui::Modifiers| {
+            let event = MouseEvent {
+                kind,
+                button,
+                row,
+                col,
+                modifiers: MouseModifiers {
+                    shift: modifiers.shift,
+                    alt: modifiers.alt,
+                    ctrl: modifiers.ctrl,
+                },
+            };
+            if let Some(bytes) = protocol.encode(&event) {
+                out.extend_from_slice(&bytes);
+            }
+        };
 
-    #[test]
-    fn test_screen_buffer_new() {
-        let buffer = ScreenBuffer::new(10, 20);
-        assert_eq!(buffer.rows, 10);
-        assert_eq!(buffer.cols, 20);
-        assert_eq!(buffer.cursor_row, 0);
-        assert_eq!(buffer.cursor_col, 0);
+        match event {
+            egui::Event::PointerButton {
+                pos,
+                button,
+                pressed,
+                modifiers,
+            } => {
+                let button = match button {
+                    egui::PointerButton::Primary => MouseButton::Left,
+                    egui::PointerButton::Middle => MouseButton::Middle,
+                    egui::PointerButton::Secondary => MouseButton::Right,
+                    _ => return,
+                };
+                // Ctrl/Cmd+Click on a link opens it instead
+                if *pressed && modifiers.command && self.link_at(*pos).is_some() {
+                    return;
+                }
+                if *pressed {
+                    if let Some(cell) = self.cell_at(*pos, false) {
+                        self.mouse_button = Some(button);
+                        self.last_mouse_cell = Some(cell);
+                        send(MouseEventKind::Press, button, cell, *modifiers);
+                    }
+                } else if self.mouse_button == Some(button) {
+                    self.mouse_button = None;
+                    if let Some(cell) = self.cell_at(*pos, true) {
+                        send(MouseEventKind::Release, button, cell, *modifiers);
+                    }
+                }
+            }
+            egui::Event::PointerMoved(pos) => {
+                // Drags keep reporting (clamped) when the pointer leaves the screen
+                let Some(cell) = self.cell_at(*pos, self.mouse_button.is_some()) else {
+                    return;
+                };
+                if self.last_mouse_cell != Some(cell) {
+                    self.last_mouse_cell = Some(cell);
+                    let button = self.mouse_button.unwrap_or(MouseButton::None);
+                    send(MouseEventKind::Motion, button, cell, current_modifiers);
+                }
+            }
+            egui::Event::MouseWheel {
+                unit,
+                delta,
+                modifiers,
+                ..
+            } => {
+                let Some(cell) = hover_pos.and_then(|pos| self.cell_at(pos, false)) else {
+                    return;
+                };
+                // Positive deltas move content down, i.e. scroll towards the top
+                let lines = self.wheel_lines(*unit, *delta);
+                let button = if lines > 0 {
+                    MouseButton::WheelUp
+                } else {
+                    MouseButton::WheelDown
+                };
+                for _ in 0..lines.unsigned_abs() {
+                    send(MouseEventKind::Press, button, cell, *modifiers);
+                }
+            }
+            _ => {}
+        }
     }
+}
 
-    #[test]
-    fn test_screen_buffer_clear() {
-        let mut buffer = ScreenBuffer::new(5, 10);
-        buffer.write_char('a');
-        buffer.clear();
-        assert_eq!(buffer.cursor_row, 0);
-        assert_eq!(buffer.cursor_col, 0);
-        let rendered = render_text(&buffer);
-        assert!(rendered.trim().is_empty() || rendered.chars().all(|c| c == ' ' || c == '\n'));
-    }
+/// Bytes sent to the PTY for pasted text.
+///
+/// Line breaks become carriage returns, as typed Enter would. With bracketed
+/// paste (mode 2004) the text is wrapped in `CSI 200~` / `CSI 201~` so the app
+/// can tell it from typing; an embedded end marker is removed so pasted text
+/// cannot break out early.
+fn paste_sequence(text: &str, bracketed: bool) -> Vec<u8> {
+    let text = text.replace("\r\n", "\r").replace('\n', "\r");
+    if !bracketed {
+        return text.into_bytes();
+    }
+    let mut out = b"\x1b[200~".to_vec();
+    out.extend_from_slice(text.replace("\x1b[201~", "").as_bytes());
+    out.extend_from_slice(b"\x1b[201~");
+    out
+}
 
-    #[test]
-    fn test_screen_buffer_write_char() {
-        let mut buffer = ScreenBuffer::new(5, 10);
-        buffer.write_char('a');
-        assert_eq!(buffer.cursor_col, 1);
-        buffer.write_char('b');
-        assert_eq!(buffer.cursor_col, 2);
-    }
+/// Convert egui key to terminal escape sequence.
+///
+/// Cursor keys follow DECCKM, and modified cursor, editing and function keys
+/// use xterm's `CSI 1 ; mods X` / `CSI n ; mods ~` forms. Printable keys are
+/// normally sent through text events; they are encoded here only when Ctrl
+/// is held, or as `CSI 27 ; mods ; code ~` when modifyOtherKeys asks for it.
+fn key_to_terminal_sequence(
+    key: egui::Key,
+    modifiers: &egui::Modifiers,
+    modes: &KeyboardModes,
+) -> Vec<u8> {
+    let mods = modifier_param(modifiers.shift, modifiers.alt, modifiers.ctrl);
+    // Cursor keys and Home/End: CSI or (in application mode) SS3
+    let cursor = |last: char| {
+        if mods > 1 {
+            format!("\x1b[1;{}{}", mods, last).into_bytes()
+        } else if modes.application_cursor {
+            format!("\x1bO{}", last).into_bytes()
+        } else {
+            format!("\x1b[{}", last).into_bytes()
+        }
+    };
+    // F1-F4 are SS3 keys unless a modifier is held
+    let ss3 = |last: char| {
+        if mods > 1 {
+            format!("\x1b[1;{}{}", mods, last).into_bytes()
+        } else {
+            format!("\x1bO{}", last).into_bytes()
+        }
+    };
+    let tilde = |code: u8| {
+        if mods > 1 {
+            format!("\x1b[{};{}~", code, mods).into_bytes()
+        } else {
+            format!("\x1b[{}~", code).into_bytes()
+        }
+    };
 
-    #[test]
-    fn test_screen_buffer_newline() {
-        let mut buffer = ScreenBuffer::new(5, 10);
-        buffer.write_char('\n');
-        assert_eq!(buffer.cursor_row, 1);
-        assert_eq!(buffer.cursor_col, 0);
+    match key {
+        egui::Key::ArrowUp => cursor('A'),
+        egui::Key::ArrowDown => cursor('B'),
+        egui::Key::ArrowRight => cursor('C'),
+        egui::Key::ArrowLeft => cursor('D'),
+        egui::Key::Home => cursor('H'),
+        egui::Key::End => cursor('F'),
+        egui::Key::Insert => tilde(2),
+        egui::Key::Delete => tilde(3),
+        egui::Key::PageUp => tilde(5),
+        egui::Key::PageDown => tilde(6),
+        // Function keys
+        egui::Key::F1 => ss3('P'),
+        egui::Key::F2 => ss3('Q'),
+        egui::Key::F3 => ss3('R'),
+        egui::Key::F4 => ss3('S'),
+        egui::Key::F5 => tilde(15),
+        egui::Key::F6 => tilde(17),
+        egui::Key::F7 => tilde(18),
+        egui::Key::F8 => tilde(19),
+        egui::Key::F9 => tilde(20),
+        egui::Key::F10 => tilde(21),
+        egui::Key::F11 => tilde(23),
+        egui::Key::F12 => tilde(24),
+        _ => other_key_sequence(key, modifiers, mods, modes.modify_other_keys),
     }
+}
 
-    #[test]
-    fn test_screen_buffer_carriage_return() {
-        let mut buffer = ScreenBuffer::new(5, 10);
-        buffer.write_char('a');
-        buffer.write_char('\r');
-        assert_eq!(buffer.cursor_col, 0);
-    }
+/// Encode Enter, Tab, Backspace, Escape and printable keys
+fn other_key_sequence(key: egui::Key, modifiers: &egui::Modifiers, mods: u8, level: u8) -> Vec<u8> {
+    let Some(code) = key_code(key, modifiers.shift) else {
+        return Vec::new();
+    };
+    let printable = code >= 0x20 && code != 0x7f;
+
+    // Legacy encoding, and whether it loses one of the held modifiers
+    let (legacy, ambiguous) = match code {
+        b'\r' => (vec![b'\r'], modifiers.shift || modifiers.ctrl),
+        b'\t' if modifiers.shift => (b"\x1b[Z".to_vec(), modifiers.ctrl),
+        b'\t' => (vec![b'\t'], modifiers.ctrl),
+        0x7f if modifiers.ctrl => (vec![0x08], modifiers.shift),
+        0x7f => (vec![0x7f], modifiers.shift),
+        0x1b => (vec![0x1b], mods > 1),
+        _ if modifiers.ctrl => match control_code(code) {
+            Some(byte) => (vec![byte], modifiers.shift && code.is_ascii_alphabetic()),
+            None => (Vec::new(), true),
+        },
+        // Printable without Ctrl: typed through text events
+        _ => (Vec::new(), false),
+    };
+
+    // Printable keys without Ctrl also produce a text event; leave those to it
+    let has_text = printable && !modifiers.ctrl;
+    let extended = mods > 1 && !has_text && (level == 2 || (level == 1 && ambiguous));
+    if extended {
+        return format!("\x1b[27;{};{}~", mods, code).into_bytes();
+    }
+    if legacy.is_empty() {
+        return legacy;
+    }
+    if modifiers.alt && code != 0x1b {
+        // Alt sends ESC before the key, like xterm's metaSendsEscape
+        let mut out = vec![0x1b];
+        out.extend_from_slice(&legacy);
+        return out;
+    }
+    legacy
+}
 
-    #[test]
-    fn test_screen_buffer_tab() {
-        let mut buffer = ScreenBuffer::new(5, 20);
-        buffer.write_char('\t');
-        assert_eq!(buffer.cursor_col, 8);
-    }
+/// Character code of a key for the `CSI 27` form (letters follow Shift)
+fn key_code(key: egui::Key, shift: bool) -> Option<u8> {
+    let name = key.name();
+    let code = match key {
+        egui::Key::Enter => b'\r',
+        egui::Key::Tab => b'\t',
+        egui::Key::Backspace => 0x7f,
+        egui::Key::Escape => 0x1b,
+        egui::Key::Space => b' ',
+        egui::Key::OpenBracket => b'[',
+        egui::Key::CloseBracket => b']',
+        egui::Key::Backslash => b'\\',
+        egui::Key::Slash => b'/',
+        egui::Key::Minus => b'-',
+        egui::Key::Equals => b'=',
+        egui::Key::Comma => b',',
+        egui::Key::Period => b'.',
+        egui::Key::Semicolon => b';',
+        egui::Key::Quote => b'\'',
+        egui::Key::Backtick => b'`',
+        _ if name.len() == 1 && name.as_bytes()[0].is_ascii_alphanumeric() => {
+            let byte = name.as_bytes()[0];
+            if shift {
+                byte.to_ascii_uppercase()
+            } else {
+                byte.to_ascii_lowercase()
+            }
+        }
+        _ => return None,
+    };
+    Some(code)
+}
 
-    #[test]
-    fn test_screen_buffer_wrap() {
-        let mut buffer = ScreenBuffer::new(5, 3);
-        buffer.write_char('a');
-        buffer.write_char('b');
-        buffer.write_char('c');
-        buffer.write_char('d');
-        assert_eq!(buffer.cursor_row, 1);
-        assert_eq!(buffer.cursor_col, 1);
-    }
+/// Whether a printable key pressed with `modifiers` types text (and so
+/// arrives as a text event) rather than a shortcut
+fn types_text(modifiers: &egui::Modifiers) -> bool {
+    // Option composes characters on macOS; elsewhere Alt makes a shortcut
+    let alt_shortcut = modifiers.alt && !cfg!(target_os = "macos");
+    !(modifiers.ctrl || modifiers.mac_cmd || alt_shortcut)
+}
 
-    #[test]
-    fn test_screen_buffer_move_cursor() {
-        let mut buffer = ScreenBuffer::new(10, 20);
-        buffer.move_cursor(5, 10);
-        assert_eq!(buffer.cursor_row, 4);
-        assert_eq!(buffer.cursor_col, 9);
-    }
+/// Press, repeat or release, as the kitty protocol reports them
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum KeyEventKind {
+    Press = 1,
+    Repeat = 2,
+    Release = 3,
+}
 
-    #[test]
-    fn test_screen_buffer_render_text() {
-        let mut buffer = ScreenBuffer::new(5, 10);
-        for ch in "Hello".chars() {
-            buffer.write_char(ch);
+/// Encode a key event with the kitty keyboard protocol, given the flags the
+/// application pushed (`CSI > flags u`).
+///
+/// Reports take the form `CSI number[:shifted] ; mods[:event] [; text] final`.
+/// Text keys without Ctrl/Alt are left to text events unless every key is
+/// reported, and plain Enter, Tab and Backspace keep their legacy bytes so a
+/// shell stays usable after a crashed app leaves the flags set.
+fn kitty_key_sequence(
+    key: egui::Key,
+    modifiers: &egui::Modifiers,
+    kind: KeyEventKind,
+    flags: u8,
+) -> Vec<u8> {
+    let report_all = flags & KITTY_REPORT_ALL_KEYS != 0;
+    if kind != KeyEventKind::Press && flags & KITTY_REPORT_EVENT_TYPES == 0 {
+        return Vec::new();
+    }
+    let mods = modifier_param(modifiers.shift, modifiers.alt, modifiers.ctrl)
+        + 8 * u8::from(modifiers.mac_cmd);
+
+    let mut shifted = None;
+    let mut text = None;
+    let (number, last) = match key {
+        egui::Key::ArrowUp => (1, 'A'),
+        egui::Key::ArrowDown => (1, 'B'),
+        egui::Key::ArrowRight => (1, 'C'),
+        egui::Key::ArrowLeft => (1, 'D'),
+        egui::Key::Home => (1, 'H'),
+        egui::Key::End => (1, 'F'),
+        egui::Key::Insert => (2, '~'),
+        egui::Key::Delete => (3, '~'),
+        egui::Key::PageUp => (5, '~'),
+        egui::Key::PageDown => (6, '~'),
+        egui::Key::F1 => (1, 'P'),
+        egui::Key::F2 => (1, 'Q'),
+        // `CSI R` would read as a cursor position report
+        egui::Key::F3 => (13, '~'),
+        egui::Key::F4 => (1, 'S'),
+        egui::Key::F5 => (15, '~'),
+        egui::Key::F6 => (17, '~'),
+        egui::Key::F7 => (18, '~'),
+        egui::Key::F8 => (19, '~'),
+        egui::Key::F9 => (20, '~'),
+        egui::Key::F10 => (21, '~'),
+        egui::Key::F11 => (23, '~'),
+        egui::Key::F12 => (24, '~'),
+        _ => {
+            let Some(code) = key_code(key, false) else {
+                return Vec::new();
+            };
+            let printable = code >= 0x20 && code != 0x7f;
+            if !report_all {
+                if printable && types_text(modifiers) {
+                    return Vec::new();
+                }
+                let legacy = (matches!(code, b'\r' | b'\t' | 0x7f) && mods == 1)
+                    || (code == 0x1b && flags & KITTY_DISAMBIGUATE == 0);
+                if legacy {
+                    return match kind {
+                        KeyEventKind::Release => Vec::new(),
+                        _ => vec![code],
+                    };
+                }
+            }
+            if printable && modifiers.shift {
+                shifted = key_code(key, true).filter(|&upper| upper != code);
+            }
+            if printable && types_text(modifiers) && kind != KeyEventKind::Release {
+                text = Some(shifted.unwrap_or(code));
+            }
+            (u32::from(code), 'u')
         }
-        buffer.write_char('\n');
-        for ch in "World".chars() {
-            buffer.write_char(ch);
+    };
+
+    let mut out = String::from("\x1b[");
+    let with_event = kind != KeyEventKind::Press;
+    let with_text = text.is_some() && flags & KITTY_REPORT_TEXT != 0;
+    let with_mods = mods > 1 || with_event || with_text;
+    if number != 1 || last == 'u' || with_mods {
+        out.push_str(&number.to_string());
+    }
+    if let Some(upper) = shifted.filter(|_| flags & KITTY_REPORT_ALTERNATE_KEYS != 0) {
+        out.push_str(&format!(":{}", upper));
+    }
+    if with_mods {
+        out.push_str(&format!(";{}", mods));
+        if with_event {
+            out.push_str(&format!(":{}", kind as u8));
         }
-        let rendered = render_text(&buffer);
-        assert!(rendered.contains("Hello"));
-        assert!(rendered.contains("World"));
     }
+    if let Some(text) = text.filter(|_| with_text) {
+        out.push_str(&format!(";{}", text));
+    }
+    out.push(last);
+    out.into_bytes()
+}
 
-    #[test]
-    fn test_screen_buffer_resize() {
-        let mut buffer = ScreenBuffer::new(5, 10);
-        buffer.write_char('X');
-        buffer.resize(10, 20);
-        assert_eq!(buffer.rows, 10);
-        assert_eq!(buffer.cols, 20);
-        assert_eq!(buffer.grid[0][0].ch, 'X');
+/// C0 control sent for Ctrl plus a printable character, where one exists
+fn control_code(code: u8) -> Option<u8> {
+    match code.to_ascii_lowercase() {
+        b'a'..=b'z' | b'[' | b'\\' | b']' => Some(code & 0x1f),
+        b' ' | b'2' => Some(0x00),
+        b'3'..=b'7' => Some(code - b'3' + 0x1b),
+        b'/' => Some(0x1f),
+        b'8' => Some(0x7f),
+        _ => None,
     }
+}
 
-    #[test]
-    fn test_clear_modes() {
-        let mut buffer = ScreenBuffer::new(5, 10);
-        for ch in "Hello".chars() {
-            buffer.write_char(ch);
-        }
-        buffer.move_cursor(1, 3);
-        buffer.clear_line_from_cursor();
-        assert_eq!(buffer.grid[0][0].ch, 'H');
-        assert_eq!(buffer.grid[0][1].ch, 'e');
-        assert_eq!(buffer.grid[0][4].ch, ' ');
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terminal::grid::{Color, Rgb};
+
+    fn render_text(grid: &TerminalGrid) -> String {
+        (0..grid.rows())
+            .map(|row| grid.row_text(row).trim_end().to_string())
+            .collect::<Vec<_>>()
+            .join("\n")
     }
 
-    #[test]
-    fn test_scroll_region() {
-        let mut buffer = ScreenBuffer::new(5, 10);
-        buffer.scroll_top = 1;
-        buffer.scroll_bottom = 3;
-        for ch in "Line0".chars() {
-            buffer.write_char(ch);
-        }
-        buffer.move_cursor(2, 1);
-        for ch in "Line1".chars() {
-            buffer.write_char(ch);
-        }
-        buffer.scroll_up(1);
-        assert_eq!(buffer.grid[0][0].ch, 'L');
+    fn overlay_with(input: &str) -> TuiOverlay {
+        let mut overlay = TuiOverlay::new();
+        overlay.add_raw_output(input.as_bytes());
+        overlay
     }
 
     #[test]
     fn test_sgr_colors() {
-        let mut overlay = TuiOverlay::new();
-        overlay.process_ansi_text("\x1b[31mRed\x1b[0mNormal");
-        assert_eq!(overlay.screen_buffer.grid[0][0].style.fg, ANSI_COLORS[1]);
-        assert_eq!(overlay.screen_buffer.grid[0][3].style.fg, DEFAULT_FG);
+        let overlay = overlay_with("\x1b[31mRed\x1b[0mNormal");
+        assert_eq!(overlay.grid.cell(0, 0).unwrap().style.fg, Color::Indexed(1));
+        assert_eq!(overlay.grid.cell(0, 3).unwrap().style.fg, Color::Default);
     }
 
     #[test]
     fn test_sgr_bold_reverse() {
-        let mut overlay = TuiOverlay::new();
-        overlay.process_ansi_text("\x1b[1;7mBR\x1b[0m");
-        assert!(overlay.screen_buffer.grid[0][0].style.bold);
-        assert!(overlay.screen_buffer.grid[0][0].style.reverse);
+        let overlay = overlay_with("\x1b[1;7mBR\x1b[0m");
+        assert!(overlay.grid.cell(0, 0).unwrap().style.bold);
+        assert!(overlay.grid.cell(0, 0).unwrap().style.reverse);
     }
 
     #[test]
     fn test_cursor_movement_csi() {
-        let mut overlay = TuiOverlay::new();
-        overlay.process_ansi_text("\x1b[5;10Hx");
-        assert_eq!(overlay.screen_buffer.grid[4][9].ch, 'x');
+        let mut overlay = overlay_with("\x1b[5;10Hx");
+        assert_eq!(overlay.grid.cell(4, 9).unwrap().ch, 'x');
 
-        overlay.process_ansi_text("\x1b[2Ay");
-        assert_eq!(overlay.screen_buffer.cursor_row, 2);
+        overlay.add_raw_output(b"\x1b[2Ay");
+        assert_eq!(overlay.grid.cursor_position().0, 2);
     }
 
     #[test]
     fn test_erase_in_display_modes() {
-        let mut overlay = TuiOverlay::new();
-        overlay.process_ansi_text("ABCDE\x1b[1;3H\x1b[0J");
-        assert_eq!(overlay.screen_buffer.grid[0][0].ch, 'A');
-        assert_eq!(overlay.screen_buffer.grid[0][1].ch, 'B');
-        assert_eq!(overlay.screen_buffer.grid[0][4].ch, ' ');
+        let overlay = overlay_with("ABCDE\x1b[1;3H\x1b[0J");
+        assert_eq!(overlay.grid.cell(0, 0).unwrap().ch, 'A');
+        assert_eq!(overlay.grid.cell(0, 1).unwrap().ch, 'B');
+        assert_eq!(overlay.grid.cell(0, 4).unwrap().ch, ' ');
     }
 
     #[test]
     fn test_erase_in_line_modes() {
-        let mut overlay = TuiOverlay::new();
-        overlay.process_ansi_text("ABCDE\x1b[1;3H\x1b[1K");
-        assert_eq!(overlay.screen_buffer.grid[0][0].ch, ' ');
-        assert_eq!(overlay.screen_buffer.grid[0][1].ch, ' ');
-        assert_eq!(overlay.screen_buffer.grid[0][2].ch, ' ');
-        assert_eq!(overlay.screen_buffer.grid[0][3].ch, 'D');
+        let overlay = overlay_with("ABCDE\x1b[1;3H\x1b[1K");
+        assert_eq!(overlay.grid.cell(0, 0).unwrap().ch, ' ');
+        assert_eq!(overlay.grid.cell(0, 1).unwrap().ch, ' ');
+        assert_eq!(overlay.grid.cell(0, 2).unwrap().ch, ' ');
+        assert_eq!(overlay.grid.cell(0, 3).unwrap().ch, 'D');
     }
 
     #[test]
     fn test_insert_delete_lines() {
-        let mut overlay = TuiOverlay::new();
-        overlay.process_ansi_text("AAA\nBBB\nCCC");
-        overlay.screen_buffer.cursor_row = 1;
-        overlay.screen_buffer.insert_lines(1);
-        assert_eq!(overlay.screen_buffer.grid[1][0].ch, ' ');
-        assert_eq!(overlay.screen_buffer.grid[2][0].ch, 'B');
+        let overlay = overlay_with("AAA\r\nBBB\r\nCCC\x1b[2H\x1b[L");
+        assert_eq!(overlay.grid.cell(1, 0).unwrap().ch, ' ');
+        assert_eq!(overlay.grid.cell(2, 0).unwrap().ch, 'B');
     }
 
     #[test]
@@ -1434,6 +1367,14 @@ mod tests {
         assert!(!overlay.has_exited());
     }
 
+    #[test]
+    fn test_tui_overlay_start_clears_screen() {
+        let mut overlay = overlay_with("\x1b[31mstale");
+        overlay.start("vim".to_string(), "pty-123".to_string());
+        assert!(render_text(&overlay.grid).trim().is_empty());
+        assert_eq!(overlay.grid.cursor_position(), (0, 0));
+    }
+
     #[test]
     fn test_tui_overlay_mark_exited() {
         let mut overlay = TuiOverlay::new();
@@ -1445,69 +1386,491 @@ mod tests {
 
     #[test]
     fn test_tui_overlay_add_raw_output() {
-        let mut overlay = TuiOverlay::new();
-        overlay.add_raw_output(b"Hello\nWorld");
+        let overlay = overlay_with("Hello\r\nWorld");
+        let rendered = render_text(&overlay.grid);
+        assert!(rendered.starts_with("Hello\nWorld"));
     }
 
     #[test]
     fn test_tui_overlay_process_ansi_clear() {
-        let mut overlay = TuiOverlay::new();
-        overlay.process_ansi_text("Hello\x1b[2JWorld");
-        let rendered = render_text(&overlay.screen_buffer);
+        let overlay = overlay_with("Hello\x1b[2JWorld");
+        let rendered = render_text(&overlay.grid);
         assert!(rendered.contains("World"));
         assert!(!rendered.contains("Hello"));
     }
 
     #[test]
     fn test_tui_overlay_ansi_cursor_position() {
-        let mut overlay = TuiOverlay::new();
-        overlay.process_ansi_text("\x1b[5;10HTest");
-        let rendered = render_text(&overlay.screen_buffer);
+        let overlay = overlay_with("\x1b[5;10HTest");
+        let rendered = render_text(&overlay.grid);
         assert!(rendered.contains("Test"));
     }
 
     #[test]
     fn test_tui_overlay_256_color() {
-        let mut overlay = TuiOverlay::new();
-        overlay.process_ansi_text("\x1b[38;5;196mRed256\x1b[0m");
-        let expected = AnsiColor::from_256(196);
-        assert_eq!(overlay.screen_buffer.grid[0][0].style.fg, expected);
+        let overlay = overlay_with("\x1b[38;5;196mRed256\x1b[0m");
+        let fg = overlay.grid.cell(0, 0).unwrap().style.fg;
+        assert_eq!(fg, Color::Indexed(196));
+        assert_eq!(overlay.grid.palette().resolve_fg(fg), Rgb::new(255, 0, 0));
     }
 
     #[test]
     fn test_tui_overlay_truecolor() {
-        let mut overlay = TuiOverlay::new();
-        overlay.process_ansi_text("\x1b[38;2;100;150;200mTC\x1b[0m");
+        let overlay = overlay_with("\x1b[38;2;100;150;200mTC\x1b[0m");
+        assert_eq!(
+            overlay.grid.cell(0, 0).unwrap().style.fg,
+            Color::Rgb(Rgb::new(100, 150, 200))
+        );
+    }
+
+    #[test]
+    fn test_grid_layout_job_covers_full_screen() {
+        let mut grid = TerminalGrid::new(3, 4);
+        grid.advance(b"\x1b[31mab");
+        let (job, _) = grid_layout_job(
+            &grid.view(0),
+            &Palette::default(),
+            egui::FontId::monospace(13.0),
+            true,
+        );
+        assert_eq!(job.text, "ab  \n    \n    ");
+    }
+
+    #[test]
+    fn test_grid_layout_job_skips_wide_spacers() {
+        let mut grid = TerminalGrid::new(1, 6);
+        grid.advance("a中\x1b[31mb".as_bytes());
+        let (job, _) = grid_layout_job(
+            &grid.view(0),
+            &Palette::default(),
+            egui::FontId::monospace(13.0),
+            true,
+        );
+        assert_eq!(job.text, "a中b  ");
+    }
+
+    #[test]
+    fn test_grid_layout_job_text_attributes() {
+        let mut grid = TerminalGrid::new(1, 4);
+        grid.advance(b"\x1b[9ma\x1b[0;5mb\x1b[0;4;58;5;1mc");
+        let palette = Palette::default();
+        let (job, _) = grid_layout_job(
+            &grid.view(0),
+            &palette,
+            egui::FontId::monospace(13.0),
+            false,
+        );
+        let formats: Vec<_> = job.sections.iter().map(|s| &s.format).collect();
+        assert_ne!(formats[0].strikethrough, egui::Stroke::NONE);
+        assert_eq!(formats[1].color, egui::Color32::TRANSPARENT);
         assert_eq!(
-            overlay.screen_buffer.grid[0][0].style.fg,
-            AnsiColor::rgb(100, 150, 200)
+            formats[2].underline,
+            egui::Stroke::new(1.0, to_color32(palette.indexed(1)))
         );
     }
 
+    #[test]
+    fn test_scroll_view_through_scrollback() {
+        let mut overlay = TuiOverlay::new();
+        overlay.grid.resize(2, 10);
+        overlay.add_raw_output(b"1\r\n2\r\n3\r\n4");
+        assert_eq!(overlay.grid.scrollback_len(), 2);
+
+        overlay.scroll_view(1);
+        assert_eq!(overlay.scroll_offset(), 1);
+        // New output keeps the browsed lines in view
+        overlay.add_raw_output(b"\r\n5");
+        assert_eq!(overlay.scroll_offset(), 2);
+        overlay.scroll_view(10);
+        assert_eq!(overlay.scroll_offset(), 3);
+        overlay.scroll_view(-10);
+        assert_eq!(overlay.scroll_offset(), 0);
+
+        overlay.set_scrollback_limit(1);
+        overlay.scroll_view(5);
+        assert_eq!(overlay.scroll_offset(), 1);
+        overlay.stop();
+        assert_eq!(overlay.scroll_offset(), 0);
+        assert_eq!(overlay.grid.scrollback_len(), 0);
+    }
+
     #[test]
     fn test_key_to_terminal_sequence() {
         assert_eq!(
-            key_to_terminal_sequence(egui::Key::Enter, &egui::Modifiers::default()),
+            key_to_terminal_sequence(
+                egui::Key::Enter,
+                &egui::Modifiers::default(),
+                &KeyboardModes::default()
+            ),
             vec![b'\r']
         );
         assert_eq!(
-            key_to_terminal_sequence(egui::Key::Backspace, &egui::Modifiers::default()),
+            key_to_terminal_sequence(
+                egui::Key::Backspace,
+                &egui::Modifiers::default(),
+                &KeyboardModes::default()
+            ),
             vec![b'\x7f']
         );
         assert_eq!(
-            key_to_terminal_sequence(egui::Key::Tab, &egui::Modifiers::default()),
+            key_to_terminal_sequence(
+                egui::Key::Tab,
+                &egui::Modifiers::default(),
+                &KeyboardModes::default()
+            ),
             vec![b'\t']
         );
         assert_eq!(
-            key_to_terminal_sequence(egui::Key::Escape, &egui::Modifiers::default()),
+            key_to_terminal_sequence(
+                egui::Key::Escape,
+                &egui::Modifiers::default(),
+                &KeyboardModes::default()
+            ),
             vec![b'\x1b']
         );
         assert_eq!(
-            key_to_terminal_sequence(egui::Key::ArrowUp, &egui::Modifiers::default()),
+            key_to_terminal_sequence(
+                egui::Key::ArrowUp,
+                &egui::Modifiers::default(),
+                &KeyboardModes::default()
+            ),
             vec![b'\x1b', b'[', b'A']
         );
     }
 
+    fn key_bytes(key: egui::Key, modifiers: egui::Modifiers, modes: KeyboardModes) -> String {
+        String::from_utf8(key_to_terminal_sequence(key, &modifiers, &modes)).unwrap()
+    }
+
+    #[test]
+    fn test_cursor_shapes() {
+        let cell = egui::Rect::from_min_size(egui::pos2(10.0, 20.0), egui::vec2(8.0, 16.0));
+        assert_eq!(cursor_rect(cell, CursorShape::Block), cell);
+
+        let underline = cursor_rect(cell, CursorShape::Underline);
+        assert_eq!(underline.max, cell.max);
+        assert_eq!(underline.width(), cell.width());
+        assert!(underline.height() < cell.height());
+
+        let bar = cursor_rect(cell, CursorShape::Bar);
+        assert_eq!(bar.min, cell.min);
+        assert_eq!(bar.height(), cell.height());
+        assert!(bar.width() < cell.width());
+    }
+
+    #[test]
+    fn test_cursor_blink_phase() {
+        assert!(cursor_blink_on(0.0));
+        assert!(!cursor_blink_on(CURSOR_BLINK_INTERVAL + 0.01));
+        assert!(cursor_blink_on(2.0 * CURSOR_BLINK_INTERVAL + 0.01));
+    }
+
+    #[test]
+    fn test_focus_events() {
+        let mut overlay = TuiOverlay::new();
+        overlay.start("vim".to_string(), "pty-1".to_string());
+        assert_eq!(overlay.set_focused(false), None);
+        assert_eq!(overlay.set_focused(true), None);
+
+        overlay.add_raw_output(b"\x1b[?1004h");
+        assert_eq!(overlay.set_focused(true), None);
+        assert_eq!(overlay.set_focused(false), Some(b"\x1b[O".to_vec()));
+        assert_eq!(overlay.set_focused(false), None);
+        assert_eq!(overlay.set_focused(true), Some(b"\x1b[I".to_vec()));
+
+        overlay.stop();
+        assert_eq!(overlay.set_focused(false), None);
+    }
+
+    #[test]
+    fn test_synchronized_update_holds_repaints() {
+        let mut overlay = TuiOverlay::new();
+        let now = std::time::Instant::now();
+        assert_eq!(overlay.sync_hold_remaining(now), None);
+
+        overlay.add_raw_output(b"\x1b[?2026h\x1b[2Jpartial");
+        let started = overlay.sync_started.unwrap();
+        assert!(overlay.sync_hold_remaining(started).is_some());
+        assert_eq!(
+            overlay.sync_hold_remaining(started + SYNC_OUTPUT_TIMEOUT),
+            None
+        );
+
+        // More of the same update keeps the original start time
+        overlay.add_raw_output(b" redraw");
+        assert_eq!(overlay.sync_started, Some(started));
+
+        overlay.add_raw_output(b"\x1b[?2026l");
+        assert_eq!(overlay.sync_hold_remaining(std::time::Instant::now()), None);
+
+        // An update begun and finished within one chunk holds nothing
+        overlay.add_raw_output(b"\x1b[?2026hframe\x1b[?2026l");
+        assert_eq!(overlay.sync_started, None);
+    }
+
+    #[test]
+    fn test_default_cursor_style_follows_app() {
+        let mut overlay = TuiOverlay::new();
+        overlay.set_cursor_style(CursorStyle {
+            shape: CursorShape::Bar,
+            blinking: true,
+        });
+        assert_eq!(overlay.grid.cursor_style().shape, CursorShape::Bar);
+        overlay.add_raw_output(b"\x1b[2 q\x1b[?25l");
+        assert_eq!(overlay.grid.cursor_style().shape, CursorShape::Block);
+        assert!(!overlay.grid.cursor_style().blinking);
+        assert!(!overlay.grid.cursor_visible());
+    }
+
+    #[test]
+    fn test_application_cursor_keys() {
+        let app = KeyboardModes {
+            application_cursor: true,
+            ..KeyboardModes::default()
+        };
+        let none = egui::Modifiers::default();
+        assert_eq!(key_bytes(egui::Key::ArrowUp, none, app), "\x1bOA");
+        assert_eq!(key_bytes(egui::Key::Home, none, app), "\x1bOH");
+        assert_eq!(key_bytes(egui::Key::PageUp, none, app), "\x1b[5~");
+        assert_eq!(
+            key_bytes(egui::Key::ArrowLeft, egui::Modifiers::CTRL, app),
+            "\x1b[1;5D"
+        );
+    }
+
+    #[test]
+    fn test_modified_special_keys() {
+        let modes = KeyboardModes::default();
+        let ctrl_shift = egui::Modifiers::CTRL | egui::Modifiers::SHIFT;
+        assert_eq!(
+            key_bytes(egui::Key::ArrowRight, egui::Modifiers::CTRL, modes),
+            "\x1b[1;5C"
+        );
+        assert_eq!(
+            key_bytes(egui::Key::End, egui::Modifiers::SHIFT, modes),
+            "\x1b[1;2F"
+        );
+        assert_eq!(
+            key_bytes(egui::Key::F1, egui::Modifiers::ALT, modes),
+            "\x1b[1;3P"
+        );
+        assert_eq!(key_bytes(egui::Key::F5, ctrl_shift, modes), "\x1b[15;6~");
+        assert_eq!(
+            key_bytes(egui::Key::Delete, egui::Modifiers::ALT, modes),
+            "\x1b[3;3~"
+        );
+        assert_eq!(
+            key_bytes(egui::Key::Tab, egui::Modifiers::SHIFT, modes),
+            "\x1b[Z"
+        );
+        assert_eq!(
+            key_bytes(egui::Key::Backspace, egui::Modifiers::ALT, modes),
+            "\x1b\x7f"
+        );
+    }
+
+    #[test]
+    fn test_ctrl_and_alt_keys() {
+        let modes = KeyboardModes::default();
+        let ctrl = egui::Modifiers::CTRL;
+        assert_eq!(key_bytes(egui::Key::A, ctrl, modes), "\x01");
+        assert_eq!(key_bytes(egui::Key::D, ctrl, modes), "\x04");
+        assert_eq!(key_bytes(egui::Key::Q, ctrl, modes), "\x11");
+        assert_eq!(key_bytes(egui::Key::Space, ctrl, modes), "\x00");
+        assert_eq!(key_bytes(egui::Key::OpenBracket, ctrl, modes), "\x1b");
+        assert_eq!(
+            key_bytes(egui::Key::B, ctrl | egui::Modifiers::ALT, modes),
+            "\x1b\x02"
+        );
+        // Plain and Alt-only printable keys are typed through text events
+        assert_eq!(key_bytes(egui::Key::B, egui::Modifiers::ALT, modes), "");
+        assert_eq!(key_bytes(egui::Key::B, egui::Modifiers::NONE, modes), "");
+    }
+
+    #[test]
+    fn test_modify_other_keys() {
+        let level = |modify_other_keys| KeyboardModes {
+            modify_other_keys,
+            ..KeyboardModes::default()
+        };
+        let ctrl = egui::Modifiers::CTRL;
+        let ctrl_shift = ctrl | egui::Modifiers::SHIFT;
+
+        // Level 0 falls back to the legacy (lossy) forms
+        assert_eq!(key_bytes(egui::Key::Enter, ctrl, level(0)), "\r");
+        assert_eq!(key_bytes(egui::Key::A, ctrl_shift, level(0)), "\x01");
+
+        // Level 1 only extends keys whose legacy form loses a modifier
+        assert_eq!(key_bytes(egui::Key::A, ctrl, level(1)), "\x01");
+        assert_eq!(
+            key_bytes(egui::Key::A, ctrl_shift, level(1)),
+            "\x1b[27;6;65~"
+        );
+        assert_eq!(key_bytes(egui::Key::Enter, ctrl, level(1)), "\x1b[27;5;13~");
+        assert_eq!(key_bytes(egui::Key::Num1, ctrl, level(1)), "\x1b[27;5;49~");
+        assert_eq!(
+            key_bytes(egui::Key::Enter, egui::Modifiers::SHIFT, level(1)),
+            "\x1b[27;2;13~"
+        );
+
+        // Level 2 extends every modified key
+        assert_eq!(key_bytes(egui::Key::A, ctrl, level(2)), "\x1b[27;5;97~");
+        assert_eq!(key_bytes(egui::Key::Tab, ctrl, level(2)), "\x1b[27;5;9~");
+        assert_eq!(
+            key_bytes(egui::Key::Enter, egui::Modifiers::NONE, level(2)),
+            "\r"
+        );
+    }
+
+    fn kitty_bytes(
+        key: egui::Key,
+        modifiers: egui::Modifiers,
+        kind: KeyEventKind,
+        flags: u8,
+    ) -> String {
+        String::from_utf8(kitty_key_sequence(key, &modifiers, kind, flags)).unwrap()
+    }
+
+    #[test]
+    fn test_kitty_disambiguate() {
+        let press = KeyEventKind::Press;
+        let flags = KITTY_DISAMBIGUATE;
+        let none = egui::Modifiers::NONE;
+        let ctrl = egui::Modifiers::CTRL;
+        assert_eq!(
+            kitty_bytes(egui::Key::Escape, none, press, flags),
+            "\x1b[27u"
+        );
+        assert_eq!(kitty_bytes(egui::Key::I, ctrl, press, flags), "\x1b[105;5u");
+        assert_eq!(kitty_bytes(egui::Key::Tab, none, press, flags), "\t");
+        assert_eq!(kitty_bytes(egui::Key::Tab, ctrl, press, flags), "\x1b[9;5u");
+        assert_eq!(kitty_bytes(egui::Key::Enter, none, press, flags), "\r");
+        assert_eq!(
+            kitty_bytes(egui::Key::Enter, egui::Modifiers::SHIFT, press, flags),
+            "\x1b[13;2u"
+        );
+        assert_eq!(
+            kitty_bytes(egui::Key::ArrowUp, none, press, flags),
+            "\x1b[A"
+        );
+        assert_eq!(
+            kitty_bytes(egui::Key::ArrowUp, ctrl, press, flags),
+            "\x1b[1;5A"
+        );
+        assert_eq!(kitty_bytes(egui::Key::F3, none, press, flags), "\x1b[13~");
+        // Text keys are typed through text events
+        assert_eq!(kitty_bytes(egui::Key::A, none, press, flags), "");
+        assert_eq!(
+            kitty_bytes(egui::Key::A, egui::Modifiers::SHIFT, press, flags),
+            ""
+        );
+    }
+
+    #[test]
+    fn test_kitty_event_types() {
+        let flags = KITTY_DISAMBIGUATE | KITTY_REPORT_EVENT_TYPES;
+        let ctrl = egui::Modifiers::CTRL;
+        let none = egui::Modifiers::NONE;
+        assert_eq!(
+            kitty_bytes(egui::Key::A, ctrl, KeyEventKind::Repeat, flags),
+            "\x1b[97;5:2u"
+        );
+        assert_eq!(
+            kitty_bytes(egui::Key::ArrowLeft, none, KeyEventKind::Release, flags),
+            "\x1b[1;1:3D"
+        );
+        assert_eq!(
+            kitty_bytes(egui::Key::Enter, none, KeyEventKind::Release, flags),
+            ""
+        );
+        assert_eq!(
+            kitty_bytes(
+                egui::Key::A,
+                ctrl,
+                KeyEventKind::Release,
+                KITTY_DISAMBIGUATE
+            ),
+            ""
+        );
+    }
+
+    #[test]
+    fn test_kitty_report_all_keys() {
+        let all = KITTY_DISAMBIGUATE | KITTY_REPORT_ALL_KEYS;
+        let press = KeyEventKind::Press;
+        let shift = egui::Modifiers::SHIFT;
+        assert_eq!(
+            kitty_bytes(egui::Key::A, egui::Modifiers::NONE, press, all),
+            "\x1b[97u"
+        );
+        assert_eq!(
+            kitty_bytes(egui::Key::Enter, egui::Modifiers::NONE, press, all),
+            "\x1b[13u"
+        );
+        assert_eq!(kitty_bytes(egui::Key::A, shift, press, all), "\x1b[97;2u");
+        assert_eq!(
+            kitty_bytes(
+                egui::Key::A,
+                shift,
+                press,
+                all | KITTY_REPORT_ALTERNATE_KEYS
+            ),
+            "\x1b[97:65;2u"
+        );
+        assert_eq!(
+            kitty_bytes(egui::Key::A, shift, press, all | KITTY_REPORT_TEXT),
+            "\x1b[97;2;65u"
+        );
+        assert_eq!(
+            kitty_bytes(
+                egui::Key::B,
+                egui::Modifiers::NONE,
+                press,
+                all | KITTY_REPORT_TEXT
+            ),
+            "\x1b[98;1;98u"
+        );
+    }
+
+    #[test]
+    fn test_kitty_flags_route_key_events() {
+        let mut overlay = TuiOverlay::new();
+        overlay.active = true;
+        overlay.add_raw_output(b"\x1b[?1049h\x1b[>1u");
+        let ctx = egui::Context::default();
+        let key = |key, pressed, modifiers| egui::Event::Key {
+            key,
+            physical_key: None,
+            pressed,
+            repeat: false,
+            modifiers,
+        };
+        let mut input = egui::RawInput {
+            events: vec![
+                key(egui::Key::Escape, true, egui::Modifiers::NONE),
+                key(egui::Key::Escape, false, egui::Modifiers::NONE),
+                key(egui::Key::X, true, egui::Modifiers::NONE),
+                egui::Event::Text("x".to_string()),
+            ],
+            ..Default::default()
+        };
+        let mut sent = None;
+        let _ = ctx.run_ui(std::mem::take(&mut input), |ui| {
+            sent = overlay.handle_input(ui.ctx());
+        });
+        assert_eq!(sent.as_deref(), Some(&b"\x1b[27ux"[..]));
+    }
+
+    #[test]
+    fn test_paste_sequence() {
+        assert_eq!(paste_sequence("a\nb\r\nc", false), b"a\rb\rc");
+        assert_eq!(paste_sequence("x\ny", true), b"\x1b[200~x\ry\x1b[201~");
+        assert_eq!(
+            paste_sequence("evil\x1b[201~rm -rf ~\n", true),
+            b"\x1b[200~evilrm -rf ~\r\x1b[201~"
+        );
+    }
+
     #[test]
     fn test_tui_overlay_default() {
         let overlay = TuiOverlay::default();
@@ -1521,4 +1884,116 @@ mod tests {
         assert_eq!(rows, 50);
         assert_eq!(cols, 120);
     }
+
+    /// Overlay with a 10x20 screen drawn at the origin with 10x20 pixel cells
+    fn mouse_overlay(modes: &str) -> TuiOverlay {
+        let mut overlay = TuiOverlay::new();
+        overlay.grid.resize(10, 20);
+        overlay.cell_area = Some((
+            egui::Rect::from_min_size(egui::Pos2::ZERO, egui::vec2(200.0, 200.0)),
+            egui::vec2(10.0, 20.0),
+        ));
+        overlay.add_raw_output(modes.as_bytes());
+        overlay
+    }
+
+    fn pointer_bytes(overlay: &mut TuiOverlay, events: &[egui::Event]) -> Vec<u8> {
+        let mut out = Vec::new();
+        for event in events {
+            overlay.encode_pointer_event(
+                event,
+                Some(egui::pos2(15.0, 25.0)),
+                egui::Modifiers::default(),
+                &mut out,
+            );
+        }
+        out
+    }
+
+    fn button(x: f32, y: f32, pressed: bool, modifiers: egui::Modifiers) -> egui::Event {
+        egui::Event::PointerButton {
+            pos: egui::pos2(x, y),
+            button: egui::PointerButton::Primary,
+            pressed,
+            modifiers,
+        }
+    }
+
+    #[test]
+    fn test_mouse_click_sgr() {
+        let mut overlay = mouse_overlay("\x1b[?1000;1006h");
+        let bytes = pointer_bytes(
+            &mut overlay,
+            &[
+                button(35.0, 45.0, true, egui::Modifiers::CTRL),
+                egui::Event::PointerMoved(egui::pos2(55.0, 45.0)),
+                button(55.0, 45.0, false, egui::Modifiers::NONE),
+            ],
+        );
+        // Motion is not reported in mode 1000
+        assert_eq!(bytes, b"\x1b[<16;4;3M\x1b[<0;6;3m");
+    }
+
+    #[test]
+    fn test_mouse_drag_clamped() {
+        let mut overlay = mouse_overlay("\x1b[?1002;1006h");
+        let bytes = pointer_bytes(
+            &mut overlay,
+            &[
+                button(5.0, 5.0, true, egui::Modifiers::NONE),
+                egui::Event::PointerMoved(egui::pos2(500.0, 5.0)),
+            ],
+        );
+        assert_eq!(bytes, b"\x1b[<0;1;1M\x1b[<32;20;1M");
+    }
+
+    #[test]
+    fn test_mouse_default_encoding_and_hover() {
+        let mut overlay = mouse_overlay("\x1b[?1003h");
+        let bytes = pointer_bytes(
+            &mut overlay,
+            &[
+                egui::Event::PointerMoved(egui::pos2(5.0, 5.0)),
+                egui::Event::PointerMoved(egui::pos2(6.0, 6.0)),
+            ],
+        );
+        assert_eq!(bytes, b"\x1b[MC!!");
+    }
+
+    #[test]
+    fn test_mouse_wheel() {
+        let mut overlay = mouse_overlay("\x1b[?1000;1006h");
+        let wheel = |y| egui::Event::MouseWheel {
+            unit: egui::MouseWheelUnit::Point,
+            delta: egui::vec2(0.0, y),
+            phase: egui::TouchPhase::Move,
+            modifiers: egui::Modifiers::NONE,
+        };
+        let bytes = pointer_bytes(&mut overlay, &[wheel(30.0), wheel(-50.0)]);
+        assert_eq!(bytes, b"\x1b[<64;2;2M\x1b[<65;2;2M\x1b[<65;2;2M");
+    }
+
+    #[test]
+    fn test_mouse_ignored_when_disabled() {
+        let mut overlay = mouse_overlay("");
+        let mut out = Vec::new();
+        // handle_input only forwards pointer events when a mode is enabled
+        assert!(!overlay.grid.mouse_protocol().is_enabled());
+        overlay.encode_pointer_event(
+            &button(5.0, 5.0, true, egui::Modifiers::NONE),
+            None,
+            egui::Modifiers::NONE,
+            &mut out,
+        );
+        assert!(out.is_empty());
+    }
+
+    #[test]
+    fn test_start_resends_last_size() {
+        let mut overlay = TuiOverlay::new();
+        overlay.last_char_size = Some((30, 100));
+        overlay.start("htop".to_string(), "pty-123".to_string());
+        assert_eq!(overlay.take_pending_resize(), Some((30, 100)));
+        assert_eq!(overlay.take_pending_resize(), None);
+    }
 }
@@ -0,0 +1,198 @@
+//! Conformance tests for the terminal grid emulator
+//!
+//! Each `*.vt` file in `tests/conformance/fixtures` is a vttest-style case: a
+//! byte stream fed to [`TerminalGrid`] and the screen it must produce.
+//!
+//! ```text
+//! # comment
+//! size: 3x10
+//! input: Hello\r\nWorld
+//! ---
+//! |Hello     |
+//! |World     |
+//! |          |
+//! cursor: 1,5
+//! ```
+//!
+//! `input:` lines are concatenated and may use the escapes `\e`, `\r`, `\n`,
+//! `\t`, `\b`, `\a`, `\xHH` and `\\`. Expected rows are written at full width
+//! between `|` markers, where a wide character counts as two columns;
+//! `cursor:` (zero-based row,col) is optional.
+
+use mosaicterm::terminal::width::str_width;
+use mosaicterm::terminal::TerminalGrid;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+struct Fixture {
+    name: String,
+    rows: usize,
+    cols: usize,
+    input: Vec<u8>,
+    expected_rows: Vec<String>,
+    cursor: Option<(usize, usize)>,
+}
+
+fn fixtures_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/conformance/fixtures")
+}
+
+fn unescape(line: &str) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut chars = line.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            let mut buf = [0u8; 4];
+            out.extend_from_slice(ch.encode_utf8(&mut buf).as_bytes());
+            continue;
+        }
+        match chars.next() {
+            Some('e') => out.push(0x1b),
+            Some('r') => out.push(b'\r'),
+            Some('n') => out.push(b'\n'),
+            Some('t') => out.push(b'\t'),
+            Some('b') => out.push(0x08),
+            Some('a') => out.push(0x07),
+            Some('\\') => out.push(b'\\'),
+            Some('x') => {
+                let hex: String = chars.by_ref().take(2).collect();
+                out.push(u8::from_str_radix(&hex, 16).expect("invalid \\x escape"));
+            }
+            other => panic!("unknown escape \\{:?} in {:?}", other, line),
+        }
+    }
+    out
+}
+
+fn parse_fixture(path: &Path) -> Fixture {
+    let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+    let content = fs::read_to_string(path).expect("failed to read fixture");
+
+    let mut size = None;
+    let mut input = Vec::new();
+    let mut expected_rows = Vec::new();
+    let mut cursor = None;
+    let mut in_expected = false;
+
+    for line in content.lines() {
+        if line.starts_with('#') || line.trim().is_empty() {
+            continue;
+        }
+        if line == "---" {
+            in_expected = true;
+        } else if let Some(value) = line.strip_prefix("size:") {
+            let (rows, cols) = value.trim().split_once('x').expect("size must be RxC");
+            size = Some((rows.parse().unwrap(), cols.parse().unwrap()));
+        } else if let Some(value) = line.strip_prefix("input:") {
+            input.extend(unescape(value.strip_prefix(' ').unwrap_or(value)));
+        } else if let Some(value) = line.strip_prefix("cursor:") {
+            let (row, col) = value.trim().split_once(',').expect("cursor must be R,C");
+            cursor = Some((row.trim().parse().unwrap(), col.trim().parse().unwrap()));
+        } else if in_expected && line.starts_with('|') && line.ends_with('|') && line.len() >= 2 {
+            expected_rows.push(line[1..line.len() - 1].to_string());
+        } else {
+            panic!("{}: unrecognised line {:?}", name, line);
+        }
+    }
+
+    let (rows, cols) = size.unwrap_or_else(|| panic!("{}: missing size", name));
+    Fixture {
+        name,
+        rows,
+        cols,
+        input,
+        expected_rows,
+        cursor,
+    }
+}
+
+fn run_fixture(fixture: &Fixture) -> Result<(), String> {
+    let mut grid = TerminalGrid::new(fixture.rows, fixture.cols);
+    grid.advance(&fixture.input);
+
+    let actual = grid.dump();
+    let expected = fixture.expected_rows.join("\n");
+    let mut problems = Vec::new();
+
+    if actual != expected {
+        let frame = |s: &str| {
+            s.lines()
+                .map(|l| format!("  |{}|", l))
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+        problems.push(format!(
+            "screen mismatch\n expected:\n{}\n actual:\n{}",
+            frame(&expected),
+            frame(&actual)
+        ));
+    }
+    if let Some(cursor) = fixture.cursor {
+        if grid.cursor_position() != cursor {
+            problems.push(format!(
+                "cursor expected {:?}, got {:?}",
+                cursor,
+                grid.cursor_position()
+            ));
+        }
+    }
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("{}: {}", fixture.name, problems.join("\n")))
+    }
+}
+
+#[test]
+fn test_grid_conformance_fixtures() {
+    let mut paths: Vec<PathBuf> = fs::read_dir(fixtures_dir())
+        .expect("fixtures directory missing")
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "vt"))
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty(), "no conformance fixtures found");
+
+    let failures: Vec<String> = paths
+        .iter()
+        .map(|path| parse_fixture(path))
+        .filter_map(|fixture| run_fixture(&fixture).err())
+        .collect();
+
+    assert!(
+        failures.is_empty(),
+        "{} of {} conformance fixtures failed:\n\n{}",
+        failures.len(),
+        paths.len(),
+        failures.join("\n\n")
+    );
+}
+
+#[test]
+fn test_fixture_rows_match_declared_size() {
+    for entry in fs::read_dir(fixtures_dir()).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_none_or(|ext| ext != "vt") {
+            continue;
+        }
+        let fixture = parse_fixture(&path);
+        assert_eq!(
+            fixture.expected_rows.len(),
+            fixture.rows,
+            "{}: row count",
+            fixture.name
+        );
+        for row in &fixture.expected_rows {
+            assert_eq!(str_width(row), fixture.cols, "{}: row width", fixture.name);
+        }
+    }
+}
+
+#[test]
+fn test_unescape() {
+    assert_eq!(
+        unescape(r"a\e[1m\r\n\t\b\a\x41\\"),
+        b"a\x1b[1m\r\n\t\x08\x07A\\"
+    );
+}
@@ -0,0 +1,59 @@
+//! Integration Tests for Fullscreen Detection
+//!
+//! These tests spawn a real shell on a PTY and check that a foreground job
+//! switching the terminal to raw mode is noticed, while the idle shell is not.
+//!
+//! Note: These tests are excluded from tarpaulin coverage runs because PTY fork/exec
+//! operations hang under ptrace instrumentation.
+#![cfg(all(unix, not(tarpaulin)))]
+
+use mosaicterm::pty::{PtyHandle, PtyManager};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+async fn spawn_shell(manager: &PtyManager) -> PtyHandle {
+    manager
+        .create_pty("/bin/sh", &[], &HashMap::new(), None)
+        .await
+        .expect("failed to spawn /bin/sh")
+}
+
+/// Poll until `is_foreground_raw` reports `expected` or the deadline passes
+async fn wait_for_raw(manager: &PtyManager, handle: &PtyHandle, expected: bool) -> bool {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while Instant::now() < deadline {
+        let _ = manager.read_output(handle, 50).await;
+        if manager.is_foreground_raw(handle).await.unwrap() == expected {
+            return true;
+        }
+    }
+    false
+}
+
+#[tokio::test]
+async fn test_idle_shell_is_not_raw() {
+    let manager = PtyManager::new();
+    let handle = spawn_shell(&manager).await;
+
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert!(!manager.is_foreground_raw(&handle).await.unwrap());
+
+    let _ = manager.terminate_pty(&handle).await;
+}
+
+#[tokio::test]
+async fn test_raw_foreground_job_is_detected() {
+    let manager = PtyManager::new();
+    let handle = spawn_shell(&manager).await;
+
+    manager
+        .send_input(&handle, b"stty raw -echo; sleep 3; stty sane\n")
+        .await
+        .expect("failed to send command");
+    assert!(wait_for_raw(&manager, &handle, true).await);
+
+    // Back at the prompt once the job has restored the terminal
+    assert!(wait_for_raw(&manager, &handle, false).await);
+
+    let _ = manager.terminate_pty(&handle).await;
+}
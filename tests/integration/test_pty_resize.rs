@@ -0,0 +1,94 @@
+//! Integration Tests for PTY Resize Propagation
+//!
+//! These tests spawn a real shell on a PTY, resize it, and check that the
+//! child sees the new window size through `stty size`.
+//!
+//! Note: These tests are excluded from tarpaulin coverage runs because PTY fork/exec
+//! operations hang under ptrace instrumentation.
+#![cfg(all(unix, not(tarpaulin)))]
+
+use mosaicterm::pty::{PtyHandle, PtyManager};
+use mosaicterm::terminal::{GridSize, Terminal};
+use mosaicterm::TerminalShellType;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+async fn spawn_shell(manager: &PtyManager) -> PtyHandle {
+    manager
+        .create_pty("/bin/sh", &[], &HashMap::new(), None)
+        .await
+        .expect("failed to spawn /bin/sh")
+}
+
+/// Run `stty size` in the shell and return the reported "rows cols"
+async fn stty_size(manager: &PtyManager, handle: &PtyHandle) -> String {
+    manager
+        .send_input(handle, b"echo SIZE=$(stty size)=\n")
+        .await
+        .expect("failed to send stty");
+
+    let deadline = Instant::now() + Duration::from_secs(5);
+    let mut output = String::new();
+    while Instant::now() < deadline {
+        let chunk = manager.read_output(handle, 100).await.unwrap_or_default();
+        output.push_str(&String::from_utf8_lossy(&chunk));
+        // The echoed command line contains "$(stty size)", so only a
+        // numeric match is the command's output
+        if let Some(size) = output
+            .split("SIZE=")
+            .skip(1)
+            .filter_map(|rest| rest.split('=').next())
+            .find(|size| size.chars().all(|c| c.is_ascii_digit() || c == ' ') && !size.is_empty())
+        {
+            return size.to_string();
+        }
+    }
+    panic!("no stty size output, got: {:?}", output);
+}
+
+#[tokio::test]
+async fn test_resize_reaches_child() {
+    let manager = PtyManager::new();
+    let handle = spawn_shell(&manager).await;
+
+    manager.resize_pty(&handle, 40, 120).await.unwrap();
+    assert_eq!(stty_size(&manager, &handle).await, "40 120");
+
+    let _ = manager.terminate_pty(&handle).await;
+}
+
+#[tokio::test]
+async fn test_repeated_resizes_report_latest_size() {
+    let manager = PtyManager::new();
+    let handle = spawn_shell(&manager).await;
+
+    manager.resize_pty(&handle, 50, 200).await.unwrap();
+    manager.resize_pty(&handle, 30, 90).await.unwrap();
+    assert_eq!(stty_size(&manager, &handle).await, "30 90");
+
+    manager.resize_pty(&handle, 12, 45).await.unwrap();
+    assert_eq!(stty_size(&manager, &handle).await, "12 45");
+
+    let _ = manager.terminate_pty(&handle).await;
+}
+
+#[tokio::test]
+async fn test_terminal_resize_pty_updates_state_and_child() {
+    let manager = Arc::new(PtyManager::new());
+    let handle = spawn_shell(&manager).await;
+
+    let session =
+        mosaicterm::models::TerminalSession::new(TerminalShellType::Bash, PathBuf::from("/bin/sh"));
+    let mut terminal = Terminal::new(session, manager.clone());
+    terminal.initialize_with_pty(handle.clone()).unwrap();
+
+    terminal.resize_pty(GridSize::new(33, 101)).await.unwrap();
+
+    let dimensions = &terminal.state().dimensions;
+    assert_eq!((dimensions.rows, dimensions.cols), (33, 101));
+    assert_eq!(stty_size(&manager, &handle).await, "33 101");
+
+    let _ = manager.terminate_pty(&handle).await;
+}
@@ -0,0 +1,189 @@
+//! Integration Tests for Shell Integration Marks
+//!
+//! These tests start a real bash with the rcfile MosaicTerm generates and
+//! check that the OSC 133 marks it emits split command output from prompts
+//! and echoes, and carry each command's exact exit status, and that the
+//! working directory is reported with OSC 7.
+//!
+//! Note: These tests are excluded from tarpaulin coverage runs because PTY fork/exec
+//! operations hang under ptrace instrumentation.
+#![cfg(all(unix, not(tarpaulin)))]
+
+use chrono::Utc;
+use mosaicterm::pty::{shell_state, PtyHandle, PtyManager};
+use mosaicterm::terminal::{OutputChunk, OutputProcessor, PromptMark, ReportedCwd, StreamType};
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// A bash session whose output is fed through an [`OutputProcessor`]
+struct Session {
+    manager: PtyManager,
+    handle: PtyHandle,
+    processor: OutputProcessor,
+    lines: Vec<String>,
+    marks: Vec<PromptMark>,
+    cwd: Option<ReportedCwd>,
+}
+
+impl Session {
+    async fn start(rcfile: &Path) -> Self {
+        let manager = PtyManager::new();
+        let args = [
+            "--noprofile".to_string(),
+            "--noediting".to_string(),
+            "--rcfile".to_string(),
+            rcfile.display().to_string(),
+            "-i".to_string(),
+        ];
+        let handle = manager
+            .create_pty("bash", &args, &HashMap::new(), None)
+            .await
+            .expect("failed to spawn bash");
+        let mut session = Self {
+            manager,
+            handle,
+            processor: OutputProcessor::new(),
+            lines: Vec::new(),
+            marks: Vec::new(),
+            cwd: None,
+        };
+        assert!(
+            session
+                .wait_for(|mark| *mark == PromptMark::InputStart)
+                .await,
+            "bash never drew a marked prompt"
+        );
+        session
+    }
+
+    /// Read output until a mark matching `pred` arrives or the deadline passes
+    async fn wait_for(&mut self, pred: impl Fn(&PromptMark) -> bool) -> bool {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while Instant::now() < deadline {
+            let data = self
+                .manager
+                .read_output(&self.handle, 100)
+                .await
+                .unwrap_or_default();
+            let chunk = OutputChunk {
+                data,
+                timestamp: Utc::now(),
+                stream_type: StreamType::Stdout,
+                is_complete: false,
+            };
+            let lines = self.processor.process_chunk(chunk).unwrap();
+            self.lines.extend(lines.into_iter().map(|line| line.text));
+            if let Some(cwd) = self.processor.take_reported_cwd() {
+                self.cwd = Some(cwd);
+            }
+            let marks = self.processor.take_prompt_marks();
+            let found = marks.iter().any(&pred);
+            self.marks.extend(marks);
+            if found {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Run a command and return its output lines and reported exit status
+    async fn run(&mut self, command: &str) -> (Vec<String>, Option<i32>) {
+        self.lines.clear();
+        self.marks.clear();
+        self.manager
+            .send_input(&self.handle, format!("{}\n", command).as_bytes())
+            .await
+            .expect("failed to send command");
+        assert!(
+            self.wait_for(|mark| *mark == PromptMark::InputStart).await,
+            "no prompt after `{}`",
+            command
+        );
+        let exit_code = self.marks.iter().find_map(|mark| match mark {
+            PromptMark::CommandFinished { exit_code } => Some(*exit_code),
+            _ => None,
+        });
+        (self.lines.clone(), exit_code.flatten())
+    }
+
+    async fn stop(self) {
+        let _ = self.manager.terminate_pty(&self.handle).await;
+    }
+}
+
+/// The generated rcfile, or `None` when bash isn't installed
+fn bash_rcfile(id: u32) -> Option<std::path::PathBuf> {
+    let has_bash = std::process::Command::new("bash")
+        .arg("-c")
+        .arg("true")
+        .status()
+        .is_ok_and(|status| status.success());
+    if !has_bash {
+        return None;
+    }
+    shell_state::create_bash_rcfile(id)
+}
+
+#[tokio::test]
+async fn test_bash_reports_exact_exit_codes() {
+    let id = std::process::id().wrapping_mul(10).wrapping_add(1);
+    let Some(rcfile) = bash_rcfile(id) else {
+        return;
+    };
+    let mut session = Session::start(&rcfile).await;
+
+    assert_eq!(session.run("true").await.1, Some(0));
+    assert_eq!(session.run("false").await.1, Some(1));
+    assert_eq!(session.run("(exit 3)").await.1, Some(3));
+    assert_eq!(session.run("sh -c 'kill -9 $$'").await.1, Some(137));
+
+    session.stop().await;
+    shell_state::cleanup_shell_files(id);
+}
+
+#[tokio::test]
+async fn test_bash_output_is_split_from_prompt_and_echo() {
+    let id = std::process::id().wrapping_mul(10).wrapping_add(2);
+    let Some(rcfile) = bash_rcfile(id) else {
+        return;
+    };
+    let mut session = Session::start(&rcfile).await;
+
+    // The first command establishes that bash sends output-start marks
+    session.run("true").await;
+
+    let (lines, exit_code) = session.run("printf 'one\\ntwo\\nthree'").await;
+    assert_eq!(lines, ["one", "two", "three"]);
+    assert_eq!(exit_code, Some(0));
+
+    let (lines, exit_code) = session.run("echo killed; ls /nonexistent-dir").await;
+    assert_eq!(lines.first().map(String::as_str), Some("killed"));
+    assert_eq!(lines.len(), 2);
+    assert_eq!(exit_code, Some(2));
+
+    session.stop().await;
+    shell_state::cleanup_shell_files(id);
+}
+
+#[tokio::test]
+async fn test_bash_reports_working_directory() {
+    let id = std::process::id().wrapping_mul(10).wrapping_add(3);
+    let Some(rcfile) = bash_rcfile(id) else {
+        return;
+    };
+    let mut session = Session::start(&rcfile).await;
+    assert!(session.cwd.is_some(), "no OSC 7 before the first prompt");
+
+    let dir = tempfile::tempdir().unwrap();
+    let target = dir.path().join("with space");
+    std::fs::create_dir(&target).unwrap();
+    session.run(&format!("cd '{}'", target.display())).await;
+
+    let cwd = session.cwd.clone().expect("no OSC 7 after cd");
+    assert_eq!(cwd.path, target);
+    assert!(!cwd.is_remote());
+
+    session.stop().await;
+    shell_state::cleanup_shell_files(id);
+}